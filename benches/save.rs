@@ -0,0 +1,75 @@
+// Writing a large buffer out: the rope chunk by chunk, as saving does, against
+// a byte at a time, as it used to. Run with `cargo bench`.
+
+use std::{
+    fs::File,
+    hint::black_box,
+    io::{self, BufWriter, Write},
+    time::{Duration, Instant},
+};
+
+use ropey::Rope;
+use shark::buffer;
+
+const RUNS: u32 = 5;
+
+// Mostly code-like ASCII, with some wider characters and \r\n lines.
+fn text(bytes: usize) -> Rope {
+    let mut text = String::with_capacity(bytes + 100);
+    let mut n = 0;
+    while text.len() < bytes {
+        let ending = if n % 7 == 0 { "\r\n" } else { "\n" };
+        text.push_str(&format!(
+            "    let value_{n} = \"héllo 漢字 🦀\" + {n};{ending}"
+        ));
+        n += 1;
+    }
+    Rope::from_str(&text)
+}
+
+fn byte_at_a_time(rope: &Rope, out: &mut impl Write) -> io::Result<()> {
+    for byte in rope.bytes() {
+        out.write_all(&[byte])?;
+    }
+    Ok(())
+}
+
+fn time(name: &str, mut run: impl FnMut() -> io::Result<()>) -> Duration {
+    let mut best = Duration::MAX;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        run().expect(name);
+        best = best.min(start.elapsed());
+    }
+    println!("{name:<24} {:>8.1} ms", best.as_secs_f64() * 1000.0);
+    best
+}
+
+fn main() -> io::Result<()> {
+    let rope = text(50 << 20);
+    let path = std::env::temp_dir().join(format!("shark-bench-{}", std::process::id()));
+    println!("{} MB, best of {RUNS}", rope.len_bytes() >> 20);
+
+    let chunks = time("chunks, to a sink", || {
+        buffer::write_rope(black_box(&rope), &mut io::sink())
+    });
+    let bytes = time("bytes, to a sink", || {
+        byte_at_a_time(black_box(&rope), &mut BufWriter::new(io::sink()))
+    });
+    time("chunks, to a file", || {
+        let mut out = BufWriter::new(File::create(&path)?);
+        buffer::write_rope(&rope, &mut out)?;
+        out.flush()
+    });
+    time("bytes, to a file", || {
+        let mut out = BufWriter::new(File::create(&path)?);
+        byte_at_a_time(&rope, &mut out)?;
+        out.flush()
+    });
+    println!(
+        "chunks are {:.1}x as fast",
+        bytes.as_secs_f64() / chunks.as_secs_f64()
+    );
+
+    std::fs::remove_file(&path)
+}
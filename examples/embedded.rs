@@ -0,0 +1,107 @@
+// An editor pane inside a frame the host draws: a title bar, a border around
+// the pane and a help line below it. The host owns the terminal and the event
+// loop, and hands the pane its events.
+//
+//     cargo run --example embedded -- some-file.toml
+
+use std::{
+    env,
+    io::{self, Write},
+    path::PathBuf,
+    time::Duration,
+};
+
+use crossterm::{
+    cursor::{Hide, MoveTo, Show},
+    event, execute, queue,
+    style::{Attribute, Print, SetAttribute},
+    terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use shark::widget::{EditorView, Event, Rect};
+
+// The pane fills the terminal, less the title bar, the border and the help line.
+fn pane(width: u16, height: u16) -> Rect {
+    Rect::new(2, 2, width.saturating_sub(4), height.saturating_sub(5))
+}
+
+fn draw_frame(out: &mut impl Write, path: &str, area: Rect) -> io::Result<()> {
+    let (width, height) = terminal::size()?;
+    queue!(out, Clear(ClearType::All), MoveTo(0, 0))?;
+    queue!(
+        out,
+        SetAttribute(Attribute::Reverse),
+        Print(format!(
+            "{:width$}",
+            " config editor",
+            width = width as usize
+        )),
+        SetAttribute(Attribute::Reset)
+    )?;
+
+    let horizontal = "─".repeat(area.width as usize);
+    queue!(out, MoveTo(1, 1), Print(format!("┌{horizontal}┐")))?;
+    for row in area.y..area.y + area.height {
+        queue!(out, MoveTo(1, row), Print("│"))?;
+        queue!(out, MoveTo(area.x + area.width, row), Print("│"))?;
+    }
+    let bottom = area.y + area.height;
+    queue!(out, MoveTo(1, bottom), Print(format!("└{horizontal}┘")))?;
+    queue!(
+        out,
+        MoveTo(1, height.saturating_sub(1)),
+        Print(format!("Editing {path}. Ctrl+S saves, Ctrl+Q returns"))
+    )
+}
+
+fn run(out: &mut impl Write, path: PathBuf) -> io::Result<()> {
+    let (width, height) = terminal::size()?;
+    let mut view = EditorView::open(&path, pane(width, height))?;
+    let name = path.display().to_string();
+
+    draw_frame(out, &name, view.area())?;
+    loop {
+        view.render(out)?;
+        match view.cursor() {
+            Some((column, row)) => queue!(out, MoveTo(column, row), Show)?,
+            None => queue!(out, Hide)?,
+        }
+        out.flush()?;
+
+        if !event::poll(Duration::from_millis(250))? {
+            view.tick()?;
+            continue;
+        }
+        match event::read()? {
+            Event::Resize(width, height) => {
+                view.set_area(pane(width, height))?;
+                draw_frame(out, &name, view.area())?;
+            }
+            event => {
+                if !view.handle(event)? {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+fn main() -> io::Result<()> {
+    let path = env::args_os()
+        .nth(1)
+        .map_or_else(|| PathBuf::from("embedded.toml"), PathBuf::from);
+
+    let mut out = io::stdout();
+    terminal::enable_raw_mode()?;
+    execute!(out, EnterAlternateScreen, event::EnableBracketedPaste)?;
+
+    let result = run(&mut out, path);
+
+    execute!(
+        out,
+        event::DisableBracketedPaste,
+        LeaveAlternateScreen,
+        Show
+    )?;
+    terminal::disable_raw_mode()?;
+    result
+}
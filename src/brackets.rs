@@ -0,0 +1,137 @@
+use ropey::Rope;
+use tree_sitter::{Node, Tree};
+
+// Opener and closer of each kind of bracket that gets matched.
+const PAIRS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+
+// How many chars the scan for a buffer without a tree looks through before
+// giving up, so a stray bracket in a huge file doesn't slow down every frame.
+const SCAN_LIMIT: usize = 100_000;
+
+// A bracket by the cursor, as char indices. `partner` is None when nothing
+// closes (or opens) it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Match {
+    pub bracket: usize,
+    pub partner: Option<usize>,
+}
+
+// The bracket under the cursor, or else the one just before it. With a tree,
+// only brackets the grammar sees as such count, so ones in strings and
+// comments are left alone; without one the text is scanned, counting every
+// bracket.
+pub fn find(rope: &Rope, tree: Option<&Tree>, cursor: usize) -> Option<Match> {
+    for at in [Some(cursor), cursor.checked_sub(1)].into_iter().flatten() {
+        let Some(c) = rope.get_char(at) else {
+            continue;
+        };
+        let Some(pair) = PAIRS
+            .into_iter()
+            .find(|&(open, close)| c == open || c == close)
+        else {
+            continue;
+        };
+
+        let partner = match tree {
+            Some(tree) => in_tree(rope, tree, at, pair),
+            None => scan(rope, at, pair),
+        };
+        if let Some(partner) = partner {
+            return Some(Match {
+                bracket: at,
+                partner,
+            });
+        }
+    }
+
+    None
+}
+
+// Brackets are tokens among their siblings, so the partner is the sibling at
+// the same depth. One the parser had to make up to recover from an error, which
+// takes no room in the text, means the bracket is unmatched.
+fn in_tree(
+    rope: &Rope,
+    tree: &Tree,
+    at: usize,
+    (open, close): (char, char),
+) -> Option<Option<usize>> {
+    let byte = rope.char_to_byte(at);
+    let node = tree.root_node().descendant_for_byte_range(byte, byte + 1)?;
+    let c = rope.char(at);
+    if node.child_count() != 0 || node.byte_range() != (byte..byte + 1) || !is(node, c) {
+        return None;
+    }
+
+    let parent = node.parent()?;
+    let mut walk = parent.walk();
+    let siblings: Vec<Node> = parent.children(&mut walk).collect();
+    let index = siblings.iter().position(|s| s.id() == node.id())?;
+
+    let (toward, away, search): (char, char, Vec<Node>) = if c == open {
+        (close, open, siblings[index + 1..].to_vec())
+    } else {
+        (
+            open,
+            close,
+            siblings[..index].iter().rev().copied().collect(),
+        )
+    };
+
+    let mut depth = 0;
+    for sibling in search {
+        if is(sibling, away) {
+            depth += 1;
+        } else if is(sibling, toward) {
+            if depth == 0 {
+                let partner =
+                    (!sibling.is_missing()).then(|| rope.byte_to_char(sibling.start_byte()));
+                return Some(partner);
+            }
+            depth -= 1;
+        }
+    }
+
+    Some(None)
+}
+
+fn is(node: Node, c: char) -> bool {
+    node.kind().chars().eq([c])
+}
+
+// None when the limit ran out first, so nothing is shown rather than a
+// bracket wrongly marked unmatched.
+fn scan(rope: &Rope, at: usize, (open, close): (char, char)) -> Option<Option<usize>> {
+    let mut depth = 0usize;
+    let c = rope.char(at);
+
+    let mut step = |index: usize, ch: char, away: char, toward: char| {
+        if ch == away {
+            depth += 1;
+        } else if ch == toward {
+            if depth == 0 {
+                return Some(index);
+            }
+            depth -= 1;
+        }
+        None
+    };
+
+    if c == open {
+        for (offset, ch) in rope.chars_at(at + 1).take(SCAN_LIMIT).enumerate() {
+            if let Some(index) = step(at + 1 + offset, ch, open, close) {
+                return Some(Some(index));
+            }
+        }
+        return (rope.len_chars() - at - 1 <= SCAN_LIMIT).then_some(None);
+    }
+
+    let mut chars = rope.chars_at(at);
+    for index in (at.saturating_sub(SCAN_LIMIT)..at).rev() {
+        let ch = chars.prev()?;
+        if let Some(index) = step(index, ch, close, open) {
+            return Some(Some(index));
+        }
+    }
+    (at <= SCAN_LIMIT).then_some(None)
+}
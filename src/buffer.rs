@@ -0,0 +1,885 @@
+use std::{
+    collections::VecDeque,
+    fs::{self, File},
+    io::{self, Read, Write},
+    ops::Range,
+    path::Path,
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use ropey::{Rope, RopeBuilder};
+use tree_sitter::{InputEdit, Point, Tree};
+
+use crate::{
+    document::lines,
+    language::Language,
+    syntax_errors,
+    task::Token,
+    undo::{Change, Restore, UndoError, UndoLimits, UndoStack},
+};
+
+// Recent edit positions kept per buffer for jumping back to them.
+const MAX_EDIT_POSITIONS: usize = 32;
+
+// Places big moves left from, kept per buffer for jumping back to them.
+const MAX_JUMPS: usize = 100;
+
+pub struct Buffer {
+    pub rope: Rope,
+    pub filename: Option<String>,
+    pub scroll: usize,
+    // Display columns scrolled off the left edge, for lines wider than the view.
+    pub h_scroll: usize,
+    // Rows of the line at `scroll` above the top of the view, while wrapping.
+    pub row_scroll: usize,
+    // (line, char column) of the cursor, saved while the buffer is in the background.
+    pub cursor: (usize, usize),
+    // And of the selection's anchor, if there was one.
+    pub selection: Option<(usize, usize)>,
+    pub modified: bool,
+    // Nothing existed at the path when the buffer was opened; saving creates it.
+    pub new_file: bool,
+    // Edits and saving are refused until this is cleared.
+    pub read_only: bool,
+    hygiene: Hygiene,
+    hygiene_stale: bool,
+    // None until the first (deferred) parse of the buffer.
+    pub tree: Option<Tree>,
+    pub tree_stale: bool,
+    // What `syntax_errors` found, and the generation and language it was for.
+    syntax_errors: Option<(u64, Language, Vec<Range<usize>>)>,
+    // Size of the file when last read, while following it as it grows.
+    pub follow: Option<u64>,
+    // The file as of the last load, reload or save, to notice it being changed
+    // by something else. None when it didn't exist or hasn't been read.
+    disk: Option<Stamp>,
+    // A change on disk the user chose to leave alone; it isn't asked about
+    // again until the file changes once more.
+    pub ignored: Option<Stamp>,
+    // Chosen from the language menu; kept for as long as the buffer is open.
+    language_override: Option<Language>,
+    line_ending: LineEnding,
+    undo: UndoStack,
+    // The contents, restore point and generation from before the edits since
+    // begin_batch(), which undo takes back together.
+    batch: Option<(Rope, Restore, u64)>,
+    generation: u64,
+    // (line, char column) where recent edits left the cursor, newest last.
+    edits: VecDeque<(usize, usize)>,
+    // (line, char column) where big moves started, oldest first, and which of
+    // them stepping back and forth is at; `jumps.len()` while not stepping.
+    jumps: VecDeque<(usize, usize)>,
+    jump: usize,
+    // Not yet handed to the manager, which knows the buffer's index.
+    events: Vec<BufferEvent>,
+}
+
+// Enough of a file's metadata to tell that it was rewritten: the time alone
+// can miss two writes within the filesystem's resolution.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Stamp {
+    modified: SystemTime,
+    len: u64,
+}
+
+impl Stamp {
+    pub fn of(path: &Path) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+        Some(Self {
+            modified: metadata.modified().ok()?,
+            len: metadata.len(),
+        })
+    }
+}
+
+// Moments in a buffer's life that other parts of the editor react to. They're
+// queued as they happen and delivered once the operation causing them is done,
+// so a listener never runs in the middle of an edit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BufferEvent {
+    Opened,
+    // `removed` chars at `at` were replaced by `inserted` chars.
+    Changed {
+        at: usize,
+        removed: usize,
+        inserted: usize,
+    },
+    // The whole text was swapped out, e.g. by a reload or an undo snapshot.
+    Replaced,
+    Saved,
+    Closed,
+}
+
+// File-hygiene facts shown as status indicators. They are recomputed lazily
+// after edits rather than on every keystroke.
+#[derive(Clone, Copy, Default)]
+pub struct Hygiene {
+    pub final_newline: bool,
+    pub first_trailing_whitespace: Option<usize>,
+}
+
+// Tells the tree about an edit at char `at` of `rope`, which already has it, so
+// the next parse only redoes the part that changed.
+fn edit_tree(tree: &mut Option<Tree>, rope: &Rope, at: usize, removed: &str, inserted: &str) {
+    let Some(tree) = tree else {
+        return;
+    };
+
+    let start_byte = rope.char_to_byte(at);
+    let line = rope.byte_to_line(start_byte);
+    let start = Point::new(line, start_byte - rope.line_to_byte(line));
+
+    tree.edit(&InputEdit {
+        start_byte,
+        old_end_byte: start_byte + removed.len(),
+        new_end_byte: start_byte + inserted.len(),
+        start_position: start,
+        old_end_position: end_point(start, removed),
+        new_end_position: end_point(start, inserted),
+    });
+}
+
+// Contents swapped out wholesale count as one edit over all of the text.
+fn edit_whole_tree(tree: &mut Option<Tree>, old: &Rope, new: &Rope) {
+    let Some(tree) = tree else {
+        return;
+    };
+
+    let end = |rope: &Rope| {
+        let line = rope.len_lines() - 1;
+        Point::new(line, rope.len_bytes() - rope.line_to_byte(line))
+    };
+
+    tree.edit(&InputEdit {
+        start_byte: 0,
+        old_end_byte: old.len_bytes(),
+        new_end_byte: new.len_bytes(),
+        start_position: Point::new(0, 0),
+        old_end_position: end(old),
+        new_end_position: end(new),
+    });
+}
+
+fn end_point(start: Point, text: &str) -> Point {
+    match text.rfind('\n') {
+        Some(i) => Point::new(start.row + text.matches('\n').count(), text.len() - i - 1),
+        None => Point::new(start.row, start.column + text.len()),
+    }
+}
+
+const READ_ATTEMPTS: usize = 3;
+
+const READ_CHUNK: usize = 1024 * 1024;
+
+// A file another process is appending to can grow between the stat and the
+// read. The read is retried briefly until the size before and after agree,
+// and the last attempt is kept regardless. Returns the size that was read.
+pub fn read_file(path: &Path) -> io::Result<(Rope, u64)> {
+    // A fresh token is never cancelled.
+    read_file_cancellable(path, &Token::default()).map(Option::unwrap)
+}
+
+// Like read_file, but advances `token` by the bytes read and stops between
+// chunks once it's cancelled, returning None.
+pub fn read_file_cancellable(path: &Path, token: &Token) -> io::Result<Option<(Rope, u64)>> {
+    let mut attempt = 0;
+
+    loop {
+        let before = fs::metadata(path)?.len();
+        let Some(rope) = read_chunks(File::open(path)?, token)? else {
+            return Ok(None);
+        };
+        let after = fs::metadata(path)?.len();
+        attempt += 1;
+
+        if before == after && rope.len_bytes() as u64 == after || attempt == READ_ATTEMPTS {
+            let len = rope.len_bytes() as u64;
+            return Ok(Some((rope, len)));
+        }
+
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+// Text piped into the editor, read to the end.
+pub fn read_stdin() -> io::Result<Rope> {
+    read_chunks(io::stdin().lock(), &Token::default()).map(Option::unwrap)
+}
+
+// A character split across two reads is carried over to the next one.
+fn read_chunks(mut reader: impl Read, token: &Token) -> io::Result<Option<Rope>> {
+    let mut builder = RopeBuilder::new();
+    let mut buffer = vec![0; READ_CHUNK];
+    let mut carried = 0;
+
+    loop {
+        if token.is_cancelled() {
+            return Ok(None);
+        }
+
+        let read = match reader.read(&mut buffer[carried..]) {
+            Ok(0) if carried == 0 => break,
+            Ok(0) => return Err(invalid_utf8()),
+            Ok(read) => read,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error),
+        };
+        token.advance_by(read);
+
+        let filled = carried + read;
+        let valid = match std::str::from_utf8(&buffer[..filled]) {
+            Ok(_) => filled,
+            Err(error) if error.error_len().is_none() => error.valid_up_to(),
+            Err(_) => return Err(invalid_utf8()),
+        };
+
+        builder.append(std::str::from_utf8(&buffer[..valid]).map_err(|_| invalid_utf8())?);
+        buffer.copy_within(valid..filled, 0);
+        carried = filled - valid;
+    }
+
+    Ok(Some(builder.finish()))
+}
+
+fn invalid_utf8() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "stream did not contain valid UTF-8",
+    )
+}
+
+// How new lines are terminated in a buffer. The rope holds the text exactly as
+// it is on disk, so this only matters for text that goes in.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    // Whatever the first line ends with; a file without any, like a new one,
+    // gets \n.
+    pub fn detect(rope: &Rope) -> Self {
+        let line = (0..lines::count(rope)).find(|&l| !lines::terminator(rope, l).is_empty());
+        match line.map(|l| lines::terminator(rope, l)) {
+            Some(terminator) if terminator == "\r\n" => LineEnding::CrLf,
+            _ => LineEnding::Lf,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+}
+
+// Text from outside the buffer, with any mix of \n, \r\n and lone \r line
+// endings, with `ending` instead.
+pub fn normalize_newlines(text: &str, ending: LineEnding) -> String {
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    match ending {
+        LineEnding::Lf => text,
+        LineEnding::CrLf => text.replace('\n', "\r\n"),
+    }
+}
+
+pub fn write_rope(rope: &Rope, out: &mut impl Write) -> io::Result<()> {
+    for chunk in rope.chunks() {
+        out.write_all(chunk.as_bytes())?;
+    }
+
+    Ok(())
+}
+
+impl Buffer {
+    pub fn new(rope: Rope, filename: Option<String>) -> Self {
+        Self {
+            line_ending: LineEnding::detect(&rope),
+            rope,
+            filename,
+            scroll: 0,
+            h_scroll: 0,
+            row_scroll: 0,
+            cursor: (0, 0),
+            selection: None,
+            modified: false,
+            new_file: false,
+            read_only: false,
+            hygiene: Hygiene::default(),
+            hygiene_stale: true,
+            tree: None,
+            tree_stale: true,
+            syntax_errors: None,
+            follow: None,
+            disk: None,
+            ignored: None,
+            language_override: None,
+            undo: UndoStack::default(),
+            batch: None,
+            generation: 0,
+            edits: VecDeque::new(),
+            jumps: VecDeque::new(),
+            jump: 0,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn mark_edited(&mut self) {
+        self.generation += 1;
+        self.modified = true;
+        self.hygiene_stale = true;
+        self.tree_stale = true;
+    }
+
+    pub fn line_ending(&self) -> LineEnding {
+        self.line_ending
+    }
+
+    // After the contents were swapped for what's on disk now, which may have
+    // been converted since.
+    pub fn detect_line_ending(&mut self) {
+        self.line_ending = LineEnding::detect(&self.rope);
+    }
+
+    // Converts every line terminator to `ending`, which lines added from now
+    // on get too. Returns false when it can't be undone.
+    pub fn set_line_ending(&mut self, ending: LineEnding, cursor: (usize, usize)) -> bool {
+        self.line_ending = ending;
+        let text = normalize_newlines(&self.rope.to_string(), ending);
+        if self.rope == text.as_str() {
+            return true;
+        }
+        self.replace_contents(Rope::from_str(&text), true, cursor)
+    }
+
+    // Unless overridden, the language follows the file's extension.
+    pub fn language(&self) -> Language {
+        self.language_override
+            .unwrap_or_else(|| Language::from_extension(self.extension().unwrap_or_default()))
+    }
+
+    pub fn language_override(&self) -> Option<Language> {
+        self.language_override
+    }
+
+    // Drops the tree, so the next parse goes through the deferred path like a
+    // freshly opened buffer.
+    pub fn set_language(&mut self, language: Language) {
+        self.language_override = Some(language);
+        self.tree = None;
+        self.tree_stale = true;
+    }
+
+    // True until the first parse of a buffer that has a grammar.
+    pub fn awaiting_parse(&self) -> bool {
+        self.tree.is_none() && self.language().grammar().is_some()
+    }
+
+    // Char ranges of the syntax errors in the tree, found again only after an
+    // edit or a change of language. None while there's no up-to-date tree.
+    pub fn syntax_errors(&mut self) -> Option<&[Range<usize>]> {
+        let tree = self.tree.as_ref().filter(|_| !self.tree_stale)?;
+        let key = (self.generation, self.language());
+        if self
+            .syntax_errors
+            .as_ref()
+            .is_none_or(|(generation, language, _)| (*generation, *language) != key)
+        {
+            let errors = syntax_errors::find(&self.rope, tree);
+            self.syntax_errors = Some((key.0, key.1, errors));
+        }
+        self.syntax_errors
+            .as_ref()
+            .map(|(_, _, errors)| errors.as_slice())
+    }
+
+    pub fn hygiene(&self) -> Hygiene {
+        self.hygiene
+    }
+
+    // Returns true when the recomputed values differ from the cached ones.
+    pub fn refresh_hygiene(&mut self) -> bool {
+        if !self.hygiene_stale {
+            return false;
+        }
+
+        let final_newline =
+            self.rope.len_chars() == 0 || self.rope.char(self.rope.len_chars() - 1) == '\n';
+        let first_trailing_whitespace = self.rope.lines().position(|line| {
+            let mut chars = line.chars_at(line.len_chars());
+            loop {
+                match chars.prev() {
+                    Some('\r' | '\n') => continue,
+                    last => break matches!(last, Some(' ' | '\t')),
+                }
+            }
+        });
+
+        let previous = self.hygiene;
+        self.hygiene = Hygiene {
+            final_newline,
+            first_trailing_whitespace,
+        };
+        self.hygiene_stale = false;
+
+        previous.final_newline != final_newline
+            || previous.first_trailing_whitespace != first_trailing_whitespace
+    }
+
+    // Strips trailing whitespace from every line and makes the buffer end with
+    // exactly one line terminator. Returns false when it can't be undone; a
+    // buffer with nothing to fix is left alone.
+    pub fn fix_hygiene(&mut self, cursor: (usize, usize)) -> bool {
+        let mut lines: Vec<(String, String)> = self
+            .rope
+            .lines()
+            .map(|line| {
+                let line = line.to_string();
+                let content = line.trim_end_matches(['\r', '\n']);
+                let terminator = line[content.len()..].to_string();
+                (
+                    content.trim_end_matches([' ', '\t']).to_string(),
+                    terminator,
+                )
+            })
+            .collect();
+
+        while lines.len() > 1 && lines.last().is_some_and(|(content, _)| content.is_empty()) {
+            lines.pop();
+        }
+        if let Some((_, terminator)) = lines.last_mut().filter(|(_, t)| t.is_empty()) {
+            terminator.push_str(self.line_ending.as_str());
+        }
+
+        let text: String = lines
+            .iter()
+            .flat_map(|(c, t)| [c.as_str(), t.as_str()])
+            .collect();
+        if self.rope == text.as_str() {
+            return true;
+        }
+        self.replace_contents(Rope::from_str(&text), true, cursor)
+    }
+
+    // Swaps in new contents wholesale, keeping the old ones as an undo
+    // snapshot. Returns false when the buffer was too big to snapshot.
+    pub fn replace_contents(&mut self, rope: Rope, modified: bool, cursor: (usize, usize)) -> bool {
+        let undoable = self.undo.fits(&self.rope);
+        let restore = self.restore(cursor);
+        let (from, previous) = (self.generation, std::mem::replace(&mut self.rope, rope));
+
+        edit_whole_tree(&mut self.tree, &previous, &self.rope);
+        self.mark_edited();
+        self.modified = modified;
+        self.events.push(BufferEvent::Replaced);
+
+        if undoable {
+            self.undo
+                .push(Change::Contents(previous), restore, from, self.generation);
+        } else {
+            self.undo.clear();
+        }
+
+        undoable
+    }
+
+    // For contents read from disk, when opening or following a growing file;
+    // not undoable, and the buffer stays unmodified.
+    pub fn replace_unmodified(&mut self, rope: Rope) {
+        self.line_ending = LineEnding::detect(&rope);
+        let previous = std::mem::replace(&mut self.rope, rope);
+        edit_whole_tree(&mut self.tree, &previous, &self.rope);
+        self.mark_edited();
+        self.modified = false;
+        self.undo.clear();
+        self.events.push(BufferEvent::Replaced);
+    }
+
+    pub fn mark_saved(&mut self) {
+        self.modified = false;
+        self.new_file = false;
+        self.record_disk();
+        self.events.push(BufferEvent::Saved);
+    }
+
+    // Remembers the file as it is now, after the buffer was read from or
+    // written to it.
+    pub fn record_disk(&mut self) {
+        self.disk = self
+            .filename
+            .as_deref()
+            .and_then(|f| Stamp::of(Path::new(f)));
+        self.ignored = None;
+    }
+
+    // How the file looks now when something else changed it since it was
+    // last read or written. A file that's gone isn't a change, as saving just
+    // puts it back.
+    pub fn disk_changed(&self) -> Option<Stamp> {
+        let now = Stamp::of(Path::new(self.filename.as_deref()?))?;
+        let known = self.disk.is_some() || self.new_file;
+        (known && self.disk != Some(now)).then_some(now)
+    }
+
+    // Everything typed or pasted goes in through here, so no insertion can
+    // break the newline convention. Returns the char index just after the text.
+    pub fn insert_text(&mut self, at: usize, text: &str, cursor: (usize, usize)) -> usize {
+        let text = normalize_newlines(text, self.line_ending);
+        self.edit(at..at, &text, cursor);
+        at + text.chars().count()
+    }
+
+    // Replaces `range` with `text` as one undoable edit. `cursor` is where the
+    // cursor was before it, for undo to put back.
+    pub fn edit(&mut self, range: Range<usize>, text: &str, cursor: (usize, usize)) {
+        let removed = self.rope.slice(range.clone()).to_string();
+        let (restore, from) = (self.restore(cursor), self.generation);
+
+        tracing::debug!(
+            at = range.start,
+            removed = range.len(),
+            inserted = text.chars().count(),
+            "edit"
+        );
+        self.rope.remove(range.clone());
+        self.rope.insert(range.start, text);
+        edit_tree(&mut self.tree, &self.rope, range.start, &removed, text);
+        self.mark_edited();
+        self.events.push(BufferEvent::Changed {
+            at: range.start,
+            removed: range.len(),
+            inserted: text.chars().count(),
+        });
+
+        if self.batch.is_some() {
+            return;
+        }
+        let change = Change::Edit {
+            at: range.start,
+            removed,
+            inserted: text.to_string(),
+        };
+        self.undo.push(change, restore, from, self.generation);
+    }
+
+    // Edits from here to end_batch() are undone and redone as one, going back
+    // to a snapshot of the contents from before them. A buffer too big for
+    // the snapshot keeps its edits separate, and a batch begun while another
+    // is open joins it.
+    pub fn begin_batch(&mut self, cursor: (usize, usize)) {
+        if self.batch.is_none() && self.undo.fits(&self.rope) {
+            self.batch = Some((self.rope.clone(), self.restore(cursor), self.generation));
+        }
+    }
+
+    pub fn end_batch(&mut self) {
+        if let Some((rope, restore, from)) = self.batch.take() {
+            if from != self.generation {
+                self.undo
+                    .push(Change::Contents(rope), restore, from, self.generation);
+            }
+        }
+    }
+
+    fn restore(&self, cursor: (usize, usize)) -> Restore {
+        Restore {
+            modified: self.modified,
+            scroll: self.scroll,
+            cursor,
+        }
+    }
+
+    // Both return the (line, char column) to put the cursor back at; `cursor` is
+    // where it is now, for when the step is taken back again.
+    pub fn undo(&mut self, cursor: (usize, usize)) -> Result<(usize, usize), UndoError> {
+        self.travel(false, cursor)
+    }
+
+    pub fn redo(&mut self, cursor: (usize, usize)) -> Result<(usize, usize), UndoError> {
+        self.travel(true, cursor)
+    }
+
+    pub fn discard_redo(&mut self) {
+        self.undo.discard_redo();
+    }
+
+    fn travel(&mut self, redo: bool, cursor: (usize, usize)) -> Result<(usize, usize), UndoError> {
+        let current = self.restore(cursor);
+        // mark_edited below moves the generation on by one.
+        let (generation, next) = (self.generation, self.generation + 1);
+        let (restore, applied) = if redo {
+            self.undo.redo(&mut self.rope, current, generation, next)?
+        } else {
+            self.undo.undo(&mut self.rope, current, generation, next)?
+        };
+
+        // What just went onto the other stack, now describing the change made.
+        let event = match applied {
+            Change::Contents(previous) => {
+                edit_whole_tree(&mut self.tree, previous, &self.rope);
+                // The contents going back may be from before a conversion.
+                self.line_ending = LineEnding::detect(&self.rope);
+                BufferEvent::Replaced
+            }
+            Change::Edit {
+                at,
+                removed,
+                inserted,
+            } => {
+                edit_tree(&mut self.tree, &self.rope, *at, removed, inserted);
+                BufferEvent::Changed {
+                    at: *at,
+                    removed: removed.chars().count(),
+                    inserted: inserted.chars().count(),
+                }
+            }
+        };
+        tracing::debug!(redo, ?event, "undo step");
+        self.events.push(event);
+        self.mark_edited();
+        self.modified = restore.modified;
+        self.scroll = restore.scroll;
+
+        Ok(restore.cursor)
+    }
+
+    // Changes every time the contents do, including through undo.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    // Further edits on the same line only move its entry, so typing a word
+    // doesn't push out everything older.
+    pub fn record_edit(&mut self, line: usize, column: usize) {
+        if self.edits.back().is_some_and(|&(l, _)| l == line) {
+            self.edits.pop_back();
+        }
+        if self.edits.len() == MAX_EDIT_POSITIONS {
+            self.edits.pop_front();
+        }
+        self.edits.push_back((line, column));
+    }
+
+    pub fn edit_count(&self) -> usize {
+        self.edits.len()
+    }
+
+    // The `back`th most recent edit position, clamped to the current contents
+    // since lines may have gone away since it was recorded.
+    pub fn edit_position(&self, back: usize) -> Option<(usize, usize)> {
+        let &(line, column) = self.edits.iter().rev().nth(back)?;
+        let line = line.min(self.line_count().saturating_sub(1));
+        let length = lines::line_content_range(&self.rope, line).len();
+
+        Some((line, column.min(length)))
+    }
+
+    // A jump from where an earlier one was stepped back to drops the ones
+    // after it, like a browser's history. Jumping again from the same place
+    // doesn't add it twice.
+    pub fn record_jump(&mut self, from: (usize, usize)) {
+        self.jumps.truncate(self.jump);
+        if self.jumps.back() != Some(&from) {
+            if self.jumps.len() == MAX_JUMPS {
+                self.jumps.pop_front();
+            }
+            self.jumps.push_back(from);
+        }
+        self.jump = self.jumps.len();
+    }
+
+    // Steps back or forward through the jumps. The first step back also
+    // keeps `cursor`, so stepping forward again returns there. Returns where
+    // to go, clamped to the current contents, with its place in the list
+    // (from 1) and the list's length.
+    pub fn step_jump(
+        &mut self,
+        forward: bool,
+        cursor: (usize, usize),
+    ) -> Option<((usize, usize), usize, usize)> {
+        if forward {
+            if self.jump + 1 >= self.jumps.len() {
+                return None;
+            }
+            self.jump += 1;
+        } else {
+            if self.jumps.is_empty() {
+                return None;
+            }
+            if self.jump == self.jumps.len() {
+                self.record_jump(cursor);
+                self.jump = self.jumps.len() - 1;
+            }
+            self.jump = self.jump.checked_sub(1)?;
+        }
+
+        let (line, column) = self.jumps[self.jump];
+        let line = line.min(self.line_count().saturating_sub(1));
+        let length = lines::line_content_range(&self.rope, line).len();
+        Some(((line, column.min(length)), self.jump + 1, self.jumps.len()))
+    }
+
+    pub fn set_undo_limits(&mut self, limits: UndoLimits) {
+        self.undo.set_limits(limits);
+    }
+
+    // Number of undo entries and their approximate size in bytes.
+    pub fn undo_usage(&self) -> (usize, usize) {
+        (self.undo.len(), self.undo.bytes())
+    }
+
+    pub fn scratch() -> Self {
+        Self::new(Rope::from_str("\n"), None)
+    }
+
+    pub fn display_name(&self) -> &str {
+        self.filename.as_deref().unwrap_or("[scratch]")
+    }
+
+    fn extension(&self) -> Option<&str> {
+        self.filename
+            .as_deref()
+            .and_then(|f| Path::new(f).extension())
+            .and_then(|e| e.to_str())
+    }
+
+    // Info string for Markdown code fences.
+    pub fn language_tag(&self) -> &str {
+        match self.extension().unwrap_or("") {
+            "rs" => "rust",
+            extension => extension,
+        }
+    }
+
+    pub fn line_count(&self) -> usize {
+        lines::count(&self.rope)
+    }
+}
+
+// Owns every open buffer and which one is being edited. There is always at
+// least one buffer; closing the last one replaces it with a scratch buffer.
+pub struct BufferManager {
+    buffers: Vec<Buffer>,
+    active: usize,
+    // Indices of every buffer, most recently active first.
+    recent: Vec<usize>,
+    // Events with the index their buffer had at the time, oldest first.
+    events: Vec<(usize, BufferEvent)>,
+}
+
+impl BufferManager {
+    pub fn new(buffers: Vec<Buffer>) -> Self {
+        let mut manager = Self {
+            recent: (0..buffers.len().max(1)).collect(),
+            buffers,
+            active: 0,
+            events: Vec::new(),
+        };
+        if manager.buffers.is_empty() {
+            manager.buffers.push(Buffer::scratch());
+        }
+        manager.events = (0..manager.buffers.len())
+            .map(|i| (i, BufferEvent::Opened))
+            .collect();
+        manager
+    }
+
+    pub fn active(&self) -> &Buffer {
+        &self.buffers[self.active]
+    }
+
+    pub fn active_mut(&mut self) -> &mut Buffer {
+        &mut self.buffers[self.active]
+    }
+
+    pub fn active_index(&self) -> usize {
+        self.active
+    }
+
+    pub fn len(&self) -> usize {
+        self.buffers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffers.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Buffer> {
+        self.buffers.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Buffer> {
+        self.buffers.iter_mut()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Buffer> {
+        self.buffers.get(index)
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut Buffer> {
+        self.buffers.get_mut(index)
+    }
+
+    pub fn switch(&mut self, index: usize) {
+        if index < self.buffers.len() {
+            self.active = index;
+            self.recent.retain(|&i| i != index);
+            self.recent.insert(0, index);
+        }
+    }
+
+    // The buffer that was active before this one, if there's another.
+    pub fn alternate(&self) -> Option<usize> {
+        self.recent.get(1).copied()
+    }
+
+    pub fn close(&mut self, index: usize) {
+        if index >= self.buffers.len() {
+            return;
+        }
+
+        // Whatever the buffers queued happened before the close, under the
+        // indices they had then.
+        self.collect_events();
+        self.events.push((index, BufferEvent::Closed));
+
+        self.buffers.remove(index);
+        self.recent.retain(|&i| i != index);
+        for i in &mut self.recent {
+            if *i > index {
+                *i -= 1;
+            }
+        }
+
+        if self.buffers.is_empty() {
+            self.buffers.push(Buffer::scratch());
+            self.recent.push(0);
+            self.events.push((0, BufferEvent::Opened));
+        }
+
+        // Closing the active buffer falls back to the one used most recently.
+        if index == self.active {
+            self.active = self.recent[0];
+        } else if index < self.active {
+            self.active -= 1;
+        }
+    }
+
+    // Events since the last call, in order for each buffer and relative to
+    // opening and closing.
+    pub fn take_events(&mut self) -> Vec<(usize, BufferEvent)> {
+        self.collect_events();
+        std::mem::take(&mut self.events)
+    }
+
+    fn collect_events(&mut self) {
+        for (index, buffer) in self.buffers.iter_mut().enumerate() {
+            self.events
+                .extend(buffer.events.drain(..).map(|event| (index, event)));
+        }
+    }
+}
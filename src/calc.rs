@@ -0,0 +1,234 @@
+use std::{fmt, iter::Peekable, str::Chars};
+
+// Integers stay exact until a float literal or an inexact division shows up.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Number {
+    Int(i64),
+    Float(f64),
+}
+
+impl Number {
+    fn as_float(self) -> f64 {
+        match self {
+            Number::Int(n) => n as f64,
+            Number::Float(n) => n,
+        }
+    }
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Number::Int(n) => write!(f, "{n}"),
+            Number::Float(n) => write!(f, "{n}"),
+        }
+    }
+}
+
+// Evaluates `+ - * /` over integer and float literals, with parentheses and
+// unary signs. Whitespace between tokens is ignored.
+pub fn evaluate(expression: &str) -> Result<Number, String> {
+    let mut parser = Parser {
+        chars: expression.chars().peekable(),
+    };
+    let value = parser.expression()?;
+
+    match parser.peek() {
+        None => Ok(value),
+        Some(c) => Err(format!("unexpected '{c}'")),
+    }
+}
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl Parser<'_> {
+    fn peek(&mut self) -> Option<char> {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+        self.chars.peek().copied()
+    }
+
+    fn expression(&mut self) -> Result<Number, String> {
+        let mut value = self.term()?;
+
+        while let Some(op @ ('+' | '-')) = self.peek() {
+            self.chars.next();
+            value = apply(op, value, self.term()?)?;
+        }
+
+        Ok(value)
+    }
+
+    fn term(&mut self) -> Result<Number, String> {
+        let mut value = self.factor()?;
+
+        while let Some(op @ ('*' | '/')) = self.peek() {
+            self.chars.next();
+            value = apply(op, value, self.factor()?)?;
+        }
+
+        Ok(value)
+    }
+
+    fn factor(&mut self) -> Result<Number, String> {
+        match self.peek() {
+            Some('-') => {
+                self.chars.next();
+                apply('-', Number::Int(0), self.factor()?)
+            }
+            Some('+') => {
+                self.chars.next();
+                self.factor()
+            }
+            Some('(') => {
+                self.chars.next();
+                let value = self.expression()?;
+                match self.peek() {
+                    Some(')') => {
+                        self.chars.next();
+                        Ok(value)
+                    }
+                    _ => Err("missing ')'".to_string()),
+                }
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => self.number(),
+            Some(c) => Err(format!("unexpected '{c}'")),
+            None => Err("unexpected end of expression".to_string()),
+        }
+    }
+
+    fn number(&mut self) -> Result<Number, String> {
+        let mut literal = String::new();
+        while let Some(c) = self.chars.next_if(|c| c.is_ascii_digit() || *c == '.') {
+            literal.push(c);
+        }
+
+        if let Ok(n) = literal.parse::<i64>() {
+            return Ok(Number::Int(n));
+        }
+        literal
+            .parse::<f64>()
+            .map(Number::Float)
+            .map_err(|_| format!("invalid number '{literal}'"))
+    }
+}
+
+fn apply(op: char, a: Number, b: Number) -> Result<Number, String> {
+    if op == '/' && b.as_float() == 0.0 {
+        return Err("division by zero".to_string());
+    }
+
+    if let (Number::Int(a), Number::Int(b)) = (a, b) {
+        let exact = match op {
+            '+' => a.checked_add(b),
+            '-' => a.checked_sub(b),
+            '*' => a.checked_mul(b),
+            _ => (a.checked_rem(b) == Some(0))
+                .then(|| a.checked_div(b))
+                .flatten(),
+        };
+        if let Some(n) = exact {
+            return Ok(Number::Int(n));
+        }
+    }
+
+    let (a, b) = (a.as_float(), b.as_float());
+    let value = match op {
+        '+' => a + b,
+        '-' => a - b,
+        '*' => a * b,
+        _ => a / b,
+    };
+
+    if value.is_finite() {
+        Ok(Number::Float(value))
+    } else {
+        Err("result out of range".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn int(expression: &str) -> i64 {
+        match evaluate(expression) {
+            Ok(Number::Int(n)) => n,
+            other => panic!("{expression}: {other:?}"),
+        }
+    }
+
+    fn error(expression: &str) -> String {
+        evaluate(expression).expect_err(expression)
+    }
+
+    #[test]
+    fn multiplication_and_division_bind_tighter() {
+        assert_eq!(int("1 + 2 * 3"), 7);
+        assert_eq!(int("10 - 4 - 3"), 3);
+        assert_eq!(int("2 * 3 + 4 * 5"), 26);
+        assert_eq!(int("24 / 4 / 2"), 3);
+    }
+
+    #[test]
+    fn parentheses_group() {
+        assert_eq!(int("(1 + 2) * 3"), 9);
+        assert_eq!(int("((2))"), 2);
+        assert_eq!(int("2 * (3 - (4 - 1))"), 0);
+        assert_eq!(error("(1 + 2"), "missing ')'");
+        assert_eq!(error("1 + 2)"), "unexpected ')'");
+    }
+
+    #[test]
+    fn unary_signs() {
+        assert_eq!(int("-3 * -2"), 6);
+        assert_eq!(int("-(2 + 3)"), -5);
+        assert_eq!(int("2 - -1"), 3);
+        assert_eq!(int("+4"), 4);
+    }
+
+    #[test]
+    fn integers_stay_exact_until_a_float_turns_up() {
+        assert_eq!(evaluate("1920*1080/8"), Ok(Number::Int(259200)));
+        assert_eq!(evaluate("7/2"), Ok(Number::Float(3.5)));
+        assert_eq!(evaluate("1.5 * 2"), Ok(Number::Float(3.0)));
+        assert_eq!(evaluate(".5 + 1"), Ok(Number::Float(1.5)));
+        assert_eq!(Number::Float(3.0).to_string(), "3");
+        assert_eq!(Number::Float(3.5).to_string(), "3.5");
+    }
+
+    #[test]
+    fn division_by_zero_is_an_error() {
+        for expression in ["1/0", "1/0.0", "1/(2-2)", "0/0"] {
+            assert_eq!(error(expression), "division by zero", "{expression}");
+        }
+    }
+
+    #[test]
+    fn anything_left_over_is_an_error() {
+        assert_eq!(error("1 + 2 x"), "unexpected 'x'");
+        assert_eq!(error("2 3"), "unexpected '3'");
+        assert_eq!(error("1 +"), "unexpected end of expression");
+        assert_eq!(error(""), "unexpected end of expression");
+        assert_eq!(error("1..2"), "invalid number '1..2'");
+    }
+
+    #[test]
+    fn integer_overflow_falls_back_to_floats() {
+        assert_eq!(
+            evaluate("9223372036854775807 + 1"),
+            Ok(Number::Float(9_223_372_036_854_775_808.0))
+        );
+        assert_eq!(
+            evaluate("-9223372036854775807 - 2"),
+            Ok(Number::Float(-9_223_372_036_854_775_809.0))
+        );
+        assert_eq!(evaluate("99999999999999999999"), Ok(Number::Float(1e20)));
+        assert_eq!(int("3037000499 * 3037000499"), 9_223_372_030_926_249_001);
+        assert_eq!(
+            evaluate("3037000500 * 3037000500"),
+            Ok(Number::Float(9_223_372_037_000_250_000.0))
+        );
+    }
+}
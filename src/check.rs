@@ -0,0 +1,125 @@
+use std::{
+    io::{self, BufRead, BufReader},
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+};
+
+use serde_json::Value;
+
+pub struct CheckDiagnostic {
+    pub file: PathBuf,
+    // Zero-based, like the rest of the editor.
+    pub line: usize,
+    pub column: usize,
+    pub level: String,
+    pub message: String,
+}
+
+pub enum Outcome {
+    Diagnostics(Vec<CheckDiagnostic>),
+    // Shown as is when cargo's output couldn't be understood.
+    Raw(String),
+}
+
+// A `cargo check` running in the background. Dropping it without calling
+// cancel() leaves the process to finish on its own.
+pub struct Check {
+    child: Child,
+    receiver: Receiver<(Vec<CheckDiagnostic>, Option<String>)>,
+}
+
+impl Check {
+    // Returns None without running anything when `root` isn't a cargo project.
+    pub fn start(root: &Path) -> io::Result<Option<Self>> {
+        if !root.join("Cargo.toml").is_file() {
+            return Ok(None);
+        }
+
+        let mut child = Command::new("cargo")
+            .args(["check", "--message-format=json"])
+            .current_dir(root)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        let stdout = child.stdout.take().unwrap();
+        let stderr = child.stderr.take().unwrap();
+        let root = root.to_path_buf();
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            // stderr is drained alongside stdout so neither pipe can fill up and stall cargo.
+            let first_error = thread::spawn(move || {
+                BufReader::new(stderr)
+                    .lines()
+                    .map_while(Result::ok)
+                    .find(|line| line.trim_start().starts_with("error"))
+            });
+
+            let mut diagnostics = Vec::new();
+            let mut unparsed = None;
+
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                match serde_json::from_str::<Value>(&line) {
+                    Ok(message) => diagnostics.extend(parse(&root, &message)),
+                    Err(_) => {
+                        if unparsed.is_none() && line.contains("error") {
+                            unparsed = Some(line);
+                        }
+                    }
+                }
+            }
+
+            let raw = unparsed.or_else(|| first_error.join().ok().flatten());
+            let _ = sender.send((diagnostics, raw));
+        });
+
+        Ok(Some(Self { child, receiver }))
+    }
+
+    pub fn try_finish(&mut self) -> Option<Outcome> {
+        let (diagnostics, raw) = match self.receiver.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Disconnected) => (Vec::new(), None),
+        };
+
+        let failed = !self.child.wait().is_ok_and(|status| status.success());
+
+        Some(match raw {
+            Some(raw) if failed && diagnostics.is_empty() => Outcome::Raw(raw),
+            None if failed && diagnostics.is_empty() => {
+                Outcome::Raw("cargo check failed".to_string())
+            }
+            _ => Outcome::Diagnostics(diagnostics),
+        })
+    }
+
+    pub fn cancel(mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn parse(root: &Path, message: &Value) -> Option<CheckDiagnostic> {
+    if message["reason"] != "compiler-message" {
+        return None;
+    }
+
+    let message = &message["message"];
+    let span = message["spans"]
+        .as_array()?
+        .iter()
+        .find(|span| span["is_primary"] == true)?;
+
+    Some(CheckDiagnostic {
+        file: root.join(span["file_name"].as_str()?),
+        line: (span["line_start"].as_u64()? as usize).saturating_sub(1),
+        column: (span["column_start"].as_u64()? as usize).saturating_sub(1),
+        level: message["level"].as_str()?.to_string(),
+        message: message["message"].as_str()?.to_string(),
+    })
+}
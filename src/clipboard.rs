@@ -0,0 +1,77 @@
+use std::io::Write;
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+
+    out
+}
+
+// Sets the system clipboard through the OSC 52 escape sequence, which most
+// terminals (and tmux, over ssh) forward to the host clipboard.
+pub fn copy(out: &mut impl Write, text: &str) -> std::io::Result<()> {
+    write!(out, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+    out.flush()
+}
+
+pub fn markdown_fence(text: &str, language: &str) -> String {
+    let fence = if text.contains("```") { "````" } else { "```" };
+    let newline = if text.ends_with('\n') { "" } else { "\n" };
+
+    format!("{fence}{language}\n{text}{newline}{fence}\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_pads_to_whole_groups() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+        assert_eq!(base64("é".as_bytes()), "w6k=");
+    }
+
+    #[test]
+    fn copy_writes_one_osc_52_sequence() {
+        let mut out = Vec::new();
+        copy(&mut out, "hi").unwrap();
+
+        assert_eq!(out, b"\x1b]52;c;aGk=\x07");
+    }
+
+    #[test]
+    fn fences_end_on_their_own_line() {
+        assert_eq!(
+            markdown_fence("let x = 1;", "rust"),
+            "```rust\nlet x = 1;\n```\n"
+        );
+        assert_eq!(markdown_fence("a\n", ""), "```\na\n```\n");
+    }
+
+    #[test]
+    fn fences_are_longer_than_any_inside() {
+        assert_eq!(markdown_fence("```", "md"), "````md\n```\n````\n");
+    }
+}
@@ -0,0 +1,174 @@
+use std::{
+    borrow::Cow,
+    ops::Range,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use ropey::RopeSlice;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+// Conversions between terminal columns and char offsets within a line. The
+// cursor always sits on a grapheme boundary, and every grapheme takes up as
+// many columns as it is displayed with (two for most CJK text and emoji, none
+// for zero-width characters).
+
+// Explicit BiDi controls silently reorder the text around them, which can make
+// source code read differently from what it does. They're drawn as this
+// placeholder instead, one column wide.
+const BIDI_PLACEHOLDER: char = '\u{FFFD}';
+
+// Columns a tab takes. It's drawn as that many spaces wherever it is, rather
+// than reaching to the next tab stop, so a piece of a line is as wide on its
+// own as it is in place.
+static TAB_WIDTH: AtomicUsize = AtomicUsize::new(4);
+
+pub fn set_tab_width(width: usize) {
+    TAB_WIDTH.store(width.max(1), Ordering::Relaxed);
+}
+
+pub fn tab_width() -> usize {
+    TAB_WIDTH.load(Ordering::Relaxed)
+}
+
+fn is_bidi_control(c: char) -> bool {
+    matches!(
+        c,
+        '\u{061C}' | '\u{200E}' | '\u{200F}' | '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}'
+    )
+}
+
+fn grapheme_width(grapheme: &str) -> usize {
+    if grapheme == "\t" {
+        return tab_width();
+    }
+    grapheme.width() + grapheme.chars().filter(|&c| is_bidi_control(c)).count()
+}
+
+// The text as it's drawn, with BiDi controls made visible and tabs expanded.
+pub fn display(text: &str) -> Cow<'_, str> {
+    if text.chars().any(|c| c == '\t' || is_bidi_control(c)) {
+        let mut shown = String::with_capacity(text.len());
+        for c in text.chars() {
+            match c {
+                '\t' => shown.extend(std::iter::repeat_n(' ', tab_width())),
+                c if is_bidi_control(c) => shown.push(BIDI_PLACEHOLDER),
+                c => shown.push(c),
+            }
+        }
+        Cow::Owned(shown)
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+// Right-to-left scripts may be laid out by the terminal in an order the column
+// math knows nothing about.
+pub fn is_rtl(c: char) -> bool {
+    matches!(
+        c,
+        '\u{0590}'..='\u{08FF}'
+            | '\u{FB1D}'..='\u{FDFF}'
+            | '\u{FE70}'..='\u{FEFC}'
+            | '\u{10800}'..='\u{10FFF}'
+            | '\u{1E800}'..='\u{1EFFF}'
+    )
+}
+
+// The line without its terminator.
+pub fn content(line: RopeSlice) -> String {
+    line.to_string().trim_end_matches(['\r', '\n']).to_string()
+}
+
+pub fn width(text: &str) -> usize {
+    text.graphemes(true).map(grapheme_width).sum()
+}
+
+pub fn line_width(line: RopeSlice) -> usize {
+    width(&content(line))
+}
+
+// The part of `text`, a piece of one line starting at display column `start`,
+// that falls within the columns of `window`. A wide grapheme cut by an edge
+// shows as spaces for the part inside.
+pub fn clip(text: &str, start: usize, window: Range<usize>) -> Cow<'_, str> {
+    if start >= window.start && start + width(text) <= window.end {
+        return Cow::Borrowed(text);
+    }
+
+    let mut clipped = String::new();
+    let mut column = start;
+
+    for grapheme in text.graphemes(true) {
+        if column >= window.end {
+            break;
+        }
+
+        let end = column + grapheme_width(grapheme);
+        if column >= window.start && end <= window.end {
+            clipped.push_str(grapheme);
+        } else {
+            let shown = end.min(window.end).saturating_sub(column.max(window.start));
+            clipped.extend(std::iter::repeat_n(' ', shown));
+        }
+        column = end;
+    }
+
+    Cow::Owned(clipped)
+}
+
+// Char offset of the grapheme at `column`; a column inside a wide grapheme snaps
+// to its start, and columns past the end snap to the end of the line.
+pub fn column_to_char(line: RopeSlice, column: usize) -> usize {
+    let mut current = 0;
+    let mut offset = 0;
+
+    for grapheme in content(line).graphemes(true) {
+        let next = current + grapheme_width(grapheme);
+        if next > column {
+            break;
+        }
+        current = next;
+        offset += grapheme.chars().count();
+    }
+
+    offset
+}
+
+pub fn char_to_column(line: RopeSlice, char_offset: usize) -> usize {
+    let text = content(line);
+    let end = text
+        .char_indices()
+        .nth(char_offset)
+        .map_or(text.len(), |(i, _)| i);
+    width(&text[..end])
+}
+
+// Start column of the grapheme before `column`.
+pub fn previous_column(line: RopeSlice, column: usize) -> usize {
+    let mut current = 0;
+
+    for grapheme in content(line).graphemes(true) {
+        let next = current + grapheme_width(grapheme);
+        if next >= column {
+            break;
+        }
+        current = next;
+    }
+
+    current
+}
+
+// Start column of the grapheme after the one at `column`.
+pub fn next_column(line: RopeSlice, column: usize) -> usize {
+    let mut current = 0;
+
+    for grapheme in content(line).graphemes(true) {
+        current += grapheme_width(grapheme);
+        if current > column {
+            break;
+        }
+    }
+
+    current
+}
@@ -0,0 +1,109 @@
+use std::{collections::BTreeMap, env, fs, io, path::PathBuf};
+
+use crossterm::style::Color;
+use serde::Deserialize;
+
+// The optional config file. Everything in it can be left out, and command-line
+// flags win over it. Bindings take the action names --list-actions prints and
+// the same chords as --bind:
+//
+//     [keybindings]
+//     save-as = "ctrl+shift+s"
+//
+//     [editor]
+//     tab_width = 2
+//     use_spaces = true
+//     show_line_numbers = false
+//     backup = true
+//     trim_trailing_whitespace_on_save = true
+//     wrap = true
+//     scroll_margin = 5
+//     wheel_scroll_lines = 3
+//     page_overlap_lines = 2
+//     half_page = false
+//     auto_pairs = true
+//     altgr_inserts = true
+//     paste_guard = true
+//     paste_max_kb = 100
+//     paste_max_lines = 5000
+//
+//     [theme]
+//     keyword = "dark_magenta"
+//     comment = "#6a737d"
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub keybindings: BTreeMap<String, String>,
+    pub editor: EditorOptions,
+    pub theme: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct EditorOptions {
+    pub tab_width: Option<u16>,
+    pub use_spaces: Option<bool>,
+    pub show_line_numbers: Option<bool>,
+    pub backup: Option<bool>,
+    pub trim_trailing_whitespace_on_save: Option<bool>,
+    pub wrap: Option<bool>,
+    pub scroll_margin: Option<usize>,
+    pub wheel_scroll_lines: Option<usize>,
+    pub page_overlap_lines: Option<usize>,
+    pub half_page: Option<bool>,
+    pub auto_pairs: Option<bool>,
+    pub altgr_inserts: Option<bool>,
+    pub paste_guard: Option<bool>,
+    pub paste_max_kb: Option<usize>,
+    pub paste_max_lines: Option<usize>,
+}
+
+pub fn path() -> Option<PathBuf> {
+    match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir).join("shark/config.toml")),
+        _ => env::var_os("HOME").map(|home| PathBuf::from(home).join(".config/shark/config.toml")),
+    }
+}
+
+// No file is the same as an empty one. A file that can't be read or parsed is
+// reported and ignored as a whole, so a typo never keeps the editor from
+// starting.
+pub fn load() -> Result<Config, String> {
+    let Some(path) = path() else {
+        return Ok(Config::default());
+    };
+
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Config::default()),
+        Err(error) => return Err(format!("{}: {error}", path.display())),
+    };
+
+    // The error's own Display spans several lines to point at the mistake,
+    // which the status bar has no room for.
+    toml::from_str(&text).map_err(|error| {
+        let line = error
+            .span()
+            .map_or(1, |span| text[..span.start].matches('\n').count() + 1);
+        let message: Vec<&str> = error.message().lines().collect();
+        format!("{} line {line}: {}", path.display(), message.join("; "))
+    })
+}
+
+// A name crossterm knows, like `dark_red`, or `#rrggbb`.
+pub fn parse_color(color: &str) -> Option<Color> {
+    if let Some(hex) = color.strip_prefix('#') {
+        if hex.len() != 6 || !hex.is_ascii() {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+        return Some(Color::Rgb {
+            r: channel(0)?,
+            g: channel(2)?,
+            b: channel(4)?,
+        });
+    }
+
+    Color::try_from(color).ok()
+}
@@ -0,0 +1,79 @@
+use tree_sitter::{Node, Tree};
+
+pub struct Diagnostic {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+const SNIPPET_LEN: usize = 40;
+
+// Collects ERROR and MISSING nodes from the tree as diagnostics, in document order.
+pub fn syntax_errors(tree: &Tree, source: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    collect(tree.root_node(), source, &mut diagnostics);
+    diagnostics
+}
+
+fn collect(node: Node, source: &str, diagnostics: &mut Vec<Diagnostic>) {
+    if !node.has_error() {
+        return;
+    }
+
+    let message = if node.is_missing() {
+        Some(format!("syntax error: missing `{}`", node.kind()))
+    } else if node.is_error() {
+        let text = source.get(node.byte_range()).unwrap_or_default();
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        let snippet: String = text.chars().take(SNIPPET_LEN).collect();
+        let ellipsis = if snippet.len() < text.len() {
+            "…"
+        } else {
+            ""
+        };
+
+        Some(format!(
+            "syntax error: unexpected `{snippet}{ellipsis}` while parsing {}",
+            node.parent().map_or("the file", |parent| parent.kind())
+        ))
+    } else {
+        None
+    };
+
+    if let Some(message) = message {
+        diagnostics.push(Diagnostic {
+            line: node.start_position().row,
+            column: node.start_position().column,
+            message,
+        });
+    }
+
+    for child in node.children(&mut node.walk()) {
+        collect(child, source, diagnostics);
+    }
+}
+
+// Greedy word wrap used for popups. A word longer than a whole line is broken
+// across lines, so nothing is drawn past the popup's edge.
+pub fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = vec![String::new()];
+    let chars: Vec<Vec<char>> = text
+        .split_whitespace()
+        .map(|w| w.chars().collect())
+        .collect();
+
+    for word in chars.iter().flat_map(|word| word.chunks(width.max(1))) {
+        let line = lines.last_mut().unwrap();
+
+        if !line.is_empty() && line.chars().count() + 1 + word.len() > width {
+            lines.push(word.iter().collect());
+        } else {
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.extend(word);
+        }
+    }
+
+    lines
+}
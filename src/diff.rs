@@ -0,0 +1,120 @@
+use std::time::Duration;
+
+use similar::{ChangeTag, TextDiff};
+
+// Unified diffs between a buffer and its file on disk, as lines ready to show.
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Header,
+    Hunk,
+    Context,
+    Added,
+    Removed,
+    Note,
+}
+
+pub struct Line {
+    pub kind: Kind,
+    pub text: String,
+}
+
+// Past this many lines the rest of the diff is left out.
+const MAX_LINES: usize = 5000;
+// After this long the diff settles for a coarser answer instead of freezing
+// the editor on a large file.
+const TIMEOUT: Duration = Duration::from_millis(500);
+// How much of the file is looked at to tell whether it's text.
+const SNIFF_BYTES: usize = 8000;
+
+// Appends the diff from `disk` (None when the file doesn't exist) to `buffer`
+// under a header naming `name`.
+pub fn unified(name: &str, disk: Option<&[u8]>, buffer: &str, lines: &mut Vec<Line>) {
+    let line = |kind, text: String| Line { kind, text };
+    // Every buffer's diff goes into the same lines, and each gets the limit.
+    let start = lines.len();
+    let old_name = if disk.is_some() { name } else { "/dev/null" };
+    lines.push(line(Kind::Header, format!("--- {old_name} (on disk)")));
+    lines.push(line(Kind::Header, format!("+++ {name} (buffer)")));
+
+    let disk = disk.unwrap_or_default();
+    let text = match std::str::from_utf8(disk) {
+        Ok(text) if !disk[..disk.len().min(SNIFF_BYTES)].contains(&0) => text,
+        _ => {
+            lines.push(line(
+                Kind::Note,
+                "Binary file on disk, files differ".to_string(),
+            ));
+            return;
+        }
+    };
+
+    let diff = TextDiff::configure()
+        .timeout(TIMEOUT)
+        .diff_lines(text, buffer);
+    let headers = lines.len();
+
+    for hunk in diff.unified_diff().iter_hunks() {
+        lines.push(line(Kind::Hunk, hunk.header().to_string()));
+
+        for change in hunk.iter_changes() {
+            if lines.len() - start >= MAX_LINES {
+                lines.push(line(
+                    Kind::Note,
+                    format!("Diff truncated after {MAX_LINES} lines"),
+                ));
+                return;
+            }
+
+            let (kind, sign) = match change.tag() {
+                ChangeTag::Equal => (Kind::Context, ' '),
+                ChangeTag::Insert => (Kind::Added, '+'),
+                ChangeTag::Delete => (Kind::Removed, '-'),
+            };
+            let value = change.value().trim_end_matches(['\n', '\r']);
+            lines.push(line(kind, format!("{sign}{value}")));
+
+            if change.missing_newline() {
+                lines.push(line(Kind::Note, "\\ No newline at end of file".to_string()));
+            }
+        }
+    }
+
+    if lines.len() == headers {
+        lines.push(line(Kind::Note, "No differences from disk".to_string()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn truncated(lines: &[Line]) -> bool {
+        lines
+            .iter()
+            .any(|line| line.text.starts_with("Diff truncated"))
+    }
+
+    #[test]
+    fn a_long_diff_is_truncated() {
+        let buffer = "added\n".repeat(MAX_LINES);
+        let mut lines = Vec::new();
+        unified("long.txt", Some(b""), &buffer, &mut lines);
+        assert!(truncated(&lines));
+        assert_eq!(lines.len(), MAX_LINES + 1);
+    }
+
+    #[test]
+    fn each_buffer_gets_the_limit_to_itself() {
+        let mut lines = Vec::new();
+        let first = "added\n".repeat(MAX_LINES - 10);
+        unified("first.txt", Some(b""), &first, &mut lines);
+        assert!(!truncated(&lines));
+
+        let start = lines.len();
+        unified("second.txt", Some(b"old\n"), "new\n", &mut lines);
+        assert!(!truncated(&lines));
+        assert_eq!(lines[start].text, "--- second.txt (on disk)");
+        assert_eq!(lines.last().unwrap().text, "+new");
+    }
+}
@@ -0,0 +1,3 @@
+pub mod lines;
+pub mod target;
+pub mod words;
@@ -0,0 +1,295 @@
+use std::ops::Range;
+
+use ropey::Rope;
+
+// Line-range primitives over the rope. A line's terminator is whatever the
+// file actually has (`\n`, `\r\n`, or none on an unterminated last line), and
+// a trailing newline doesn't start an extra, empty line.
+
+const DEFAULT_TERMINATOR: &str = "\n";
+
+pub fn count(rope: &Rope) -> usize {
+    let lines = rope.len_lines();
+    if rope.line(lines - 1).len_chars() == 0 {
+        lines - 1
+    } else {
+        lines
+    }
+}
+
+// Chars of `line` including its terminator.
+pub fn line_char_range(rope: &Rope, line: usize) -> Range<usize> {
+    rope.line_to_char(line)..rope.line_to_char(line + 1)
+}
+
+// Chars of `line` without its terminator.
+pub fn line_content_range(rope: &Rope, line: usize) -> Range<usize> {
+    let range = line_char_range(rope, line);
+    range.start..range.start + content_len(rope, line)
+}
+
+fn content_len(rope: &Rope, line: usize) -> usize {
+    let slice = rope.line(line);
+    let mut len = slice.len_chars();
+    let mut chars = slice.chars_at(len);
+
+    while let Some('\r' | '\n') = chars.prev() {
+        len -= 1;
+    }
+
+    len
+}
+
+pub fn terminator(rope: &Rope, line: usize) -> String {
+    let range = line_char_range(rope, line);
+    rope.slice(line_content_range(rope, line).end..range.end)
+        .to_string()
+}
+
+// The terminator new lines should get: the one of the nearest terminated line.
+pub fn preferred_terminator(rope: &Rope, line: usize) -> String {
+    let lines = count(rope);
+    let line = line.min(lines.saturating_sub(1));
+
+    (line..lines)
+        .chain((0..line).rev())
+        .map(|l| terminator(rope, l))
+        .find(|t| !t.is_empty())
+        .unwrap_or_else(|| DEFAULT_TERMINATOR.to_string())
+}
+
+// The chars removing whole lines takes out. Removing the last line of a file
+// without a final newline also drops the terminator before it, so no empty
+// line is left behind.
+pub fn removal(rope: &Rope, lines: Range<usize>) -> Range<usize> {
+    if lines.is_empty() {
+        return 0..0;
+    }
+
+    let total = count(rope);
+    let mut start = rope.line_to_char(lines.start);
+    let end = rope.line_to_char(lines.end.min(total));
+
+    if lines.end >= total && lines.start > 0 && terminator(rope, total - 1).is_empty() {
+        start = line_content_range(rope, lines.start - 1).end;
+    }
+
+    start..end
+}
+
+// The char offset and text that insert `new` as whole lines before `line`;
+// `line == count()` appends.
+pub fn insertion(rope: &Rope, line: usize, new: &[&str]) -> (usize, String) {
+    if new.is_empty() {
+        return (0, String::new());
+    }
+
+    let total = count(rope);
+    let line = line.min(total);
+    let newline = preferred_terminator(rope, line);
+    let mut text = String::new();
+
+    if line == total && total > 0 && terminator(rope, total - 1).is_empty() {
+        // Appending after an unterminated last line: it gets the terminator, and
+        // the new last line stays unterminated like it was.
+        text.push_str(&newline);
+        text.push_str(&new.join(&newline));
+    } else {
+        for l in new {
+            text.push_str(l);
+            text.push_str(&newline);
+        }
+    }
+
+    let index = if line == total {
+        rope.len_chars()
+    } else {
+        rope.line_to_char(line)
+    };
+    (index, text)
+}
+
+// What swapping the contents of two lines replaces, from the start of the
+// earlier line to the end of the later one's contents, and what it puts
+// there. Each position keeps its terminator.
+pub fn swap(rope: &Rope, a: usize, b: usize) -> (Range<usize>, String) {
+    let (first, second) = (a.min(b), a.max(b));
+    let first_range = line_content_range(rope, first);
+    let second_range = line_content_range(rope, second);
+
+    let mut text = rope.slice(second_range.clone()).to_string();
+    text.push_str(&rope.slice(first_range.end..second_range.start).to_string());
+    text.push_str(&rope.slice(first_range.clone()).to_string());
+    (first_range.start..second_range.end, text)
+}
+
+// Lines of `after` that differ from `before`, found by trimming the lines the
+// two have in common at either end. A pure deletion yields the line where the
+// removed text was. None when both are equal.
+pub fn changed_lines(before: &Rope, after: &Rope) -> Option<Range<usize>> {
+    let (old, new) = (before.len_lines(), after.len_lines());
+    let shorter = old.min(new);
+
+    let prefix = (0..shorter)
+        .take_while(|&i| before.line(i) == after.line(i))
+        .count();
+    if prefix == shorter && old == new {
+        return None;
+    }
+
+    let suffix = (0..shorter - prefix)
+        .take_while(|&i| before.line(old - 1 - i) == after.line(new - 1 - i))
+        .count();
+
+    let start = prefix.min(new.saturating_sub(1));
+    Some(start..(new - suffix).max(start + 1).min(new.max(1)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    // The naive model: each line's contents and its terminator.
+    type Model = Vec<(String, String)>;
+
+    fn render(model: &Model) -> String {
+        model
+            .iter()
+            .map(|(text, end)| format!("{text}{end}"))
+            .collect()
+    }
+
+    // Any file: empty ones, mixed endings, and ones lacking a final newline.
+    fn file() -> impl Strategy<Value = Model> {
+        let line = ("[a-z é字]{0,4}", prop_oneof!["\n", "\r\n"]);
+        (prop::collection::vec(line, 0..6), any::<bool>()).prop_map(|(mut lines, terminated)| {
+            if let Some(last) = lines.last_mut().filter(|_| !terminated) {
+                last.1.clear();
+            }
+            // An empty, unterminated last line isn't a line at all.
+            if lines
+                .last()
+                .is_some_and(|(text, end)| text.is_empty() && end.is_empty())
+            {
+                lines.pop();
+            }
+            lines
+        })
+    }
+
+    fn preferred(model: &Model, line: usize) -> String {
+        let line = line.min(model.len().saturating_sub(1));
+        (line..model.len())
+            .chain((0..line).rev())
+            .map(|l| model[l].1.clone())
+            .find(|end| !end.is_empty())
+            .unwrap_or_else(|| "\n".to_string())
+    }
+
+    proptest! {
+        #[test]
+        fn ranges_match_the_model(model in file()) {
+            let rope = Rope::from_str(&render(&model));
+
+            prop_assert_eq!(count(&rope), model.len());
+            for (i, (text, end)) in model.iter().enumerate() {
+                let content = rope.slice(line_content_range(&rope, i)).to_string();
+                let whole = rope.slice(line_char_range(&rope, i)).to_string();
+                prop_assert_eq!(&content, text);
+                prop_assert_eq!(whole, format!("{text}{end}"));
+                prop_assert_eq!(&terminator(&rope, i), end);
+            }
+        }
+
+        #[test]
+        fn removal_matches_the_model(
+            (model, start, end) in file().prop_flat_map(|model| {
+                let len = model.len();
+                (Just(model), 0..=len).prop_flat_map(move |(model, start)| {
+                    (Just(model), Just(start), start..=len)
+                })
+            })
+        ) {
+            let mut rope = Rope::from_str(&render(&model));
+            rope.remove(removal(&rope, start..end));
+
+            let mut expected = model.clone();
+            let unterminated = model.last().is_some_and(|(_, end)| end.is_empty());
+            expected.drain(start..end);
+            if start < end && end == model.len() && start > 0 && unterminated {
+                expected[start - 1].1.clear();
+            }
+            prop_assert_eq!(rope.to_string(), render(&expected));
+        }
+
+        #[test]
+        fn insertion_matches_the_model(
+            (model, at) in file().prop_flat_map(|model| {
+                let len = model.len();
+                (Just(model), 0..=len)
+            }),
+            new in prop::collection::vec("[a-z字]{0,3}", 0..3),
+        ) {
+            let mut rope = Rope::from_str(&render(&model));
+            let new: Vec<&str> = new.iter().map(String::as_str).collect();
+            let (index, text) = insertion(&rope, at, &new);
+            rope.insert(index, &text);
+
+            let newline = preferred(&model, at);
+            let mut expected = model.clone();
+            let unterminated = model.last().is_some_and(|(_, end)| end.is_empty());
+            if at == model.len() && unterminated && !new.is_empty() {
+                expected.last_mut().unwrap().1 = newline.clone();
+                expected.extend(new.iter().map(|l| (l.to_string(), newline.clone())));
+                expected.last_mut().unwrap().1.clear();
+            } else {
+                let lines = new.iter().map(|l| (l.to_string(), newline.clone()));
+                expected.splice(at..at, lines);
+            }
+            prop_assert_eq!(rope.to_string(), render(&expected));
+        }
+
+        #[test]
+        fn swap_matches_the_model(
+            (model, a, b) in file().prop_filter("two lines", |m| m.len() >= 2).prop_flat_map(|m| {
+                let len = m.len();
+                (Just(m), 0..len - 1).prop_flat_map(move |(m, a)| (Just(m), Just(a), a + 1..len))
+            })
+        ) {
+            let mut rope = Rope::from_str(&render(&model));
+            let (range, text) = swap(&rope, a, b);
+            rope.remove(range.clone());
+            rope.insert(range.start, &text);
+
+            let mut expected = model.clone();
+            let (first, second) = (expected[a].0.clone(), expected[b].0.clone());
+            expected[a].0 = second;
+            expected[b].0 = first;
+            prop_assert_eq!(rope.to_string(), render(&expected));
+        }
+    }
+
+    #[test]
+    fn an_empty_file_has_no_lines() {
+        let rope = Rope::new();
+
+        assert_eq!(count(&rope), 0);
+        assert_eq!(preferred_terminator(&rope, 0), "\n");
+    }
+
+    #[test]
+    fn changed_lines_trims_the_common_ends() {
+        let before = Rope::from_str("a\nb\nc\nd\n");
+
+        assert_eq!(changed_lines(&before, &before), None);
+        assert_eq!(
+            changed_lines(&before, &Rope::from_str("a\nx\ny\nc\nd\n")),
+            Some(1..3)
+        );
+        assert_eq!(
+            changed_lines(&before, &Rope::from_str("a\nd\n")),
+            Some(1..2)
+        );
+    }
+}
@@ -0,0 +1,166 @@
+use std::ops::Range;
+
+use ropey::Rope;
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::lines;
+
+// What a command acts on: the selection when there is one, otherwise the
+// cursor's line or the whole buffer, whichever the command falls back to.
+// Every command resolves it here, so they all agree on what a selection
+// covers and none of them splits a grapheme at its ends.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Target {
+    Selection(Range<usize>),
+    Line(usize),
+    Buffer,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fallback {
+    Line,
+    Buffer,
+}
+
+impl Target {
+    // `selection` is a char range, which is widened to whole graphemes; an
+    // empty one is no selection at all.
+    pub fn resolve(
+        rope: &Rope,
+        selection: Option<Range<usize>>,
+        line: usize,
+        fallback: Fallback,
+    ) -> Self {
+        let selection = selection
+            .map(|range| snap_start(rope, range.start)..snap_end(rope, range.end))
+            .filter(|range| !range.is_empty());
+
+        match (selection, fallback) {
+            (Some(range), _) => Target::Selection(range),
+            (None, Fallback::Line) => Target::Line(line.min(lines::count(rope).saturating_sub(1))),
+            (None, Fallback::Buffer) => Target::Buffer,
+        }
+    }
+
+    // The lines it touches. A selection ending at the start of a line doesn't
+    // take that line in.
+    pub fn lines(&self, rope: &Rope) -> Range<usize> {
+        match self {
+            Target::Selection(range) => {
+                let first = rope.char_to_line(range.start);
+                let last = rope.char_to_line(range.end);
+                if last > first && range.end == rope.line_to_char(last) {
+                    first..last
+                } else {
+                    first..last + 1
+                }
+            }
+            Target::Line(line) => *line..*line + 1,
+            Target::Buffer => 0..lines::count(rope),
+        }
+    }
+}
+
+// Graphemes of the line `index` is on, as char ranges, terminator included.
+fn graphemes(rope: &Rope, index: usize) -> Vec<Range<usize>> {
+    let line = rope.char_to_line(index);
+    let text = rope.line(line).to_string();
+    let mut start = rope.line_to_char(line);
+
+    text.graphemes(true)
+        .map(|grapheme| {
+            let range = start..start + grapheme.chars().count();
+            start = range.end;
+            range
+        })
+        .collect()
+}
+
+// The start of the grapheme `index` is inside, or `index` on a boundary.
+fn snap_start(rope: &Rope, index: usize) -> usize {
+    let index = index.min(rope.len_chars());
+    graphemes(rope, index)
+        .into_iter()
+        .find(|grapheme| grapheme.contains(&index))
+        .map_or(index, |grapheme| grapheme.start)
+}
+
+// The end of the grapheme `index` is inside, or `index` on a boundary.
+fn snap_end(rope: &Rope, index: usize) -> usize {
+    let index = index.min(rope.len_chars());
+    graphemes(rope, index)
+        .into_iter()
+        .find(|grapheme| grapheme.start < index && index < grapheme.end)
+        .map_or(index, |grapheme| grapheme.end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(text: &str, selection: Option<Range<usize>>, fallback: Fallback) -> Target {
+        Target::resolve(&Rope::from_str(text), selection, 1, fallback)
+    }
+
+    #[test]
+    fn without_a_selection_it_falls_back() {
+        let rope = Rope::from_str("one\ntwo\r\nthree\n");
+
+        let line = resolve("one\ntwo\r\nthree\n", None, Fallback::Line);
+        assert_eq!(line, Target::Line(1));
+        assert_eq!(line.lines(&rope), 1..2);
+
+        let buffer = resolve("one\ntwo\r\nthree\n", None, Fallback::Buffer);
+        assert_eq!(buffer, Target::Buffer);
+        assert_eq!(buffer.lines(&rope), 0..3);
+    }
+
+    #[test]
+    fn an_empty_selection_is_none() {
+        assert_eq!(
+            resolve("one\ntwo\n", Some(2..2), Fallback::Line),
+            Target::Line(1)
+        );
+    }
+
+    #[test]
+    fn a_selection_wins_over_either_fallback() {
+        let rope = Rope::from_str("one\ntwo\nthree\n");
+        for fallback in [Fallback::Line, Fallback::Buffer] {
+            let target = resolve("one\ntwo\nthree\n", Some(1..6), fallback);
+            assert_eq!(target, Target::Selection(1..6));
+            assert_eq!(target.lines(&rope), 0..2);
+        }
+    }
+
+    #[test]
+    fn a_selection_ending_at_a_line_start_leaves_that_line_out() {
+        let rope = Rope::from_str("one\ntwo\nthree\n");
+        let target = Target::resolve(&rope, Some(0..8), 0, Fallback::Line);
+        assert_eq!(target.lines(&rope), 0..2);
+
+        // Unless it's all there is.
+        let target = Target::resolve(&rope, Some(4..8), 0, Fallback::Line);
+        assert_eq!(target.lines(&rope), 1..2);
+    }
+
+    #[test]
+    fn selection_bounds_snap_out_to_whole_graphemes() {
+        // e with a combining acute, then a family emoji of five chars.
+        let text = "ae\u{301}b 👨\u{200d}👩\u{200d}👧 c\n";
+        let rope = Rope::from_str(text);
+
+        let target = Target::resolve(&rope, Some(2..3), 0, Fallback::Line);
+        assert_eq!(target, Target::Selection(1..3));
+        let target = Target::resolve(&rope, Some(6..7), 0, Fallback::Line);
+        assert_eq!(target, Target::Selection(5..10));
+        assert_eq!(rope.slice(5..10).to_string(), "👨\u{200d}👩\u{200d}👧");
+    }
+
+    #[test]
+    fn a_bound_between_cr_and_lf_takes_the_whole_terminator() {
+        let rope = Rope::from_str("one\r\ntwo\r\n");
+        let target = Target::resolve(&rope, Some(4..9), 0, Fallback::Line);
+        assert_eq!(target, Target::Selection(3..10));
+    }
+}
@@ -0,0 +1,114 @@
+use ropey::Rope;
+
+// Word boundaries over the rope, by char index. A word is a run of
+// alphanumerics and underscores; everything else, line breaks included,
+// separates words and is skipped over.
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// Start of the next word after the one at `from`, or the end of the buffer.
+pub fn next_start(rope: &Rope, from: usize) -> usize {
+    let mut index = from.min(rope.len_chars());
+    let mut chars = rope.chars_at(index).peekable();
+
+    while chars.next_if(|&c| is_word(c)).is_some() {
+        index += 1;
+    }
+    while chars.next_if(|&c| !is_word(c)).is_some() {
+        index += 1;
+    }
+
+    index
+}
+
+// Start of the word before `from`, or of the one `from` is inside.
+pub fn previous_start(rope: &Rope, from: usize) -> usize {
+    let mut index = from.min(rope.len_chars());
+    let mut chars = rope.chars_at(index).reversed().peekable();
+
+    while chars.next_if(|&c| !is_word(c)).is_some() {
+        index -= 1;
+    }
+    while chars.next_if(|&c| is_word(c)).is_some() {
+        index -= 1;
+    }
+
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every stop going forward from the start, and back from the end.
+    fn stops(text: &str) -> (Vec<usize>, Vec<usize>) {
+        let rope = Rope::from_str(text);
+        let end = rope.len_chars();
+
+        let mut forward = vec![0];
+        while *forward.last().unwrap() < end {
+            forward.push(next_start(&rope, *forward.last().unwrap()));
+        }
+        let mut backward = vec![end];
+        while *backward.last().unwrap() > 0 {
+            backward.push(previous_start(&rope, *backward.last().unwrap()));
+        }
+        (forward, backward)
+    }
+
+    #[test]
+    fn punctuation_separates_words() {
+        // foo.bar(baz, qux);
+        let (forward, backward) = stops("foo.bar(baz, qux);");
+        assert_eq!(forward, [0, 4, 8, 13, 18]);
+        assert_eq!(backward, [18, 13, 8, 4, 0]);
+    }
+
+    #[test]
+    fn underscores_and_digits_are_part_of_a_word() {
+        let (forward, _) = stops("snake_case x2 _private __");
+        assert_eq!(forward, [0, 11, 14, 23, 25]);
+    }
+
+    #[test]
+    fn words_cross_line_breaks_and_blank_lines() {
+        let text = "end\n\n\n  next_line\r\nlast";
+        let rope = Rope::from_str(text);
+        assert_eq!(next_start(&rope, 3), 8);
+        assert_eq!(next_start(&rope, 0), 8);
+        assert_eq!(next_start(&rope, 8), 19);
+        assert_eq!(previous_start(&rope, 19), 8);
+        assert_eq!(previous_start(&rope, 8), 0);
+        assert_eq!(previous_start(&rope, 5), 0);
+    }
+
+    #[test]
+    fn inside_a_word_goes_to_its_ends() {
+        let rope = Rope::from_str("alpha beta");
+        assert_eq!(next_start(&rope, 2), 6);
+        assert_eq!(previous_start(&rope, 8), 6);
+        assert_eq!(previous_start(&rope, 6), 0);
+    }
+
+    #[test]
+    fn the_ends_of_the_buffer_stop_it() {
+        let rope = Rope::from_str("  word  ");
+        assert_eq!(next_start(&rope, 2), 8);
+        assert_eq!(next_start(&rope, 8), 8);
+        assert_eq!(next_start(&rope, 100), 8);
+        assert_eq!(previous_start(&rope, 2), 0);
+        assert_eq!(previous_start(&rope, 0), 0);
+
+        let empty = Rope::new();
+        assert_eq!(next_start(&empty, 0), 0);
+        assert_eq!(previous_start(&empty, 0), 0);
+    }
+
+    #[test]
+    fn letters_of_any_script_make_words() {
+        let (forward, _) = stops("héllo, 漢字 мир");
+        assert_eq!(forward, [0, 7, 10, 13]);
+    }
+}
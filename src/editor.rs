@@ -1,324 +1,5754 @@
 use std::{
-    fs::File,
-    io::{BufWriter, Stdout, Write},
+    io::{ErrorKind, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    time::{Duration, Instant},
 };
 
 use crossterm::{
     cursor,
-    event::{read, Event, KeyCode, KeyEventKind, KeyModifiers},
+    event::{
+        Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent,
+        MouseEventKind,
+    },
     execute, queue,
-    style::{self, Color::*, Print},
+    style::{self, Print},
     terminal::{self, ClearType},
 };
 use ropey::Rope;
-use tree_sitter::Node;
+use tree_sitter::{Parser, Tree};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{
+    brackets,
+    buffer::{self, Buffer, BufferEvent, BufferManager, LineEnding},
+    calc,
+    check::{self, Check, CheckDiagnostic},
+    clipboard, columns, diagnostics, diff,
+    document::{
+        lines,
+        target::{Fallback, Target},
+        words,
+    },
+    highlight::Highlights,
+    keymap::{self, Action, Chord, Keymap, Resolved},
+    language::{Language, LANGUAGES},
+    overlay::Overlay,
+    pairs::{self, QuoteContext},
+    paths,
+    positions::{self, Position},
+    project::{self, Project},
+    prompt::{self, Prompt, PromptEvent},
+    recovery, references, reflow, replace,
+    screen::Screen,
+    search,
+    stats::{Frame, FrameStats},
+    task::Task,
+    terminal::Terminal,
+    theme::Theme,
+    undo::{UndoError, UndoLimits},
+    viewport::{Chrome, Row, Viewport},
+    wrap, write,
+};
+
+#[cfg(unix)]
+use crate::rpc;
 
 pub struct Editor {
-    rope: Rope,
-    stdout: Stdout,
-    filename: String,
-    scroll: usize,
+    buffers: BufferManager,
+    project: Project,
+    screen: Screen,
+    // Line and char column of the cursor in the active buffer. The terminal
+    // cursor only follows it when drawing.
+    cursor_row: usize,
+    cursor_col: usize,
+    count: Option<usize>,
+    // The start of a multi-chord sequence, until it's finished or the deadline.
+    pending_chords: Option<(Vec<Chord>, Instant)>,
+    // When the active buffer's file was last looked at by check_disk().
+    disk_checked: Instant,
+    // How far back the last jump to a change went, while jumps are repeated.
+    change_step: Option<usize>,
+    flash: bool,
+    rtl_warning: bool,
+    // Without line numbers there's no gutter at all.
+    line_numbers: bool,
+    // Numbers in the gutter count lines away from the cursor.
+    relative_numbers: bool,
+    // Long lines wrap onto further rows instead of scrolling sideways.
+    wrap: bool,
+    // What each row of the text area showed in the last frame.
+    rows: Vec<Row>,
+    altgr_inserts: bool,
+    decorations: Vec<Decoration>,
+    // The query while search mode is highlighting its matches.
+    search: Option<String>,
+    // (line, char column) where the selection started; it runs to the cursor.
+    // Edits and plain movement drop it.
+    selection: Option<(usize, usize)>,
+    // Off leaves the mouse to the terminal, e.g. for selecting text to copy.
+    mouse: bool,
+    // Copy a file to `<name>~` before saving over it.
+    backup: bool,
+    // Strip trailing whitespace and fix the final newline before saving.
+    trim_on_save: bool,
+    // Draw spaces and tabs as faint glyphs.
+    show_whitespace: bool,
+    // Where the left button went down, while it's held.
+    mouse_anchor: Option<(usize, usize)>,
+    // The status bar's clickable segments and the columns they took up when
+    // it was last drawn.
+    status_targets: Vec<(Range<u16>, StatusTarget)>,
+    // Set by an edit that keeps the selection, which edits otherwise end.
+    keep_selection: bool,
+    // The display column moving up and down aims for, or the cell within the
+    // row while wrapping. Kept from the first vertical move until the cursor
+    // moves any other way.
+    goal_column: Option<usize>,
+    keep_goal: bool,
+    paste_guard: Option<PasteGuard>,
+    keymap: Keymap,
+    message: Option<String>,
+    // Whether the message reports a failure, which is shown in red.
+    failed: bool,
+    register: Register,
+    parser: Option<(Language, Parser)>,
+    // Compiled on first use like the parser; None when the query doesn't compile.
+    highlights: Option<(Language, Option<Highlights>)>,
+    // Languages whose grammar the linked tree-sitter rejected. Their buffers
+    // fall back to plain text.
+    broken_grammars: Vec<Language>,
+    // Where a language's grammar comes from, Language::grammar outside tests.
+    grammar: fn(Language) -> Option<tree_sitter::Language>,
+    text_width: usize,
+    page_overlap: usize,
+    // Lines (or rows, while wrapping) kept in view above and below the cursor.
+    scroll_margin: usize,
+    half_page: bool,
+    // Lines one step of the mouse wheel scrolls.
+    wheel_lines: usize,
+    // Repeated to the text width for separators outside Markdown.
+    separator: char,
+    stats: Option<FrameStats>,
+    // The terminal's width and height, kept up to date by resize events so
+    // drawing doesn't have to ask the terminal.
+    size: (u16, u16),
+    viewport: Viewport,
+    chrome: Chrome,
+    locations: LocationList,
+    theme: Theme,
+    auto_pairs: bool,
+    check_on_save: bool,
+    hard_tabs: bool,
+    // Scrolls with the terminal's own scrolling, holds back rows that only
+    // changed color until idle, and drops the change flash.
+    low_bandwidth: bool,
+    drawn: Option<Drawn>,
+    check: Option<Check>,
+    // Scripts talking to the editor over --listen; see rpc.rs.
+    #[cfg(unix)]
+    rpc: Option<rpc::Server>,
+    check_results: Vec<CheckDiagnostic>,
+    check_summary: Option<String>,
+    // An event read ahead while collecting typed text, handled by the next step().
+    pending_event: Option<Event>,
+    // Parse time spent since the last frame, including parses done by tick().
+    parse_time: Duration,
+    listeners: Vec<Listener>,
+    // Topmost last; see Overlay.
+    overlays: Vec<Box<dyn Overlay>>,
+}
+
+// Pastes above either limit ask for confirmation before they are inserted.
+#[derive(Clone, Copy)]
+pub struct PasteGuard {
+    pub max_bytes: usize,
+    pub max_lines: usize,
+}
+
+impl Default for PasteGuard {
+    fn default() -> Self {
+        Self {
+            max_bytes: 100 * 1024,
+            max_lines: 5000,
+        }
+    }
+}
+
+// What the last copy captured. Linewise text holds whole lines and is pasted
+// as lines of its own below the cursor, the way vim's linewise registers are.
+#[derive(Default)]
+struct Register {
+    text: String,
+    linewise: bool,
+}
+
+impl Register {
+    // How many lines pasting would add, when it's more than part of one.
+    fn lines(&self) -> Option<usize> {
+        let text = self.text.strip_suffix('\n').unwrap_or(&self.text);
+        let lines = text.split('\n').count();
+        (self.linewise || lines > 1).then_some(lines)
+    }
 }
 
+// What clicking a segment of the status bar does.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum StatusTarget {
+    // The cursor's line:column, for going to another line.
+    Position,
+    Language,
+    LineEnding,
+    // The [+] of a modified buffer, which saves it.
+    Modified,
+}
+
+struct Location {
+    line: usize,
+    // Char offset into the line.
+    column: usize,
+    snippet: String,
+}
+
+// The results of the last command that produced several places to visit,
+// stepped through with next/previous-location.
+#[derive(Default)]
+struct LocationList {
+    entries: Vec<Location>,
+    current: usize,
+}
+
+#[derive(Clone, Copy)]
 enum CursorMovement {
     Up,
     Down,
     Left,
     Right,
+    LineStart,
+    LineEnd,
+    PageUp,
+    PageDown,
+    FileStart,
+    FileEnd,
+    WordLeft,
+    WordRight,
+}
+
+// What applying one file of a project replace did, so a cancelled replace can
+// put it back.
+enum Applied {
+    Buffer {
+        index: usize,
+        rope: Rope,
+        modified: bool,
+        undoable: bool,
+    },
+    File {
+        in_place: bool,
+    },
+}
+
+// A background buffer's cursor is kept in it; the active one's has to be put
+// there first.
+fn position(buffer: &Buffer) -> Option<(String, Position)> {
+    let filename = buffer.filename.clone()?;
+    let (line, column) = buffer.cursor;
+    let scroll = buffer.scroll;
+    let language = buffer.language_override();
+    Some((
+        filename,
+        Position {
+            line,
+            column,
+            scroll,
+            language,
+        },
+    ))
+}
+
+// How long the main loop waits for input before catching up in tick().
+const IDLE_TIMEOUT: Duration = Duration::from_millis(250);
+// Ticks come this often while --listen is on, so scripts aren't kept waiting.
+#[cfg(unix)]
+const RPC_POLL: Duration = Duration::from_millis(20);
+
+// How long the rest of a chord sequence is waited for.
+const CHORD_TIMEOUT: Duration = Duration::from_secs(1);
+
+// How often the active buffer's file is looked at for changes made by
+// something else.
+const DISK_CHECK: Duration = Duration::from_secs(1);
+
+// How long changed text stays highlighted after an operation.
+const FLASH_DURATION: Duration = Duration::from_millis(300);
+
+// Transient highlight over buffer lines, drawn on top of the text until it
+// expires. It's tied to the contents it was made for and dropped once they
+// change again.
+struct Decoration {
+    buffer: usize,
+    generation: u64,
+    lines: Range<usize>,
+    expires: Instant,
+}
+
+// Where the last frame was drawn from, so that low-bandwidth mode can tell a
+// plain scroll, or rows that only changed color because of highlighting.
+#[derive(PartialEq)]
+struct Drawn {
+    buffer: usize,
+    scroll: usize,
+    h_scroll: usize,
+    row_scroll: usize,
+    viewport: Viewport,
+    // Everything besides syntax highlighting that colors text.
+    selection: Option<Range<usize>>,
+    search: Option<String>,
+    decorations: usize,
+    bracket: Option<brackets::Match>,
+    // Whether whitespace is shown, and the line trailing whitespace isn't
+    // marked on.
+    whitespace: (bool, usize),
+}
+
+// Prints buffer text from the top of the view down, keeping only the columns
+// on screen. `column` is where the next text goes within its line, before
+// horizontal scrolling.
+struct Pen {
+    left: u16,
+    top: u16,
+    rows: Vec<Row>,
+    line: usize,
+    column: usize,
+    // Where the terminal cursor is after the last print.
+    at: (u16, u16),
+}
+
+impl Pen {
+    // Starts at the beginning of the line on the first row, with the terminal
+    // cursor at the top left of the text area.
+    fn new(viewport: Viewport, rows: &[Row]) -> Self {
+        Self {
+            left: viewport.left,
+            top: viewport.top,
+            line: rows.first().map_or(0, |row| row.line),
+            rows: rows.to_vec(),
+            column: 0,
+            at: (viewport.left, viewport.top),
+        }
+    }
+
+    // Each piece of a line goes on the rows showing its columns.
+    fn print(&mut self, out: &mut impl Write, text: &str) -> std::io::Result<()> {
+        for (i, piece) in text.split('\n').enumerate() {
+            if i > 0 {
+                self.line += 1;
+                self.column = 0;
+            }
+
+            // The \r of a \r\n line ending isn't drawn.
+            let piece = piece.trim_end_matches('\r');
+            for (index, row) in self.rows.iter().enumerate() {
+                if row.line != self.line {
+                    continue;
+                }
+                let shown = columns::clip(piece, self.column, row.columns.clone());
+                if shown.is_empty() {
+                    continue;
+                }
+
+                let offset = self.column.max(row.columns.start) - row.columns.start;
+                let position = (self.left + offset as u16, self.top + index as u16);
+                if position != self.at {
+                    queue!(out, cursor::MoveTo(position.0, position.1))?;
+                }
+                queue!(out, Print(columns::display(&shown)))?;
+                self.at = (position.0 + columns::width(&shown) as u16, position.1);
+            }
+            self.column += columns::width(piece);
+        }
+
+        Ok(())
+    }
+}
+
+// Stepping through the matches of the search in `Editor::search`: n, Enter or
+// F3 go to the next one, N or Shift+F3 to the previous, and Tab ends it with
+// the match selected. Any other key ends it and is handled as usual, except
+// for help, which opens on top.
+struct SearchMode {
+    done: bool,
+}
+
+impl Overlay for SearchMode {
+    fn handle_key(&mut self, editor: &mut Editor, key: KeyEvent) -> std::io::Result<bool> {
+        let Some(query) = &editor.search else {
+            self.done = true;
+            return Ok(false);
+        };
+
+        let index = editor.get_cursor_index();
+        let rope = &editor.buffer().rope;
+        let found = match (key.code, key.modifiers) {
+            (KeyCode::Enter | KeyCode::Char('n') | KeyCode::F(3), KeyModifiers::NONE) => {
+                search::next(rope, query, index + 1)
+            }
+            (KeyCode::Char('N'), KeyModifiers::SHIFT) | (KeyCode::F(3), KeyModifiers::SHIFT) => {
+                search::previous(rope, query, index)
+            }
+            (KeyCode::Tab, KeyModifiers::NONE) => {
+                self.done = true;
+                editor.select_match();
+                return Ok(true);
+            }
+            _ if editor.keymap.lookup(&key) == Some(Action::Help) => return Ok(false),
+            _ => {
+                self.done = true;
+                return Ok(false);
+            }
+        };
+
+        if let Some(found) = found {
+            editor.record_jump();
+            editor.jump_to_char(found);
+        }
+        Ok(true)
+    }
+
+    // The matches and the status line are drawn from `Editor::search`.
+    fn render(&self, _: &mut Editor) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    fn wants_dismiss(&self) -> bool {
+        self.done
+    }
+
+    fn dismissed(&mut self, editor: &mut Editor) {
+        editor.search = None;
+    }
+}
+
+// Every action with its chords and description, scrolled with the arrows and
+// Page Up / Page Down.
+struct Help {
+    lines: Vec<String>,
+    offset: usize,
+}
+
+impl Help {
+    fn new(keymap: &Keymap) -> Self {
+        let lines = Action::all()
+            .map(|action| {
+                format!(
+                    "{:<24} {}",
+                    keymap.chords_for(action).join(", "),
+                    action.description()
+                )
+            })
+            .collect();
+
+        Self { lines, offset: 0 }
+    }
+
+    fn rows(editor: &Editor) -> usize {
+        (editor.size.1 as usize).saturating_sub(3)
+    }
+}
+
+impl Overlay for Help {
+    fn handle_key(&mut self, editor: &mut Editor, key: KeyEvent) -> std::io::Result<bool> {
+        let rows = Self::rows(editor).max(1);
+        let last = self.lines.len().saturating_sub(rows);
+
+        self.offset = match key.code {
+            KeyCode::Up => self.offset.saturating_sub(1),
+            KeyCode::Down => self.offset + 1,
+            KeyCode::PageUp => self.offset.saturating_sub(rows),
+            KeyCode::PageDown => self.offset + rows,
+            _ => self.offset,
+        }
+        .min(last);
+
+        Ok(true)
+    }
+
+    fn render(&self, editor: &mut Editor) -> std::io::Result<()> {
+        editor.draw_list(
+            &self.lines,
+            None,
+            self.offset + Self::rows(editor).max(1) - 1,
+        )
+    }
+
+    fn wants_dismiss(&self) -> bool {
+        false
+    }
+}
+
+// Covers the paths that return early with an error instead of calling close().
+impl Drop for Editor {
+    fn drop(&mut self) {
+        let _ = self.screen.leave();
+    }
 }
 
-const COLORS: [style::Color; 12] = [
-    Red,
-    DarkRed,
-    Green,
-    DarkGreen,
-    Yellow,
-    DarkYellow,
-    Blue,
-    DarkBlue,
-    Magenta,
-    DarkMagenta,
-    Cyan,
-    DarkCyan,
-];
+// Called with the index of the buffer an event is about; see Editor::listen.
+pub type Listener = fn(&mut Editor, usize, &BufferEvent);
+
+const NOT_UNDOABLE: &str = "Buffer too large to keep an undo snapshot, this can't be undone";
+
+const TITLE_WIDTH: usize = 60;
+
+const POPUP_WIDTH: usize = 60;
+const POPUP_HEIGHT: usize = 6;
 
 impl Editor {
-    pub fn new(stdout: Stdout, mut rope: Rope, filename: String) -> Self {
-        let mut indices = Vec::new();
+    pub fn new(terminal: Box<dyn Terminal>, buffers: Vec<Buffer>, project: Project) -> Self {
+        let mut editor = Self {
+            screen: Screen::new(terminal),
+            buffers: BufferManager::new(buffers),
+            project,
+            cursor_row: 0,
+            cursor_col: 0,
+            count: None,
+            pending_chords: None,
+            disk_checked: Instant::now(),
+            change_step: None,
+            flash: true,
+            rtl_warning: true,
+            line_numbers: true,
+            relative_numbers: false,
+            wrap: false,
+            rows: Vec::new(),
+            altgr_inserts: false,
+            decorations: Vec::new(),
+            search: None,
+            selection: None,
+            mouse: true,
+            backup: false,
+            trim_on_save: false,
+            show_whitespace: false,
+            mouse_anchor: None,
+            status_targets: Vec::new(),
+            keep_selection: false,
+            goal_column: None,
+            keep_goal: false,
+            paste_guard: Some(PasteGuard::default()),
+            keymap: Keymap::default(),
+            message: None,
+            failed: false,
+            register: Register::default(),
+            parser: None,
+            highlights: None,
+            broken_grammars: Vec::new(),
+            grammar: Language::grammar,
+            text_width: 80,
+            page_overlap: 0,
+            scroll_margin: 3,
+            half_page: false,
+            wheel_lines: 3,
+            separator: '─',
+            stats: None,
+            pending_event: None,
+            size: (80, 24),
+            viewport: Viewport::new((80, 24), Chrome::default()),
+            // The bottom row is the status bar.
+            chrome: Chrome {
+                bottom: 1,
+                ..Chrome::default()
+            },
+            locations: LocationList::default(),
+            theme: Theme::default(),
+            auto_pairs: false,
+            check_on_save: false,
+            hard_tabs: false,
+            low_bandwidth: false,
+            drawn: None,
+            #[cfg(unix)]
+            rpc: None,
+            check: None,
+            check_results: Vec::new(),
+            check_summary: None,
+            parse_time: Duration::ZERO,
+            listeners: Vec::new(),
+            overlays: Vec::new(),
+        };
+
+        editor.listen(Self::check_after_save);
+        editor.listen(Self::forget_closed_buffer);
+        editor
+    }
+
+    // Listeners hear about every buffer event, in order, once the step that
+    // caused them is over. Events they cause in turn wait for the next step.
+    pub fn listen(&mut self, listener: Listener) {
+        self.listeners.push(listener);
+    }
 
-        for (i, c) in rope.chars().enumerate() {
-            if c == '\n' {
-                indices.push(i);
+    fn deliver_events(&mut self) {
+        let events = self.buffers.take_events();
+        let listeners = self.listeners.clone();
+
+        for (index, event) in &events {
+            for listener in &listeners {
+                listener(self, *index, event);
             }
         }
+    }
 
-        for (ref mut offset, i) in indices.into_iter().enumerate() {
-            rope.insert_char(i + *offset, '\r');
-            *offset += 1;
+    fn check_after_save(&mut self, _: usize, event: &BufferEvent) {
+        if *event == BufferEvent::Saved && self.check_on_save {
+            self.start_check();
         }
+    }
 
-        Self {
-            stdout,
-            rope,
-            filename,
-            scroll: 0,
+    // Decorations refer to buffers by index, which closing one shifts down.
+    fn forget_closed_buffer(&mut self, index: usize, event: &BufferEvent) {
+        if *event != BufferEvent::Closed {
+            return;
+        }
+
+        self.decorations.retain(|d| d.buffer != index);
+        for decoration in &mut self.decorations {
+            if decoration.buffer > index {
+                decoration.buffer -= 1;
+            }
         }
     }
 
     pub fn init(&mut self) -> std::io::Result<()> {
+        self.screen.enter(self.mouse)?;
         execute!(
-            self.stdout,
-            terminal::EnterAlternateScreen,
+            self.screen,
             cursor::EnableBlinking,
             cursor::SetCursorStyle::BlinkingBar,
             cursor::MoveTo(0, 0)
         )?;
-        terminal::enable_raw_mode()?;
+        self.theme.detect_background();
+        self.refresh_size()?;
+        self.update_title()?;
         self.redraw()?;
 
         Ok(())
     }
 
-    pub fn close(&mut self) -> std::io::Result<()> {
-        execute!(self.stdout, terminal::LeaveAlternateScreen)?;
-        terminal::disable_raw_mode()?;
+    // Reads files after the terminal is set up, so a huge one shows progress
+    // instead of a blank terminal. Returns false when loading was cancelled, in
+    // which case the editor should close without running.
+    pub fn load_buffers(&mut self, indices: &[usize]) -> std::io::Result<bool> {
+        for &index in indices {
+            let Some(filename) = self.buffers.get(index).and_then(|b| b.filename.clone()) else {
+                continue;
+            };
+            let size = match std::fs::metadata(&filename) {
+                Ok(metadata) => metadata.len(),
+                Err(error) if error.kind() == ErrorKind::NotFound => {
+                    if let Some(buffer) = self.buffers.get_mut(index) {
+                        buffer.new_file = true;
+                    }
+                    continue;
+                }
+                Err(_) => 0,
+            };
+            let path = PathBuf::from(&filename);
+
+            let task = Task::spawn(move |token| {
+                buffer::read_file_cancellable(&path, token)
+                    .map(|read| read.map(|(rope, _)| rope))
+                    .transpose()
+            });
+            let name = paths::shorten(&filename, 40);
+            let loaded = self.wait_for(task, |read| {
+                let percent = (read as u64 * 100).checked_div(size).unwrap_or(100);
+                format!("Loading {name}… {}%", percent.min(100))
+            })?;
+
+            match loaded {
+                Some(Ok(rope)) => {
+                    if let Some(buffer) = self.buffers.get_mut(index) {
+                        buffer.replace_unmodified(rope);
+                        buffer.record_disk();
+                        if let Some(position) = positions::find(&filename) {
+                            let last = buffer.line_count().saturating_sub(1);
+                            buffer.cursor = (position.line.min(last), position.column);
+                            buffer.scroll = position.scroll;
+                            if let Some(language) = position.language {
+                                buffer.set_language(language);
+                            }
+                        }
+                    }
+                }
+                Some(Err(error)) => {
+                    return Err(std::io::Error::new(
+                        error.kind(),
+                        format!("{filename}: {error}"),
+                    ))
+                }
+                None => return Ok(false),
+            }
+        }
+
+        self.restore_buffer_cursor()?;
+        self.redraw()?;
+        Ok(true)
+    }
+
+    // Asks the terminal, for when it may have been resized without an event
+    // saying so, e.g. while it was handed to another program.
+    fn refresh_size(&mut self) -> std::io::Result<()> {
+        self.size = self.screen.size()?;
+        self.refresh_viewport();
         Ok(())
     }
 
-    fn save(&mut self) {
-        let mut file = BufWriter::new(File::create(&self.filename).unwrap());
-        let bytes = self.rope.bytes().filter(|c| *c != b'\r');
+    // Every event is read through here, so that however deep in a modal loop a
+    // resize arrives, the size drawing uses is right.
+    fn read_event(&mut self) -> std::io::Result<Event> {
+        let event = self.screen.read()?;
+        if let Event::Resize(width, height) = event {
+            self.size = (width, height);
+            self.refresh_viewport();
+        }
+        Ok(event)
+    }
+
+    // Needs calling whenever the terminal size or the surrounding UI changes.
+    fn refresh_viewport(&mut self) {
+        self.viewport = Viewport::new(self.size, self.chrome);
+    }
 
-        for b in bytes {
-            file.write_all(&[b]).unwrap();
+    pub fn close(&mut self) -> std::io::Result<()> {
+        if let Some(check) = self.check.take() {
+            check.cancel();
         }
 
-        file.flush().unwrap();
+        self.screen.flush()?;
+        self.screen.leave()
     }
 
-    pub fn step(&mut self) -> std::io::Result<bool> {
-        let event = read()?;
-
-        match event {
-            Event::Key(event) if event.kind == KeyEventKind::Press => match event.code {
-                KeyCode::Esc => return Ok(false),
-                KeyCode::Up => self.attempt_cursor_move(CursorMovement::Up)?,
-                KeyCode::Down => self.attempt_cursor_move(CursorMovement::Down)?,
-                KeyCode::Left => self.attempt_cursor_move(CursorMovement::Left)?,
-                KeyCode::Right => self.attempt_cursor_move(CursorMovement::Right)?,
-                KeyCode::Char(c) => {
-                    if c == 's' && event.modifiers == KeyModifiers::CONTROL {
-                        self.save();
-                    } else {
-                        self.rope.insert_char(self.get_cursor_index()?, c);
-                        self.attempt_cursor_move(CursorMovement::Right)?;
-                        self.redraw()?;
-                    }
-                }
-                KeyCode::Enter => {
-                    self.rope.insert(self.get_cursor_index()?, "\r\n");
-                    self.attempt_cursor_move(CursorMovement::Down)?;
-                    execute!(self.stdout, cursor::MoveToColumn(0))?;
-                    self.redraw()?;
+    fn save(&mut self) -> std::io::Result<()> {
+        if self.buffer().read_only {
+            self.refuse_edit();
+            return Ok(());
+        }
+
+        let Some(filename) = self.buffer().filename.clone() else {
+            return self.save_as();
+        };
+
+        if let Err(message) = self.project.check(Path::new(&filename)) {
+            self.fail(format!("Refusing to save: {message}"));
+            return Ok(());
+        }
+
+        if let Some(stamp) = self.buffer().disk_changed() {
+            let question = format!(
+                "{} changed on disk: [r]eload it, [o]verwrite it, or Esc",
+                paths::shorten(&filename, TITLE_WIDTH)
+            );
+            match self.ask(&question)?.code {
+                KeyCode::Char('r') => return self.reload(),
+                KeyCode::Char('o') => {}
+                _ => {
+                    self.buffer_mut().ignored = Some(stamp);
+                    self.message = Some("Not saved".to_string());
+                    return self.redraw();
                 }
-                KeyCode::Backspace => {
-                    let pos = cursor::position()?;
-                    let idx = self.get_cursor_index()?;
+            }
+        }
 
-                    if pos.0 > 0 {
-                        self.rope.remove(idx - 1..idx);
-                        self.attempt_cursor_move(CursorMovement::Left)?;
-                    } else if self.get_current_line_len()? == 0 && self.get_line_number()? != 0 {
-                        if pos.1 == 0 {
-                            self.scroll -= 1;
-                        }
+        self.overwrite(&filename)
+    }
 
-                        self.rope.remove(idx..idx + 2);
-                        self.attempt_cursor_move(CursorMovement::Up)?;
-                        let line_length = self.get_current_line_len()?;
-                        execute!(self.stdout, cursor::MoveToColumn(line_length as u16))?;
-                    } else if self.get_line_number()? != 0 {
-                        if pos.1 == 0 {
-                            self.scroll -= 1;
-                        }
+    // Saves to the buffer's own file without asking about changes on disk.
+    fn overwrite(&mut self, filename: &str) -> std::io::Result<()> {
+        self.trim_for_save();
+        let verb = if self.buffer().new_file {
+            "Created"
+        } else {
+            "Saved"
+        };
 
-                        self.attempt_cursor_move(CursorMovement::Up)?;
-                        let line_length = self.get_current_line_len()?;
-                        execute!(self.stdout, cursor::MoveToColumn(line_length as u16))?;
-                        self.rope.remove(idx - 2..idx);
-                    }
+        // A backup that can't be made keeps the save from going ahead, unless
+        // it's for want of permission, which the save itself runs into.
+        let backup = if self.backup {
+            write::backup(Path::new(filename))
+        } else {
+            Ok(())
+        };
+        let written = match backup {
+            Err(error) if error.kind() != ErrorKind::PermissionDenied => {
+                tracing::error!(filename, %error, "backup failed");
+                self.fail(format!("Couldn't back up {filename}, not saved: {error}"));
+                return Ok(());
+            }
+            Err(error) => Err(error),
+            Ok(()) => self.write_buffer(Path::new(filename)),
+        };
 
-                    self.redraw()?;
+        match &written {
+            Ok(Some(warning)) => tracing::warn!(filename, warning, "saved"),
+            Ok(None) => {}
+            Err(error) => tracing::error!(filename, %error, "save failed"),
+        }
+        match written {
+            Ok(warning) => {
+                self.saved(filename, verb);
+                if warning.is_some() {
+                    self.message = warning;
                 }
-                _ => {}
-            },
-            _ => {}
+            }
+            Err(error) if error.kind() == ErrorKind::PermissionDenied => {
+                self.save_denied(filename)?
+            }
+            Err(error) => self.save_failed(filename, &error)?,
         }
 
-        let line_len = self.get_current_line_len()? as u16;
+        Ok(())
+    }
 
-        execute!(
-            self.stdout,
-            cursor::MoveTo(
-                std::cmp::min(cursor::position()?.0, line_len),
-                cursor::position()?.1
-            )
-        )?;
+    // With trim_on_save set, fixes whitespace the way fix-whitespace does, as
+    // an edit undo can take back, so the buffer matches what's written. The
+    // cursor is kept on the text when its line is trimmed or dropped.
+    fn trim_for_save(&mut self) {
+        if !self.trim_on_save {
+            return;
+        }
 
-        Ok(true)
+        let cursor = self.cursor();
+        if !self.buffer_mut().fix_hygiene(cursor) {
+            self.message = Some(NOT_UNDOABLE.to_string());
+        }
+        self.buffer_mut().refresh_hygiene();
+        self.move_cursor_to(cursor.0, cursor.1);
     }
 
-    fn get_cursor_index(&self) -> std::io::Result<usize> {
-        let mut pos = cursor::position()?;
-        pos.1 += self.scroll as u16;
-        Ok(self.get_rope_index((pos.0 as usize, pos.1 as usize)))
+    // Fixes the whole buffer, or with a selection only strips the trailing
+    // whitespace of the lines it touches.
+    fn fix_whitespace(&mut self) -> std::io::Result<()> {
+        let cursor = self.cursor();
+        let before = self.buffer().rope.clone();
+
+        match self.target(Fallback::Buffer) {
+            target @ Target::Selection(_) => {
+                let rope = &self.buffer().rope;
+                let selected = target.lines(rope);
+                let range = rope.line_to_char(selected.start)..rope.line_to_char(selected.end);
+                let text: String = selected
+                    .map(|line| {
+                        let content = rope
+                            .slice(lines::line_content_range(rope, line))
+                            .to_string();
+                        let content = content.trim_end_matches([' ', '\t']);
+                        format!("{content}{}", lines::terminator(rope, line))
+                    })
+                    .collect();
+                if rope.slice(range.clone()) != text.as_str() {
+                    self.buffer_mut().edit(range, &text, cursor);
+                }
+            }
+            Target::Line(_) | Target::Buffer => {
+                if !self.buffer_mut().fix_hygiene(cursor) {
+                    self.message = Some(NOT_UNDOABLE.to_string());
+                }
+            }
+        }
+
+        self.flash_changes(&before);
+        self.buffer_mut().refresh_hygiene();
+        self.move_cursor_to(cursor.0, cursor.1);
+        self.redraw()
     }
 
-    // pos represents the position from the start of the file, not the viewport
-    fn get_rope_index(&self, pos: (usize, usize)) -> usize {
-        let mut count = 0;
+    // The buffer stays as it was, so the save can be tried again, or made to
+    // another file.
+    fn save_failed(&mut self, filename: &str, error: &std::io::Error) -> std::io::Result<()> {
+        let question =
+            format!("Couldn't save {filename}: {error}. [r]etry, save [a]s another file, or Esc");
 
-        for (i, line) in self.rope.lines().enumerate() {
-            if i >= pos.1 {
-                count += pos.0;
-                break;
-            } else {
-                count += line.len_chars();
+        match self.ask(&question)?.code {
+            KeyCode::Char('r' | 'R') => self.save(),
+            KeyCode::Char('a' | 'A') => self.save_as(),
+            _ => {
+                self.fail(format!("Couldn't save {filename}: {error}"));
+                Ok(())
             }
         }
-
-        count
     }
 
-    fn redraw(&mut self) -> std::io::Result<()> {
-        execute!(
-            self.stdout,
-            cursor::Hide,
-            terminal::Clear(ClearType::All),
-            cursor::SavePosition,
-            cursor::MoveTo(0, 0),
-        )?;
-        let mut parser = tree_sitter::Parser::new();
+    // Unlike save_copy, the buffer takes the new name, and is saved once
+    // that's written.
+    fn save_as(&mut self) -> std::io::Result<()> {
+        let current = self.buffer().filename.clone().unwrap_or_default();
+        let Some(path) = self.prompt_with("Save as: ", &current)? else {
+            self.message = Some("Not saved".to_string());
+            return Ok(());
+        };
 
-        parser
-            .set_language(&tree_sitter_rust::LANGUAGE.into())
-            .unwrap();
+        if let Err(message) = self.project.check(Path::new(&path)) {
+            self.fail(format!("Refusing to save: {message}"));
+            return Ok(());
+        }
 
-        let tree = parser.parse(self.rope.to_string(), None).unwrap();
+        let renamed =
+            project::canonicalize(Path::new(&path)) != project::canonicalize(Path::new(&current));
+        if renamed
+            && Path::new(&path).exists()
+            && !self.confirm(&format!("{path} exists, overwrite it? [y/N]"))?
+        {
+            self.message = Some("Not saved".to_string());
+            return Ok(());
+        }
 
-        let mut nodes = Vec::new();
-        nodes.append(&mut Self::expand_node(tree.root_node()));
+        let buffer = self.buffer_mut();
+        let language = buffer.language();
+        let previous = buffer.filename.replace(path.clone());
+        buffer.new_file = !Path::new(&path).exists();
+        // A new extension can mean another grammar.
+        if buffer.language() != language {
+            buffer.tree = None;
+            buffer.tree_stale = true;
+        }
+        self.save()?;
 
-        let mut last_pos = self.get_rope_index((0, self.scroll));
+        if let Some(previous) = previous.filter(|_| !self.buffer().modified) {
+            recovery::remove(&previous);
+        }
+        Ok(())
+    }
 
-        for node in nodes {
-            if node.start_position().row < self.scroll {
-                continue;
-            }
+    fn fail(&mut self, message: String) {
+        self.message = Some(message);
+        self.failed = true;
+    }
 
-            if node.start_position().row > self.scroll + terminal::size()?.1 as usize - 1 {
-                continue;
-            }
+    fn refuse_edit(&mut self) {
+        let name = paths::shorten(self.buffer().display_name(), TITLE_WIDTH);
+        let message = match self.keymap.chords_for(Action::ToggleReadOnly).first() {
+            Some(chord) => format!("{name} is read-only, {chord} allows edits"),
+            None => format!("{name} is read-only"),
+        };
+        self.fail(message);
+    }
 
-            let index =
-                self.get_rope_index((node.start_position().column, node.start_position().row));
+    // Making a buffer read-only takes effect right away; allowing edits again
+    // asks first, all the more when the file can't be written anyway.
+    fn toggle_read_only(&mut self) -> std::io::Result<()> {
+        let name = paths::shorten(self.buffer().display_name(), TITLE_WIDTH);
+        if !self.buffer().read_only {
+            self.buffer_mut().read_only = true;
+            self.message = Some(format!("{name} is read-only"));
+            return self.redraw();
+        }
+
+        let writable = self
+            .buffer()
+            .filename
+            .as_ref()
+            .is_none_or(|filename| write::writable(Path::new(filename)));
+        let question = if writable {
+            format!("Allow edits to {name}? [y/N]")
+        } else {
+            format!("{name} isn't writable, allow edits anyway? [y/N]")
+        };
+        if self.confirm(&question)? {
+            self.buffer_mut().read_only = false;
+            self.message = Some(format!("Edits to {name} allowed"));
+        }
+        self.redraw()
+    }
 
-            if index > last_pos {
-                queue!(self.stdout, Print(self.rope.slice(last_pos..index)))?;
+    // Returns false when the editor should exit. Saving only covers the active
+    // buffer, so quitting still waits if another one has unsaved changes.
+    fn quit(&mut self) -> std::io::Result<bool> {
+        if !self.buffers.iter().any(|b| b.modified) {
+            return Ok(false);
+        }
+
+        let question = "Unsaved changes: [s]ave and quit, [q]uit without saving, [d]iff, [c]ancel";
+        let modified: Vec<usize> = (0..self.buffers.len())
+            .filter(|&i| self.buffers.get(i).is_some_and(|b| b.modified))
+            .collect();
+        match self.ask_unsaved(question, &modified)?.code {
+            KeyCode::Char('s') => {
+                self.save()?;
+                if !self.buffers.iter().any(|b| b.modified) {
+                    return Ok(false);
+                }
+                if !self.buffer().modified {
+                    self.message = Some("Other buffers have unsaved changes".to_string());
+                }
             }
+            KeyCode::Char('q') => return Ok(false),
+            _ => {}
+        }
 
-            let diff = node.end_position().column - node.start_position().column;
-            let end = index + diff;
+        self.redraw()?;
+        Ok(true)
+    }
 
-            queue!(
-                self.stdout,
-                crossterm::style::SetForegroundColor(COLORS[(node.kind_id() % 12) as usize]),
-                Print(self.rope.slice(index..end).to_string())
-            )?;
+    // Returns a warning when the file had to be written in place.
+    fn write_buffer(&self, path: &Path) -> std::io::Result<Option<String>> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
 
-            last_pos = end;
+        let rope = &self.buffer().rope;
+        write::write_atomic_with(path, |mut out| buffer::write_rope(rope, &mut out))
+    }
+
+    fn saved(&mut self, filename: &str, verb: &str) {
+        self.buffer_mut().mark_saved();
+        recovery::remove(filename);
+        self.buffer_mut().cursor = self.cursor();
+        if let Some(file) = position(self.buffer()) {
+            positions::remember(&[file]);
         }
 
-        execute!(self.stdout, cursor::RestorePosition, cursor::Show)?;
+        let width = self.size.0 as usize;
+        self.message = Some(format!(
+            "{verb} {}",
+            paths::shorten(filename, width.saturating_sub(verb.len() + 1))
+        ));
+    }
+
+    // Offers a way around a file we may not write. Anything but a successful
+    // write leaves the buffer modified.
+    fn save_denied(&mut self, filename: &str) -> std::io::Result<()> {
+        let question = if cfg!(unix) {
+            "Permission denied: [s]udo tee, save a [c]opy elsewhere, or Esc"
+        } else {
+            "Permission denied: save a [c]opy elsewhere, or Esc"
+        };
 
-        self.stdout.flush()?;
+        match self.ask(question)?.code {
+            KeyCode::Char('s' | 'S') if cfg!(unix) => self.save_with_sudo(filename)?,
+            KeyCode::Char('c' | 'C') => self.save_copy()?,
+            _ => self.message = Some("Not saved".to_string()),
+        }
 
         Ok(())
     }
 
-    fn expand_node(node: Node) -> Vec<Node> {
-        let mut nodes = Vec::new();
+    fn save_copy(&mut self) -> std::io::Result<()> {
+        let Some(path) = self.prompt("Save a copy to: ")? else {
+            self.message = Some("Not saved".to_string());
+            return Ok(());
+        };
 
-        if node.child_count() == 0 {
-            nodes.push(node);
+        if let Err(message) = self.project.check(Path::new(&path)) {
+            self.fail(format!("Refusing to save: {message}"));
+            return Ok(());
         }
 
-        for n in node.children(&mut node.walk()) {
-            let children = Self::expand_node(n);
-            for child in children {
-                nodes.push(child);
+        match self.write_buffer(Path::new(&path)) {
+            Ok(_) => {
+                self.message = Some(format!(
+                    "Saved a copy to {path}, the buffer is still unsaved"
+                ))
+            }
+            Err(error) => {
+                tracing::error!(path, %error, "saving a copy failed");
+                self.fail(format!("Couldn't save {path}: {error}"));
             }
         }
-
-        nodes
+        Ok(())
     }
 
-    fn get_visible_lines_len(&self) -> std::io::Result<usize> {
-        let mut size = 0;
-        for i in 0..self.rope.len_lines() - 1 {
-            if i >= self.scroll && i < self.scroll + (terminal::size()?.1 as usize) {
-                size += 1;
+    // Leaves the alternate screen so sudo can ask for a password, and streams
+    // the buffer through `sudo tee`. Its exit status decides whether it saved.
+    fn save_with_sudo(&mut self, filename: &str) -> std::io::Result<()> {
+        self.screen.leave()?;
+        self.screen
+            .print(&format!("Writing {filename} with sudo tee\n"))?;
+        self.screen.flush()?;
+
+        let result = Command::new("sudo")
+            .args(["tee", "--", filename])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .and_then(|mut child| {
+                // A failed write means sudo gave up, which the status reports.
+                if let Some(mut stdin) = child.stdin.take() {
+                    let _ = buffer::write_rope(&self.buffer().rope, &mut stdin);
+                }
+                child.wait()
+            });
+
+        self.screen.enter(self.mouse)?;
+        self.refresh_size()?;
+
+        match result {
+            Ok(status) if status.success() => self.saved(filename, "Saved with sudo"),
+            Ok(status) => {
+                tracing::error!(filename, %status, "sudo tee failed");
+                self.fail(format!("sudo tee failed ({status}), not saved"));
+            }
+            Err(error) => {
+                tracing::error!(filename, %error, "couldn't run sudo");
+                self.fail(format!("Couldn't run sudo: {error}"));
             }
         }
 
-        Ok(size)
+        Ok(())
     }
 
-    fn get_current_line_len(&self) -> std::io::Result<usize> {
-        Ok(self
-            .rope
-            .get_line(self.get_line_number()?)
-            .unwrap()
-            .to_string()
-            .len()
-            - 2)
-    }
+    // A save during a running check restarts it, since its results would be stale.
+    fn start_check(&mut self) {
+        if let Some(check) = self.check.take() {
+            check.cancel();
+        }
 
-    fn get_line_number(&self) -> std::io::Result<usize> {
-        Ok(cursor::position()?.1 as usize + self.scroll)
+        match Check::start(&self.project.root) {
+            Ok(check) => self.check = check,
+            Err(error) => self.check_summary = Some(format!("check: {error}")),
+        }
     }
 
-    fn attempt_cursor_move(&mut self, movement: CursorMovement) -> std::io::Result<()> {
-        match movement {
-            CursorMovement::Up => {
-                if cursor::position()?.1 == 0 {
-                    self.scroll = self.scroll.saturating_sub(1);
-                }
+    fn finish_check(&mut self) -> bool {
+        let Some(outcome) = self.check.as_mut().and_then(Check::try_finish) else {
+            return false;
+        };
+        self.check = None;
 
-                execute!(self.stdout, cursor::MoveUp(1))?;
-                self.redraw()?;
-            }
-            CursorMovement::Down => {
-                if self.get_line_number()? < self.rope.lines().len() - 2 {
-                    if cursor::position()?.1 == terminal::size()?.1 - 1 {
-                        self.scroll += 1;
-                    }
-                    execute!(self.stdout, cursor::MoveDown(1))?;
+        match outcome {
+            check::Outcome::Diagnostics(diagnostics) => {
+                let errors = diagnostics.iter().filter(|d| d.level == "error").count();
+                let warnings = diagnostics.len() - errors;
 
-                    self.redraw()?;
-                }
+                self.check_summary = Some(if diagnostics.is_empty() {
+                    "check: ok".to_string()
+                } else {
+                    format!("check: {errors} errors, {warnings} warnings")
+                });
+                self.check_results = diagnostics;
             }
-            CursorMovement::Left => {
-                execute!(self.stdout, cursor::MoveLeft(1))?;
-            }
-            CursorMovement::Right => {
-                if cursor::position()?.0 < self.get_current_line_len()? as u16 {
-                    execute!(self.stdout, cursor::MoveRight(1))?;
-                }
+            check::Outcome::Raw(line) => {
+                self.check_summary = Some("check failed".to_string());
+                self.message = Some(line);
+                self.check_results.clear();
             }
         }
 
-        self.redraw()?;
-        Ok(())
+        true
+    }
+
+    pub fn set_paste_guard(&mut self, guard: Option<PasteGuard>) {
+        self.paste_guard = guard;
+    }
+
+    pub fn set_keymap(&mut self, keymap: Keymap) {
+        self.keymap = keymap;
+    }
+
+    pub fn set_undo_limits(&mut self, limits: UndoLimits) {
+        for buffer in self.buffers.iter_mut() {
+            buffer.set_undo_limits(limits);
+        }
+    }
+
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+    }
+
+    pub fn set_auto_pairs(&mut self, enabled: bool) {
+        self.auto_pairs = enabled;
+    }
+
+    pub fn set_check_on_save(&mut self, enabled: bool) {
+        self.check_on_save = enabled;
+    }
+
+    #[cfg(unix)]
+    pub fn set_rpc(&mut self, server: rpc::Server) {
+        self.rpc = Some(server);
+    }
+
+    // Tab inserts a tab character when `hard_tabs` is set, and otherwise
+    // spaces to the next multiple of `width`, which is also how wide a tab is
+    // drawn.
+    pub fn set_tabs(&mut self, width: usize, hard_tabs: bool) {
+        columns::set_tab_width(width);
+        self.hard_tabs = hard_tabs;
+    }
+
+    pub fn set_mouse(&mut self, enabled: bool) {
+        self.mouse = enabled;
+    }
+
+    pub fn set_backup(&mut self, enabled: bool) {
+        self.backup = enabled;
+    }
+
+    pub fn set_trim_on_save(&mut self, enabled: bool) {
+        self.trim_on_save = enabled;
+    }
+
+    // The view starts over at the top of the line it was on.
+    pub fn set_wrap(&mut self, enabled: bool) {
+        self.wrap = enabled;
+        self.goal_column = None;
+        let buffer = self.buffers.active_mut();
+        buffer.h_scroll = 0;
+        buffer.row_scroll = 0;
+    }
+
+    pub fn set_line_numbers(&mut self, enabled: bool) {
+        self.line_numbers = enabled;
+    }
+
+    // For problems found before the editor was up, e.g. in the config file.
+    pub fn report(&mut self, message: String) {
+        self.fail(message);
+    }
+
+    pub fn set_low_bandwidth(&mut self, enabled: bool) {
+        self.low_bandwidth = enabled;
+    }
+
+    pub fn set_text_width(&mut self, width: usize) {
+        self.text_width = width;
+    }
+
+    pub fn set_paging(&mut self, overlap: usize, half_page: bool) {
+        self.page_overlap = overlap;
+        self.half_page = half_page;
+    }
+
+    // Rows of text a page spans, which the overlap has to be smaller than.
+    pub fn page_height(&self) -> usize {
+        self.viewport.height as usize
+    }
+
+    pub fn set_wheel_lines(&mut self, lines: usize) {
+        self.wheel_lines = lines;
+    }
+
+    pub fn set_scroll_margin(&mut self, margin: usize) {
+        self.scroll_margin = margin;
+    }
+
+    pub fn set_separator(&mut self, separator: char) {
+        self.separator = separator;
+    }
+
+    pub fn set_altgr_inserts(&mut self, enabled: bool) {
+        self.altgr_inserts = enabled;
+    }
+
+    pub fn set_flash(&mut self, enabled: bool) {
+        self.flash = enabled;
+    }
+
+    pub fn set_rtl_warning(&mut self, enabled: bool) {
+        self.rtl_warning = enabled;
+    }
+
+    pub fn set_relative_numbers(&mut self, enabled: bool) {
+        self.relative_numbers = enabled;
+    }
+
+    pub fn set_debug_overlay(&mut self, enabled: bool) {
+        self.stats = enabled.then(FrameStats::default);
+    }
+
+    pub fn dump_recovery(&self) {
+        for buffer in self.buffers.iter() {
+            if let (true, Some(filename)) = (buffer.modified, &buffer.filename) {
+                recovery::write(filename, &buffer.rope);
+            }
+        }
+    }
+
+    // Where each file was left, for the next time it's opened.
+    pub fn remember_positions(&mut self) {
+        self.buffer_mut().cursor = self.cursor();
+        let files: Vec<_> = self.buffers.iter().filter_map(position).collect();
+        positions::remember(&files);
+    }
+
+    // Recovery files of buffers that were closed or saved are already gone.
+    pub fn remove_recovery(&self) {
+        for buffer in self.buffers.iter() {
+            if let Some(filename) = &buffer.filename {
+                recovery::remove(filename);
+            }
+        }
+    }
+
+    pub fn buffer(&self) -> &Buffer {
+        self.buffers.active()
+    }
+
+    fn buffer_mut(&mut self) -> &mut Buffer {
+        self.buffers.active_mut()
+    }
+
+    fn update_title(&mut self) -> std::io::Result<()> {
+        let title = format!(
+            "{} - shark",
+            paths::shorten(self.buffer().display_name(), TITLE_WIDTH)
+        );
+        execute!(self.screen, terminal::SetTitle(title))
+    }
+
+    // Called when no input arrived for a while, to catch up on lazily computed state.
+    pub fn tick(&mut self) -> std::io::Result<()> {
+        let highlight = self.buffer().awaiting_parse();
+        if highlight {
+            self.ensure_tree();
+        }
+
+        // Idle, so rows held back in low-bandwidth mode can go out now.
+        self.screen.flush_deferred()?;
+        let checked = self.finish_check();
+        self.follow_file()?;
+        self.check_disk()?;
+        #[cfg(unix)]
+        let served = self.serve_rpc();
+        #[cfg(not(unix))]
+        let served = false;
+        self.deliver_events();
+        self.enforce_invariants();
+
+        let now = Instant::now();
+        let timed_out = self
+            .pending_chords
+            .take_if(|(_, deadline)| *deadline <= now)
+            .is_some();
+        let decorated = self.decorations.len();
+        self.decorations.retain(|d| d.expires > now);
+        let expired = self.decorations.len() != decorated;
+
+        if self.buffer_mut().refresh_hygiene()
+            || highlight
+            || checked
+            || expired
+            || timed_out
+            || served
+        {
+            self.redraw()?;
+        }
+
+        Ok(())
+    }
+
+    // Whether step() has an event to handle within `timeout`.
+    pub fn poll(&mut self, timeout: Duration) -> std::io::Result<bool> {
+        Ok(self.pending_event.is_some() || self.screen.poll(timeout)?)
+    }
+
+    pub fn step(&mut self) -> std::io::Result<bool> {
+        let event = match self.pending_event.take() {
+            Some(event) => event,
+            None => self.read_event()?,
+        };
+
+        // The terminal reports every move of the mouse, which changes nothing.
+        if let Event::Mouse(MouseEvent {
+            kind: MouseEventKind::Moved,
+            ..
+        }) = event
+        {
+            return Ok(true);
+        }
+
+        let (active, generation) = (self.buffers.active_index(), self.buffer().generation());
+        let cursor = self.cursor();
+        let repeating = matches!(&event, Event::Key(key) if self.keymap.lookup(key) == Some(Action::LastChange));
+        if !repeating {
+            self.change_step = None;
+        }
+
+        let running = match event {
+            Event::Key(event) if event.kind == KeyEventKind::Press => {
+                tracing::debug!(code = ?event.code, modifiers = ?event.modifiers, "key");
+                self.route_key(event, self.overlays.len())?
+            }
+            // Overlays only take keys.
+            Event::Paste(_) if !self.overlays.is_empty() => true,
+            Event::Paste(text) => {
+                self.pending_chords = None;
+                if self.buffer().read_only {
+                    self.refuse_edit();
+                } else if self.confirm_paste(&text)? {
+                    let before = self.buffer().rope.clone();
+                    self.replace_selection(|editor| editor.insert_text(&text))?;
+                    self.flash_changes(&before);
+                }
+
+                self.redraw()?;
+                true
+            }
+            Event::Resize(..) => {
+                self.screen.clear()?;
+                self.redraw()?;
+                true
+            }
+            Event::Mouse(event) if self.overlays.is_empty() => {
+                self.handle_mouse(event)?;
+                true
+            }
+            _ => true,
+        };
+
+        if !running {
+            return Ok(false);
+        }
+        self.deliver_events();
+
+        self.enforce_invariants();
+        self.draw_status_bar(self.buffer().awaiting_parse())?;
+        self.place_cursor()?;
+
+        // Every edit bumps the generation, through the same path undo keys on.
+        if self.buffers.active_index() == active && self.buffer().generation() != generation {
+            let (line, column) = self.cursor();
+            self.buffer_mut().record_edit(line, column);
+        }
+        let kept_goal = std::mem::take(&mut self.keep_goal);
+        if !kept_goal
+            && (self.buffers.active_index() != active
+                || self.buffer().generation() != generation
+                || self.cursor() != cursor)
+        {
+            self.goal_column = None;
+        }
+        // A buffer switched to has its own selection back.
+        let kept = std::mem::take(&mut self.keep_selection);
+        if !kept
+            && self.buffers.active_index() == active
+            && self.buffer().generation() != generation
+        {
+            self.selection = None;
+        }
+
+        Ok(true)
+    }
+
+    // Hands the key to the overlay at `depth` in the stack, counting from the
+    // bottom, and on down while it isn't used; depth 0 is the buffer. Returns
+    // false when the editor should exit.
+    fn route_key(&mut self, event: KeyEvent, depth: usize) -> std::io::Result<bool> {
+        if depth == 0 {
+            return self.handle_key(event);
+        }
+
+        // Out of the stack while it runs, so it can open overlays above itself.
+        let index = depth - 1;
+        let mut overlay = self.overlays.remove(index);
+
+        if event.code == KeyCode::Esc {
+            overlay.dismissed(self);
+            self.enforce_invariants();
+            self.redraw()?;
+            return Ok(true);
+        }
+
+        let used = overlay.handle_key(self, event)?;
+        if overlay.wants_dismiss() {
+            overlay.dismissed(self);
+            self.enforce_invariants();
+        } else {
+            self.overlays
+                .insert(index.min(self.overlays.len()), overlay);
+        }
+
+        if !used {
+            return self.route_key(event, index.min(self.overlays.len()));
+        }
+
+        self.redraw()?;
+        Ok(true)
+    }
+
+    fn open_overlay(&mut self, overlay: Box<dyn Overlay>) -> std::io::Result<()> {
+        self.overlays.push(overlay);
+        self.redraw()
+    }
+
+    fn render_overlays(&mut self) -> std::io::Result<()> {
+        let overlays = std::mem::take(&mut self.overlays);
+        let rendered = overlays.iter().try_for_each(|overlay| overlay.render(self));
+        self.overlays = overlays;
+        rendered
+    }
+
+    // Returns false when the editor should exit.
+    fn handle_key(&mut self, event: KeyEvent) -> std::io::Result<bool> {
+        self.message = None;
+        self.failed = false;
+
+        let mut sequence = match self.pending_chords.take() {
+            Some(_) if event.code == KeyCode::Esc => {
+                self.count = None;
+                self.redraw()?;
+                return Ok(true);
+            }
+            Some((chords, _)) => chords,
+            None => Vec::new(),
+        };
+
+        if let KeyCode::Char(c @ '0'..='9') = event.code {
+            if event.modifiers == KeyModifiers::ALT && sequence.is_empty() {
+                let digit = c as usize - '0' as usize;
+                self.count = Some(
+                    self.count
+                        .unwrap_or(0)
+                        .saturating_mul(10)
+                        .saturating_add(digit),
+                );
+                self.redraw()?;
+                return Ok(true);
+            }
+        }
+
+        // The count waits along with the sequence.
+        sequence.push((event.code, event.modifiers));
+        match self.keymap.resolve(&sequence) {
+            Resolved::Action(action) => {
+                let count = self.count.take();
+                return self.dispatch(action, count);
+            }
+            Resolved::Prefix => {
+                self.pending_chords = Some((sequence, Instant::now() + CHORD_TIMEOUT));
+                self.redraw()?;
+            }
+            Resolved::Undefined if sequence.len() > 1 => {
+                self.count = None;
+                self.fail(format!("undefined: {}", keymap::format_sequence(&sequence)));
+                self.screen.bell()?;
+                self.redraw()?;
+            }
+            Resolved::Undefined => {
+                self.count = None;
+                if let Some(c) = self.typed_char(&event) {
+                    if self.buffer().read_only {
+                        self.refuse_edit();
+                        self.redraw()?;
+                        return Ok(true);
+                    }
+                    if self.auto_pairs && self.wrap_selection(c) {
+                        self.redraw()?;
+                        return Ok(true);
+                    }
+                    let paired = self.replace_selection(|editor| {
+                        if editor.auto_pairs && editor.type_paired(c)? {
+                            return Ok(true);
+                        }
+
+                        let text = editor.read_typed_run(c)?;
+                        editor.insert_text(&text)?;
+                        Ok(false)
+                    })?;
+                    if !paired {
+                        self.redraw()?;
+                    }
+                }
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn typed_char(&self, event: &KeyEvent) -> Option<char> {
+        let KeyCode::Char(c) = event.code else {
+            return None;
+        };
+        if !matches!(
+            self.keymap.resolve(&[(event.code, event.modifiers)]),
+            Resolved::Undefined
+        ) {
+            return None;
+        }
+
+        let modifiers = event.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT);
+        let typed = modifiers.is_empty()
+            || (Self::is_altgr(modifiers) && (self.altgr_inserts || !c.is_ascii_alphanumeric()));
+        typed.then_some(c)
+    }
+
+    // AltGr is reported as Alt, or as Ctrl+Alt on Windows. There's no telling it
+    // apart from a real Alt, so unless altgr_inserts is set only symbols and
+    // non-ASCII letters count as typed. Bound chords always win.
+    fn is_altgr(modifiers: KeyModifiers) -> bool {
+        modifiers == KeyModifiers::ALT || modifiers == KeyModifiers::CONTROL | KeyModifiers::ALT
+    }
+
+    // Input methods and terminals sending emoji or composed text deliver one
+    // grapheme as several char events. Everything already queued is inserted as a
+    // single edit, so the cursor advances by the grapheme's width and no frame is
+    // drawn mid-composition.
+    fn read_typed_run(&mut self, first: char) -> std::io::Result<String> {
+        let mut text = first.to_string();
+
+        while self.pending_event.is_none() && self.screen.poll(Duration::ZERO)? {
+            match self.read_event()? {
+                Event::Key(event) if event.kind != KeyEventKind::Press => {}
+                Event::Key(event) => match self.typed_char(&event) {
+                    Some(c) => text.push(c),
+                    None => self.pending_event = Some(Event::Key(event)),
+                },
+                event => self.pending_event = Some(event),
+            }
+        }
+
+        Ok(text)
+    }
+
+    // Types the opener of a pair along with its closer, leaving the cursor
+    // between them, unless it's right before a word. A closer typed just
+    // before the same one steps over it. Returns false for chars that aren't
+    // part of a pair, to be typed as usual.
+    fn type_paired(&mut self, c: char) -> std::io::Result<bool> {
+        if c == '"' || c == '\'' {
+            self.type_quote(c)?;
+            return Ok(true);
+        }
+
+        let index = self.get_cursor_index();
+        let next = self.buffer().rope.get_char(index);
+        if pairs::PAIRS.iter().any(|&(_, close)| close == c) {
+            if next != Some(c) {
+                return Ok(false);
+            }
+            self.jump_to_char(index + 1);
+        } else if let Some(close) = pairs::closer(c) {
+            if next.is_some_and(|n| n.is_alphanumeric() || n == '_') {
+                return Ok(false);
+            }
+            self.insert_text(&format!("{c}{close}"))?;
+            self.jump_to_char(index + 1);
+        } else {
+            return Ok(false);
+        }
+
+        self.redraw()?;
+        Ok(true)
+    }
+
+    // Closes the string being opened, or steps over the closing quote of the one
+    // being typed. Quotes typed inside strings and comments, after a word or
+    // where a Rust lifetime goes are inserted as is.
+    fn type_quote(&mut self, quote: char) -> std::io::Result<()> {
+        self.ensure_tree();
+
+        let index = self.get_cursor_index();
+        let rope = &self.buffer().rope;
+        let line = rope.char_to_line(index);
+        let column = index - rope.line_to_char(line);
+        let byte = rope.char_to_byte(index);
+        let before = rope.slice(rope.line_to_char(line)..index).to_string();
+        let rust = self.buffer().language() == Language::Rust;
+
+        let (closes, context, lifetime) = match &self.buffer().tree {
+            Some(tree) => (
+                pairs::closes_string(tree, byte, quote),
+                pairs::quote_context(tree, byte, &before),
+                quote == '\'' && rust && pairs::expects_lifetime(tree, byte, &before),
+            ),
+            None => (false, QuoteContext::String, false),
+        };
+
+        if closes {
+            self.move_cursor_to(line, column + 1);
+        } else if context == QuoteContext::Code && !lifetime && !pairs::after_word(&before, quote) {
+            self.insert_text(&format!("{quote}{quote}"))?;
+            self.move_cursor_to(line, column + 1);
+        } else {
+            self.insert_text(&quote.to_string())?;
+        }
+
+        self.redraw()
+    }
+
+    // Typing an opener with text selected puts the pair around it instead of
+    // replacing it, and keeps the text inside selected.
+    fn wrap_selection(&mut self, open: char) -> bool {
+        let (Some(close), Some(range)) = (pairs::closer(open), self.selection_range()) else {
+            return false;
+        };
+
+        let rope = &self.buffer().rope;
+        let text = format!("{open}{}{close}", rope.slice(range.clone()));
+        let forward = self.get_cursor_index() == range.end;
+        let cursor = self.cursor();
+        self.buffer_mut().edit(range.clone(), &text, cursor);
+
+        let (anchor, head) = if forward {
+            (range.start + 1, range.end + 1)
+        } else {
+            (range.end + 1, range.start + 1)
+        };
+        let rope = &self.buffer().rope;
+        let line = rope.char_to_line(anchor);
+        self.selection = Some((line, anchor - rope.line_to_char(line)));
+        self.keep_selection = true;
+        self.jump_to_char(head);
+        true
+    }
+
+    // Returns false when the editor should exit.
+    fn dispatch(&mut self, action: Action, count: Option<usize>) -> std::io::Result<bool> {
+        if action.edits() && self.buffer().read_only {
+            self.refuse_edit();
+            self.redraw()?;
+            return Ok(true);
+        }
+
+        match action {
+            Action::SelectUp | Action::SelectDown | Action::SelectLeft | Action::SelectRight => {
+                self.selection.get_or_insert(self.cursor());
+            }
+            Action::MoveUp
+            | Action::MoveDown
+            | Action::MoveLeft
+            | Action::MoveRight
+            | Action::LineStart
+            | Action::LineEnd
+            | Action::PageUp
+            | Action::PageDown
+            | Action::FileStart
+            | Action::FileEnd
+            | Action::WordLeft
+            | Action::WordRight
+            | Action::JumpBack
+            | Action::JumpForward
+            | Action::Cancel => self.selection = None,
+            _ => {}
+        }
+
+        match action {
+            Action::Quit => return self.quit(),
+            Action::Cancel => self.redraw()?,
+            Action::SelectUp => self.move_cursor_by(CursorMovement::Up, count.unwrap_or(1))?,
+            Action::SelectDown => self.move_cursor_by(CursorMovement::Down, count.unwrap_or(1))?,
+            Action::SelectLeft => self.move_cursor_by(CursorMovement::Left, count.unwrap_or(1))?,
+            Action::SelectRight => {
+                self.move_cursor_by(CursorMovement::Right, count.unwrap_or(1))?
+            }
+            Action::Copy => self.copy_selection(false)?,
+            Action::Cut => self.copy_selection(true)?,
+            Action::Save => {
+                self.save()?;
+                self.redraw()?;
+            }
+            Action::SaveAs => {
+                self.save_as()?;
+                self.redraw()?;
+            }
+            Action::MoveUp => self.move_cursor_by(CursorMovement::Up, count.unwrap_or(1))?,
+            Action::MoveDown => self.move_cursor_by(CursorMovement::Down, count.unwrap_or(1))?,
+            Action::MoveLeft => self.move_cursor_by(CursorMovement::Left, count.unwrap_or(1))?,
+            Action::MoveRight => self.move_cursor_by(CursorMovement::Right, count.unwrap_or(1))?,
+            Action::LineStart => self.move_cursor_by(CursorMovement::LineStart, 1)?,
+            Action::LineEnd => self.move_cursor_by(CursorMovement::LineEnd, 1)?,
+            Action::PageUp => self.move_cursor_by(CursorMovement::PageUp, count.unwrap_or(1))?,
+            Action::PageDown => {
+                self.move_cursor_by(CursorMovement::PageDown, count.unwrap_or(1))?
+            }
+            Action::FileStart => self.move_cursor_by(CursorMovement::FileStart, 1)?,
+            Action::FileEnd => self.move_cursor_by(CursorMovement::FileEnd, 1)?,
+            Action::WordLeft => {
+                self.move_cursor_by(CursorMovement::WordLeft, count.unwrap_or(1))?
+            }
+            Action::WordRight => {
+                self.move_cursor_by(CursorMovement::WordRight, count.unwrap_or(1))?
+            }
+            Action::Newline => {
+                self.replace_selection(|editor| {
+                    let indent = editor.indent_before_cursor();
+                    editor.insert_text(&format!("\n{indent}"))
+                })?;
+                self.redraw()?;
+            }
+            Action::InsertTab => {
+                self.replace_selection(|editor| editor.repeat_edit(count, Self::insert_tab))?;
+                self.redraw()?;
+            }
+            Action::CopyLine => self.copy_line(false)?,
+            Action::CutLine => self.copy_line(true)?,
+            Action::DeleteLine => self.delete_line()?,
+            Action::DuplicateLine => self.duplicate_line()?,
+            Action::MoveLineUp => self.move_line(true)?,
+            Action::MoveLineDown => self.move_line(false)?,
+            Action::Paste => self.paste_register()?,
+            Action::PreviewPaste => self.preview_paste()?,
+            Action::FindReferences => self.find_references()?,
+            Action::Follow => self.toggle_follow()?,
+            Action::Evaluate => self.evaluate(false)?,
+            Action::EvaluateAppend => self.evaluate(true)?,
+            Action::SetLanguage => self.language_menu()?,
+            Action::LastChange => self.last_change()?,
+            Action::GoToLine => self.go_to_line()?,
+            Action::Search => self.search()?,
+            Action::Replace => self.replace()?,
+            Action::JumpToMatch => self.jump_to_match()?,
+            Action::CenterCursor => self.center_cursor()?,
+            Action::NextSyntaxError => self.next_syntax_error()?,
+            Action::JumpBack => self.step_jump(false)?,
+            Action::JumpForward => self.step_jump(true)?,
+            Action::ToggleRelativeNumbers => {
+                self.relative_numbers = !self.relative_numbers;
+                self.redraw()?;
+            }
+            Action::ToggleReadOnly => self.toggle_read_only()?,
+            Action::ToggleWrap => {
+                self.set_wrap(!self.wrap);
+                let state = if self.wrap { "on" } else { "off" };
+                self.message = Some(format!("Line wrapping {state}"));
+                self.redraw()?;
+            }
+            Action::Help => self.open_overlay(Box::new(Help::new(&self.keymap)))?,
+            Action::AlternateBuffer => match self.buffers.alternate() {
+                Some(index) => self.switch_buffer(index)?,
+                None => {
+                    self.message = Some("No other buffer".to_string());
+                    self.redraw()?;
+                }
+            },
+            Action::NextBuffer | Action::PreviousBuffer => {
+                let (len, active) = (self.buffers.len(), self.buffers.active_index());
+                if len == 1 {
+                    self.message = Some("No other buffer".to_string());
+                    self.redraw()?;
+                } else if action == Action::NextBuffer {
+                    self.switch_buffer((active + 1) % len)?;
+                } else {
+                    self.switch_buffer((active + len - 1) % len)?;
+                }
+            }
+            Action::NextLocation => {
+                self.record_jump();
+                self.step_location(true);
+                self.jump_to_location(self.locations.current)?;
+            }
+            Action::PreviousLocation => {
+                self.record_jump();
+                self.step_location(false);
+                self.jump_to_location(self.locations.current)?;
+            }
+            Action::OpenLineBelow => self.open_line(false)?,
+            Action::InsertSeparator => self.insert_separator()?,
+            Action::OpenLineAbove => self.open_line(true)?,
+            Action::Backspace => {
+                if !self.delete_selection() {
+                    let count = count.map(|count| count.min(self.buffer().rope.len_chars()));
+                    self.repeat_edit(count, |editor| {
+                        editor.backspace();
+                        Ok(())
+                    })?;
+                }
+
+                self.redraw()?;
+            }
+            Action::Delete => {
+                if !self.delete_selection() {
+                    self.repeat_edit(count, |editor| {
+                        editor.delete_forward();
+                        Ok(())
+                    })?;
+                }
+
+                self.redraw()?;
+            }
+            Action::DeleteWord => {
+                if !self.delete_selection() {
+                    self.repeat_edit(count, |editor| {
+                        editor.delete_word_forward();
+                        Ok(())
+                    })?;
+                }
+
+                self.redraw()?;
+            }
+            Action::BackspaceWord => {
+                if !self.delete_selection() {
+                    self.repeat_edit(count, |editor| {
+                        editor.backspace_word();
+                        Ok(())
+                    })?;
+                }
+
+                self.redraw()?;
+            }
+            Action::DuplicateAndComment => self.duplicate_and_comment()?,
+            Action::ToggleComment => self.toggle_comment()?,
+            Action::Reflow => self.reflow()?,
+            Action::BufferList => self.buffer_list()?,
+            Action::JumpToWhitespace => {
+                self.buffer_mut().refresh_hygiene();
+                let hygiene = self.buffer().hygiene();
+
+                if let Some(line) = hygiene.first_trailing_whitespace {
+                    let column = lines::line_content_range(&self.buffer().rope, line).len();
+                    self.move_cursor_to(line, column);
+                } else if !hygiene.final_newline {
+                    let line = self.buffer().rope.len_lines() - 1;
+                    let column = self.buffer().rope.line(line).len_chars();
+                    self.move_cursor_to(line, column);
+                } else {
+                    self.message = Some("No whitespace problems".to_string());
+                }
+
+                self.redraw()?;
+            }
+            Action::FixWhitespace => self.fix_whitespace()?,
+            Action::ToggleWhitespace => {
+                self.show_whitespace = !self.show_whitespace;
+                self.message = Some(format!(
+                    "Whitespace {}",
+                    if self.show_whitespace {
+                        "shown"
+                    } else {
+                        "hidden"
+                    }
+                ));
+                self.redraw()?;
+            }
+            Action::ToggleDebugOverlay => {
+                self.set_debug_overlay(self.stats.is_none());
+                self.redraw()?;
+            }
+            Action::Reload => self.reload()?,
+            Action::ProjectReplace => self.project_replace()?,
+            Action::ToggleLowBandwidth => {
+                self.low_bandwidth = !self.low_bandwidth;
+                self.message = Some(format!(
+                    "Low-bandwidth mode {}",
+                    if self.low_bandwidth { "on" } else { "off" }
+                ));
+                self.redraw()?;
+            }
+            Action::ToggleAutoPairs => {
+                self.auto_pairs = !self.auto_pairs;
+                self.message = Some(format!(
+                    "Bracket and quote pairing {}",
+                    if self.auto_pairs { "on" } else { "off" }
+                ));
+                self.redraw()?;
+            }
+            Action::Undo => self.undo(false)?,
+            Action::Redo => self.undo(true)?,
+            Action::CopyLocation => self.copy_location(false)?,
+            Action::CopyLocationWithColumn => self.copy_location(true)?,
+            Action::CopyMarkdown => {
+                let line = self.get_line_number();
+                let text = self.buffer().rope.line(line).to_string().replace('\r', "");
+                let fenced = clipboard::markdown_fence(&text, self.buffer().language_tag());
+
+                self.copy_to_clipboard(fenced, false)?;
+                self.message = Some("Copied line as a Markdown code block".to_string());
+                self.redraw()?;
+            }
+            Action::ShowDiagnostic => {
+                if let Some(event) = self.diagnostic_popup()? {
+                    return self.handle_key(event);
+                }
+            }
+            Action::ShowPath => {
+                let name = self.buffer().display_name();
+                let path = std::fs::canonicalize(name)
+                    .map_or_else(|_| name.to_string(), |p| p.display().to_string());
+                self.message = Some(path);
+                self.redraw()?;
+            }
+        }
+
+        Ok(true)
+    }
+
+    // Modal list of open buffers: Enter switches, d or Ctrl+W closes, Esc leaves.
+    fn buffer_list(&mut self) -> std::io::Result<()> {
+        let mut selected = self.buffers.active_index();
+
+        loop {
+            self.redraw()?;
+            self.draw_buffer_list(selected)?;
+
+            let Event::Key(event) = self.read_event()? else {
+                continue;
+            };
+            if event.kind != KeyEventKind::Press {
+                continue;
+            }
+
+            match event.code {
+                KeyCode::Esc => break,
+                KeyCode::Up => selected = selected.saturating_sub(1),
+                KeyCode::Down => selected = (selected + 1).min(self.buffers.len() - 1),
+                KeyCode::Enter => {
+                    self.switch_buffer(selected)?;
+                    break;
+                }
+                KeyCode::Char('d') if event.modifiers == KeyModifiers::NONE => {
+                    self.close_buffer(selected)?;
+                    selected = selected.min(self.buffers.len() - 1);
+                }
+                KeyCode::Char('w') if event.modifiers == KeyModifiers::CONTROL => {
+                    self.close_buffer(selected)?;
+                    selected = selected.min(self.buffers.len() - 1);
+                }
+                _ => {}
+            }
+        }
+
+        self.redraw()
+    }
+
+    // Takes `line` or `line:column`, both counted from 1 like the status bar.
+    // A line past the end goes to the last one.
+    fn go_to_line(&mut self) -> std::io::Result<()> {
+        let Some(answer) = self.prompt("Go to line: ")? else {
+            return self.redraw();
+        };
+
+        let (line, column) = match answer.trim().split_once(':') {
+            Some((line, column)) => (line, Some(column)),
+            None => (answer.trim(), None),
+        };
+        let parse = |number: &str| number.trim().parse::<usize>().ok().filter(|&n| n > 0);
+        let target = match (parse(line), column.map(parse)) {
+            (Some(line), None) => (line, 1),
+            (Some(line), Some(Some(column))) => (line, column),
+            _ => {
+                self.fail(format!("Not a line number: {answer}"));
+                return self.redraw();
+            }
+        };
+
+        self.selection = None;
+        self.record_jump();
+        self.jump_centered(target.0 - 1, target.1 - 1);
+        self.redraw()
+    }
+
+    // Big moves call this before moving, so step_jump can go back.
+    fn record_jump(&mut self) {
+        let cursor = self.cursor();
+        self.buffer_mut().record_jump(cursor);
+    }
+
+    fn step_jump(&mut self, forward: bool) -> std::io::Result<()> {
+        let cursor = self.cursor();
+        match self.buffer_mut().step_jump(forward, cursor) {
+            Some(((line, column), at, len)) => {
+                self.jump_centered(line, column);
+                self.message = Some(format!("Jump {at} of {len}"));
+            }
+            None if forward => self.message = Some("No newer jumps".to_string()),
+            None => self.message = Some("No older jumps".to_string()),
+        }
+        self.redraw()
+    }
+
+    // Moves the cursor like move_cursor_to, but a line that was off screen
+    // ends up in the middle of the view rather than at its edge.
+    fn jump_centered(&mut self, line: usize, column: usize) {
+        let line = line.min(self.line_count().saturating_sub(1));
+        let height = self.viewport.height as usize;
+        let scroll = self.buffer().scroll;
+
+        if line < scroll || line >= scroll + height {
+            let centered = line
+                .saturating_sub(height / 2)
+                .min(self.viewport.max_scroll(self.line_count()));
+            self.buffer_mut().scroll = centered;
+        }
+        self.move_cursor_to(line, column);
+    }
+
+    // Repeated presses go further back, wrapping around to the newest change.
+    // Only the first is a jump, so JumpBack returns to where they started.
+    fn last_change(&mut self) -> std::io::Result<()> {
+        let count = self.buffer().edit_count();
+        if count == 0 {
+            self.message = Some("No changes yet".to_string());
+            return self.redraw();
+        }
+
+        let step = self.change_step.map_or(0, |s| (s + 1) % count);
+        let (line, column) = self.buffer().edit_position(step).unwrap();
+        if self.change_step.is_none() {
+            self.record_jump();
+        }
+        self.change_step = Some(step);
+
+        self.move_cursor_to(line, column);
+        self.message = Some(format!("Change {} of {count}", step + 1));
+        self.redraw()
+    }
+
+    // Overrides the language of the active buffer. The new tree is parsed by
+    // tick(), as for a freshly opened buffer, so large files don't stall here.
+    fn language_menu(&mut self) -> std::io::Result<()> {
+        let current = self.buffer().language();
+        let mut selected = LANGUAGES.iter().position(|&l| l == current).unwrap_or(0);
+        let lines: Vec<String> = LANGUAGES
+            .iter()
+            .map(|l| format!(" {} ", l.name()))
+            .collect();
+
+        loop {
+            self.redraw()?;
+            self.draw_list(&lines, Some(selected), selected)?;
+
+            let Event::Key(event) = self.read_event()? else {
+                continue;
+            };
+            if event.kind != KeyEventKind::Press {
+                continue;
+            }
+
+            match event.code {
+                KeyCode::Esc => break,
+                KeyCode::Up => selected = selected.saturating_sub(1),
+                KeyCode::Down => selected = (selected + 1).min(LANGUAGES.len() - 1),
+                KeyCode::Enter => {
+                    let language = LANGUAGES[selected];
+                    self.buffer_mut().set_language(language);
+                    self.message = Some(format!("Language set to {}", language.name()));
+                    break;
+                }
+                _ => {}
+            }
+        }
+
+        self.redraw()
+    }
+
+    fn project_replace(&mut self) -> std::io::Result<()> {
+        let Some(pattern) = self.prompt("Replace in project: ")? else {
+            return self.redraw();
+        };
+        let Some(replacement) = self.prompt(&format!("Replace {pattern} with: "))? else {
+            return self.redraw();
+        };
+
+        let open: Vec<(PathBuf, String)> = self
+            .buffers
+            .iter()
+            .filter_map(|b| {
+                let path = project::canonicalize(Path::new(b.filename.as_ref()?));
+                Some((path, b.rope.to_string()))
+            })
+            .collect();
+        let root = self.project.root.clone();
+        let (needle, with) = (pattern.clone(), replacement.clone());
+        let task = Task::spawn(move |token| replace::search(&root, &needle, &with, &open, token));
+        let Some(mut changes) = self.wait_for(task, |n| format!("Searching files… {n}"))? else {
+            self.message = Some("Search cancelled".to_string());
+            return self.redraw();
+        };
+
+        if changes.is_empty() {
+            self.message = Some(format!("No occurrences of {pattern}"));
+            return self.redraw();
+        }
+
+        if !self.replace_preview(&mut changes, &pattern)? {
+            return self.redraw();
+        }
+
+        let (mut occurrences, mut in_place) = (0, 0);
+        let mut applied = Vec::new();
+        let mut failures = Vec::new();
+        let enabled: Vec<&replace::FileChange> = changes
+            .iter()
+            .filter(|c| c.enabled && c.occurrences(&pattern) > 0)
+            .collect();
+
+        for (i, change) in enabled.iter().enumerate() {
+            if self.cancel_requested()? {
+                failures.extend(self.roll_back(applied));
+                self.message = Some("Replace cancelled, nothing was changed".to_string());
+                return self.show_failures(failures);
+            }
+            self.show_progress(&format!(
+                "Replacing… {i}/{} files (Esc cancels)",
+                enabled.len()
+            ))?;
+
+            match self.apply_change(change) {
+                Ok(done) => {
+                    occurrences += change.occurrences(&pattern);
+                    in_place += matches!(done, Applied::File { in_place: true }) as usize;
+                    applied.push((*change, done));
+                }
+                Err(error) => failures.push(format!(
+                    "{}: {error}",
+                    self.project.relative(&change.path).display()
+                )),
+            }
+        }
+        let files = applied.len();
+
+        self.message = Some(format!(
+            "Replaced {occurrences} occurrences in {files} files"
+        ));
+        if in_place > 0 {
+            self.message = self
+                .message
+                .take()
+                .map(|m| format!("{m} ({in_place} written in place to keep their metadata)"));
+        }
+
+        self.show_failures(failures)
+    }
+
+    fn show_failures(&mut self, failures: Vec<String>) -> std::io::Result<()> {
+        if !failures.is_empty() {
+            let mut lines = vec![format!("{} files could not be changed:", failures.len())];
+            lines.extend(failures);
+            self.redraw()?;
+            self.draw_list(&lines, None, 0)?;
+            while !matches!(self.read_event()?, Event::Key(e) if e.kind == KeyEventKind::Press) {}
+        }
+
+        self.redraw()
+    }
+
+    // Undoes changes made so far by a cancelled replace, newest first. Returns
+    // the files that couldn't be restored.
+    fn roll_back(&mut self, applied: Vec<(&replace::FileChange, Applied)>) -> Vec<String> {
+        let mut failures = Vec::new();
+
+        for (change, done) in applied.into_iter().rev() {
+            match done {
+                Applied::Buffer {
+                    index,
+                    rope,
+                    modified,
+                    undoable,
+                } => {
+                    let Some(buffer) = self.buffers.get_mut(index) else {
+                        continue;
+                    };
+                    let cursor = buffer.cursor;
+                    if undoable && buffer.undo(cursor).is_ok() {
+                        // The cancelled replace shouldn't come back with redo.
+                        buffer.discard_redo();
+                    } else {
+                        buffer.replace_unmodified(rope);
+                        buffer.modified = modified;
+                    }
+                }
+                Applied::File { .. } => {
+                    if let Err(error) = write::write_atomic(&change.path, &change.original) {
+                        failures.push(format!(
+                            "{}: not restored: {error}",
+                            self.project.relative(&change.path).display()
+                        ));
+                    }
+                }
+            }
+        }
+
+        failures
+    }
+
+    // Open buffers are changed in memory, the rest of the files on disk.
+    fn apply_change(&mut self, change: &replace::FileChange) -> Result<Applied, String> {
+        let text = change.apply();
+        let cursor = self.cursor();
+        let active = self.buffers.active_index();
+        let open = (0..self.buffers.len()).find(|&i| {
+            self.buffers
+                .get(i)
+                .and_then(|b| b.filename.as_ref())
+                .is_some_and(|f| project::canonicalize(Path::new(f)) == change.path)
+        });
+
+        if let Some(index) = open {
+            let buffer = self.buffers.get_mut(index).unwrap();
+            let cursor = if index == active {
+                cursor
+            } else {
+                buffer.cursor
+            };
+            let (rope, modified) = (buffer.rope.clone(), buffer.modified);
+            let undoable = buffer.replace_contents(Rope::from_str(&text), true, cursor);
+            return Ok(Applied::Buffer {
+                index,
+                rope,
+                modified,
+                undoable,
+            });
+        }
+
+        match std::fs::read_to_string(&change.path) {
+            Ok(current) if current == change.original => {}
+            Ok(_) => return Err("changed since the preview".to_string()),
+            Err(error) => return Err(error.to_string()),
+        }
+
+        write::write_atomic(&change.path, &text)
+            .map(|warning| Applied::File {
+                in_place: warning.is_some(),
+            })
+            .map_err(|e| e.to_string())
+    }
+
+    // Runs until the task finishes, with its progress on the bottom row. Esc
+    // cancels it; other keys are dropped, since nothing may change under it.
+    fn wait_for<T: Send + 'static>(
+        &mut self,
+        task: Task<T>,
+        describe: impl Fn(usize) -> String,
+    ) -> std::io::Result<Option<T>> {
+        while !task.is_finished() {
+            let status = if task.token().is_cancelled() {
+                "cancelling"
+            } else {
+                "Esc cancels"
+            };
+            let progress = describe(task.token().progress());
+            self.show_progress(&format!("{progress} ({status})"))?;
+
+            if self.screen.poll(Duration::from_millis(100))? {
+                match self.read_event()? {
+                    Event::Key(event)
+                        if event.kind == KeyEventKind::Press && event.code == KeyCode::Esc =>
+                    {
+                        task.token().cancel();
+                    }
+                    event @ Event::Resize(..) => self.pending_event = Some(event),
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(task.join())
+    }
+
+    // For work done between polls on this thread. Drains queued input and
+    // reports whether Esc was among it.
+    fn cancel_requested(&mut self) -> std::io::Result<bool> {
+        let mut cancel = false;
+
+        while self.screen.poll(Duration::ZERO)? {
+            match self.read_event()? {
+                Event::Key(event) if event.kind == KeyEventKind::Press => {
+                    cancel |= event.code == KeyCode::Esc;
+                }
+                event @ Event::Resize(..) => self.pending_event = Some(event),
+                _ => {}
+            }
+        }
+
+        Ok(cancel)
+    }
+
+    // Draws over the bottom row without a full redraw, for progress updates.
+    fn show_progress(&mut self, text: &str) -> std::io::Result<()> {
+        let (width, height) = self.size;
+        execute!(
+            self.screen,
+            cursor::SavePosition,
+            cursor::MoveTo(0, height - 1),
+            terminal::Clear(ClearType::CurrentLine),
+            style::ResetColor,
+            Print(paths::truncate_start(text, width as usize)),
+            cursor::RestorePosition,
+        )
+    }
+
+    // Returns true when the edits should be applied. Space toggles the file or
+    // line under the selection.
+    fn replace_preview(
+        &mut self,
+        changes: &mut [replace::FileChange],
+        pattern: &str,
+    ) -> std::io::Result<bool> {
+        let mut selected = 0;
+
+        loop {
+            let mut lines = Vec::new();
+            let mut targets = Vec::new();
+            let mut selected_line = 0;
+
+            for (f, change) in changes.iter().enumerate() {
+                if targets.len() == selected {
+                    selected_line = lines.len();
+                }
+                targets.push((f, None));
+                lines.push(format!(
+                    "[{}] {} ({})",
+                    if change.enabled { 'x' } else { ' ' },
+                    self.project.relative(&change.path).display(),
+                    change.occurrences(pattern),
+                ));
+
+                for (h, hunk) in change.hunks.iter().enumerate() {
+                    if targets.len() == selected {
+                        selected_line = lines.len();
+                    }
+                    targets.push((f, Some(h)));
+                    lines.push(format!(
+                        "    [{}] {:>5} - {}",
+                        if hunk.enabled { 'x' } else { ' ' },
+                        hunk.line + 1,
+                        hunk.before.trim()
+                    ));
+                    lines.push(format!("              + {}", hunk.after.trim()));
+                }
+            }
+
+            let total: usize = changes.iter().map(|c| c.occurrences(pattern)).sum();
+            self.message = Some(format!(
+                "{total} occurrences selected, Space toggles, Enter applies, Esc cancels"
+            ));
+            self.redraw()?;
+            self.draw_list(&lines, Some(selected_line), selected_line)?;
+
+            let Event::Key(event) = self.read_event()? else {
+                continue;
+            };
+            if event.kind != KeyEventKind::Press {
+                continue;
+            }
+
+            match event.code {
+                KeyCode::Esc => {
+                    self.message = None;
+                    return Ok(false);
+                }
+                KeyCode::Enter => {
+                    self.message = None;
+                    return Ok(true);
+                }
+                KeyCode::Up => selected = selected.saturating_sub(1),
+                KeyCode::Down => selected = (selected + 1).min(targets.len() - 1),
+                KeyCode::Char(' ') => match targets[selected] {
+                    (f, None) => changes[f].enabled = !changes[f].enabled,
+                    (f, Some(h)) => changes[f].hunks[h].enabled = !changes[f].hunks[h].enabled,
+                },
+                _ => {}
+            }
+        }
+    }
+
+    // Full-width overlay over the text area, scrolled so `focus` stays visible.
+    fn draw_list(
+        &mut self,
+        lines: &[String],
+        selected: Option<usize>,
+        focus: usize,
+    ) -> std::io::Result<()> {
+        let height = self.size.1;
+        self.draw_list_at(
+            lines,
+            selected,
+            focus,
+            1,
+            (height as usize).saturating_sub(3),
+        )
+    }
+
+    fn draw_list_at(
+        &mut self,
+        lines: &[String],
+        selected: Option<usize>,
+        focus: usize,
+        top: u16,
+        rows: usize,
+    ) -> std::io::Result<()> {
+        let width = (self.size.0 as usize).saturating_sub(4);
+        let offset = (focus + 1).saturating_sub(rows);
+
+        queue!(self.screen, cursor::SavePosition, cursor::Hide)?;
+        self.draw_text_cursor()?;
+
+        for (i, line) in lines.iter().enumerate().skip(offset).take(rows) {
+            queue!(
+                self.screen,
+                cursor::MoveTo(2, top + (i - offset) as u16),
+                style::ResetColor
+            )?;
+
+            if Some(i) == selected {
+                queue!(self.screen, style::SetAttribute(style::Attribute::Reverse))?;
+            }
+
+            let line: String = line.chars().take(width).collect();
+            queue!(
+                self.screen,
+                Print(format!("{line:<width$}")),
+                style::SetAttribute(style::Attribute::Reset)
+            )?;
+        }
+
+        execute!(self.screen, cursor::RestorePosition)
+    }
+
+    // Single-line input on the bottom row. Returns None when cancelled with Esc
+    // or left empty.
+    fn prompt(&mut self, label: &str) -> std::io::Result<Option<String>> {
+        self.prompt_with(label, "")
+    }
+
+    // Like prompt, with `text` already typed in for editing.
+    fn prompt_with(&mut self, label: &str, text: &str) -> std::io::Result<Option<String>> {
+        let mut prompt = Prompt::new(label);
+        prompt.insert(text);
+        self.run_prompt(prompt)
+    }
+
+    fn run_prompt(&mut self, mut prompt: Prompt) -> std::io::Result<Option<String>> {
+        queue!(self.screen, cursor::SavePosition)?;
+        self.draw_text_cursor()?;
+
+        let answer = loop {
+            let (width, height) = self.size;
+            prompt.render(&mut self.screen, height - 1, width)?;
+
+            let event = match self.read_event()? {
+                Event::Key(event) if event.kind == KeyEventKind::Press => event,
+                Event::Paste(text) => {
+                    prompt.insert(&text);
+                    continue;
+                }
+                _ => continue,
+            };
+
+            match prompt.handle_key(&event) {
+                PromptEvent::Continue => {}
+                PromptEvent::Cancel => break None,
+                PromptEvent::Submit => {
+                    break (!prompt.text().is_empty()).then(|| prompt.text().to_string())
+                }
+            }
+        };
+
+        execute!(self.screen, cursor::RestorePosition)?;
+        Ok(answer)
+    }
+
+    fn draw_buffer_list(&mut self, selected: usize) -> std::io::Result<()> {
+        let (width, height) = self.size;
+        let width = (width as usize).saturating_sub(4).min(72);
+        let name_width = width.saturating_sub(20);
+
+        queue!(self.screen, cursor::SavePosition, cursor::Hide)?;
+        self.draw_text_cursor()?;
+
+        for (i, buffer) in self.buffers.iter().enumerate() {
+            if i + 2 >= height as usize {
+                break;
+            }
+
+            let entry = format!(
+                " {:>2} {} {:<name_width$} {:>8} lines ",
+                i + 1,
+                if buffer.modified { "[+]" } else { "   " },
+                paths::shorten(buffer.display_name(), name_width),
+                buffer.line_count(),
+            );
+
+            queue!(
+                self.screen,
+                cursor::MoveTo(2, i as u16 + 1),
+                style::ResetColor
+            )?;
+
+            if i == selected {
+                queue!(self.screen, style::SetAttribute(style::Attribute::Reverse))?;
+            }
+
+            queue!(
+                self.screen,
+                Print(entry),
+                style::SetAttribute(style::Attribute::Reset)
+            )?;
+        }
+
+        execute!(self.screen, cursor::RestorePosition)
+    }
+
+    // Shows every diagnostic on the cursor line in a popup next to the cursor.
+    // PageUp/PageDown scroll it; any other key closes it, and keys other than
+    // Esc are handed back so they still take effect.
+    fn diagnostic_popup(&mut self) -> std::io::Result<Option<KeyEvent>> {
+        let line = self.get_line_number();
+        let source = self.buffer().rope.to_string();
+        self.ensure_tree();
+        let Some(tree) = &self.buffer().tree else {
+            return Ok(None);
+        };
+        let mut messages: Vec<String> = diagnostics::syntax_errors(tree, &source)
+            .into_iter()
+            .filter(|d| d.line == line)
+            .map(|d| format!("{}:{}: {}", d.line + 1, d.column + 1, d.message))
+            .collect();
+
+        if let Some(filename) = &self.buffer().filename {
+            let path = project::canonicalize(Path::new(filename));
+            messages.extend(
+                self.check_results
+                    .iter()
+                    .filter(|d| d.line == line && project::canonicalize(&d.file) == path)
+                    .map(|d| {
+                        format!(
+                            "{}:{}: {}: {}",
+                            d.line + 1,
+                            d.column + 1,
+                            d.level,
+                            d.message
+                        )
+                    }),
+            );
+        }
+
+        if messages.is_empty() {
+            self.message = Some("No diagnostics on this line".to_string());
+            self.redraw()?;
+            return Ok(None);
+        }
+
+        let (width, height) = self.size;
+        let (column, row) = self
+            .cursor_screen_position()
+            .unwrap_or((self.viewport.left, self.viewport.top));
+        // A margin either side of at least a column of text.
+        let popup_width = (width as usize).saturating_sub(2).min(POPUP_WIDTH);
+        if popup_width < 3 || height < 2 {
+            self.message = Some("Too small a terminal to show the diagnostic".to_string());
+            self.redraw()?;
+            return Ok(None);
+        }
+        let lines: Vec<String> = messages
+            .iter()
+            .flat_map(|m| diagnostics::wrap(m, popup_width - 2))
+            .collect();
+        let popup_height = lines.len().min(POPUP_HEIGHT).min(height as usize - 1);
+
+        // Below the cursor when it fits, above it otherwise.
+        let top = if row as usize + 1 + popup_height <= height as usize {
+            row as usize + 1
+        } else {
+            (row as usize).saturating_sub(popup_height)
+        };
+        let left = (column as usize).min(width as usize - popup_width);
+        let mut offset = 0;
+
+        loop {
+            queue!(self.screen, cursor::SavePosition, cursor::Hide)?;
+
+            for (i, line) in lines.iter().skip(offset).take(popup_height).enumerate() {
+                queue!(
+                    self.screen,
+                    cursor::MoveTo(left as u16, (top + i) as u16),
+                    style::ResetColor,
+                    style::SetAttribute(style::Attribute::Reverse),
+                    Print(format!(" {:<w$} ", line, w = popup_width - 2)),
+                    style::SetAttribute(style::Attribute::Reset),
+                )?;
+            }
+
+            execute!(self.screen, cursor::RestorePosition, cursor::Show)?;
+
+            let Event::Key(event) = self.read_event()? else {
+                continue;
+            };
+            if event.kind != KeyEventKind::Press {
+                continue;
+            }
+
+            match event.code {
+                KeyCode::PageDown => {
+                    offset = (offset + 1).min(lines.len() - popup_height);
+                }
+                KeyCode::PageUp => offset = offset.saturating_sub(1),
+                KeyCode::Esc => {
+                    self.redraw()?;
+                    return Ok(None);
+                }
+                _ => {
+                    self.redraw()?;
+                    return Ok(Some(event));
+                }
+            }
+        }
+    }
+
+    fn switch_buffer(&mut self, index: usize) -> std::io::Result<()> {
+        self.buffer_mut().cursor = self.cursor();
+        self.buffer_mut().selection = self.selection.take();
+        self.buffers.switch(index);
+        self.restore_buffer_cursor()?;
+        self.redraw()
+    }
+
+    fn close_buffer(&mut self, index: usize) -> std::io::Result<()> {
+        let Some(buffer) = self.buffers.get(index) else {
+            return Ok(());
+        };
+
+        if buffer.modified {
+            let question = format!(
+                "{} has unsaved changes, close anyway? [y/N], [d]iff",
+                buffer.display_name()
+            );
+
+            let answer = self.ask_unsaved(&question, &[index])?;
+            if !matches!(answer.code, KeyCode::Char('y' | 'Y')) {
+                return Ok(());
+            }
+        }
+
+        self.buffer_mut().cursor = self.cursor();
+        if let Some(file) = self.buffers.get(index).and_then(position) {
+            positions::remember(&[file]);
+        }
+        self.selection = None;
+        self.buffers.close(index);
+        self.restore_buffer_cursor()?;
+        self.redraw()
+    }
+
+    fn reload(&mut self) -> std::io::Result<()> {
+        let Some(filename) = self.buffer().filename.clone() else {
+            self.message = Some("Buffer has no file name".to_string());
+            return self.redraw();
+        };
+
+        let rope = match buffer::read_file(Path::new(&filename)) {
+            Ok((rope, _)) => rope,
+            Err(error) => {
+                self.message = Some(format!("Could not reload: {error}"));
+                return self.redraw();
+            }
+        };
+
+        let cursor = self.cursor();
+        let undoable = self.buffer_mut().replace_contents(rope, false, cursor);
+        self.buffer_mut().detect_line_ending();
+        self.buffer_mut().record_disk();
+        self.message = Some(if undoable {
+            format!("Reloaded {}", paths::shorten(&filename, TITLE_WIDTH))
+        } else {
+            NOT_UNDOABLE.to_string()
+        });
+
+        self.enforce_invariants();
+        self.redraw()
+    }
+
+    // Notices the active buffer's file being changed by something else. A
+    // buffer without changes of its own just takes the new contents; one with
+    // changes asks which to keep, and being told to leave it, waits for the
+    // file to change again or for the next save to ask.
+    fn check_disk(&mut self) -> std::io::Result<()> {
+        if self.disk_checked.elapsed() < DISK_CHECK || self.buffer().follow.is_some() {
+            return Ok(());
+        }
+        self.disk_checked = Instant::now();
+
+        let (Some(stamp), Some(filename)) =
+            (self.buffer().disk_changed(), self.buffer().filename.clone())
+        else {
+            return Ok(());
+        };
+        if self.buffer().ignored == Some(stamp) {
+            return Ok(());
+        }
+
+        // Until a reload succeeds, so one that fails isn't retried every check.
+        self.buffer_mut().ignored = Some(stamp);
+        if !self.buffer().modified {
+            return self.reload();
+        }
+
+        let question = format!(
+            "{} changed on disk: [r]eload, dropping your changes, [o]verwrite, or Esc",
+            paths::shorten(&filename, TITLE_WIDTH)
+        );
+        match self.ask(&question)?.code {
+            KeyCode::Char('r') => self.reload(),
+            KeyCode::Char('o') => {
+                self.overwrite(&filename)?;
+                self.redraw()
+            }
+            _ => self.redraw(),
+        }
+    }
+
+    // Starts following the file, or resumes it after the cursor moved off the
+    // last line, or stops when already following at the end.
+    fn toggle_follow(&mut self) -> std::io::Result<()> {
+        let Some(filename) = self.buffer().filename.clone() else {
+            self.message = Some("Buffer has no file name".to_string());
+            return self.redraw();
+        };
+
+        let at_end = self.get_line_number() + 1 >= self.buffer().line_count();
+
+        if self.buffer().follow.is_some() && at_end {
+            self.buffer_mut().follow = None;
+            self.message = Some("Stopped following".to_string());
+            return self.redraw();
+        }
+
+        if self.buffer().follow.is_none() {
+            let len = std::fs::metadata(&filename).map_or(0, |m| m.len());
+            self.buffer_mut().follow = Some(len);
+        }
+
+        let last = self.buffer().line_count().saturating_sub(1);
+        self.move_cursor_to(last, 0);
+        self.redraw()
+    }
+
+    // Reloads a followed file that changed size. The view only stays pinned to the
+    // end while the cursor is on the last line; moving up pauses that.
+    fn follow_file(&mut self) -> std::io::Result<()> {
+        let (Some(len), Some(filename)) = (self.buffer().follow, self.buffer().filename.clone())
+        else {
+            return Ok(());
+        };
+
+        if std::fs::metadata(&filename).map_or(true, |m| m.len() == len) {
+            return Ok(());
+        }
+
+        if self.buffer().modified {
+            self.message = Some("Not following: the buffer has unsaved changes".to_string());
+            return self.redraw();
+        }
+
+        let Ok((rope, len)) = buffer::read_file(Path::new(&filename)) else {
+            return Ok(());
+        };
+
+        let pinned = self.get_line_number() + 1 >= self.buffer().line_count();
+        let line = self.get_line_number();
+        let column = self.get_cursor_index() - self.buffer().rope.line_to_char(line);
+
+        self.buffer_mut().replace_unmodified(rope);
+        self.buffer_mut().record_disk();
+        self.buffer_mut().follow = Some(len);
+
+        if pinned {
+            let last = self.buffer().line_count().saturating_sub(1);
+            self.move_cursor_to(last, 0);
+        } else {
+            self.move_cursor_to(line, column);
+        }
+
+        self.redraw()
+    }
+
+    fn undo(&mut self, redo: bool) -> std::io::Result<()> {
+        let before = self.buffer().rope.clone();
+        let cursor = self.cursor();
+        let result = if redo {
+            self.buffer_mut().redo(cursor)
+        } else {
+            self.buffer_mut().undo(cursor)
+        };
+        let verb = if redo { "redo" } else { "undo" };
+
+        match result {
+            Ok((line, column)) => {
+                self.flash_changes(&before);
+                self.move_cursor_to(line, column);
+                self.redraw()?;
+            }
+            Err(UndoError::Empty) => {
+                self.message = Some(format!("Nothing to {verb}"));
+                self.redraw()?;
+            }
+            Err(UndoError::Diverged) => {
+                self.message = Some(format!("Buffer was edited since, can't {verb}"));
+                self.redraw()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // What has to hold after every action, however it moved things: the view
+    // isn't scrolled past the end, the cursor is on one of the buffer's lines
+    // and on a grapheme boundary within it, and so is the selection's anchor.
+    // The end of a line counts as within it, since that's where typing appends.
+    fn enforce_invariants(&mut self) {
+        // Rows, not lines, fill the view when lines wrap.
+        let max_scroll = if self.wrap {
+            self.line_count().saturating_sub(1)
+        } else {
+            self.viewport.max_scroll(self.line_count())
+        };
+        if self.buffer().scroll > max_scroll {
+            self.buffer_mut().scroll = max_scroll;
+        }
+
+        let line_number = self.get_line_number();
+        let column = self.buffer().rope.get_line(line_number).map_or(0, |line| {
+            columns::column_to_char(line, columns::char_to_column(line, self.cursor_col))
+        });
+        self.move_cursor_to(line_number, column);
+        self.clamp_selection();
+    }
+
+    fn clamp_selection(&mut self) {
+        if let Some((line, column)) = self.selection {
+            let rope = &self.buffer().rope;
+            let line = line.min(self.buffer().rope.len_lines() - 1);
+            let len = rope
+                .get_line(line)
+                .map_or(0, |_| lines::line_content_range(rope, line).len());
+            self.selection = Some((line, column.min(len)));
+        }
+    }
+
+    // Puts the cursor back where it was when the buffer was left. The saved state
+    // is clamped first, since the buffer may have been reloaded or the terminal
+    // resized in the meantime.
+    fn restore_buffer_cursor(&mut self) -> std::io::Result<()> {
+        let last = self.buffer().line_count().saturating_sub(1);
+        let scroll = self.buffer().scroll.min(last);
+        self.buffer_mut().scroll = scroll;
+
+        let (line, column) = self.buffer().cursor;
+        self.move_cursor_to(line, column);
+        self.selection = self.buffer_mut().selection.take();
+        self.clamp_selection();
+        self.update_title()
+    }
+
+    fn copy_location(&mut self, with_column: bool) -> std::io::Result<()> {
+        let Some(filename) = &self.buffer().filename else {
+            self.message = Some("Buffer has no file name".to_string());
+            return self.redraw();
+        };
+
+        let path = self.project.relative(Path::new(filename));
+        let line = self.get_line_number() + 1;
+        let location = if with_column {
+            let line_start = self.buffer().rope.line_to_char(line - 1);
+            let column = self.get_cursor_index() - line_start + 1;
+            format!("{}:{line}:{column}", path.display())
+        } else {
+            format!("{}:{line}", path.display())
+        };
+
+        self.message = Some(format!("Copied {location}"));
+        self.copy_to_clipboard(location, false)?;
+        self.redraw()
+    }
+
+    // Char range from the selection's anchor to the cursor, if it isn't empty.
+    fn selection_range(&self) -> Option<Range<usize>> {
+        let (line, column) = self.selection?;
+        let rope = &self.buffer().rope;
+        let line = line.min(self.buffer().rope.len_lines() - 1);
+        let content = lines::line_content_range(rope, line);
+        let anchor = (content.start + column).min(content.end);
+        let cursor = self.get_cursor_index();
+
+        (anchor != cursor).then(|| anchor.min(cursor)..anchor.max(cursor))
+    }
+
+    // What a command falling back to `fallback` acts on right now.
+    fn target(&self, fallback: Fallback) -> Target {
+        Target::resolve(
+            &self.buffer().rope,
+            self.selection_range(),
+            self.cursor_row,
+            fallback,
+        )
+    }
+
+    // Returns whether there was a selection to delete.
+    fn delete_selection(&mut self) -> bool {
+        let Some(range) = self.selection_range() else {
+            return false;
+        };
+
+        let cursor = self.cursor();
+        self.buffer_mut().edit(range.clone(), "", cursor);
+        self.jump_to_char(range.start);
+        self.selection = None;
+        true
+    }
+
+    // Deletes the selection, if there is one, and makes `insert` in its place,
+    // undone together with the deletion.
+    fn replace_selection<T>(
+        &mut self,
+        insert: impl FnOnce(&mut Self) -> std::io::Result<T>,
+    ) -> std::io::Result<T> {
+        let selected = self.selection_range().is_some();
+        if selected {
+            let cursor = self.cursor();
+            self.buffer_mut().begin_batch(cursor);
+        }
+        self.delete_selection();
+        let result = insert(self);
+        if selected {
+            self.buffer_mut().end_batch();
+        }
+        result
+    }
+
+    // With nothing selected, the current line goes, as with copy_line.
+    fn copy_selection(&mut self, cut: bool) -> std::io::Result<()> {
+        let Target::Selection(range) = self.target(Fallback::Line) else {
+            return self.copy_line(cut);
+        };
+
+        let text = self
+            .buffer()
+            .rope
+            .slice(range.clone())
+            .to_string()
+            .replace('\r', "");
+        self.copy_to_clipboard(text, false)?;
+        if cut {
+            let cursor = self.cursor();
+            self.buffer_mut().edit(range.clone(), "", cursor);
+            self.jump_to_char(range.start);
+            self.selection = None;
+        }
+
+        self.redraw()
+    }
+
+    fn copy_to_clipboard(&mut self, text: String, linewise: bool) -> std::io::Result<()> {
+        clipboard::copy(&mut self.screen, &text)?;
+        self.register = Register { text, linewise };
+        Ok(())
+    }
+
+    // Copies the current line, or every line the selection touches, each with
+    // a terminator, optionally removing them.
+    fn copy_line(&mut self, cut: bool) -> std::io::Result<()> {
+        let rope = &self.buffer().rope;
+        let selected = self.target(Fallback::Line).lines(rope);
+        let text: String = selected
+            .clone()
+            .map(|line| format!("{}\n", rope.slice(lines::line_content_range(rope, line))))
+            .collect();
+
+        self.copy_to_clipboard(text, true)?;
+
+        if cut {
+            self.selection = None;
+            self.remove_lines(selected.clone());
+            let last = self.buffer().line_count().saturating_sub(1);
+            self.move_cursor_to(selected.start.min(last), 0);
+        }
+
+        self.redraw()
+    }
+
+    // Like cutting the line, but the register and clipboard keep what they had.
+    // Deleting the only line leaves the buffer empty.
+    fn delete_line(&mut self) -> std::io::Result<()> {
+        let line_number = self.get_line_number();
+        if line_number >= self.buffer().line_count() {
+            return self.redraw();
+        }
+
+        self.remove_lines(line_number..line_number + 1);
+        let last = self.buffer().line_count().saturating_sub(1);
+        self.move_cursor_to(line_number.min(last), 0);
+        self.redraw()
+    }
+
+    // The cursor moves onto the copy, in the same column.
+    fn duplicate_line(&mut self) -> std::io::Result<()> {
+        let line_number = self.get_line_number();
+        if line_number >= self.buffer().line_count() {
+            return self.redraw();
+        }
+
+        let rope = &self.buffer().rope;
+        let content = rope
+            .slice(lines::line_content_range(rope, line_number))
+            .to_string();
+        self.insert_lines(line_number + 1, &[&content]);
+        self.move_cursor_to(line_number + 1, self.cursor_col);
+        self.redraw()
+    }
+
+    // Swaps the line with its neighbor as one edit, and the cursor goes along
+    // with it. The terminators stay where they were, so an unterminated last
+    // line stays that way.
+    fn move_line(&mut self, up: bool) -> std::io::Result<()> {
+        let line_number = self.get_line_number();
+        let count = self.buffer().line_count();
+        let neighbor = if up {
+            line_number.checked_sub(1)
+        } else {
+            Some(line_number + 1).filter(|&l| l < count)
+        };
+        let Some(neighbor) = neighbor.filter(|_| line_number < count) else {
+            return self.redraw();
+        };
+
+        let (range, text) = lines::swap(&self.buffer().rope, line_number, neighbor);
+        let cursor = self.cursor();
+        self.buffer_mut().edit(range, &text, cursor);
+        self.move_cursor_to(neighbor, self.cursor_col);
+        self.redraw()
+    }
+
+    // Evaluates the selected arithmetic, or the expression under the cursor
+    // bounded by whitespace, and replaces it with the result or appends
+    // ` = result`. A bad expression leaves the text alone.
+    fn evaluate(&mut self, append: bool) -> std::io::Result<()> {
+        let target = self.target(Fallback::Line);
+        let rope = &self.buffer().rope;
+        let line_number = target.lines(rope).start;
+        let range = lines::line_content_range(rope, line_number);
+        let line: Vec<char> = rope.slice(range.clone()).chars().collect();
+
+        let (start, end) = match target {
+            Target::Selection(selected) if selected.end <= range.end => {
+                let (start, end) = (selected.start - range.start, selected.end - range.start);
+                let leading = line[start..end]
+                    .iter()
+                    .take_while(|c| c.is_whitespace())
+                    .count();
+                let start = start + leading;
+                let trailing = line[start..end]
+                    .iter()
+                    .rev()
+                    .take_while(|c| c.is_whitespace());
+                (start, end - trailing.count())
+            }
+            Target::Selection(_) => {
+                self.message = Some("Select an expression on one line".to_string());
+                return self.redraw();
+            }
+            Target::Line(_) | Target::Buffer => {
+                let index = self.get_cursor_index();
+                let at = index.clamp(range.start, range.end) - range.start;
+                let start = line[..at]
+                    .iter()
+                    .rposition(|c| c.is_whitespace())
+                    .map_or(0, |i| i + 1);
+                let end = line[at..]
+                    .iter()
+                    .position(|c| c.is_whitespace())
+                    .map_or(line.len(), |i| at + i);
+                (start, end)
+            }
+        };
+
+        if start == end {
+            self.message = Some("No expression under the cursor".to_string());
+            return self.redraw();
+        }
+
+        let expression: String = line[start..end].iter().collect();
+        let result = match calc::evaluate(&expression) {
+            Ok(result) => result,
+            Err(error) => {
+                self.message = Some(format!("Can't evaluate {expression}: {error}"));
+                return self.redraw();
+            }
+        };
+
+        let (replaced, text) = if append {
+            (end..end, format!(" = {result}"))
+        } else {
+            (start..end, result.to_string())
+        };
+        let offset = range.start;
+        let cursor = self.cursor();
+        let before = rope.clone();
+        self.buffer_mut().edit(
+            offset + replaced.start..offset + replaced.end,
+            &text,
+            cursor,
+        );
+        self.flash_changes(&before);
+
+        self.move_cursor_to(line_number, replaced.start + text.chars().count());
+        self.redraw()
+    }
+
+    // Charwise text goes in at the cursor. Linewise text, or anything ending in
+    // a newline, goes on new lines below the current one, which stays intact.
+    fn paste_register(&mut self) -> std::io::Result<()> {
+        let before = self.buffer().rope.clone();
+        self.replace_selection(Self::insert_register)?;
+        self.flash_changes(&before);
+        self.redraw()
+    }
+
+    fn insert_register(&mut self) -> std::io::Result<()> {
+        let text = self.register.text.replace('\r', "");
+
+        if text.is_empty() {
+            self.message = Some("Nothing to paste".to_string());
+        } else if self.register.linewise || text.ends_with('\n') {
+            let line_number = self.get_line_number();
+            let new: Vec<&str> = text
+                .strip_suffix('\n')
+                .unwrap_or(&text)
+                .split('\n')
+                .collect();
+            let indent = new[0].len() - new[0].trim_start().len();
+
+            self.insert_lines(line_number + 1, &new);
+            self.move_cursor_to(line_number + 1, indent);
+        } else {
+            self.insert_text(&text)?;
+        }
+
+        Ok(())
+    }
+
+    // Pastes and highlights the result until it's confirmed; anything but yes
+    // takes it back out. A paste taller than the view shows from its start.
+    fn preview_paste(&mut self) -> std::io::Result<()> {
+        let before = self.buffer().rope.clone();
+        let modified = self.buffer().modified;
+        self.insert_register()?;
+
+        let Some(changed) = lines::changed_lines(&before, &self.buffer().rope) else {
+            return self.redraw();
+        };
+        let pasted = self.cursor();
+        let height = self.viewport.height as usize;
+        if changed.len() > height {
+            let scroll = changed
+                .start
+                .min(self.viewport.max_scroll(self.line_count()));
+            self.buffer_mut().scroll = scroll;
+            self.move_cursor_to(changed.start, 0);
+        } else {
+            let line_count = self.buffer().line_count();
+            let scroll =
+                self.viewport
+                    .scroll_to_show(changed.end - 1, self.buffer().scroll, 0, line_count);
+            self.buffer_mut().scroll =
+                self.viewport
+                    .scroll_to_show(changed.start, scroll, 0, line_count);
+        }
+
+        self.decorations.push(Decoration {
+            buffer: self.buffers.active_index(),
+            generation: self.buffer().generation(),
+            lines: changed.clone(),
+            expires: Instant::now() + Duration::from_secs(24 * 60 * 60),
+        });
+        self.redraw()?;
+
+        let hidden = changed.end.saturating_sub(self.buffer().scroll + height);
+        let question = if hidden > 0 {
+            format!("Keep this paste? [y/N] (+{hidden} more lines)")
+        } else {
+            "Keep this paste? [y/N]".to_string()
+        };
+        let keep = self.confirm(&question)?;
+        self.decorations.pop();
+
+        if keep {
+            self.move_cursor_to(pasted.0, pasted.1);
+            return self.redraw();
+        }
+
+        let cursor = self.cursor();
+        let buffer = self.buffers.active_mut();
+        match buffer.undo(cursor) {
+            // Undo can take more than the paste if it extended the last edit.
+            Ok(restored) if buffer.rope == before => {
+                buffer.discard_redo();
+                self.move_cursor_to(restored.0, restored.1);
+            }
+            _ => {
+                buffer.replace_unmodified(before);
+                buffer.modified = modified;
+            }
+        }
+
+        self.message = Some("Paste undone".to_string());
+        self.redraw()
+    }
+
+    fn confirm_paste(&mut self, text: &str) -> std::io::Result<bool> {
+        let Some(guard) = self.paste_guard else {
+            return Ok(true);
+        };
+
+        let text = text.trim();
+        let lines = text.lines().count();
+
+        if text.len() <= guard.max_bytes && lines <= guard.max_lines {
+            return Ok(true);
+        }
+
+        self.confirm(&format!(
+            "Paste {} KB ({} lines)? [y/N]",
+            text.len().div_ceil(1024),
+            lines
+        ))
+    }
+
+    // Shows a yes/no question on the bottom row and waits for the answer.
+    fn confirm(&mut self, message: &str) -> std::io::Result<bool> {
+        let answer = self.ask(message)?;
+        Ok(answer.code == KeyCode::Char('y') || answer.code == KeyCode::Char('Y'))
+    }
+
+    // Like ask, but d shows how the buffers at `indices` differ from their
+    // files on disk and then asks again.
+    fn ask_unsaved(&mut self, question: &str, indices: &[usize]) -> std::io::Result<KeyEvent> {
+        loop {
+            let answer = self.ask(question)?;
+            if !matches!(answer.code, KeyCode::Char('d' | 'D')) {
+                return Ok(answer);
+            }
+
+            self.show_diff(indices)?;
+            self.redraw()?;
+        }
+    }
+
+    // Takes over the screen until Esc, scrolling with the arrows and pages.
+    fn show_diff(&mut self, indices: &[usize]) -> std::io::Result<()> {
+        let mut lines = Vec::new();
+        for buffer in indices.iter().filter_map(|&i| self.buffers.get(i)) {
+            let mut text = Vec::new();
+            buffer::write_rope(&buffer.rope, &mut text)?;
+            let disk = buffer.filename.as_ref().and_then(|f| std::fs::read(f).ok());
+            diff::unified(
+                buffer.display_name(),
+                disk.as_deref(),
+                &String::from_utf8_lossy(&text),
+                &mut lines,
+            );
+        }
+
+        let mut offset = 0;
+        loop {
+            let (width, height) = self.size;
+            let rows = (height as usize).saturating_sub(1).max(1);
+            offset = offset.min(lines.len().saturating_sub(rows));
+            self.draw_diff(&lines, offset, rows, width as usize)?;
+
+            match self.read_event()? {
+                Event::Key(event) if event.kind == KeyEventKind::Press => match event.code {
+                    KeyCode::Esc | KeyCode::Char('q') => return Ok(()),
+                    KeyCode::Up => offset = offset.saturating_sub(1),
+                    KeyCode::Down => offset += 1,
+                    KeyCode::PageUp => offset = offset.saturating_sub(rows),
+                    KeyCode::PageDown => offset += rows,
+                    KeyCode::Home => offset = 0,
+                    KeyCode::End => offset = lines.len(),
+                    _ => {}
+                },
+                Event::Resize(..) => {
+                    self.screen.clear()?;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn draw_diff(
+        &mut self,
+        lines: &[diff::Line],
+        offset: usize,
+        rows: usize,
+        width: usize,
+    ) -> std::io::Result<()> {
+        queue!(self.screen, cursor::Hide)?;
+        self.screen.begin_frame();
+
+        for (row, line) in lines.iter().skip(offset).take(rows).enumerate() {
+            queue!(self.screen, cursor::MoveTo(0, row as u16))?;
+            let color = match line.kind {
+                diff::Kind::Added => self.theme.diff_added(),
+                diff::Kind::Removed => self.theme.diff_removed(),
+                _ => None,
+            };
+            if let Some(color) = color {
+                queue!(self.screen, style::SetForegroundColor(color))?;
+            }
+            if matches!(line.kind, diff::Kind::Header | diff::Kind::Note) {
+                queue!(self.screen, style::SetAttribute(style::Attribute::Bold))?;
+            }
+
+            queue!(
+                self.screen,
+                Print(columns::clip(&columns::display(&line.text), 0, 0..width)),
+                style::SetAttribute(style::Attribute::Reset),
+                style::ResetColor,
+            )?;
+        }
+
+        let status = format!(
+            " Diff, lines {}-{} of {} (arrows and Page Up / Down scroll, Esc returns)",
+            (offset + 1).min(lines.len()),
+            (offset + rows).min(lines.len()),
+            lines.len()
+        );
+        queue!(
+            self.screen,
+            cursor::MoveTo(0, rows as u16),
+            style::SetAttribute(style::Attribute::Reverse),
+            Print(format!("{:width$}", columns::clip(&status, 0, 0..width))),
+            style::SetAttribute(style::Attribute::Reset),
+        )?;
+
+        self.screen.present(false)?;
+        self.screen.flush()
+    }
+
+    // Shows a question on the bottom row and returns the key pressed in answer.
+    fn ask(&mut self, message: &str) -> std::io::Result<KeyEvent> {
+        queue!(self.screen, cursor::SavePosition)?;
+        self.draw_text_cursor()?;
+        execute!(
+            self.screen,
+            cursor::MoveTo(0, self.size.1 - 1),
+            terminal::Clear(ClearType::CurrentLine),
+            style::ResetColor,
+            style::SetAttribute(style::Attribute::Reverse),
+            Print(message),
+            style::SetAttribute(style::Attribute::Reset),
+        )?;
+
+        let answer = loop {
+            if let Event::Key(event) = self.read_event()? {
+                if event.kind == KeyEventKind::Press {
+                    break event;
+                }
+            }
+        };
+
+        execute!(self.screen, cursor::RestorePosition)?;
+        Ok(answer)
+    }
+
+    fn insert_text(&mut self, text: &str) -> std::io::Result<()> {
+        let (index, cursor) = (self.get_cursor_index(), self.cursor());
+        let end = self.buffer_mut().insert_text(index, text, cursor);
+        self.jump_to_char(end);
+        Ok(())
+    }
+
+    // line is relative to the start of the file and column is a char offset into
+    // it, scrolling as needed. Both are clamped to the buffer.
+    // A click puts the cursor on the cell clicked, or the nearest one with
+    // text, and dragging from there selects. The wheel scrolls without moving
+    // the cursor, unless it would go out of view.
+    fn handle_mouse(&mut self, event: MouseEvent) -> std::io::Result<()> {
+        let position = (event.column, event.row);
+        let (scroll, h_scroll) = (self.buffer().scroll, self.buffer().h_scroll);
+
+        match event.kind {
+            MouseEventKind::Down(MouseButton::Left) if event.row == self.size.1 - 1 => {
+                return self.click_status(event.column);
+            }
+            MouseEventKind::Down(MouseButton::Left) => {
+                let Some((line, column)) = self.screen_to_buffer(position, scroll, h_scroll) else {
+                    return Ok(());
+                };
+                self.selection = None;
+                self.move_cursor_to_column(line, column);
+                self.mouse_anchor = Some(self.cursor());
+            }
+            MouseEventKind::Drag(MouseButton::Left) => {
+                let Some(anchor) = self.mouse_anchor else {
+                    return Ok(());
+                };
+                // Past the edges of the text area counts as the nearest cell.
+                let cell = (
+                    position.0.max(self.viewport.left),
+                    position
+                        .1
+                        .clamp(self.viewport.top, self.viewport.bottom_row()),
+                );
+                let Some((line, column)) = self.screen_to_buffer(cell, scroll, h_scroll) else {
+                    return Ok(());
+                };
+                self.move_cursor_to_column(line, column);
+                self.selection = (self.cursor() != anchor).then_some(anchor);
+            }
+            MouseEventKind::Up(MouseButton::Left) => self.mouse_anchor = None,
+            MouseEventKind::ScrollUp | MouseEventKind::ScrollDown if self.wrap => {
+                self.scroll_rows(event.kind == MouseEventKind::ScrollUp, self.wheel_lines);
+            }
+            MouseEventKind::ScrollUp | MouseEventKind::ScrollDown => {
+                let line_count = self.buffer().line_count();
+                let scroll = if event.kind == MouseEventKind::ScrollUp {
+                    scroll.saturating_sub(self.wheel_lines)
+                } else {
+                    (scroll + self.wheel_lines).min(self.viewport.max_scroll(line_count))
+                };
+                self.buffer_mut().scroll = scroll;
+
+                // Short of the ends of the buffer, the cursor stays out of
+                // the margins, or redrawing would scroll back.
+                let margin = self.viewport.fit_margin(self.scroll_margin);
+                let first = if scroll == 0 { 0 } else { scroll + margin };
+                let last = scroll + self.viewport.height as usize - 1;
+                let last = if scroll >= self.viewport.max_scroll(line_count) {
+                    last
+                } else {
+                    last - margin
+                };
+                let line = self.cursor_row.clamp(first, last);
+                if line != self.cursor_row {
+                    let column = self.cursor_column();
+                    self.move_cursor_to_column(line, column);
+                }
+            }
+            _ => return Ok(()),
+        }
+
+        self.redraw()
+    }
+
+    // Goes by where the segments were when the status bar was last drawn, so
+    // what's clicked is what was seen.
+    fn click_status(&mut self, column: u16) -> std::io::Result<()> {
+        let target = self
+            .status_targets
+            .iter()
+            .find(|(columns, _)| columns.contains(&column))
+            .map(|&(_, target)| target);
+
+        match target {
+            Some(StatusTarget::Position) => self.go_to_line(),
+            Some(StatusTarget::Language) => self.language_menu(),
+            Some(StatusTarget::LineEnding) if self.buffer().read_only => {
+                self.refuse_edit();
+                self.redraw()
+            }
+            Some(StatusTarget::LineEnding) => {
+                self.toggle_line_ending();
+                self.redraw()
+            }
+            Some(StatusTarget::Modified) => {
+                self.save()?;
+                self.redraw()
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn toggle_line_ending(&mut self) {
+        let ending = match self.buffer().line_ending() {
+            LineEnding::Lf => LineEnding::CrLf,
+            LineEnding::CrLf => LineEnding::Lf,
+        };
+        let cursor = self.cursor();
+        if !self.buffer_mut().set_line_ending(ending, cursor) {
+            self.message = Some(NOT_UNDOABLE.to_string());
+        }
+        self.move_cursor_to(cursor.0, cursor.1);
+    }
+
+    // The buffer line and display column under a screen cell, going by the
+    // rows of the last frame when lines wrap.
+    fn screen_to_buffer(
+        &self,
+        position: (u16, u16),
+        scroll: usize,
+        h_scroll: usize,
+    ) -> Option<(usize, usize)> {
+        let (line, column) = self.viewport.screen_to_buffer(position, scroll, h_scroll)?;
+        if !self.wrap {
+            return Some((line, column));
+        }
+
+        let index = line - scroll;
+        Some(match self.rows.get(index) {
+            Some(row) => (row.line, row.columns.start + column),
+            None => (self.buffer().line_count(), column),
+        })
+    }
+
+    // Scrolls the view `count` rows when lines wrap, no further down than
+    // where the last row is at the bottom, moving the cursor onto the nearest
+    // row still in view.
+    fn scroll_rows(&mut self, up: bool, count: usize) {
+        let height = self.viewport.height as usize;
+        let last = self.line_count().saturating_sub(1);
+        let line = self.buffer().scroll.min(last);
+        let row = self
+            .buffer()
+            .row_scroll
+            .min(self.row_starts(line).len() - 1);
+
+        let (mut line, mut row) = self.step_rows((line, row), !up, count);
+        if !up {
+            let end = self.row_starts(last).len() - 1;
+            let bottom = self.step_rows((last, end), false, height - 1);
+            (line, row) = (line, row).min(bottom);
+        }
+        let buffer = self.buffer_mut();
+        buffer.scroll = line;
+        buffer.row_scroll = row;
+
+        let rows = self
+            .viewport
+            .wrapped_rows(line, row, self.buffer().line_count(), |line| {
+                self.row_starts(line)
+            });
+        // Short of the ends of the buffer, the cursor stays out of the
+        // margins, or redrawing would scroll back.
+        let margin = self.viewport.fit_margin(self.scroll_margin);
+        let at_start = (line, row) == (0, 0);
+        let at_end = rows.last().is_some_and(|bottom| {
+            let starts = self.row_starts(last);
+            bottom.line == last && wrap::row_of(&starts, bottom.columns.start) == starts.len() - 1
+        });
+        let top = rows.get(if at_start { 0 } else { margin });
+        let bottom = rows
+            .len()
+            .checked_sub(if at_end { 1 } else { margin + 1 })
+            .and_then(|index| rows.get(index));
+        let (Some(top), Some(bottom)) = (top, bottom) else {
+            return;
+        };
+        let column = self.cursor_column();
+        let cursor = (self.cursor_row, column);
+        let starts = self.row_starts(self.cursor_row);
+        let offset = column - starts[wrap::row_of(&starts, column)];
+        if cursor < (top.line, top.columns.start) {
+            self.move_cursor_to_column(top.line, top.columns.start + offset);
+        } else if cursor >= (bottom.line, bottom.columns.end) {
+            let column = (bottom.columns.start + offset).min(bottom.columns.end - 1);
+            self.move_cursor_to_column(bottom.line, column);
+        }
+    }
+
+    // Like move_cursor_to, with a display column instead of a char one.
+    fn move_cursor_to_column(&mut self, line: usize, column: usize) {
+        let line = line.min(self.line_count().saturating_sub(1));
+        let column = columns::column_to_char(self.buffer().rope.line(line), column);
+        self.move_cursor_to(line, column);
+    }
+
+    fn move_cursor_to(&mut self, line: usize, column: usize) {
+        let line = line.min(self.buffer().rope.len_lines() - 1);
+        let rope = &self.buffer().rope;
+        let len = rope
+            .get_line(line)
+            .map_or(0, |_| lines::line_content_range(rope, line).len());
+        self.cursor_row = line;
+        self.cursor_col = column.min(len);
+
+        // Wrapped lines are scrolled into view by rows when drawn.
+        if !self.wrap {
+            self.ensure_cursor_visible();
+        }
+    }
+
+    // Scrolls the least it takes to show the cursor with `scroll_margin` lines
+    // of context above and below it, or rows while wrapping, short of
+    // scrolling past either end of the buffer. Goto-line, search and paging
+    // all end up here through move_cursor_to, and every frame through redraw.
+    fn ensure_cursor_visible(&mut self) {
+        if self.wrap {
+            self.scroll_wrapped();
+            self.buffer_mut().h_scroll = 0;
+            return;
+        }
+
+        let buffer = self.buffers.active();
+        let scroll = self.viewport.scroll_to_show(
+            self.cursor_row,
+            buffer.scroll,
+            self.scroll_margin,
+            self.line_count(),
+        );
+        let h_scroll = self
+            .viewport
+            .h_scroll_to_show(self.cursor_column(), buffer.h_scroll);
+        let buffer = self.buffer_mut();
+        buffer.scroll = scroll;
+        buffer.h_scroll = h_scroll;
+    }
+
+    // Scrolls so the cursor's line (or row) is in the middle of the view, as
+    // far as the end of the buffer allows.
+    fn center_cursor(&mut self) -> std::io::Result<()> {
+        let height = self.viewport.height as usize;
+        if self.wrap {
+            let starts = self.row_starts(self.cursor_row);
+            let cursor = (self.cursor_row, wrap::row_of(&starts, self.cursor_column()));
+            let (line, row) = self.step_rows(cursor, false, height / 2);
+            let buffer = self.buffer_mut();
+            buffer.scroll = line;
+            buffer.row_scroll = row;
+        } else {
+            let scroll = self
+                .cursor_row
+                .saturating_sub(height / 2)
+                .min(self.viewport.max_scroll(self.line_count()));
+            self.buffer_mut().scroll = scroll;
+        }
+        self.redraw()
+    }
+
+    // Display column of the cursor within its line.
+    fn cursor_column(&self) -> usize {
+        self.buffer()
+            .rope
+            .get_line(self.cursor_row)
+            .map_or(0, |line| columns::char_to_column(line, self.cursor_col))
+    }
+
+    // Where the cursor shows up on screen. Drawing scrolls it into view.
+    fn cursor_screen_position(&self) -> Option<(u16, u16)> {
+        if self.wrap {
+            let column = self.cursor_column();
+            let index = self
+                .rows
+                .iter()
+                .position(|row| row.line == self.cursor_row && row.columns.contains(&column))?;
+            let start = self.rows[index].columns.start;
+            return Some((
+                self.viewport.left + (column - start) as u16,
+                self.viewport.top + index as u16,
+            ));
+        }
+
+        let row = self
+            .viewport
+            .buffer_line_to_screen_row(self.cursor_row, self.buffer().scroll)?;
+        let column = self
+            .viewport
+            .column_to_screen(self.cursor_column(), self.buffer().h_scroll)?;
+
+        Some((column, row))
+    }
+
+    // While an overlay has the terminal cursor, the text cursor is drawn as a
+    // reversed cell so its position stays visible. The next redraw clears it.
+    fn draw_text_cursor(&mut self) -> std::io::Result<()> {
+        let Some((column, row)) = self.cursor_screen_position() else {
+            return Ok(());
+        };
+
+        let rope = &self.buffer().rope;
+        let under = rope
+            .get_line(self.cursor_row)
+            .map(|line| {
+                line.slice(self.cursor_col.min(line.len_chars())..)
+                    .to_string()
+            })
+            .and_then(|rest| rest.graphemes(true).next().map(str::to_string))
+            .filter(|g| !g.starts_with(['\r', '\n', '\t']))
+            .unwrap_or_else(|| " ".to_string());
+
+        queue!(
+            self.screen,
+            cursor::MoveTo(column, row),
+            style::SetAttribute(style::Attribute::Reverse),
+            Print(columns::display(&under)),
+            style::SetAttribute(style::Attribute::Reset),
+        )
+    }
+
+    // Moves the terminal cursor to match, redrawing if its line is out of view.
+    fn place_cursor(&mut self) -> std::io::Result<()> {
+        match self.cursor_screen_position() {
+            Some((column, row)) => execute!(self.screen, cursor::MoveTo(column, row)),
+            None => self.redraw(),
+        }
+    }
+
+    // Takes only the lines it can change out of the rope, the selected ones or
+    // the paragraph around the cursor, and puts them back in one edit.
+    fn reflow(&mut self) -> std::io::Result<()> {
+        let rope = &self.buffer().rope;
+        let comment = self.buffer().language().line_comment();
+        let content = |line: usize| {
+            rope.slice(lines::line_content_range(rope, line))
+                .to_string()
+        };
+        let line_number = self.get_line_number();
+        let column = self.get_cursor_index() - rope.line_to_char(line_number);
+
+        let (first, reflowed) = match self.target(Fallback::Line) {
+            target @ Target::Selection(_) => {
+                let selected = target.lines(rope);
+                let text: Vec<String> = selected.clone().map(content).collect();
+                let cursor = (line_number.saturating_sub(selected.start), column);
+                let reflowed = reflow::reflow_all(&text, cursor, self.text_width, comment);
+                (selected.start, reflowed)
+            }
+            _ => {
+                let current = content(line_number);
+                let continues = |line: usize| reflow::continues(&current, &content(line), comment);
+                let mut start = line_number;
+                while start > 0 && continues(start - 1) {
+                    start -= 1;
+                }
+                let mut end = line_number + 1;
+                while end < lines::count(rope) && continues(end) {
+                    end += 1;
+                }
+
+                let text: Vec<String> = (start..end).map(content).collect();
+                let cursor = (line_number - start, column);
+                let reflowed = reflow::reflow(&text, cursor, self.text_width, comment);
+                (start, reflowed)
+            }
+        };
+        let Some(reflowed) = reflowed else {
+            self.message = Some("Nothing to reflow".to_string());
+            return self.redraw();
+        };
+
+        let range = rope.line_to_char(first + reflowed.lines.start)
+            ..lines::line_content_range(rope, first + reflowed.lines.end - 1).end;
+        let text = reflowed
+            .text
+            .join(&lines::preferred_terminator(rope, first));
+        let cursor = self.cursor();
+        self.buffer_mut().edit(range, &text, cursor);
+
+        self.selection = None;
+        self.move_cursor_to(first + reflowed.cursor.0, reflowed.cursor.1);
+        self.redraw()
+    }
+
+    fn find_references(&mut self) -> std::io::Result<()> {
+        self.ensure_tree();
+
+        let index = self.get_cursor_index();
+        let rope = &self.buffers.active().rope;
+        let byte = rope.char_to_byte(index);
+        let source = rope.to_string();
+        let found = match &self.buffers.active().tree {
+            Some(tree) => references::find(tree, &source, byte),
+            None => None,
+        };
+
+        let Some((name, starts)) = found else {
+            self.message = Some("No identifier under the cursor".to_string());
+            return self.redraw();
+        };
+
+        if starts.len() <= 1 {
+            self.message = Some(format!("No other references to {name}"));
+            return self.redraw();
+        }
+
+        let entries = starts
+            .iter()
+            .map(|&start| {
+                let line = rope.byte_to_line(start);
+                let content = rope.slice(lines::line_content_range(rope, line));
+                Location {
+                    line,
+                    column: rope.byte_to_char(start) - rope.line_to_char(line),
+                    snippet: content.to_string().trim().to_string(),
+                }
+            })
+            .collect();
+        let current = starts.iter().rposition(|&s| s <= byte).unwrap_or(0);
+
+        self.locations = LocationList { entries, current };
+        self.message = Some(format!("{} references to {name}", starts.len()));
+        self.location_panel()
+    }
+
+    // Jumps to the first match at or after the cursor, then stays in search mode
+    // with the visible matches highlighted. Enter, n and F3 go to the next match,
+    // N and Shift+F3 to the previous one, wrapping around the buffer, and Tab
+    // selects the match. Esc leaves search mode, and so does any other key,
+    // which is then handled as usual. A pasted query keeps its line breaks.
+    fn search(&mut self) -> std::io::Result<()> {
+        let Some(query) = self.run_prompt(Prompt::multiline("Search: "))? else {
+            return self.redraw();
+        };
+
+        let from = self.get_cursor_index();
+        let Some(found) = search::next(&self.buffer().rope, &query, from) else {
+            self.message = Some(format!("No matches for {query}"));
+            return self.redraw();
+        };
+        self.record_jump();
+        self.jump_to_char(found);
+        self.search = Some(query);
+        self.open_overlay(Box::new(SearchMode { done: false }))
+    }
+
+    // Selects the match the cursor is on, exactly as far as it goes, with the
+    // cursor at its end, so typing replaces it and Shift moves extend it.
+    fn select_match(&mut self) {
+        let Some(query) = &self.search else {
+            return;
+        };
+        let start = self.get_cursor_index();
+        let end = start + query.chars().count();
+        if search::next(&self.buffer().rope, query, start) != Some(start) {
+            return;
+        }
+
+        let rope = &self.buffer().rope;
+        let line = rope.char_to_line(start);
+        self.selection = Some((line, start - rope.line_to_char(line)));
+        self.keep_selection = true;
+        self.jump_to_char(end);
+    }
+
+    // Walks the matches from the cursor to the end of the buffer and then from
+    // the start back to the cursor, selecting each and asking what to do with
+    // it. Scanning resumes after the text just put in, so a replacement that
+    // contains the query doesn't match again.
+    fn replace(&mut self) -> std::io::Result<()> {
+        let Some(query) = self.prompt("Replace: ")? else {
+            return self.redraw();
+        };
+        let Some(replacement) = self.prompt(&format!("Replace {query} with: "))? else {
+            return self.redraw();
+        };
+
+        let (length, inserted) = (query.chars().count(), replacement.chars().count());
+        let origin = self.get_cursor_index();
+        let (mut at, mut stop, mut wrapped) = (origin, self.buffer().rope.len_chars(), false);
+        let (mut count, mut matched, mut undoable) = (0, false, true);
+        self.search = Some(query.clone());
+
+        loop {
+            let rope = &self.buffer().rope;
+            let found = search::next(rope, &query, at)
+                .filter(|&found| found >= at && found + length <= stop);
+            let Some(found) = found else {
+                if wrapped || origin == 0 {
+                    break;
+                }
+                (at, stop, wrapped) = (0, origin, true);
+                continue;
+            };
+
+            let line = rope.char_to_line(found);
+            self.selection = Some((line, found - rope.line_to_char(line)));
+            matched = true;
+            self.jump_to_char(found + length);
+            self.redraw()?;
+            let answer = self.ask("Replace? [y]es, [n]o, [a]ll remaining, Esc stops")?;
+
+            match answer.code {
+                KeyCode::Char('y') | KeyCode::Char('Y') => {
+                    let cursor = self.cursor();
+                    self.buffer_mut()
+                        .edit(found..found + length, &replacement, cursor);
+                    self.jump_to_char(found + inserted);
+                    at = found + inserted;
+                    stop = stop - length + inserted;
+                    count += 1;
+                }
+                KeyCode::Char('n') | KeyCode::Char('N') => at = found + length,
+                KeyCode::Char('a') | KeyCode::Char('A') => {
+                    let before = self.buffer().rope.clone();
+                    let mut rope = before.clone();
+                    count += search::replace_all(&mut rope, &query, &replacement, found..stop);
+                    if !wrapped {
+                        count += search::replace_all(&mut rope, &query, &replacement, 0..origin);
+                    }
+                    let cursor = self.cursor();
+                    undoable = self.buffer_mut().replace_contents(rope, true, cursor);
+                    self.flash_changes(&before);
+                    break;
+                }
+                _ => break,
+            }
+        }
+
+        self.selection = None;
+        self.search = None;
+        let summary = match count {
+            _ if !matched => format!("No matches for {query}"),
+            1 => "Replaced 1 match".to_string(),
+            n => format!("Replaced {n} matches"),
+        };
+        self.message = Some(if undoable {
+            summary
+        } else {
+            format!("{summary}. {NOT_UNDOABLE}")
+        });
+        self.redraw()
+    }
+
+    // The bracket at or just before the cursor. A tree is used when it's up
+    // to date, which drawing makes sure of unless the buffer is shown plain,
+    // so this never parses.
+    fn bracket_match(&self, plain: bool) -> Option<brackets::Match> {
+        let buffer = self.buffer();
+        let tree = buffer
+            .tree
+            .as_ref()
+            .filter(|_| !plain && !buffer.tree_stale);
+        brackets::find(&buffer.rope, tree, self.get_cursor_index())
+    }
+
+    fn jump_to_match(&mut self) -> std::io::Result<()> {
+        let plain = self.buffer().awaiting_parse() || self.buffer().language().grammar().is_none();
+        if !plain {
+            self.ensure_tree();
+        }
+
+        match self.bracket_match(plain) {
+            Some(brackets::Match {
+                partner: Some(partner),
+                ..
+            }) => {
+                self.record_jump();
+                self.jump_to_char(partner);
+            }
+            Some(_) => self.message = Some("No matching bracket".to_string()),
+            None => self.message = Some("Not on a bracket".to_string()),
+        }
+        self.redraw()
+    }
+
+    // Moves to the first syntax error after the cursor, wrapping round to the
+    // top. Waits for the first parse rather than finding nothing before it.
+    fn next_syntax_error(&mut self) -> std::io::Result<()> {
+        if self.buffer().language().grammar().is_some() {
+            self.ensure_tree();
+        }
+
+        let cursor = self.get_cursor_index();
+        let found = self.buffers.active_mut().syntax_errors().map(|errors| {
+            errors
+                .iter()
+                .position(|error| error.start > cursor)
+                .or((!errors.is_empty()).then_some(0))
+                .map(|index| (errors[index].start, index, errors.len()))
+        });
+
+        match found {
+            None => {
+                let language = self.buffer().language();
+                self.message = Some(format!("{} has no grammar", language.name()));
+            }
+            Some(None) => self.message = Some("No syntax errors".to_string()),
+            Some(Some((at, index, count))) => {
+                self.record_jump();
+                self.jump_to_char(at);
+                self.message = Some(format!("Syntax error {} of {count}", index + 1));
+            }
+        }
+        self.redraw()
+    }
+
+    fn jump_to_char(&mut self, index: usize) {
+        let rope = &self.buffer().rope;
+        let line = rope.char_to_line(index);
+        let column = index - rope.line_to_char(line);
+        self.move_cursor_to(line, column);
+    }
+
+    // Lists the locations below the text, which shrinks to make room so the
+    // current location stays in view. n/p or the arrows move through them, Enter
+    // keeps the cursor there and Esc goes back to where it was.
+    fn location_panel(&mut self) -> std::io::Result<()> {
+        let height = self.size.1 as usize;
+        let rows = self.locations.entries.len().min(height / 3).max(1);
+        let line = self.get_line_number();
+        let origin = (
+            line,
+            self.get_cursor_index() - self.buffer().rope.line_to_char(line),
+        );
+        let lines: Vec<String> = self
+            .locations
+            .entries
+            .iter()
+            .map(|l| format!("{:>5}: {}", l.line + 1, l.snippet))
+            .collect();
+
+        // One more row keeps the message row below the panel visible.
+        self.chrome.bottom += rows as u16 + 1;
+        self.refresh_viewport();
+
+        loop {
+            self.jump_to_location(self.locations.current)?;
+            let top = self.viewport.bottom_row() + 1;
+            let current = self.locations.current;
+            self.draw_list_at(&lines, Some(current), current, top, rows)?;
+
+            let Event::Key(event) = self.read_event()? else {
+                continue;
+            };
+            if event.kind != KeyEventKind::Press {
+                continue;
+            }
+
+            match event.code {
+                KeyCode::Char('n') | KeyCode::Down => self.step_location(true),
+                KeyCode::Char('p') | KeyCode::Up => self.step_location(false),
+                KeyCode::Enter => {
+                    self.buffer_mut().record_jump(origin);
+                    break;
+                }
+                KeyCode::Esc => {
+                    self.move_cursor_to(origin.0, origin.1);
+                    break;
+                }
+                _ => {}
+            }
+        }
+
+        self.chrome.bottom -= rows as u16 + 1;
+        self.refresh_viewport();
+        self.redraw()
+    }
+
+    fn step_location(&mut self, forward: bool) {
+        let len = self.locations.entries.len();
+        if len == 0 {
+            return;
+        }
+
+        let current = self.locations.current;
+        self.locations.current = if forward {
+            (current + 1) % len
+        } else {
+            (current + len - 1) % len
+        };
+    }
+
+    fn jump_to_location(&mut self, index: usize) -> std::io::Result<()> {
+        let Some(location) = self.locations.entries.get(index) else {
+            self.message = Some("No locations".to_string());
+            return self.redraw();
+        };
+
+        let (line, column) = (location.line, location.column);
+        self.move_cursor_to(line, column);
+        self.redraw()
+    }
+
+    // Starts an empty line next to the current one without splitting it, indented
+    // like the current line (or the one above it, when opening above).
+    fn open_line(&mut self, above: bool) -> std::io::Result<()> {
+        let line_number = self.get_line_number();
+        let rope = &self.buffer().rope;
+        let source = if above {
+            line_number.saturating_sub(1)
+        } else {
+            line_number
+        };
+        let content = rope
+            .slice(lines::line_content_range(rope, source))
+            .to_string();
+        let indent = &content[..content.len() - content.trim_start().len()];
+        let target = if above { line_number } else { line_number + 1 };
+
+        let indent = indent.to_string();
+        self.insert_lines(target, &[&indent]);
+
+        self.move_cursor_to(target, indent.chars().count());
+        self.redraw()
+    }
+
+    // Markdown gets a thematic break and plain text a rule across the text
+    // width. Code only gets one inside a line comment, so a separator never
+    // turns into a syntax error.
+    fn insert_separator(&mut self) -> std::io::Result<()> {
+        let language = self.buffer().language();
+        let rule = |width: usize| self.separator.to_string().repeat(width);
+        let separator = if matches!(self.buffer().language_tag(), "md" | "markdown") {
+            "---".to_string()
+        } else if language == Language::PlainText {
+            rule(self.text_width)
+        } else if let Some(leader) = language.line_comment() {
+            let rope = &self.buffer().rope;
+            let content = rope.slice(lines::line_content_range(rope, self.get_line_number()));
+            let indent: String = content
+                .chars()
+                .take_while(|c| *c == ' ' || *c == '\t')
+                .collect();
+            let width = self
+                .text_width
+                .saturating_sub(columns::width(&indent) + leader.len() + 1);
+            format!("{indent}{leader} {}", rule(width))
+        } else {
+            self.message = Some(format!(
+                "{} has no comments to put a separator in",
+                language.name()
+            ));
+            return self.redraw();
+        };
+
+        let line = self.get_line_number() + 1;
+        self.insert_lines(line, &[&separator]);
+        self.move_cursor_to(line, 0);
+        self.redraw()
+    }
+
+    // Inserts a commented copy above the current line, or above every line the
+    // selection touches, in a single edit, which leaves the cursor's lines as
+    // the editable duplicate. The copy is commented as one block at its
+    // shallowest indent, with blank lines left alone as toggle_comment does.
+    fn duplicate_and_comment(&mut self) -> std::io::Result<()> {
+        let language = self.buffer().language();
+        let Some(leader) = language.line_comment() else {
+            self.message = Some(format!("{} has no line comments", language.name()));
+            return self.redraw();
+        };
+
+        let rope = &self.buffer().rope;
+        let selected = self.target(Fallback::Line).lines(rope);
+        let contents: Vec<String> = selected
+            .clone()
+            .map(|line| {
+                rope.slice(lines::line_content_range(rope, line))
+                    .to_string()
+            })
+            .collect();
+
+        let indent = |line: &str| line.chars().take_while(|c| *c == ' ' || *c == '\t').count();
+        let shallowest = contents
+            .iter()
+            .filter(|content| !content.trim().is_empty())
+            .map(|content| indent(content))
+            .min()
+            .unwrap_or(0);
+        let commented: Vec<String> = contents
+            .iter()
+            .map(|content| {
+                if content.trim().is_empty() {
+                    return content.clone();
+                }
+                let (indent, code) = content.split_at(
+                    content
+                        .char_indices()
+                        .nth(shallowest)
+                        .map_or(content.len(), |(at, _)| at),
+                );
+                format!("{indent}{leader} {code}")
+            })
+            .collect();
+
+        let commented: Vec<&str> = commented.iter().map(String::as_str).collect();
+        self.insert_lines(selected.start, &commented);
+
+        let below = |(line, column): (usize, usize)| (line + commented.len(), column);
+        let (line, column) = below(self.cursor());
+        self.selection = self.selection.map(below);
+        self.keep_selection = true;
+        self.move_cursor_to(line, column);
+        self.redraw()
+    }
+
+    // Comments out the current line, or every line the selection touches, at
+    // their shallowest indent. When they're all commented already, the comments
+    // come off instead. Blank lines are left alone either way, and the cursor
+    // and selection stay on the same text.
+    fn toggle_comment(&mut self) -> std::io::Result<()> {
+        let language = self.buffer().language();
+        let Some(leader) = language.line_comment() else {
+            self.message = Some(format!("{} has no line comments", language.name()));
+            return self.redraw();
+        };
+
+        let rope = &self.buffer().rope;
+        let selected = self.target(Fallback::Line).lines(rope);
+        let start = rope.line_to_char(selected.start);
+        let end = lines::line_content_range(rope, selected.end - 1).end;
+        let text = rope.slice(start..end).to_string();
+
+        let indent = |line: &str| line.chars().take_while(|c| *c == ' ' || *c == '\t').count();
+        let lines: Vec<(&str, &str)> = text
+            .split_inclusive('\n')
+            .map(|line| line.split_at(line.trim_end_matches(['\r', '\n']).len()))
+            .collect();
+        let code: Vec<&str> = lines
+            .iter()
+            .map(|(content, _)| *content)
+            .filter(|content| !content.trim().is_empty())
+            .collect();
+        if code.is_empty() {
+            self.message = Some("Nothing to comment".to_string());
+            return self.redraw();
+        }
+
+        let uncomment = code
+            .iter()
+            .all(|content| content.trim_start().starts_with(leader));
+        let shallowest = code
+            .iter()
+            .map(|content| indent(content))
+            .min()
+            .unwrap_or(0);
+
+        // Where each line changed, in chars: text from `at` on moved by `by`.
+        let mut shifts = Vec::new();
+        let mut toggled = String::new();
+        for (content, terminator) in &lines {
+            let (mut at, mut by) = (0, 0);
+            if !content.trim().is_empty() {
+                let chars: Vec<char> = content.chars().collect();
+                if uncomment {
+                    at = indent(content);
+                    by = leader.chars().count();
+                    by += usize::from(chars.get(at + by) == Some(&' '));
+                    toggled.extend(&chars[..at]);
+                    toggled.extend(&chars[at + by..]);
+                } else {
+                    at = shallowest;
+                    by = leader.chars().count() + 1;
+                    toggled.extend(&chars[..at]);
+                    toggled.push_str(leader);
+                    toggled.push(' ');
+                    toggled.extend(&chars[at..]);
+                }
+            } else {
+                toggled.push_str(content);
+            }
+            toggled.push_str(terminator);
+            shifts.push((at, by));
+        }
+
+        let shift = |(line, column): (usize, usize)| {
+            let Some(&(at, by)) = line.checked_sub(selected.start).and_then(|i| shifts.get(i))
+            else {
+                return (line, column);
+            };
+            match (uncomment, column > at) {
+                (_, false) => (line, column),
+                (true, true) => (line, at + (column - at).saturating_sub(by)),
+                (false, true) => (line, column + by),
+            }
+        };
+        let cursor = self.cursor();
+        let moved = shift(cursor);
+        self.selection = self.selection.map(shift);
+        self.keep_selection = true;
+        self.buffer_mut().edit(start..end, &toggled, cursor);
+        self.move_cursor_to(moved.0, moved.1);
+        self.redraw()
+    }
+
+    // Makes the edit `count` times, or once without a count, as a single undo
+    // step.
+    fn repeat_edit(
+        &mut self,
+        count: Option<usize>,
+        mut edit: impl FnMut(&mut Self) -> std::io::Result<()>,
+    ) -> std::io::Result<()> {
+        let count = count.unwrap_or(1);
+        if count > 1 {
+            let cursor = self.cursor();
+            self.buffer_mut().begin_batch(cursor);
+        }
+        let result = (0..count).try_for_each(|_| edit(self));
+        self.buffer_mut().end_batch();
+        result
+    }
+
+    fn backspace(&mut self) {
+        let idx = self.get_cursor_index();
+        let line_number = self.get_line_number();
+
+        // Between the two halves of an empty pair, both go.
+        if self.auto_pairs && self.cursor_col > 0 {
+            let rope = &self.buffer().rope;
+            let around = (rope.char(idx - 1), rope.get_char(idx));
+            if pairs::PAIRS.iter().any(|&(o, c)| around == (o, Some(c))) {
+                let cursor = self.cursor();
+                self.buffer_mut().edit(idx - 1..idx + 1, "", cursor);
+                self.cursor_col -= 1;
+                return;
+            }
+        }
+        let before = self
+            .buffer()
+            .rope
+            .line(line_number)
+            .slice(..self.cursor_col);
+
+        if self.cursor_col > 0 && !self.hard_tabs && before.chars().all(|c| c == ' ') {
+            // Within spaces indent, a whole indent stop goes at once.
+            let width = columns::tab_width();
+            let start = idx - ((self.cursor_col - 1) % width + 1);
+
+            let cursor = self.cursor();
+            self.buffer_mut().edit(start..idx, "", cursor);
+            self.cursor_col -= idx - start;
+        } else if self.cursor_col > 0 {
+            // Removes the whole grapheme before the cursor, not just its last char.
+            let line = self.buffer().rope.line(line_number);
+            let column = columns::char_to_column(line, self.cursor_col);
+            let previous = columns::column_to_char(line, columns::previous_column(line, column));
+            let start = idx - (self.cursor_col - previous);
+
+            let cursor = self.cursor();
+            self.buffer_mut().edit(start..idx, "", cursor);
+            self.cursor_col = previous;
+        } else if line_number != 0 {
+            // Joins the line onto the end of the one above.
+            let rope = &self.buffer().rope;
+            let column = lines::line_content_range(rope, line_number - 1).len();
+
+            let joined = lines::line_content_range(rope, line_number - 1).end;
+            let cursor = self.cursor();
+            self.buffer_mut().edit(joined..idx, "", cursor);
+            self.move_cursor_to(line_number - 1, column);
+        }
+    }
+
+    fn insert_tab(&mut self) -> std::io::Result<()> {
+        if self.hard_tabs {
+            return self.insert_text("\t");
+        }
+
+        let width = columns::tab_width();
+        self.insert_text(&" ".repeat(width - self.cursor_column() % width))
+    }
+
+    // The current line's leading whitespace, up to the cursor, for the line
+    // Enter starts.
+    fn indent_before_cursor(&self) -> String {
+        let Some(line) = self.buffer().rope.get_line(self.cursor_row) else {
+            return String::new();
+        };
+
+        line.chars()
+            .take(self.cursor_col)
+            .take_while(|c| *c == ' ' || *c == '\t')
+            .collect()
+    }
+
+    // Deletes back to the start of the word, without leaving the line. At the
+    // start of a line it joins it onto the one above, like backspace.
+    fn backspace_word(&mut self) {
+        if self.cursor_col == 0 {
+            return self.backspace();
+        }
+
+        let index = self.get_cursor_index();
+        let line_start = index - self.cursor_col;
+        let start = words::previous_start(&self.buffer().rope, index).max(line_start);
+
+        let cursor = self.cursor();
+        self.buffer_mut().edit(start..index, "", cursor);
+        self.cursor_col = start - line_start;
+    }
+
+    // Removes the grapheme under the cursor, which stays where it is. At the
+    // end of a line the next one is joined on, and at the end of the buffer
+    // there's nothing to remove.
+    fn delete_forward(&mut self) {
+        let index = self.get_cursor_index();
+        let rope = &self.buffer().rope;
+        let content = lines::line_content_range(rope, self.cursor_row);
+
+        let end = if index < content.end {
+            let line = rope.line(self.cursor_row);
+            let column = columns::next_column(line, columns::char_to_column(line, self.cursor_col));
+            content.start + columns::column_to_char(line, column)
+        } else if self.cursor_row + 1 < self.line_count() {
+            // The whole terminator, so no lone \r is left behind.
+            rope.line_to_char(self.cursor_row + 1)
+        } else {
+            return;
+        };
+
+        let cursor = self.cursor();
+        self.buffer_mut().edit(index..end, "", cursor);
+    }
+
+    // Deletes up to the start of the next word, without leaving the line. At
+    // the end of a line it joins the next one on, like delete_forward.
+    fn delete_word_forward(&mut self) {
+        let index = self.get_cursor_index();
+        let rope = &self.buffer().rope;
+        let content = lines::line_content_range(rope, self.cursor_row);
+        if index >= content.end {
+            return self.delete_forward();
+        }
+
+        let end = words::next_start(rope, index).min(content.end);
+        let cursor = self.cursor();
+        self.buffer_mut().edit(index..end, "", cursor);
+    }
+
+    fn get_cursor_index(&self) -> usize {
+        let rope = &self.buffer().rope;
+
+        if self.cursor_row >= rope.len_lines() {
+            return rope.len_chars();
+        }
+
+        rope.line_to_char(self.cursor_row) + self.cursor_col
+    }
+
+    fn redraw(&mut self) -> std::io::Result<()> {
+        // The gutter's width changes where lines wrap and how much of them fits.
+        self.ensure_cursor_visible();
+        self.update_gutter()?;
+        self.ensure_cursor_visible();
+
+        let buffer = self.buffers.active();
+        let (scroll, h_scroll, row_scroll) = (buffer.scroll, buffer.h_scroll, buffer.row_scroll);
+        let line_count = self.line_count();
+        self.rows = if self.wrap {
+            self.viewport
+                .wrapped_rows(scroll, row_scroll, line_count, |line| self.row_starts(line))
+        } else {
+            self.viewport.rows(scroll, h_scroll, line_count)
+        };
+
+        // The first parse of a buffer is left to tick() so the first frame shows
+        // up right away, uncolored.
+        let highlighting = self.buffer().awaiting_parse();
+        let plain = highlighting || self.buffer().language().grammar().is_none();
+
+        if !plain {
+            self.ensure_tree();
+        }
+
+        let written = self.screen.written();
+        let drawn = Drawn {
+            buffer: self.buffers.active_index(),
+            scroll,
+            h_scroll,
+            row_scroll,
+            viewport: self.viewport,
+            selection: self.selection_range(),
+            search: self.search.clone(),
+            decorations: self.decorations.len(),
+            bracket: self.bracket_match(plain),
+            whitespace: (self.show_whitespace, self.cursor_row),
+        };
+        if self.drawn.as_ref().is_none_or(|previous| {
+            (
+                previous.buffer,
+                previous.scroll,
+                previous.h_scroll,
+                previous.row_scroll,
+            ) != (drawn.buffer, scroll, h_scroll, row_scroll)
+        }) {
+            tracing::debug!(scroll, h_scroll, row_scroll, "scrolled");
+        }
+        let previous = self.drawn.replace(drawn);
+        let restyle_only = self.drawn == previous;
+
+        // Drawn into a frame, so that only the rows that changed are written.
+        queue!(self.screen, cursor::Hide)?;
+        if self.low_bandwidth {
+            self.scroll_screen(previous)?;
+        }
+        self.screen.begin_frame();
+        queue!(
+            self.screen,
+            cursor::MoveTo(self.viewport.left, self.viewport.top)
+        )?;
+
+        // A grammar that can't be used leaves no tree either.
+        let draw_start = Instant::now();
+        match self.buffer_mut().tree.take().filter(|_| !plain) {
+            Some(tree) => {
+                self.draw_tree(&tree)?;
+                self.buffer_mut().tree = Some(tree);
+            }
+            None => self.draw_plain()?,
+        }
+        self.draw_gutter()?;
+        self.draw_whitespace()?;
+        self.draw_decorations()?;
+        self.draw_syntax_errors(plain)?;
+        self.draw_search_matches()?;
+        self.draw_bracket_match()?;
+        self.draw_selection()?;
+        let draw = draw_start.elapsed();
+
+        self.draw_status_bar(highlighting)?;
+
+        let write_start = Instant::now();
+        self.screen.present(self.low_bandwidth && restyle_only)?;
+        let (column, row) = self
+            .cursor_screen_position()
+            .unwrap_or((self.viewport.left, self.viewport.top));
+        execute!(self.screen, cursor::MoveTo(column, row), cursor::Show)?;
+        self.render_overlays()?;
+
+        self.screen.flush()?;
+        let write = write_start.elapsed();
+        let bytes = self.screen.written() - written;
+        tracing::debug!(?draw, ?write, bytes, "redrew");
+
+        if self.stats.is_some() {
+            let frame = Frame {
+                parse: std::mem::take(&mut self.parse_time),
+                draw,
+                write,
+                lines: self.get_visible_lines_len(),
+                bytes,
+            };
+            self.draw_stats(frame)?;
+        }
+
+        Ok(())
+    }
+
+    // Display columns where the rows of `line` start when lines wrap.
+    fn row_starts(&self, line: usize) -> Vec<usize> {
+        let rope = &self.buffer().rope;
+        let text = rope
+            .slice(lines::line_content_range(rope, line))
+            .to_string();
+        wrap::row_starts(&text, self.viewport.width as usize)
+    }
+
+    // Scrolls by rows rather than lines so the cursor's row is in view: up to
+    // it when it's above the view, or until it's the bottom row when it's
+    // below.
+    fn scroll_wrapped(&mut self) {
+        let height = self.viewport.height as usize;
+        let margin = self.viewport.fit_margin(self.scroll_margin);
+        let last = self.line_count().saturating_sub(1);
+        let line = self.buffer().scroll.min(last);
+        let row = self
+            .buffer()
+            .row_scroll
+            .min(self.row_starts(line).len() - 1);
+        let cursor = (
+            self.cursor_row,
+            wrap::row_of(&self.row_starts(self.cursor_row), self.cursor_column()),
+        );
+        let above = self.step_rows(cursor, false, margin);
+        let below = self.step_rows(cursor, true, margin);
+
+        let (line, row) = if above < (line, row) {
+            above
+        } else {
+            // Each line has at least one row, so this looks at no more than a
+            // screenful of lines.
+            let mut distance = 0;
+            let mut at = line;
+            while at < below.0 && distance < height {
+                distance += self.row_starts(at).len();
+                at += 1;
+            }
+            distance = (distance + below.1).saturating_sub(row);
+
+            if distance < height {
+                (line, row)
+            } else {
+                self.step_rows(below, false, height.saturating_sub(1))
+            }
+        };
+
+        let buffer = self.buffer_mut();
+        buffer.scroll = line;
+        buffer.row_scroll = row;
+    }
+
+    // Moves `count` rows up or down from row `row` of `line` when lines wrap,
+    // returning the line and row reached.
+    fn step_rows(
+        &self,
+        (mut line, mut row): (usize, usize),
+        down: bool,
+        count: usize,
+    ) -> (usize, usize) {
+        let last = self.line_count().saturating_sub(1);
+        let mut starts = self.row_starts(line);
+
+        for _ in 0..count {
+            if down && row + 1 < starts.len() {
+                row += 1;
+            } else if down && line < last {
+                line += 1;
+                starts = self.row_starts(line);
+                row = 0;
+            } else if !down && row > 0 {
+                row -= 1;
+            } else if !down && line > 0 {
+                line -= 1;
+                starts = self.row_starts(line);
+                row = starts.len() - 1;
+            } else {
+                break;
+            }
+        }
+
+        (line, row)
+    }
+
+    // The display column `offset` cells into row `row` of `line`, kept on
+    // that row when it's followed by another.
+    fn row_column(&self, line: usize, row: usize, offset: usize) -> usize {
+        let starts = self.row_starts(line);
+        let column = starts[row] + offset;
+        starts
+            .get(row + 1)
+            .map_or(column, |&next| column.min(next - 1))
+    }
+
+    // Shifts the text area when the view only moved up or down since the last
+    // frame, so the frame only has to send the lines that came into view.
+    fn scroll_screen(&mut self, previous: Option<Drawn>) -> std::io::Result<()> {
+        let (Some(previous), Some(drawn)) = (previous, &self.drawn) else {
+            return Ok(());
+        };
+        // Wrapped rows don't map to whole lines.
+        let moved_only = !self.wrap
+            && previous.buffer == drawn.buffer
+            && previous.h_scroll == drawn.h_scroll
+            && previous.viewport == drawn.viewport;
+        if !moved_only || previous.scroll == drawn.scroll {
+            return Ok(());
+        }
+
+        let top = self.viewport.top as usize;
+        let rows = top..top + self.viewport.height as usize;
+        self.screen
+            .scroll(rows, drawn.scroll as isize - previous.scroll as isize)
+    }
+
+    // The bottom row: the message if there is one, otherwise the buffer's name
+    // and whether it's modified, then indicators and the cursor position on
+    // the right.
+    fn draw_status_bar(&mut self, highlighting: bool) -> std::io::Result<()> {
+        let hygiene = self.buffers.active().hygiene();
+        let mut indicators = Vec::new();
+
+        if !hygiene.final_newline {
+            indicators.push("no EOL".to_string());
+        }
+        if let Some(line) = hygiene.first_trailing_whitespace {
+            indicators.push(format!("trailing ws: {}", line + 1));
+        }
+        if highlighting {
+            indicators.push("highlighting…".to_string());
+        }
+        match self.buffers.active_mut().syntax_errors().map(<[_]>::len) {
+            Some(1) => indicators.push("1 syntax error".to_string()),
+            Some(0) | None => {}
+            Some(errors) => indicators.push(format!("{errors} syntax errors")),
+        }
+        // Left out while there's a message, to leave it room.
+        let mut targets = Vec::new();
+        let language = self.buffers.active().language();
+        let overridden = self.buffers.active().language_override().is_some();
+        if self.message.is_none() && (language != Language::PlainText || overridden) {
+            targets.push((indicators.len(), StatusTarget::Language));
+            indicators.push(language.name().to_lowercase());
+        }
+        // Shown either way, as clicking it converts to the other.
+        if self.message.is_none() {
+            targets.push((indicators.len(), StatusTarget::LineEnding));
+            indicators.push(match self.buffers.active().line_ending() {
+                LineEnding::Lf => "LF".to_string(),
+                LineEnding::CrLf => "CRLF".to_string(),
+            });
+        }
+        if self.rtl_warning && self.rtl_visible() {
+            indicators.push("RTL".to_string());
+        }
+        if self.buffers.active().follow.is_some() {
+            indicators.push("following".to_string());
+        }
+        if self.check.is_some() {
+            indicators.push("checking…".to_string());
+        } else if let Some(summary) = &self.check_summary {
+            indicators.push(summary.clone());
+        }
+        if let Some(count) = self.count {
+            indicators.push(count.to_string());
+        }
+        if let Some((chords, _)) = &self.pending_chords {
+            indicators.push(keymap::format_sequence(chords));
+        }
+        match self.register.lines() {
+            Some(1) => indicators.push("reg: 1 line".to_string()),
+            Some(lines) => indicators.push(format!("reg: {lines} lines")),
+            None => {}
+        }
+
+        let buffer = self.buffers.active();
+        targets.push((indicators.len(), StatusTarget::Position));
+        indicators.push(format!("{}:{}", self.cursor_row + 1, self.cursor_col + 1));
+        indicators.push(format!("{} lines", buffer.line_count()));
+
+        let (width, height) = self.size;
+        let right = format!("{} ", indicators.join(" | "));
+        let position = match self.buffers.len() {
+            1 => String::new(),
+            len => format!("{}/{len} ", self.buffers.active_index() + 1),
+        };
+        let space = (width as usize).saturating_sub(columns::width(&right) + 1);
+        let left = match &self.message {
+            Some(message) => format!(
+                " {}",
+                paths::truncate_start(message, space.saturating_sub(1))
+            ),
+            // The keys are left out before any of the query is.
+            None if self.search.is_some() => {
+                let query = format!(
+                    " Search: {}",
+                    prompt::one_line(self.search.as_deref().unwrap_or_default())
+                );
+                let keys = " (n / N for next / previous, Tab to select, Esc to stop)";
+                if columns::width(&query) + keys.len() <= space {
+                    query + keys
+                } else {
+                    query
+                }
+            }
+            None => format!(
+                " {position}{}{}{}{}",
+                paths::shorten(
+                    buffer.display_name(),
+                    space.saturating_sub(16 + position.len())
+                ),
+                if buffer.new_file { " [new]" } else { "" },
+                if buffer.read_only { " [RO]" } else { "" },
+                if buffer.modified { " [+]" } else { "" }
+            ),
+        };
+        let padding =
+            (width as usize).saturating_sub(columns::width(&left) + columns::width(&right));
+        let full = format!("{left}{:padding$}{right}", "");
+        let bar = paths::truncate_start(&full, width as usize);
+
+        // Columns of the whole bar, before truncation cut its start off.
+        let mut segments = Vec::new();
+        if self.message.is_none() && self.search.is_none() && buffer.modified {
+            let end = columns::width(&left);
+            segments.push((end - 3..end, StatusTarget::Modified));
+        }
+        let mut start = columns::width(&left) + padding;
+        for (index, indicator) in indicators.iter().enumerate() {
+            let end = start + columns::width(indicator);
+            if let Some(&(_, target)) = targets.iter().find(|(i, _)| *i == index) {
+                segments.push((start..end, target));
+            }
+            start = end + " | ".len();
+        }
+        let cut = columns::width(&full) - columns::width(&bar);
+        let hidden = if cut > 0 {
+            cut + columns::width(paths::ELLIPSIS)
+        } else {
+            0
+        };
+        self.status_targets = segments
+            .into_iter()
+            .filter(|(columns, _)| columns.start >= hidden)
+            .map(|(columns, target)| {
+                (
+                    (columns.start - cut) as u16..(columns.end - cut) as u16,
+                    target,
+                )
+            })
+            .collect();
+
+        // Only a message that fits whole is colored; truncation cuts its start.
+        let failed = self.failed && self.message.is_some() && bar.starts_with(&left);
+        let failure = if failed { left.len() } else { 0 };
+
+        queue!(
+            self.screen,
+            cursor::MoveTo(0, height - 1),
+            style::ResetColor,
+            style::SetAttribute(style::Attribute::Reverse),
+        )?;
+        if failure > 0 {
+            match self.theme.error() {
+                Some(color) => queue!(self.screen, style::SetForegroundColor(color))?,
+                None => queue!(self.screen, style::SetAttribute(style::Attribute::Bold))?,
+            }
+            queue!(
+                self.screen,
+                Print(&bar[..failure]),
+                style::SetAttribute(style::Attribute::Reset),
+                style::ResetColor,
+                style::SetAttribute(style::Attribute::Reverse),
+            )?;
+        }
+        queue!(
+            self.screen,
+            Print(&bar[failure..]),
+            style::SetAttribute(style::Attribute::Reset),
+        )
+    }
+
+    // Drawn after the frame's own timings are taken so it doesn't skew them.
+    fn draw_stats(&mut self, frame: Frame) -> std::io::Result<()> {
+        let Some(stats) = &mut self.stats else {
+            return Ok(());
+        };
+
+        stats.push(frame);
+        let buffer = self.buffers.active();
+        let lines = stats.summary(buffer.rope.len_chars(), buffer.undo_usage());
+        let width = self.size.0;
+
+        queue!(self.screen, cursor::SavePosition, cursor::Hide)?;
+        for (row, line) in lines.iter().enumerate() {
+            queue!(
+                self.screen,
+                cursor::MoveTo(width.saturating_sub(line.len() as u16), row as u16),
+                style::ResetColor,
+                style::SetAttribute(style::Attribute::Reverse),
+                Print(line),
+                style::SetAttribute(style::Attribute::Reset),
+            )?;
+        }
+        execute!(self.screen, cursor::RestorePosition, cursor::Show)
+    }
+
+    // Only the visible lines are queried, and text no capture covers keeps the
+    // default foreground. The tree may not match the rope exactly (a stale
+    // parse, or a grammar reporting odd ranges), so spans are clamped to it.
+    fn draw_tree(&mut self, tree: &Tree) -> std::io::Result<()> {
+        let language = self.buffer().language();
+        if self.highlights.as_ref().is_none_or(|(l, _)| *l != language) {
+            self.highlights = Some((language, Highlights::new(language)));
+        }
+
+        let buffer = self.buffers.active();
+        let rope = &buffer.rope;
+        let visible = self.shown_lines();
+        let chars = rope.line_to_char(visible.start)..rope.line_to_char(visible.end);
+        let bytes = rope.char_to_byte(chars.start)..rope.char_to_byte(chars.end);
+        let spans = match &self.highlights {
+            Some((_, Some(highlights))) => highlights.spans(tree, rope, bytes),
+            _ => Vec::new(),
+        };
+
+        if tree.root_node().end_byte() > rope.len_bytes() {
+            tracing::warn!(
+                len_bytes = rope.len_bytes(),
+                "syntax tree does not match the buffer, clamped spans"
+            );
+        }
+
+        let mut pen = Pen::new(self.viewport, &self.rows);
+        let mut last = chars.start;
+        queue!(self.screen, style::ResetColor)?;
+
+        for (span, group) in spans {
+            let start = rope.byte_to_char(span.start).max(last);
+            let end = rope.byte_to_char(span.end);
+            if end <= start {
+                continue;
+            }
+
+            if start > last {
+                queue!(self.screen, style::ResetColor)?;
+                pen.print(&mut self.screen, &rope.slice(last..start).to_string())?;
+            }
+            match self.theme.syntax(group) {
+                Some(color) => queue!(self.screen, style::SetForegroundColor(color))?,
+                None => queue!(self.screen, style::ResetColor)?,
+            }
+            pen.print(&mut self.screen, &rope.slice(start..end).to_string())?;
+
+            last = end;
+        }
+
+        queue!(self.screen, style::ResetColor)?;
+        pen.print(&mut self.screen, &rope.slice(last..chars.end).to_string())
+    }
+
+    // Highlights whole lines in buffer coordinates, so a decoration stays on its
+    // text when the view scrolls.
+    fn draw_decorations(&mut self) -> std::io::Result<()> {
+        let now = Instant::now();
+        let active = self.buffers.active_index();
+        let buffer = self.buffers.active();
+        let mut style = style::ContentStyle::new();
+        match self.theme.flash() {
+            Some(color) => style.background_color = Some(color),
+            None => style.attributes.set(style::Attribute::Reverse),
+        }
+
+        let mut spans = Vec::new();
+        for decoration in self.decorations.iter().filter(|d| {
+            d.buffer == active && d.generation == buffer.generation() && d.expires > now
+        }) {
+            for line in decoration.lines.clone() {
+                if line >= buffer.rope.len_lines() {
+                    continue;
+                }
+                let content = buffer
+                    .rope
+                    .slice(lines::line_content_range(&buffer.rope, line))
+                    .to_string();
+                let shown = if content.is_empty() {
+                    " ".to_string()
+                } else {
+                    content
+                };
+                spans.push((line, shown));
+            }
+        }
+
+        for (line, text) in spans {
+            self.draw_over(line, 0, &text, style)?;
+        }
+        Ok(())
+    }
+
+    fn draw_search_matches(&mut self) -> std::io::Result<()> {
+        let Some(query) = &self.search else {
+            return Ok(());
+        };
+
+        let rope = &self.buffer().rope;
+        let visible = self.shown_lines();
+        let range = rope.line_to_char(visible.start)..rope.line_to_char(visible.end);
+        let mut style = style::ContentStyle::new();
+        match self.theme.search_match() {
+            Some(color) => style.background_color = Some(color),
+            None => style.attributes.set(style::Attribute::Reverse),
+        }
+
+        // A match spanning lines is drawn a line at a time.
+        let len = query.chars().count();
+        let spans: Vec<_> = search::all(rope, query, range)
+            .into_iter()
+            .flat_map(|start| {
+                let end = start + len;
+                (rope.char_to_line(start)..=rope.char_to_line(end)).filter_map(move |line| {
+                    let content = lines::line_content_range(rope, line);
+                    let piece = start.max(content.start)..end.min(content.end);
+                    if piece.is_empty() {
+                        return None;
+                    }
+                    let column =
+                        columns::char_to_column(rope.line(line), piece.start - content.start);
+                    Some((line, column, rope.slice(piece).to_string()))
+                })
+            })
+            .collect();
+
+        for (line, column, text) in spans {
+            self.draw_over(line, column, &text, style)?;
+        }
+        Ok(())
+    }
+
+    // Draws `text`, a piece of `line` starting at display column `column`, in
+    // `style` over whichever rows show it.
+    fn draw_over(
+        &mut self,
+        line: usize,
+        column: usize,
+        text: &str,
+        style: style::ContentStyle,
+    ) -> std::io::Result<()> {
+        for (index, row) in self.rows.iter().enumerate() {
+            if row.line != line {
+                continue;
+            }
+            let shown = columns::clip(text, column, row.columns.clone());
+            if shown.is_empty() {
+                continue;
+            }
+            let left =
+                self.viewport.left + (column.max(row.columns.start) - row.columns.start) as u16;
+
+            queue!(
+                self.screen,
+                cursor::MoveTo(left, self.viewport.top + index as u16),
+                style::ResetColor,
+                style::SetStyle(style),
+                Print(columns::display(&shown)),
+                style::ResetColor,
+                style::SetAttribute(style::Attribute::Reset),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    // The lines the last frame showed, or would have.
+    fn shown_lines(&self) -> Range<usize> {
+        match (self.rows.first(), self.rows.last()) {
+            (Some(first), Some(last)) => first.line..last.line + 1,
+            _ => {
+                let scroll = self.buffer().scroll.min(self.buffer().rope.len_lines());
+                scroll..scroll
+            }
+        }
+    }
+
+    // Both brackets of a matched pair are shown in bold and underlined; one
+    // with no partner is shown in the error color.
+    fn draw_bracket_match(&mut self) -> std::io::Result<()> {
+        let Some(found) = self.drawn.as_ref().and_then(|d| d.bracket) else {
+            return Ok(());
+        };
+
+        let mut style = style::ContentStyle::new();
+        let brackets = match found.partner {
+            Some(partner) => {
+                style.attributes.set(style::Attribute::Bold);
+                style.attributes.set(style::Attribute::Underlined);
+                vec![found.bracket, partner]
+            }
+            None => {
+                match self.theme.error() {
+                    Some(color) => style.foreground_color = Some(color),
+                    None => style.attributes.set(style::Attribute::Reverse),
+                }
+                vec![found.bracket]
+            }
+        };
+
+        for at in brackets {
+            let rope = &self.buffer().rope;
+            let line = rope.char_to_line(at);
+            let column = columns::char_to_column(rope.line(line), at - rope.line_to_char(line));
+            let bracket = rope.char(at).to_string();
+            self.draw_over(line, column, &bracket, style)?;
+        }
+
+        Ok(())
+    }
+
+    // A selected line break shows as a highlighted cell after the line's text.
+    fn draw_selection(&mut self) -> std::io::Result<()> {
+        let Some(range) = self.selection_range() else {
+            return Ok(());
+        };
+
+        let mut style = style::ContentStyle::new();
+        match self.theme.selection() {
+            Some(color) => style.background_color = Some(color),
+            None => style.attributes.set(style::Attribute::Reverse),
+        }
+
+        for (line, column, text) in self.span_pieces(range) {
+            self.draw_over(line, column, &text, style)?;
+        }
+        Ok(())
+    }
+
+    // Trailing spaces and tabs get the error color behind them, except on the
+    // cursor's line, where they're usually about to be typed over. With
+    // whitespace shown, every space and tab is drawn as a faint · or →.
+    fn draw_whitespace(&mut self) -> std::io::Result<()> {
+        let mut trailing = style::ContentStyle::new();
+        match self.theme.error() {
+            Some(color) => trailing.background_color = Some(color),
+            None => trailing.attributes.set(style::Attribute::Reverse),
+        }
+        if self.show_whitespace {
+            trailing.attributes.set(style::Attribute::Dim);
+        }
+        let mut faint = style::ContentStyle::new();
+        faint.attributes.set(style::Attribute::Dim);
+
+        // (line, column, text, marked), with neighbouring blanks drawn together.
+        let buffer = self.buffers.active();
+        let visible = self.shown_lines();
+        let mut runs: Vec<(usize, usize, String, bool)> = Vec::new();
+        for line in visible.start..visible.end.min(buffer.line_count()) {
+            let content = columns::content(buffer.rope.line(line));
+            let end = content.trim_end_matches([' ', '\t']).len();
+            let mut column = 0;
+            let mut joins = false;
+
+            for (at, grapheme) in content.grapheme_indices(true) {
+                let width = columns::width(grapheme);
+                let blank = grapheme == " " || grapheme == "\t";
+                let marked = blank && at >= end && line != self.cursor_row;
+                let drawn = blank && (marked || self.show_whitespace);
+                if drawn {
+                    let shown = match (self.show_whitespace, grapheme) {
+                        (false, _) => " ".repeat(width),
+                        (true, " ") => "·".to_string(),
+                        (true, _) => format!("→{}", " ".repeat(width.saturating_sub(1))),
+                    };
+                    match runs.last_mut() {
+                        Some(run) if joins && run.3 == marked => run.2.push_str(&shown),
+                        _ => runs.push((line, column, shown, marked)),
+                    }
+                }
+                joins = drawn;
+                column += width;
+            }
+        }
+
+        for (line, column, text, marked) in runs {
+            let style = if marked { trailing } else { faint };
+            self.draw_over(line, column, &text, style)?;
+        }
+        Ok(())
+    }
+
+    // Underlines what the parser couldn't make sense of, in the error color.
+    fn draw_syntax_errors(&mut self, plain: bool) -> std::io::Result<()> {
+        if plain {
+            return Ok(());
+        }
+
+        let visible = self.shown_lines();
+        let buffer = self.buffers.active_mut();
+        let (start, end) = (
+            buffer.rope.line_to_char(visible.start),
+            buffer
+                .rope
+                .line_to_char(visible.end.min(buffer.line_count())),
+        );
+        let shown: Vec<Range<usize>> = buffer
+            .syntax_errors()
+            .unwrap_or_default()
+            .iter()
+            .filter(|error| error.start <= end && error.end >= start)
+            .cloned()
+            .collect();
+
+        let mut style = style::ContentStyle::new();
+        style.attributes.set(style::Attribute::Underlined);
+        style.foreground_color = self.theme.error();
+        for error in shown {
+            for (line, column, text) in self.span_pieces(error) {
+                self.draw_over(line, column, &text, style)?;
+            }
+        }
+        Ok(())
+    }
+
+    // The visible part of each line `range` covers, as (line, display column,
+    // text). A covered line break shows as a space after the line's text.
+    fn span_pieces(&self, range: Range<usize>) -> Vec<(usize, usize, String)> {
+        let buffer = self.buffers.active();
+        let rope = &buffer.rope;
+        let visible = self.shown_lines();
+        let first = rope.char_to_line(range.start).max(visible.start);
+        let last = (rope.char_to_line(range.end) + 1)
+            .min(visible.end)
+            .min(buffer.line_count());
+
+        let mut pieces = Vec::new();
+        for line in first..last {
+            let content = lines::line_content_range(rope, line);
+            let start = range.start.max(content.start);
+            let end = range.end.min(content.end).max(start);
+            let mut text = rope.slice(start..end).to_string();
+            if range.end > content.end && range.start <= content.end {
+                text.push(' ');
+            }
+
+            let column = columns::char_to_column(rope.line(line), start - content.start);
+            pieces.push((line, column, text));
+        }
+        pieces
+    }
+
+    // Flashes whatever differs from `before` in the active buffer.
+    fn flash_changes(&mut self, before: &Rope) {
+        if !self.flash || self.low_bandwidth {
+            return;
+        }
+
+        let buffer = self.buffers.active();
+        if let Some(lines) = lines::changed_lines(before, &buffer.rope) {
+            self.decorations.push(Decoration {
+                buffer: self.buffers.active_index(),
+                generation: buffer.generation(),
+                lines,
+                expires: Instant::now() + FLASH_DURATION,
+            });
+        }
+    }
+
+    // The main loop waits at most this long for input before calling tick(),
+    // so decorations go away on time.
+    pub fn idle_timeout(&self) -> Duration {
+        #[cfg(unix)]
+        let idle = match self.rpc {
+            Some(_) => RPC_POLL,
+            None => IDLE_TIMEOUT,
+        };
+        #[cfg(not(unix))]
+        let idle = IDLE_TIMEOUT;
+
+        let now = Instant::now();
+        self.decorations
+            .iter()
+            .map(|d| d.expires)
+            .chain(self.pending_chords.as_ref().map(|(_, deadline)| *deadline))
+            .map(|expires| expires.saturating_duration_since(now))
+            .fold(idle, Duration::min)
+    }
+
+    // Carries out whatever requests arrived since the last tick. Returns
+    // whether any did, since they may have changed what's on screen.
+    #[cfg(unix)]
+    fn serve_rpc(&mut self) -> bool {
+        let mut served = false;
+
+        while let Some(request) = self.rpc.as_ref().and_then(rpc::Server::try_recv) {
+            let result = self.run_command(&request.command);
+            request.reply(result);
+            served = true;
+        }
+
+        served
+    }
+
+    #[cfg(unix)]
+    fn run_command(&mut self, command: &rpc::Command) -> Result<serde_json::Value, String> {
+        use serde_json::json;
+
+        match command {
+            rpc::Command::GetBuffer => {
+                let mut text = Vec::new();
+                buffer::write_rope(&self.buffer().rope, &mut text)
+                    .map_err(|error| error.to_string())?;
+                Ok(json!({
+                    "filename": self.buffer().filename,
+                    "text": String::from_utf8_lossy(&text),
+                }))
+            }
+            rpc::Command::GetCursor => Ok(self.rpc_cursor()),
+            rpc::Command::Goto { line, column } => {
+                let index = self.rpc_position(*line, column.unwrap_or(1))?;
+                self.selection = None;
+                self.jump_to_char(index);
+                Ok(self.rpc_cursor())
+            }
+            rpc::Command::Insert { at, text } => {
+                if self.buffer().read_only {
+                    return Err("buffer is read-only".to_string());
+                }
+                let index = self.rpc_position(at.line, at.column)?;
+                let (cursor, cursor_index) = (self.cursor(), self.get_cursor_index());
+                let end = self.buffer_mut().insert_text(index, text, cursor);
+
+                // The cursor stays on the text it was at.
+                self.selection = None;
+                if index <= cursor_index {
+                    self.jump_to_char(cursor_index + end - index);
+                }
+
+                let rope = &self.buffer().rope;
+                let line = rope.char_to_line(end);
+                Ok(json!({
+                    "end": { "line": line + 1, "column": end - rope.line_to_char(line) + 1 }
+                }))
+            }
+            rpc::Command::Save => {
+                // Unlike the Save action, a failure is only reported, since
+                // nobody may be at the terminal to answer a question.
+                if self.buffer().read_only {
+                    return Err("buffer is read-only".to_string());
+                }
+                let Some(filename) = self.buffer().filename.clone() else {
+                    return Err("buffer has no file name".to_string());
+                };
+                self.project
+                    .check(Path::new(&filename))
+                    .map_err(|message| format!("refusing to save: {message}"))?;
+                if self.buffer().disk_changed().is_some() {
+                    return Err(format!("{filename} changed on disk since it was read"));
+                }
+
+                let verb = if self.buffer().new_file {
+                    "Created"
+                } else {
+                    "Saved"
+                };
+                self.trim_for_save();
+                self.write_buffer(Path::new(&filename))
+                    .map_err(|error| format!("couldn't save {filename}: {error}"))?;
+                self.saved(&filename, verb);
+                Ok(json!({ "filename": filename }))
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    fn rpc_cursor(&self) -> serde_json::Value {
+        serde_json::json!({ "line": self.cursor_row + 1, "column": self.cursor_col + 1 })
+    }
+
+    // The char index of a 1-based line and column, which may be just past the
+    // end of the line's text.
+    #[cfg(unix)]
+    fn rpc_position(&self, line: usize, column: usize) -> Result<usize, String> {
+        let rope = &self.buffer().rope;
+        let count = self.buffer().line_count();
+        if line == 0 || line > count {
+            return Err(format!("line {line} is outside 1..={count}"));
+        }
+
+        let content = lines::line_content_range(rope, line - 1);
+        if column == 0 || column > content.len() + 1 {
+            return Err(format!(
+                "column {column} is outside 1..={} on line {line}",
+                content.len() + 1
+            ));
+        }
+
+        Ok(content.start + column - 1)
+    }
+
+    // Right-to-left text is drawn in logical order, so the terminal may show it
+    // differently from how columns are counted here.
+    fn rtl_visible(&self) -> bool {
+        let visible = self.shown_lines();
+
+        self.buffer()
+            .rope
+            .lines_at(visible.start)
+            .take(visible.len())
+            .any(|line| line.chars().any(columns::is_rtl))
+    }
+
+    // Wide enough for the largest line number on screen plus a space, so it
+    // grows as scrolling reaches longer numbers.
+    fn update_gutter(&mut self) -> std::io::Result<()> {
+        let buffer = self.buffers.active();
+        let last = self
+            .viewport
+            .visible_lines(buffer.scroll, self.line_count())
+            .end
+            .max(1);
+        let width = if self.line_numbers {
+            last.ilog10() as u16 + 2
+        } else {
+            0
+        };
+
+        if self.chrome.left != width {
+            self.chrome.left = width;
+            self.refresh_viewport();
+        }
+
+        Ok(())
+    }
+
+    // The cursor's line always shows its own number, also in relative mode.
+    fn draw_gutter(&mut self) -> std::io::Result<()> {
+        if !self.line_numbers {
+            return Ok(());
+        }
+
+        let digits = self.viewport.left.saturating_sub(1) as usize;
+
+        queue!(self.screen, style::ResetColor)?;
+        match self.theme.line_number() {
+            Some(color) => queue!(self.screen, style::SetForegroundColor(color))?,
+            None => queue!(self.screen, style::SetAttribute(style::Attribute::Dim))?,
+        }
+
+        // Rows a wrapped line carries on to get no number.
+        for (index, row) in self.rows.iter().enumerate() {
+            let line = row.line;
+            if row.continued {
+                continue;
+            }
+            let number = if self.relative_numbers && line != self.cursor_row {
+                line.abs_diff(self.cursor_row)
+            } else {
+                line + 1
+            };
+            let row = self.viewport.top + index as u16;
+
+            queue!(
+                self.screen,
+                cursor::MoveTo(0, row),
+                Print(format!("{number:>digits$} "))
+            )?;
+        }
+
+        queue!(
+            self.screen,
+            style::ResetColor,
+            style::SetAttribute(style::Attribute::Reset)
+        )
+    }
+
+    fn draw_plain(&mut self) -> std::io::Result<()> {
+        let rope = &self.buffers.active().rope;
+        let visible = self.shown_lines();
+        let slice = rope.slice(rope.line_to_char(visible.start)..rope.line_to_char(visible.end));
+        let mut pen = Pen::new(self.viewport, &self.rows);
+
+        queue!(self.screen, style::ResetColor)?;
+        pen.print(&mut self.screen, &slice.to_string())
+    }
+
+    // The parser is built on first use rather than at startup, and rebuilt when
+    // a buffer in another language needs one.
+    fn ensure_tree(&mut self) {
+        if self.buffer().tree.is_some() && !self.buffer().tree_stale {
+            return;
+        }
+
+        let language = self.buffer().language();
+        let grammar =
+            (self.grammar)(language).filter(|_| !self.broken_grammars.contains(&language));
+        let Some(grammar) = grammar else {
+            let buffer = self.buffers.active_mut();
+            if language.grammar().is_some() {
+                buffer.set_language(Language::PlainText);
+            }
+            buffer.tree = None;
+            buffer.tree_stale = false;
+            return;
+        };
+
+        // A grammar built for another ABI version than the runtime's is only
+        // found out here, the first time a buffer needs it.
+        if self.parser.as_ref().is_none_or(|(l, _)| *l != language) {
+            let mut parser = Parser::new();
+            if let Err(error) = parser.set_language(&grammar) {
+                tracing::error!(
+                    ?language,
+                    ?error,
+                    "grammar is incompatible with tree-sitter"
+                );
+                self.broken_grammars.push(language);
+                self.message = Some(format!(
+                    "{} grammar doesn't match this tree-sitter version, showing plain text",
+                    language.name()
+                ));
+                return self.ensure_tree();
+            }
+            self.parser = Some((language, parser));
+        }
+        let (_, parser) = self.parser.as_mut().unwrap();
+
+        // Edits were applied to the old tree as they happened, so the parse can
+        // reuse it. The text is read a chunk at a time, without copying it out.
+        let start = Instant::now();
+        let buffer = self.buffers.active_mut();
+        let rope = &buffer.rope;
+        buffer.tree = parser.parse_with(
+            &mut |byte, _| match rope.get_chunk_at_byte(byte) {
+                Some((chunk, start, _, _)) => &chunk.as_bytes()[byte - start..],
+                None => &[],
+            },
+            buffer.tree.as_ref(),
+        );
+        buffer.tree_stale = false;
+        let elapsed = start.elapsed();
+        tracing::debug!(language = language.name(), ?elapsed, "parsed");
+        self.parse_time += elapsed;
+    }
+
+    fn get_visible_lines_len(&self) -> usize {
+        self.shown_lines().len()
+    }
+
+    // The buffer's lines, and the empty one after its final terminator while
+    // the cursor is on it, as it is right after typing that terminator.
+    fn line_count(&self) -> usize {
+        self.buffer().line_count().max(self.cursor_row + 1)
+    }
+
+    fn get_line_number(&self) -> usize {
+        self.cursor_row
+    }
+
+    // (line, char column), the way buffers and undo keep it.
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.cursor_row, self.cursor_col)
+    }
+
+    // Where the selection was started from, the cursor being its other end.
+    pub fn selection(&self) -> Option<(usize, usize)> {
+        self.selection
+    }
+
+    // Whole-line edits go through the buffer like any other, so they're undoable.
+    fn insert_lines(&mut self, line: usize, new: &[&str]) {
+        let (index, text) = lines::insertion(&self.buffer().rope, line, new);
+        let cursor = self.cursor();
+        self.buffer_mut().edit(index..index, &text, cursor);
+    }
+
+    fn remove_lines(&mut self, lines: Range<usize>) {
+        let range = lines::removal(&self.buffer().rope, lines);
+        let cursor = self.cursor();
+        self.buffer_mut().edit(range, "", cursor);
+    }
+
+    // Moves are clamped to the buffer, so a count larger than the distance to the
+    // edge just stops there. Moving up or down aims for the column the first
+    // of those moves started from, so passing a short line doesn't lose it.
+    // Paging scrolls the view by as many lines as the cursor moves, short of scrolling
+    // past the last line.
+    fn move_cursor_by(&mut self, movement: CursorMovement, count: usize) -> std::io::Result<()> {
+        let line_number = self.get_line_number();
+        let last = self.line_count().saturating_sub(1);
+        let page = self.viewport.page_lines(self.page_overlap, self.half_page) * count;
+        let mut scroll = self.buffer().scroll;
+        let rope = &self.buffer().rope;
+        let line = rope.line(line_number);
+        let mut column = columns::char_to_column(line, self.cursor_col);
+        let vertical = matches!(
+            movement,
+            CursorMovement::Up
+                | CursorMovement::Down
+                | CursorMovement::PageUp
+                | CursorMovement::PageDown
+        );
+        let starts = self.wrap.then(|| self.row_starts(line_number));
+        let row = starts
+            .as_ref()
+            .map_or(0, |starts| wrap::row_of(starts, column));
+        let here = starts
+            .as_ref()
+            .map_or(column, |starts| column - starts[row]);
+        let goal = self.goal_column.filter(|_| vertical).unwrap_or(here);
+
+        let line_number = match movement {
+            CursorMovement::Up | CursorMovement::Down if self.wrap => {
+                let down = matches!(movement, CursorMovement::Down);
+                let (line_number, row) = self.step_rows((line_number, row), down, count);
+                column = self.row_column(line_number, row, goal);
+                line_number
+            }
+            CursorMovement::Up => {
+                column = goal;
+                line_number.saturating_sub(count)
+            }
+            CursorMovement::Down => {
+                column = goal;
+                (line_number + count).min(last)
+            }
+            CursorMovement::PageUp => {
+                column = goal;
+                scroll = scroll.saturating_sub(page);
+                line_number.saturating_sub(page)
+            }
+            CursorMovement::PageDown => {
+                column = goal;
+                scroll = (scroll + page).min(self.viewport.max_scroll(last + 1));
+                (line_number + page).min(last)
+            }
+            CursorMovement::LineStart => {
+                column = 0;
+                line_number
+            }
+            // Columns past the end snap to the end of the line.
+            CursorMovement::LineEnd => {
+                column = usize::MAX;
+                line_number
+            }
+            CursorMovement::FileStart => {
+                column = 0;
+                0
+            }
+            CursorMovement::FileEnd => {
+                column = usize::MAX;
+                last
+            }
+            // Word moves cross lines, but never onto the empty line after a
+            // final newline.
+            CursorMovement::WordLeft | CursorMovement::WordRight => {
+                let mut index = rope.line_to_char(line_number) + self.cursor_col;
+                for _ in 0..count {
+                    index = match movement {
+                        CursorMovement::WordLeft => words::previous_start(rope, index),
+                        _ => words::next_start(rope, index),
+                    };
+                }
+                let index = index.min(lines::line_content_range(rope, last).end);
+                let line_number = rope.char_to_line(index);
+                let offset = index - rope.line_to_char(line_number);
+                column = columns::char_to_column(rope.line(line_number), offset);
+                line_number
+            }
+            CursorMovement::Left | CursorMovement::Right => {
+                for _ in 0..count.min(columns::line_width(line) + 1) {
+                    column = match movement {
+                        CursorMovement::Left => columns::previous_column(line, column),
+                        _ => columns::next_column(line, column).min(columns::line_width(line)),
+                    };
+                }
+                line_number
+            }
+        };
+
+        let column = columns::column_to_char(rope.line(line_number), column);
+        let big = matches!(
+            movement,
+            CursorMovement::PageUp
+                | CursorMovement::PageDown
+                | CursorMovement::FileStart
+                | CursorMovement::FileEnd
+        );
+        if big && line_number != self.cursor_row {
+            self.record_jump();
+        }
+        if vertical {
+            self.goal_column = Some(goal);
+            self.keep_goal = true;
+        }
+        self.buffer_mut().scroll = scroll;
+        self.move_cursor_to(line_number, column);
+        self.redraw()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use ropey::Rope;
+
+    use super::*;
+    use crate::terminal::Memory;
+
+    // A grammar claiming ABI version 0, which no tree-sitter runtime accepts.
+    // Only the version is read before set_language turns it down.
+    fn incompatible(_: Language) -> Option<tree_sitter::Language> {
+        static ZEROES: [u64; 64] = [0; 64];
+        unsafe extern "C" fn language() -> *const () {
+            ZEROES.as_ptr().cast()
+        }
+        let language = unsafe { tree_sitter_language::LanguageFn::from_raw(language) };
+        Some(language.into())
+    }
+
+    // Rust buffers without files, so nothing is remembered or recovered on disk.
+    fn rust(text: &str) -> Buffer {
+        let mut buffer = Buffer::new(Rope::from_str(text), None);
+        buffer.set_language(Language::Rust);
+        buffer
+    }
+
+    #[test]
+    fn an_incompatible_grammar_falls_back_to_plain_text_once() {
+        let project = Project {
+            root: env::temp_dir(),
+            restrict: false,
+        };
+        let buffers = vec![rust("fn one() {}\n"), rust("fn two() {}\n")];
+        let mut editor = Editor::new(Box::new(Memory::new(60, 8)), buffers, project);
+        editor.grammar = incompatible;
+        editor.init().unwrap();
+        editor.tick().unwrap();
+
+        assert_eq!(editor.buffer().language(), Language::PlainText);
+        assert!(editor.buffer().tree.is_none());
+        assert_eq!(
+            editor.message.as_deref(),
+            Some("Rust grammar doesn't match this tree-sitter version, showing plain text")
+        );
+
+        // Still editable.
+        editor.insert_text("// ").unwrap();
+        editor.tick().unwrap();
+        assert_eq!(editor.buffer().rope.to_string(), "// fn one() {}\n");
+
+        // The other Rust buffer goes to plain text without trying again.
+        editor.message = None;
+        editor.switch_buffer(1).unwrap();
+        editor.tick().unwrap();
+        assert_eq!(editor.buffer().language(), Language::PlainText);
+        assert_eq!(editor.message, None);
+        assert_eq!(editor.broken_grammars, [Language::Rust]);
     }
 }
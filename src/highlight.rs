@@ -0,0 +1,117 @@
+use std::ops::Range;
+
+use ropey::Rope;
+use streaming_iterator::StreamingIterator;
+use tree_sitter::{Node, Query, QueryCursor, Tree};
+
+use crate::language::Language;
+
+// What the theme has colors for. Captures from the grammars' highlight queries
+// are sorted into these by name; the rest, like variables, operators and
+// punctuation, keep the default foreground.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Group {
+    Keyword,
+    String,
+    Comment,
+    Function,
+    Type,
+    Constant,
+    Attribute,
+    Property,
+}
+
+// A capture name is looked up as is, then with dotted parts dropped from the
+// end, so `function.macro` falls back to `function`.
+const GROUPS: [(&str, Group); 13] = [
+    ("keyword", Group::Keyword),
+    // JSON object keys.
+    ("string.special.key", Group::Property),
+    ("string", Group::String),
+    ("comment", Group::Comment),
+    ("function", Group::Function),
+    ("constructor", Group::Function),
+    ("type", Group::Type),
+    ("constant", Group::Constant),
+    ("number", Group::Constant),
+    ("boolean", Group::Constant),
+    ("escape", Group::Constant),
+    ("attribute", Group::Attribute),
+    ("property", Group::Property),
+];
+
+pub fn group(name: &str) -> Option<Group> {
+    let mut name = name;
+
+    loop {
+        if let Some((_, group)) = GROUPS.iter().find(|(n, _)| *n == name) {
+            return Some(*group);
+        }
+        name = name.rsplit_once('.')?.0;
+    }
+}
+
+// A language's highlight query, with the group of every capture looked up
+// once up front.
+pub struct Highlights {
+    query: Query,
+    groups: Vec<Option<Group>>,
+}
+
+impl Highlights {
+    pub fn new(language: Language) -> Option<Self> {
+        let query = match Query::new(&language.grammar()?, language.highlights()?) {
+            Ok(query) => query,
+            Err(error) => {
+                tracing::warn!(?language, %error, "highlight query failed to compile");
+                return None;
+            }
+        };
+        let groups = query
+            .capture_names()
+            .iter()
+            .map(|name| group(name))
+            .collect();
+
+        Some(Self { query, groups })
+    }
+
+    // Colored byte ranges within `bytes`, in order and not overlapping. Where
+    // captures overlap the first one wins, which is the precedence highlight
+    // queries are written for; that includes captures left uncolored.
+    pub fn spans(
+        &self,
+        tree: &Tree,
+        rope: &Rope,
+        bytes: Range<usize>,
+    ) -> Vec<(Range<usize>, Group)> {
+        // Predicates like #match? read the node's text. Going through chars
+        // keeps a stale tree's ranges from splitting one.
+        let text = |node: Node| {
+            let start = rope.byte_to_char(node.start_byte().min(rope.len_bytes()));
+            let end = rope.byte_to_char(node.end_byte().min(rope.len_bytes()));
+            rope.slice(start..end).chunks().map(str::as_bytes)
+        };
+        let mut cursor = QueryCursor::new();
+        cursor.set_byte_range(bytes.clone());
+        let mut captures = cursor.captures(&self.query, tree.root_node(), text);
+        let mut spans = Vec::new();
+        let mut covered = bytes.start;
+
+        while let Some((found, index)) = captures.next() {
+            let capture = found.captures[*index];
+            let start = capture.node.start_byte().max(covered);
+            let end = capture.node.end_byte().min(bytes.end);
+            if end <= start {
+                continue;
+            }
+
+            if let Some(group) = self.groups[capture.index as usize] {
+                spans.push((start..end, group));
+            }
+            covered = end;
+        }
+
+        spans
+    }
+}
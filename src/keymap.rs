@@ -0,0 +1,824 @@
+use std::collections::HashMap;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Serialize};
+
+// Everything a key can be bound to. The kebab-case names, which serde uses
+// too, are what --bind, --list-actions and keymap files use, so renaming an
+// action breaks configs; add new ones instead. Callers report unknown names
+// with the name itself, as serde's error does.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+    Quit,
+    Cancel,
+    Save,
+    SaveAs,
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Newline,
+    InsertTab,
+    Backspace,
+    Delete,
+    DuplicateAndComment,
+    ToggleComment,
+    ShowPath,
+    Reflow,
+    BufferList,
+    ShowDiagnostic,
+    JumpToWhitespace,
+    FixWhitespace,
+    ToggleWhitespace,
+    CopyLocation,
+    CopyLocationWithColumn,
+    CopyMarkdown,
+    Reload,
+    Undo,
+    Redo,
+    ToggleDebugOverlay,
+    ProjectReplace,
+    ToggleAutoPairs,
+    ToggleLowBandwidth,
+    OpenLineBelow,
+    OpenLineAbove,
+    CopyLine,
+    CutLine,
+    DeleteLine,
+    DuplicateLine,
+    MoveLineUp,
+    MoveLineDown,
+    Paste,
+    FindReferences,
+    NextLocation,
+    PreviousLocation,
+    Follow,
+    Evaluate,
+    EvaluateAppend,
+    SetLanguage,
+    LastChange,
+    GoToLine,
+    Search,
+    Replace,
+    JumpToMatch,
+    CenterCursor,
+    NextSyntaxError,
+    JumpBack,
+    JumpForward,
+    AlternateBuffer,
+    NextBuffer,
+    PreviousBuffer,
+    PreviewPaste,
+    LineStart,
+    LineEnd,
+    PageUp,
+    PageDown,
+    FileStart,
+    FileEnd,
+    WordLeft,
+    WordRight,
+    BackspaceWord,
+    DeleteWord,
+    Help,
+    SelectUp,
+    SelectDown,
+    SelectLeft,
+    SelectRight,
+    Copy,
+    Cut,
+    ToggleRelativeNumbers,
+    ToggleWrap,
+    ToggleReadOnly,
+    InsertSeparator,
+}
+
+const ACTION_NAMES: [(&str, Action); 81] = [
+    ("quit", Action::Quit),
+    ("cancel", Action::Cancel),
+    ("save", Action::Save),
+    ("save-as", Action::SaveAs),
+    ("move-up", Action::MoveUp),
+    ("move-down", Action::MoveDown),
+    ("move-left", Action::MoveLeft),
+    ("move-right", Action::MoveRight),
+    ("newline", Action::Newline),
+    ("insert-tab", Action::InsertTab),
+    ("backspace", Action::Backspace),
+    ("delete", Action::Delete),
+    ("duplicate-and-comment", Action::DuplicateAndComment),
+    ("toggle-comment", Action::ToggleComment),
+    ("show-path", Action::ShowPath),
+    ("reflow", Action::Reflow),
+    ("buffer-list", Action::BufferList),
+    ("show-diagnostic", Action::ShowDiagnostic),
+    ("jump-to-whitespace", Action::JumpToWhitespace),
+    ("fix-whitespace", Action::FixWhitespace),
+    ("toggle-whitespace", Action::ToggleWhitespace),
+    ("copy-location", Action::CopyLocation),
+    ("copy-location-with-column", Action::CopyLocationWithColumn),
+    ("copy-markdown", Action::CopyMarkdown),
+    ("reload", Action::Reload),
+    ("undo", Action::Undo),
+    ("redo", Action::Redo),
+    ("toggle-debug-overlay", Action::ToggleDebugOverlay),
+    ("project-replace", Action::ProjectReplace),
+    ("toggle-auto-pairs", Action::ToggleAutoPairs),
+    ("toggle-low-bandwidth", Action::ToggleLowBandwidth),
+    ("open-line-below", Action::OpenLineBelow),
+    ("open-line-above", Action::OpenLineAbove),
+    ("copy-line", Action::CopyLine),
+    ("cut-line", Action::CutLine),
+    ("delete-line", Action::DeleteLine),
+    ("duplicate-line", Action::DuplicateLine),
+    ("move-line-up", Action::MoveLineUp),
+    ("move-line-down", Action::MoveLineDown),
+    ("paste", Action::Paste),
+    ("find-references", Action::FindReferences),
+    ("next-location", Action::NextLocation),
+    ("previous-location", Action::PreviousLocation),
+    ("follow", Action::Follow),
+    ("evaluate", Action::Evaluate),
+    ("evaluate-append", Action::EvaluateAppend),
+    ("set-language", Action::SetLanguage),
+    ("last-change", Action::LastChange),
+    ("go-to-line", Action::GoToLine),
+    ("search", Action::Search),
+    ("replace", Action::Replace),
+    ("jump-to-match", Action::JumpToMatch),
+    ("center-cursor", Action::CenterCursor),
+    ("next-syntax-error", Action::NextSyntaxError),
+    ("jump-back", Action::JumpBack),
+    ("jump-forward", Action::JumpForward),
+    ("alternate-buffer", Action::AlternateBuffer),
+    ("next-buffer", Action::NextBuffer),
+    ("previous-buffer", Action::PreviousBuffer),
+    ("preview-paste", Action::PreviewPaste),
+    ("line-start", Action::LineStart),
+    ("line-end", Action::LineEnd),
+    ("page-up", Action::PageUp),
+    ("page-down", Action::PageDown),
+    ("file-start", Action::FileStart),
+    ("file-end", Action::FileEnd),
+    ("word-left", Action::WordLeft),
+    ("word-right", Action::WordRight),
+    ("backspace-word", Action::BackspaceWord),
+    ("delete-word", Action::DeleteWord),
+    ("help", Action::Help),
+    ("select-up", Action::SelectUp),
+    ("select-down", Action::SelectDown),
+    ("select-left", Action::SelectLeft),
+    ("select-right", Action::SelectRight),
+    ("copy", Action::Copy),
+    ("cut", Action::Cut),
+    ("toggle-relative-numbers", Action::ToggleRelativeNumbers),
+    ("toggle-wrap", Action::ToggleWrap),
+    ("toggle-read-only", Action::ToggleReadOnly),
+    ("insert-separator", Action::InsertSeparator),
+];
+
+impl Action {
+    pub fn from_name(name: &str) -> Option<Self> {
+        ACTION_NAMES
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, action)| *action)
+    }
+
+    pub fn name(self) -> &'static str {
+        ACTION_NAMES.iter().find(|(_, a)| *a == self).unwrap().0
+    }
+
+    // In the order --list-actions shows them.
+    pub fn all() -> impl Iterator<Item = Action> {
+        ACTION_NAMES.iter().map(|(_, action)| *action)
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::Quit => "Quit, asking first if there are unsaved changes",
+            Action::Cancel => "Dismiss the current message or count",
+            Action::Save => "Save the current buffer",
+            Action::SaveAs => "Save the current buffer under a new name",
+            Action::MoveUp => "Move the cursor up",
+            Action::MoveDown => "Move the cursor down",
+            Action::MoveLeft => "Move the cursor left",
+            Action::MoveRight => "Move the cursor right",
+            Action::Newline => "Split the line at the cursor, keeping its indentation",
+            Action::InsertTab => "Insert a tab, or spaces to the next indent stop",
+            Action::Backspace => "Delete the character before the cursor",
+            Action::Delete => {
+                "Delete the character under the cursor, joining lines at the end of one"
+            }
+            Action::DuplicateAndComment => {
+                "Duplicate the line or the selected lines and comment out the original"
+            }
+            Action::ToggleComment => "Comment or uncomment the line or the selected lines",
+            Action::ShowPath => "Show the full path of the buffer",
+            Action::Reflow => "Rewrap the paragraph, comment or selected lines to the text width",
+            Action::BufferList => "List open buffers",
+            Action::ShowDiagnostic => "Show diagnostics for the cursor line",
+            Action::JumpToWhitespace => "Jump to trailing whitespace or a missing final newline",
+            Action::FixWhitespace => "Strip trailing whitespace (of the selected lines, if any) and fix the final newline",
+            Action::ToggleWhitespace => "Show or hide spaces and tabs",
+            Action::CopyLocation => "Copy path:line",
+            Action::CopyLocationWithColumn => "Copy path:line:column",
+            Action::CopyMarkdown => "Copy the line as a Markdown code block",
+            Action::Reload => "Reload the buffer from disk",
+            Action::Undo => "Undo the last change",
+            Action::Redo => "Redo the last undone change",
+            Action::ToggleDebugOverlay => "Toggle the render timing overlay",
+            Action::ProjectReplace => "Find and replace across the project",
+            Action::ToggleAutoPairs => "Toggle automatic bracket and quote pairing",
+            Action::ToggleLowBandwidth => "Toggle sending as little as possible to the terminal",
+            Action::OpenLineBelow => "Open a new line below",
+            Action::OpenLineAbove => "Open a new line above",
+            Action::CopyLine => "Copy the current or selected lines",
+            Action::CutLine => "Cut the current or selected lines",
+            Action::DeleteLine => "Delete the current line without copying it",
+            Action::DuplicateLine => "Duplicate the current line below",
+            Action::MoveLineUp => "Swap the current line with the one above",
+            Action::MoveLineDown => "Swap the current line with the one below",
+            Action::Paste => "Paste the last copy",
+            Action::FindReferences => "Find references to the identifier under the cursor",
+            Action::NextLocation => "Go to the next location in the last list",
+            Action::PreviousLocation => "Go to the previous location in the last list",
+            Action::Follow => "Follow a growing file",
+            Action::Evaluate => "Replace the selected arithmetic, or that under the cursor, with its result",
+            Action::EvaluateAppend => "Append the result of the arithmetic under the cursor",
+            Action::SetLanguage => "Set the language of the buffer",
+            Action::LastChange => "Jump to the last change, further back on repeats",
+            Action::GoToLine => "Jump to a line, or line:column",
+            Action::Search => "Search the buffer, then step through the matches",
+            Action::Replace => "Replace matches in the buffer, asking at each one",
+            Action::JumpToMatch => "Jump to the bracket matching the one at the cursor",
+            Action::CenterCursor => "Scroll the cursor's line to the middle of the view",
+            Action::NextSyntaxError => "Jump to the next syntax error",
+            Action::JumpBack => "Go back to where the last big move started",
+            Action::JumpForward => "Go forward again after jumping back",
+            Action::AlternateBuffer => "Switch back to the previously active buffer",
+            Action::NextBuffer => "Switch to the next buffer in the list",
+            Action::PreviousBuffer => "Switch to the previous buffer in the list",
+            Action::PreviewPaste => "Show what pasting would do, then keep or undo it",
+            Action::LineStart => "Move the cursor to the start of the line",
+            Action::LineEnd => "Move the cursor to the end of the line",
+            Action::PageUp => "Scroll and move the cursor up a screen",
+            Action::PageDown => "Scroll and move the cursor down a screen",
+            Action::FileStart => "Move the cursor to the start of the buffer",
+            Action::FileEnd => "Move the cursor to the end of the buffer",
+            Action::WordLeft => "Move the cursor to the start of the previous word",
+            Action::WordRight => "Move the cursor to the start of the next word",
+            Action::BackspaceWord => "Delete back to the start of the word",
+            Action::DeleteWord => "Delete forward to the start of the next word",
+            Action::Help => "List the key bindings",
+            Action::SelectUp => "Extend the selection up",
+            Action::SelectDown => "Extend the selection down",
+            Action::SelectLeft => "Extend the selection left",
+            Action::SelectRight => "Extend the selection right",
+            Action::Copy => "Copy the selection or the current line",
+            Action::Cut => "Cut the selection or the current line",
+            Action::ToggleRelativeNumbers => "Switch between absolute and relative line numbers",
+            Action::ToggleWrap => "Wrap long lines onto the next rows, or scroll sideways",
+            Action::ToggleReadOnly => "Make the buffer read-only, or ask to allow edits again",
+            Action::InsertSeparator => "Insert a horizontal rule below the current line",
+        }
+    }
+
+    // Whether the action changes the buffer's text, which a read-only buffer
+    // refuses.
+    pub fn edits(self) -> bool {
+        matches!(
+            self,
+            Action::Newline
+                | Action::InsertTab
+                | Action::Backspace
+                | Action::Delete
+                | Action::DuplicateAndComment
+                | Action::ToggleComment
+                | Action::Reflow
+                | Action::FixWhitespace
+                | Action::Undo
+                | Action::Redo
+                | Action::OpenLineBelow
+                | Action::OpenLineAbove
+                | Action::CutLine
+                | Action::DeleteLine
+                | Action::DuplicateLine
+                | Action::MoveLineUp
+                | Action::MoveLineDown
+                | Action::Paste
+                | Action::Evaluate
+                | Action::EvaluateAppend
+                | Action::Replace
+                | Action::PreviewPaste
+                | Action::BackspaceWord
+                | Action::DeleteWord
+                | Action::Cut
+                | Action::InsertSeparator
+        )
+    }
+}
+
+pub type Chord = (KeyCode, KeyModifiers);
+
+// A chord either runs an action or, in sequences like "ctrl+k ctrl+s", leads
+// on to the chords that can follow it.
+enum Binding {
+    Action(Action),
+    Prefix(HashMap<Chord, Binding>),
+}
+
+pub enum Resolved {
+    Action(Action),
+    // The chords so far start a longer sequence.
+    Prefix,
+    Undefined,
+}
+
+pub struct Keymap {
+    bindings: HashMap<Chord, Binding>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = [
+            ((KeyCode::Char('q'), KeyModifiers::CONTROL), Action::Quit),
+            ((KeyCode::Esc, KeyModifiers::NONE), Action::Cancel),
+            ((KeyCode::Char('s'), KeyModifiers::CONTROL), Action::Save),
+            (
+                (
+                    KeyCode::Char('S'),
+                    KeyModifiers::CONTROL | KeyModifiers::SHIFT,
+                ),
+                Action::SaveAs,
+            ),
+            ((KeyCode::Up, KeyModifiers::NONE), Action::MoveUp),
+            ((KeyCode::Down, KeyModifiers::NONE), Action::MoveDown),
+            ((KeyCode::Left, KeyModifiers::NONE), Action::MoveLeft),
+            ((KeyCode::Right, KeyModifiers::NONE), Action::MoveRight),
+            ((KeyCode::Home, KeyModifiers::NONE), Action::LineStart),
+            ((KeyCode::End, KeyModifiers::NONE), Action::LineEnd),
+            ((KeyCode::PageUp, KeyModifiers::NONE), Action::PageUp),
+            ((KeyCode::PageDown, KeyModifiers::NONE), Action::PageDown),
+            ((KeyCode::Home, KeyModifiers::CONTROL), Action::FileStart),
+            ((KeyCode::End, KeyModifiers::CONTROL), Action::FileEnd),
+            ((KeyCode::Left, KeyModifiers::CONTROL), Action::WordLeft),
+            ((KeyCode::Right, KeyModifiers::CONTROL), Action::WordRight),
+            ((KeyCode::F(1), KeyModifiers::NONE), Action::Help),
+            ((KeyCode::Up, KeyModifiers::SHIFT), Action::SelectUp),
+            ((KeyCode::Down, KeyModifiers::SHIFT), Action::SelectDown),
+            ((KeyCode::Left, KeyModifiers::SHIFT), Action::SelectLeft),
+            ((KeyCode::Right, KeyModifiers::SHIFT), Action::SelectRight),
+            ((KeyCode::Char('c'), KeyModifiers::CONTROL), Action::Copy),
+            ((KeyCode::Char('x'), KeyModifiers::CONTROL), Action::Cut),
+            (
+                (KeyCode::Char('t'), KeyModifiers::ALT),
+                Action::ToggleRelativeNumbers,
+            ),
+            ((KeyCode::Char('z'), KeyModifiers::ALT), Action::ToggleWrap),
+            (
+                (KeyCode::Char('u'), KeyModifiers::ALT),
+                Action::ToggleReadOnly,
+            ),
+            (
+                (KeyCode::Char('-'), KeyModifiers::ALT),
+                Action::InsertSeparator,
+            ),
+            ((KeyCode::Enter, KeyModifiers::NONE), Action::Newline),
+            ((KeyCode::Tab, KeyModifiers::NONE), Action::InsertTab),
+            (
+                (KeyCode::Enter, KeyModifiers::CONTROL),
+                Action::OpenLineBelow,
+            ),
+            (
+                (KeyCode::Enter, KeyModifiers::CONTROL | KeyModifiers::SHIFT),
+                Action::OpenLineAbove,
+            ),
+            // Most terminals send Ctrl+Enter as a plain Enter, so these work everywhere.
+            (
+                (KeyCode::Char('o'), KeyModifiers::ALT),
+                Action::OpenLineBelow,
+            ),
+            (
+                (KeyCode::Char('O'), KeyModifiers::ALT | KeyModifiers::SHIFT),
+                Action::OpenLineAbove,
+            ),
+            ((KeyCode::Backspace, KeyModifiers::NONE), Action::Backspace),
+            ((KeyCode::Delete, KeyModifiers::NONE), Action::Delete),
+            ((KeyCode::Delete, KeyModifiers::CONTROL), Action::DeleteWord),
+            (
+                (KeyCode::Backspace, KeyModifiers::CONTROL),
+                Action::BackspaceWord,
+            ),
+            // What most terminals send for Ctrl+Backspace.
+            (
+                (KeyCode::Char('h'), KeyModifiers::CONTROL),
+                Action::BackspaceWord,
+            ),
+            (
+                (KeyCode::Char('d'), KeyModifiers::ALT),
+                Action::DuplicateAndComment,
+            ),
+            (
+                (KeyCode::Char('/'), KeyModifiers::CONTROL),
+                Action::ToggleComment,
+            ),
+            // What terminals send for Ctrl+/, which is the same byte as Ctrl+_.
+            (
+                (KeyCode::Char('7'), KeyModifiers::CONTROL),
+                Action::ToggleComment,
+            ),
+            (
+                (KeyCode::Char('_'), KeyModifiers::CONTROL),
+                Action::ToggleComment,
+            ),
+            ((KeyCode::Char('p'), KeyModifiers::ALT), Action::ShowPath),
+            ((KeyCode::Char('q'), KeyModifiers::ALT), Action::Reflow),
+            (
+                (KeyCode::Char('b'), KeyModifiers::CONTROL),
+                Action::BufferList,
+            ),
+            (
+                (KeyCode::Char('e'), KeyModifiers::ALT),
+                Action::ShowDiagnostic,
+            ),
+            (
+                (KeyCode::Char('w'), KeyModifiers::ALT),
+                Action::JumpToWhitespace,
+            ),
+            (
+                (KeyCode::Char('W'), KeyModifiers::ALT | KeyModifiers::SHIFT),
+                Action::FixWhitespace,
+            ),
+            (
+                (KeyCode::Char('s'), KeyModifiers::ALT),
+                Action::ToggleWhitespace,
+            ),
+            (
+                (KeyCode::Char('l'), KeyModifiers::ALT),
+                Action::CopyLocation,
+            ),
+            (
+                (KeyCode::Char('L'), KeyModifiers::ALT | KeyModifiers::SHIFT),
+                Action::CopyLocationWithColumn,
+            ),
+            (
+                (KeyCode::Char('C'), KeyModifiers::ALT | KeyModifiers::SHIFT),
+                Action::CopyMarkdown,
+            ),
+            ((KeyCode::Char('r'), KeyModifiers::ALT), Action::Reload),
+            ((KeyCode::Char('z'), KeyModifiers::CONTROL), Action::Undo),
+            ((KeyCode::Char('y'), KeyModifiers::CONTROL), Action::Redo),
+            (
+                (
+                    KeyCode::Char('Z'),
+                    KeyModifiers::CONTROL | KeyModifiers::SHIFT,
+                ),
+                Action::Redo,
+            ),
+            (
+                (KeyCode::F(12), KeyModifiers::NONE),
+                Action::ToggleDebugOverlay,
+            ),
+            (
+                (KeyCode::Char('R'), KeyModifiers::ALT | KeyModifiers::SHIFT),
+                Action::ProjectReplace,
+            ),
+            ((KeyCode::Char('y'), KeyModifiers::ALT), Action::CopyLine),
+            ((KeyCode::Char('k'), KeyModifiers::ALT), Action::CutLine),
+            (
+                (KeyCode::Char('k'), KeyModifiers::CONTROL),
+                Action::DeleteLine,
+            ),
+            (
+                (
+                    KeyCode::Char('K'),
+                    KeyModifiers::CONTROL | KeyModifiers::SHIFT,
+                ),
+                Action::DeleteLine,
+            ),
+            (
+                (KeyCode::Char('d'), KeyModifiers::CONTROL),
+                Action::DuplicateLine,
+            ),
+            ((KeyCode::Up, KeyModifiers::ALT), Action::MoveLineUp),
+            ((KeyCode::Down, KeyModifiers::ALT), Action::MoveLineDown),
+            ((KeyCode::Char('v'), KeyModifiers::CONTROL), Action::Paste),
+            (
+                (KeyCode::Char('v'), KeyModifiers::ALT),
+                Action::PreviewPaste,
+            ),
+            (
+                (KeyCode::F(12), KeyModifiers::SHIFT),
+                Action::FindReferences,
+            ),
+            (
+                (KeyCode::Char('n'), KeyModifiers::ALT),
+                Action::NextLocation,
+            ),
+            (
+                (KeyCode::Char('N'), KeyModifiers::ALT | KeyModifiers::SHIFT),
+                Action::PreviousLocation,
+            ),
+            ((KeyCode::Char('f'), KeyModifiers::ALT), Action::Follow),
+            ((KeyCode::Char('='), KeyModifiers::ALT), Action::Evaluate),
+            ((KeyCode::Char('g'), KeyModifiers::ALT), Action::SetLanguage),
+            ((KeyCode::Char('.'), KeyModifiers::ALT), Action::LastChange),
+            (
+                (KeyCode::Char('g'), KeyModifiers::CONTROL),
+                Action::GoToLine,
+            ),
+            ((KeyCode::Char('f'), KeyModifiers::CONTROL), Action::Search),
+            ((KeyCode::Char('r'), KeyModifiers::CONTROL), Action::Replace),
+            ((KeyCode::Char('m'), KeyModifiers::ALT), Action::JumpToMatch),
+            (
+                (KeyCode::Char('l'), KeyModifiers::CONTROL),
+                Action::CenterCursor,
+            ),
+            (
+                (KeyCode::Char('e'), KeyModifiers::CONTROL),
+                Action::NextSyntaxError,
+            ),
+            (
+                (KeyCode::Char('o'), KeyModifiers::CONTROL),
+                Action::JumpBack,
+            ),
+            // Terminals send Ctrl+I as Tab.
+            ((KeyCode::Char('i'), KeyModifiers::ALT), Action::JumpForward),
+            (
+                (KeyCode::Char('6'), KeyModifiers::CONTROL),
+                Action::AlternateBuffer,
+            ),
+            (
+                (KeyCode::PageDown, KeyModifiers::CONTROL),
+                Action::NextBuffer,
+            ),
+            (
+                (KeyCode::PageUp, KeyModifiers::CONTROL),
+                Action::PreviousBuffer,
+            ),
+        ];
+
+        Self {
+            bindings: bindings
+                .into_iter()
+                .map(|(chord, action)| (chord, Binding::Action(action)))
+                .collect(),
+        }
+    }
+}
+
+impl Keymap {
+    // Rebinding an action drops its previous chords so the old key stops working.
+    // A sequence through a chord that ran an action on its own takes the chord
+    // over as its prefix.
+    pub fn bind(&mut self, sequence: &[Chord], action: Action) {
+        unbind(&mut self.bindings, action);
+
+        let Some((last, prefix)) = sequence.split_last() else {
+            return;
+        };
+        let mut level = &mut self.bindings;
+        for chord in prefix {
+            let binding = level
+                .entry(*chord)
+                .or_insert_with(|| Binding::Prefix(HashMap::new()));
+            if let Binding::Action(_) = binding {
+                *binding = Binding::Prefix(HashMap::new());
+            }
+            let Binding::Prefix(next) = binding else {
+                unreachable!();
+            };
+            level = next;
+        }
+
+        level.insert(*last, Binding::Action(action));
+    }
+
+    // The action a single chord runs by itself.
+    pub fn lookup(&self, event: &KeyEvent) -> Option<Action> {
+        match self.bindings.get(&(event.code, event.modifiers)) {
+            Some(Binding::Action(action)) => Some(*action),
+            _ => None,
+        }
+    }
+
+    pub fn resolve(&self, sequence: &[Chord]) -> Resolved {
+        let mut level = &self.bindings;
+
+        for (i, chord) in sequence.iter().enumerate() {
+            match level.get(chord) {
+                Some(Binding::Action(action)) if i + 1 == sequence.len() => {
+                    return Resolved::Action(*action)
+                }
+                Some(Binding::Prefix(next)) => level = next,
+                _ => return Resolved::Undefined,
+            }
+        }
+
+        Resolved::Prefix
+    }
+
+    pub fn chords_for(&self, action: Action) -> Vec<String> {
+        let mut chords = Vec::new();
+        sequences_for(&self.bindings, action, &mut Vec::new(), &mut chords);
+        chords.sort();
+        chords
+    }
+}
+
+// Prefixes left leading nowhere go too.
+fn unbind(level: &mut HashMap<Chord, Binding>, action: Action) {
+    level.retain(|_, binding| match binding {
+        Binding::Action(a) => *a != action,
+        Binding::Prefix(next) => {
+            unbind(next, action);
+            !next.is_empty()
+        }
+    });
+}
+
+fn sequences_for(
+    level: &HashMap<Chord, Binding>,
+    action: Action,
+    prefix: &mut Vec<Chord>,
+    found: &mut Vec<String>,
+) {
+    for (chord, binding) in level {
+        prefix.push(*chord);
+        match binding {
+            Binding::Action(a) if *a == action => found.push(format_sequence(prefix)),
+            Binding::Action(_) => {}
+            Binding::Prefix(next) => sequences_for(next, action, prefix, found),
+        }
+        prefix.pop();
+    }
+}
+
+pub fn format_sequence(sequence: &[Chord]) -> String {
+    sequence
+        .iter()
+        .map(|chord| format_chord(*chord))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Chords separated by spaces, e.g. "ctrl+k ctrl+s".
+pub fn parse_sequence(sequence: &str) -> Option<Vec<Chord>> {
+    let chords: Vec<Chord> = sequence
+        .split_whitespace()
+        .map(parse_chord)
+        .collect::<Option<_>>()?;
+    (!chords.is_empty()).then_some(chords)
+}
+
+// The inverse of parse_chord, in the same spelling.
+pub fn format_chord((code, modifiers): Chord) -> String {
+    let mut parts = Vec::new();
+    for (modifier, name) in [
+        (KeyModifiers::CONTROL, "ctrl"),
+        (KeyModifiers::ALT, "alt"),
+        (KeyModifiers::SHIFT, "shift"),
+    ] {
+        if modifiers.contains(modifier) {
+            parts.push(name.to_string());
+        }
+    }
+
+    parts.push(match code {
+        KeyCode::Esc => "esc".to_string(),
+        KeyCode::Enter => "enter".to_string(),
+        KeyCode::Backspace => "backspace".to_string(),
+        KeyCode::Tab => "tab".to_string(),
+        KeyCode::Up => "up".to_string(),
+        KeyCode::Down => "down".to_string(),
+        KeyCode::Left => "left".to_string(),
+        KeyCode::Right => "right".to_string(),
+        KeyCode::Char(' ') => "space".to_string(),
+        KeyCode::F(n) => format!("f{n}"),
+        KeyCode::Char(c) => c.to_ascii_lowercase().to_string(),
+        other => format!("{other:?}").to_ascii_lowercase(),
+    });
+
+    parts.join("+")
+}
+
+// Parses chords like "ctrl+q", "alt+shift+s" or "esc".
+pub fn parse_chord(chord: &str) -> Option<Chord> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut parts: Vec<&str> = chord.split('+').collect();
+    let key = parts.pop()?;
+
+    for part in parts {
+        modifiers |= match part.to_ascii_lowercase().as_str() {
+            "ctrl" | "control" => KeyModifiers::CONTROL,
+            "alt" => KeyModifiers::ALT,
+            "shift" => KeyModifiers::SHIFT,
+            _ => return None,
+        };
+    }
+
+    let code = match key.to_ascii_lowercase().as_str() {
+        "esc" | "escape" => KeyCode::Esc,
+        "enter" | "return" => KeyCode::Enter,
+        "backspace" => KeyCode::Backspace,
+        "delete" | "del" => KeyCode::Delete,
+        "tab" => KeyCode::Tab,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "space" => KeyCode::Char(' '),
+        function if function.len() > 1 && function.starts_with('f') => {
+            KeyCode::F(function[1..].parse().ok()?)
+        }
+        _ => {
+            let mut chars = key.chars();
+            let c = chars.next()?;
+            if chars.next().is_some() {
+                return None;
+            }
+
+            // Terminals report shifted letters in upper case.
+            if modifiers.contains(KeyModifiers::SHIFT) {
+                KeyCode::Char(c.to_ascii_uppercase())
+            } else {
+                KeyCode::Char(c.to_ascii_lowercase())
+            }
+        }
+    };
+
+    Some((code, modifiers))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_action_round_trips_through_its_name() {
+        for action in Action::all() {
+            let name = action.name();
+            assert_eq!(Action::from_name(name), Some(action), "{name}");
+            assert!(
+                name.chars().all(|c| c.is_ascii_lowercase() || c == '-'),
+                "{name}"
+            );
+        }
+
+        let mut names: Vec<&str> = Action::all().map(Action::name).collect();
+        names.sort_unstable();
+        names.dedup();
+        assert_eq!(names.len(), ACTION_NAMES.len());
+        assert_eq!(Action::from_name("no-such-action"), None);
+    }
+
+    #[test]
+    fn every_action_round_trips_through_serde_by_its_name() {
+        for action in Action::all() {
+            let value = serde_json::to_value(action).unwrap();
+            assert_eq!(value, serde_json::Value::from(action.name()));
+            assert_eq!(serde_json::from_value::<Action>(value).unwrap(), action);
+        }
+
+        let error = serde_json::from_str::<Action>(r#""no-such-action""#).unwrap_err();
+        assert!(error.to_string().contains("`no-such-action`"), "{error}");
+    }
+
+    #[test]
+    fn every_default_binding_parses_back() {
+        let keymap = Keymap::default();
+        for action in Action::all() {
+            for sequence in keymap.chords_for(action) {
+                let chords = parse_sequence(&sequence).unwrap_or_else(|| panic!("{sequence}"));
+                assert_eq!(format_sequence(&chords), sequence);
+                assert!(
+                    matches!(keymap.resolve(&chords), Resolved::Action(a) if a == action),
+                    "{sequence}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn parses_the_navigation_keys() {
+        for (name, code) in [
+            ("home", KeyCode::Home),
+            ("end", KeyCode::End),
+            ("pageup", KeyCode::PageUp),
+            ("pagedown", KeyCode::PageDown),
+        ] {
+            assert_eq!(parse_chord(name), Some((code, KeyModifiers::NONE)));
+            let chord = (code, KeyModifiers::CONTROL);
+            assert_eq!(parse_chord(&format_chord(chord)), Some(chord));
+        }
+        assert_eq!(
+            parse_chord("Ctrl+PageDown"),
+            Some((KeyCode::PageDown, KeyModifiers::CONTROL))
+        );
+    }
+}
@@ -0,0 +1,116 @@
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Language {
+    Rust,
+    #[cfg(feature = "toml")]
+    Toml,
+    #[cfg(feature = "json")]
+    Json,
+    #[cfg(feature = "c")]
+    C,
+    PlainText,
+}
+
+// In the order the language menu offers them. Grammars other than Rust's are
+// cargo features, so a build without one has no such language at all.
+pub const LANGUAGES: &[Language] = &[
+    Language::Rust,
+    #[cfg(feature = "toml")]
+    Language::Toml,
+    #[cfg(feature = "json")]
+    Language::Json,
+    #[cfg(feature = "c")]
+    Language::C,
+    Language::PlainText,
+];
+
+impl Language {
+    // Anything unrecognized is plain text, which is never parsed.
+    pub fn from_extension(extension: &str) -> Language {
+        match extension {
+            "rs" => Language::Rust,
+            #[cfg(feature = "toml")]
+            "toml" => Language::Toml,
+            #[cfg(feature = "json")]
+            "json" => Language::Json,
+            #[cfg(feature = "c")]
+            "c" | "h" => Language::C,
+            _ => Language::PlainText,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Language::Rust => "Rust",
+            #[cfg(feature = "toml")]
+            Language::Toml => "TOML",
+            #[cfg(feature = "json")]
+            Language::Json => "JSON",
+            #[cfg(feature = "c")]
+            Language::C => "C",
+            Language::PlainText => "Plain text",
+        }
+    }
+
+    // The name it's stored under, e.g. in the positions file; one word.
+    pub fn key(self) -> &'static str {
+        match self {
+            Language::Rust => "rust",
+            #[cfg(feature = "toml")]
+            Language::Toml => "toml",
+            #[cfg(feature = "json")]
+            Language::Json => "json",
+            #[cfg(feature = "c")]
+            Language::C => "c",
+            Language::PlainText => "plain",
+        }
+    }
+
+    // None for ones this build doesn't have.
+    pub fn from_key(key: &str) -> Option<Language> {
+        LANGUAGES
+            .iter()
+            .copied()
+            .find(|language| language.key() == key)
+    }
+
+    // What the language starts a line comment with, if it has them.
+    pub fn line_comment(self) -> Option<&'static str> {
+        match self {
+            Language::Rust => Some("//"),
+            #[cfg(feature = "toml")]
+            Language::Toml => Some("#"),
+            #[cfg(feature = "json")]
+            Language::Json => None,
+            #[cfg(feature = "c")]
+            Language::C => Some("//"),
+            Language::PlainText => None,
+        }
+    }
+
+    pub fn highlights(self) -> Option<&'static str> {
+        match self {
+            Language::Rust => Some(tree_sitter_rust::HIGHLIGHTS_QUERY),
+            #[cfg(feature = "toml")]
+            Language::Toml => Some(tree_sitter_toml_ng::HIGHLIGHTS_QUERY),
+            #[cfg(feature = "json")]
+            Language::Json => Some(tree_sitter_json::HIGHLIGHTS_QUERY),
+            #[cfg(feature = "c")]
+            Language::C => Some(tree_sitter_c::HIGHLIGHT_QUERY),
+            Language::PlainText => None,
+        }
+    }
+
+    // Plain text has no grammar, so it's never parsed.
+    pub fn grammar(self) -> Option<tree_sitter::Language> {
+        match self {
+            Language::Rust => Some(tree_sitter_rust::LANGUAGE.into()),
+            #[cfg(feature = "toml")]
+            Language::Toml => Some(tree_sitter_toml_ng::LANGUAGE.into()),
+            #[cfg(feature = "json")]
+            Language::Json => Some(tree_sitter_json::LANGUAGE.into()),
+            #[cfg(feature = "c")]
+            Language::C => Some(tree_sitter_c::LANGUAGE.into()),
+            Language::PlainText => None,
+        }
+    }
+}
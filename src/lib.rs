@@ -0,0 +1,42 @@
+// The editor behind the shark binary. It runs on anything implementing
+// terminal::Terminal, so it can be driven without a real terminal, e.g. by
+// terminal::Memory in tests, or as a pane of another program through
+// widget::EditorView.
+mod brackets;
+pub mod buffer;
+mod calc;
+mod check;
+mod clipboard;
+mod columns;
+pub mod config;
+mod diagnostics;
+mod diff;
+mod document;
+pub mod editor;
+pub mod highlight;
+pub mod keymap;
+mod language;
+mod overlay;
+mod pairs;
+mod paths;
+mod positions;
+pub mod project;
+mod prompt;
+pub mod recovery;
+mod references;
+mod reflow;
+mod replace;
+#[cfg(unix)]
+pub mod rpc;
+mod screen;
+mod search;
+mod stats;
+mod syntax_errors;
+mod task;
+pub mod terminal;
+pub mod theme;
+pub mod undo;
+mod viewport;
+pub mod widget;
+mod wrap;
+pub mod write;
@@ -1,39 +1,562 @@
 use clap::Parser;
 use ropey::Rope;
-use std::{fs::File, io};
-use tracing::info;
+use std::{
+    collections::BTreeMap,
+    env,
+    fs::File,
+    io::{self, BufRead, IsTerminal, Write},
+    panic::{self, AssertUnwindSafe},
+    process,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
 
-mod editor;
+#[cfg(unix)]
+use shark::rpc;
+use shark::{
+    buffer, config, editor, highlight, keymap, project, recovery, terminal, theme, undo, write,
+};
+
+const TAB_WIDTHS: std::ops::RangeInclusive<i64> = 1..=16;
+const DEFAULT_TAB_WIDTH: u16 = 4;
+
+// The filename that stands for stdin.
+const STDIN: &str = "-";
 
 #[derive(clap::Parser, Debug)]
 struct Args {
-    filename: String,
+    /// Files to open; `-` reads a buffer from stdin, and none opens a scratch
+    /// buffer
+    filenames: Vec<String>,
+
+    /// Print every action with its default key chords and exit
+    #[arg(long)]
+    list_actions: bool,
+
+    /// Never ask for confirmation before inserting large pastes
+    #[arg(long)]
+    no_paste_guard: bool,
+
+    /// Pastes larger than this many kilobytes ask for confirmation
+    /// [default: 100]
+    #[arg(long)]
+    paste_max_kb: Option<usize>,
+
+    /// Pastes with more than this many lines ask for confirmation
+    /// [default: 5000]
+    #[arg(long)]
+    paste_max_lines: Option<usize>,
+
+    /// Columns a tab is drawn with, and the indent stop Tab inserts spaces to
+    /// [default: 4]
+    #[arg(long, value_parser = clap::value_parser!(u16).range(TAB_WIDTHS))]
+    tab_width: Option<u16>,
+
+    /// Make Tab insert a tab character instead of spaces
+    #[arg(long)]
+    hard_tabs: bool,
+
+    /// Column limit used when reflowing paragraphs
+    #[arg(long, default_value_t = 80)]
+    text_width: usize,
+
+    /// Lines kept in view above and below the cursor; capped to fit the
+    /// screen [default: 3]
+    #[arg(long)]
+    scroll_margin: Option<usize>,
+
+    /// Lines of the previous screen Page Up / Page Down keep in view; must be
+    /// smaller than the screen height [default: 0]
+    #[arg(long)]
+    page_overlap: Option<usize>,
+
+    /// Page Up / Page Down move half a screen
+    #[arg(long)]
+    half_page: bool,
+
+    /// Character repeated for separators in plain text and code comments
+    #[arg(long, default_value_t = '─')]
+    separator: char,
+
+    /// Refuse to open or save files outside the project root
+    #[arg(long)]
+    restrict: bool,
+
+    /// Show per-frame render timings in the top-right corner
+    #[arg(long)]
+    debug_overlay: bool,
+
+    /// Undo entries kept per buffer before the oldest are dropped
+    #[arg(long, default_value_t = 100)]
+    undo_max_entries: usize,
+
+    /// Approximate memory, in megabytes, undo history may use per buffer
+    #[arg(long, default_value_t = 64)]
+    undo_max_mb: usize,
+
+    /// Whether to use colors; `auto` turns them off when NO_COLOR is set
+    #[arg(long, value_enum, default_value_t = theme::ColorMode::Auto)]
+    color: theme::ColorMode,
+
+    /// Syntax colors for a dark or light background; `auto` asks the terminal
+    #[arg(long, value_enum, default_value_t = theme::ThemeChoice::Auto)]
+    theme: theme::ThemeChoice,
+
+    /// Close typed brackets and quotes automatically, and wrap selections in them
+    #[arg(long, alias = "auto-quote")]
+    auto_pairs: bool,
+
+    /// Insert every unbound Alt+key character, for layouts that type with AltGr
+    #[arg(long)]
+    altgr_inserts: bool,
+
+    /// Don't briefly highlight text changed by pastes, undo and other commands
+    #[arg(long)]
+    no_flash: bool,
+
+    /// Don't flag right-to-left text on screen in the status bar
+    #[arg(long)]
+    no_rtl_warning: bool,
+
+    /// Number lines in the gutter by their distance from the cursor line
+    #[arg(long)]
+    relative_numbers: bool,
+
+    /// Copy a file to `<name>~` before saving over it
+    #[arg(long)]
+    backup: bool,
+
+    /// Strip trailing whitespace and fix the final newline when saving
+    #[arg(long)]
+    trim_trailing_whitespace_on_save: bool,
+
+    /// Wrap lines too long for the window onto the next rows
+    #[arg(long)]
+    wrap: bool,
+
+    /// Refuse edits and saving; files that aren't writable always open this
+    /// way
+    #[arg(short = 'R', long)]
+    readonly: bool,
+
+    /// Leave the mouse to the terminal instead of clicking and scrolling with it
+    #[arg(long)]
+    no_mouse: bool,
+
+    /// Send as little as possible to the terminal, e.g. over a slow SSH link
+    #[arg(long)]
+    low_bandwidth: bool,
+
+    /// Run `cargo check` in the background after every save
+    #[arg(long)]
+    check_on_save: bool,
+
+    /// Serve line-delimited JSON requests from scripts on this unix socket
+    #[cfg(unix)]
+    #[arg(long, value_name = "SOCKET")]
+    listen: Option<std::path::PathBuf>,
+
+    /// Write a debug log of keys, edits, scrolling and frame timings to this
+    /// file, replacing it; SHARK_LOG=PATH does the same
+    #[arg(long, value_name = "PATH")]
+    log: Option<std::path::PathBuf>,
+
+    /// Bind an action to a key chord, e.g. --bind quit=ctrl+x, or to a sequence
+    /// of them, e.g. --bind save="ctrl+k ctrl+s"
+    #[arg(long = "bind", value_name = "ACTION=CHORD")]
+    bindings: Vec<String>,
+}
+
+// Bindings from the config file go first so the ones on the command line win.
+// A bad one in the file is reported and skipped instead of stopping startup.
+fn build_keymap(
+    bindings: &[String],
+    configured: &BTreeMap<String, String>,
+    problems: &mut Vec<String>,
+) -> Result<keymap::Keymap, String> {
+    let mut keymap = keymap::Keymap::default();
+
+    for (name, chords) in configured {
+        if let Err(message) = bind(&mut keymap, name, chords) {
+            problems.push(format!("config: {message}"));
+        }
+    }
+
+    for binding in bindings {
+        let (name, chords) = binding
+            .split_once('=')
+            .ok_or_else(|| format!("invalid binding '{binding}', expected ACTION=CHORD"))?;
+        bind(&mut keymap, name, chords)?;
+    }
+
+    Ok(keymap)
+}
+
+fn bind(keymap: &mut keymap::Keymap, name: &str, chords: &str) -> Result<(), String> {
+    let action = keymap::Action::from_name(name)
+        .ok_or_else(|| format!("unknown action '{name}', see --list-actions"))?;
+    let sequence =
+        keymap::parse_sequence(chords).ok_or_else(|| format!("invalid key chord '{chords}'"))?;
+
+    keymap.bind(&sequence, action);
+    Ok(())
+}
+
+// Colors for highlight captures, e.g. `keyword` or `function.macro`. A capture
+// colors everything sorted into the same group as it, so `function.macro` sets
+// the color of all functions.
+fn build_theme(
+    mut theme: theme::Theme,
+    configured: &BTreeMap<String, String>,
+    problems: &mut Vec<String>,
+) -> theme::Theme {
+    for (name, color) in configured {
+        let Some(group) = highlight::group(name) else {
+            problems.push(format!(
+                "config: '{name}' isn't a highlight capture with a color"
+            ));
+            continue;
+        };
+        match config::parse_color(color) {
+            Some(color) => theme.set_syntax(group, color),
+            None => problems.push(format!("config: invalid color '{color}' for {name}")),
+        }
+    }
+
+    theme
+}
+
+fn list_actions() {
+    let keymap = keymap::Keymap::default();
+
+    for action in keymap::Action::all() {
+        let chords = keymap.chords_for(action).join(", ");
+        println!(
+            "{:<26} {:<28} {}",
+            action.name(),
+            chords,
+            action.description()
+        );
+    }
+}
+
+// crossterm reads events from /dev/tty when stdin is redirected, but cursor
+// queries and drawing go through stdout, so that one has to be a terminal.
+fn check_tty() -> Result<(), &'static str> {
+    if !io::stdout().is_terminal() {
+        return Err("shark requires an interactive terminal, but stdout is not a tty");
+    }
+
+    #[cfg(unix)]
+    if !io::stdin().is_terminal() && File::open("/dev/tty").is_err() {
+        return Err(
+            "shark requires an interactive terminal, but no controlling tty is available for input",
+        );
+    }
+
+    Ok(())
+}
+
+fn ask_restore(filename: &str) -> Option<Rope> {
+    let (path, modified) = recovery::find(filename)?;
+
+    eprint!(
+        "Found unsaved changes to {} from {}. Restore them? [y/N] ",
+        filename,
+        recovery::format_age(modified)
+    );
+    io::stderr().flush().ok()?;
+
+    // From the terminal, since stdin may be the buffer `-` has already read,
+    // or a pipe the answer would be taken from.
+    #[cfg(unix)]
+    let input = File::open("/dev/tty").ok()?;
+    #[cfg(not(unix))]
+    let input = io::stdin();
+
+    let mut answer = String::new();
+    io::BufReader::new(input).read_line(&mut answer).ok()?;
+
+    if !answer.trim().eq_ignore_ascii_case("y") {
+        return None;
+    }
+
+    Rope::from_reader(File::open(path).ok()?).ok()
+}
+
+fn termination_flag() -> Arc<AtomicBool> {
+    let flag = Arc::new(AtomicBool::new(false));
+
+    #[cfg(unix)]
+    for signal in [signal_hook::consts::SIGTERM, signal_hook::consts::SIGHUP] {
+        let _ = signal_hook::flag::register(signal, Arc::clone(&flag));
+    }
+
+    flag
+}
+
+fn run(editor: &mut editor::Editor, terminated: &AtomicBool) -> io::Result<()> {
+    while !terminated.load(Ordering::Relaxed) {
+        if !editor.poll(editor.idle_timeout())? {
+            editor.tick()?;
+        } else if !editor.step()? {
+            return Ok(());
+        }
+    }
+
+    editor.dump_recovery();
+    Ok(())
 }
 
 fn main() -> std::io::Result<()> {
     let stdout = io::stdout();
     let args = Args::parse();
-    /*
+
+    if args.list_actions {
+        list_actions();
+        return Ok(());
+    }
+
+    if let Err(message) = check_tty() {
+        eprintln!("{message}");
+        process::exit(1);
+    }
+
+    // Reported once the editor is up, since nothing printed here would stay on
+    // screen.
+    let mut problems = Vec::new();
+    let config = config::load().unwrap_or_else(|message| {
+        problems.push(format!("config: {message}"));
+        config::Config::default()
+    });
+
+    let keymap = match build_keymap(&args.bindings, &config.keybindings, &mut problems) {
+        Ok(keymap) => keymap,
+        Err(message) => {
+            eprintln!("{message}");
+            process::exit(2);
+        }
+    };
+
+    // Nothing is logged, or written, unless asked for.
+    let log = args.log.clone().or_else(|| {
+        env::var_os("SHARK_LOG")
+            .filter(|path| !path.is_empty())
+            .map(Into::into)
+    });
+    if let Some(path) = log {
+        let file = match File::create(&path) {
+            Ok(file) => file,
+            Err(err) => {
+                eprintln!("failed to create the log {}: {err}", path.display());
+                process::exit(1);
+            }
+        };
         let subscriber = tracing_subscriber::fmt()
-            .with_writer(File::options().write(true).open("latest.log").unwrap())
+            .with_writer(Mutex::new(file))
             .with_ansi(false)
+            .with_max_level(tracing::Level::DEBUG)
             .finish();
+        tracing::subscriber::set_global_default(subscriber).expect("no other subscriber is set");
+    }
+    let project = match project::Project::detect(args.restrict) {
+        Ok(project) => project,
+        Err(err) => {
+            eprintln!("failed to determine the project root: {err}");
+            process::exit(1);
+        }
+    };
+
+    for filename in args.filenames.iter().filter(|f| *f != STDIN) {
+        if let Err(message) = project.check(std::path::Path::new(filename)) {
+            eprintln!("refusing to open {filename}: {message}");
+            process::exit(1);
+        }
+    }
+
+    // Piped in text has to be read before anything else looks at stdin, and
+    // can't be read again, so only the first `-` gets it.
+    let mut stdin = None;
+    if args.filenames.iter().any(|f| f == STDIN) {
+        match buffer::read_stdin() {
+            Ok(rope) => stdin = Some(rope),
+            Err(err) => {
+                eprintln!("failed to read stdin: {err}");
+                process::exit(1);
+            }
+        }
+    }
+
+    // Files are read once the terminal is set up; see Editor::load_buffers.
+    let mut unread = Vec::new();
+    let mut buffers: Vec<buffer::Buffer> = args
+        .filenames
+        .iter()
+        .enumerate()
+        .map(|(i, filename)| {
+            if filename == STDIN {
+                let rope = stdin.take().unwrap_or_default();
+                let mut buffer = buffer::Buffer::new(rope, None);
+                buffer.modified = buffer.rope.len_chars() > 0;
+                return buffer;
+            }
 
-        tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
-    */
-    let file = File::open(&args.filename).unwrap();
-    let rope = Rope::from_reader(&file).unwrap();
+            match ask_restore(filename) {
+                Some(rope) => {
+                    let mut buffer = buffer::Buffer::new(rope, Some(filename.clone()));
+                    buffer.modified = true;
+                    buffer
+                }
+                None => {
+                    unread.push(i);
+                    buffer::Buffer::new(Rope::new(), Some(filename.clone()))
+                }
+            }
+        })
+        .collect();
+    for buffer in &mut buffers {
+        buffer.read_only = args.readonly
+            || buffer
+                .filename
+                .as_ref()
+                .is_some_and(|filename| !write::writable(std::path::Path::new(filename)));
+    }
+
+    #[cfg(unix)]
+    let server = match args.listen.as_deref().map(rpc::Server::bind).transpose() {
+        Ok(server) => server,
+        Err(err) => {
+            eprintln!(
+                "failed to listen on {}: {err}",
+                args.listen.unwrap().display()
+            );
+            process::exit(1);
+        }
+    };
 
-    let mut editor = editor::Editor::new(stdout, rope, args.filename);
-    editor.init().unwrap();
+    let terminal = Box::new(terminal::Crossterm::new(stdout));
+    let mut editor = editor::Editor::new(terminal, buffers, project);
+    #[cfg(unix)]
+    if let Some(server) = server {
+        editor.set_rpc(server);
+    }
+    editor.set_keymap(keymap);
+    editor.set_text_width(args.text_width);
+    let tab_width = match (args.tab_width, config.editor.tab_width) {
+        (Some(width), _) => width,
+        (None, Some(width)) if TAB_WIDTHS.contains(&i64::from(width)) => width,
+        (None, Some(width)) => {
+            problems.push(format!("config: tab_width {width} is outside 1 to 16"));
+            DEFAULT_TAB_WIDTH
+        }
+        (None, None) => DEFAULT_TAB_WIDTH,
+    };
+    editor.set_tabs(
+        tab_width.into(),
+        args.hard_tabs || config.editor.use_spaces == Some(false),
+    );
+    editor.set_line_numbers(config.editor.show_line_numbers.unwrap_or(true));
+    editor.set_separator(args.separator);
+    let page_overlap = args.page_overlap.or(config.editor.page_overlap_lines);
+    let half_page = args.half_page || config.editor.half_page == Some(true);
+    editor.set_paging(page_overlap.unwrap_or(0), half_page);
+    editor.set_wheel_lines(config.editor.wheel_scroll_lines.unwrap_or(3));
+    editor.set_scroll_margin(
+        args.scroll_margin
+            .or(config.editor.scroll_margin)
+            .unwrap_or(3),
+    );
+    editor.set_debug_overlay(args.debug_overlay);
+    editor.set_check_on_save(args.check_on_save);
+    editor.set_low_bandwidth(args.low_bandwidth);
+    editor.set_mouse(!args.no_mouse);
+    editor.set_backup(args.backup || config.editor.backup == Some(true));
+    editor.set_trim_on_save(
+        args.trim_trailing_whitespace_on_save
+            || config.editor.trim_trailing_whitespace_on_save == Some(true),
+    );
+    editor.set_wrap(args.wrap || config.editor.wrap == Some(true));
+    editor.set_auto_pairs(args.auto_pairs || config.editor.auto_pairs == Some(true));
+    editor.set_flash(!args.no_flash);
+    editor.set_rtl_warning(!args.no_rtl_warning);
+    editor.set_relative_numbers(args.relative_numbers);
+    editor.set_altgr_inserts(args.altgr_inserts || config.editor.altgr_inserts == Some(true));
+    editor.set_theme(build_theme(
+        theme::Theme::new(args.color, args.theme),
+        &config.theme,
+        &mut problems,
+    ));
+    editor.set_undo_limits(undo::UndoLimits {
+        max_entries: args.undo_max_entries,
+        max_bytes: args.undo_max_mb.saturating_mul(1024 * 1024),
+    });
+    let guard = editor::PasteGuard::default();
+    let paste_guard = !args.no_paste_guard && config.editor.paste_guard != Some(false);
+    editor.set_paste_guard(paste_guard.then(|| {
+        editor::PasteGuard {
+            max_bytes: args
+                .paste_max_kb
+                .or(config.editor.paste_max_kb)
+                .map_or(guard.max_bytes, |kb| kb.saturating_mul(1024)),
+            max_lines: args
+                .paste_max_lines
+                .or(config.editor.paste_max_lines)
+                .unwrap_or(guard.max_lines),
+        }
+    }));
+    editor.init()?;
+    // Only known once the terminal is, and a page always has to move.
+    let height = editor.page_height();
+    if let Some(overlap) = page_overlap.filter(|&overlap| overlap >= height) {
+        let source = if args.page_overlap.is_some() {
+            "--page-overlap"
+        } else {
+            "config: page_overlap_lines"
+        };
+        problems.push(format!(
+            "{source} {overlap} isn't smaller than the {height}-line screen"
+        ));
+        editor.set_paging(0, half_page);
+    }
+    if let Some(problem) = problems.first() {
+        editor.report(match problems.len() {
+            1 => problem.clone(),
+            n => format!("{problem} (and {} more)", n - 1),
+        });
+    }
 
-    loop {
-        if !editor.step()? {
-            break;
+    match editor.load_buffers(&unread) {
+        Ok(true) => {}
+        Ok(false) => return editor.close(),
+        Err(error) => {
+            let _ = editor.close();
+            eprintln!("failed to open {error}");
+            process::exit(1);
         }
     }
 
-    editor.close().unwrap();
+    let terminated = termination_flag();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| run(&mut editor, &terminated)));
 
-    Ok(())
+    match result {
+        Ok(result) => {
+            editor.remember_positions();
+            let closed = editor.close();
+
+            if result.is_err() {
+                editor.dump_recovery();
+            } else if !terminated.load(Ordering::Relaxed) {
+                editor.remove_recovery();
+            }
+
+            result.and(closed)
+        }
+        Err(panic) => {
+            editor.dump_recovery();
+            let _ = editor.close();
+            panic::resume_unwind(panic);
+        }
+    }
 }
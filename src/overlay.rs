@@ -0,0 +1,23 @@
+use std::io;
+
+use crossterm::event::KeyEvent;
+
+use crate::editor::Editor;
+
+// Modal UI drawn over the buffer, kept on a stack by the editor. Keys go to the
+// topmost overlay first, Esc closes just that one, and the buffer only sees a
+// key once no overlay is open or every one of them has passed it on.
+pub trait Overlay {
+    // Returns whether the key was used; one that wasn't goes on to whatever is
+    // underneath. Esc never gets here.
+    fn handle_key(&mut self, editor: &mut Editor, key: KeyEvent) -> io::Result<bool>;
+
+    // Draws over the frame underneath, which has just been drawn.
+    fn render(&self, editor: &mut Editor) -> io::Result<()>;
+
+    // Asked after every key; the overlay is closed once it says so.
+    fn wants_dismiss(&self) -> bool;
+
+    // Runs once when the overlay is closed, whether by Esc or by itself.
+    fn dismissed(&mut self, _editor: &mut Editor) {}
+}
@@ -0,0 +1,140 @@
+use tree_sitter::{Node, Tree};
+
+// What auto-pairing closes, by opener.
+pub const PAIRS: [(char, char); 5] = [('(', ')'), ('[', ']'), ('{', '}'), ('"', '"'), ('\'', '\'')];
+
+// Rust nodes a lifetime can be typed straight into.
+const LIFETIME_PARENTS: [&str; 5] = [
+    "type_parameters",
+    "type_arguments",
+    "trait_bounds",
+    "where_clause",
+    "reference_type",
+];
+
+const STRINGS: [&str; 3] = ["string_literal", "raw_string_literal", "char_literal"];
+const COMMENTS: [&str; 2] = ["line_comment", "block_comment"];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum QuoteContext {
+    Code,
+    String,
+    Comment,
+}
+
+// Where a quote typed at `byte` would land. The tree is trusted unless it has
+// an error around the cursor (typically the unterminated string being typed),
+// in which case quotes on the line before the cursor are counted instead.
+pub fn quote_context(tree: &Tree, byte: usize, line_before_cursor: &str) -> QuoteContext {
+    let Some(node) = tree
+        .root_node()
+        .descendant_for_byte_range(byte.saturating_sub(1), byte)
+    else {
+        return QuoteContext::Code;
+    };
+
+    let mut current = Some(node);
+    let mut errors = false;
+
+    while let Some(node) = current {
+        if COMMENTS.contains(&node.kind()) && contains(node, byte, node.kind() == "line_comment") {
+            return QuoteContext::Comment;
+        }
+        if STRINGS.contains(&node.kind()) && contains(node, byte, false) {
+            return QuoteContext::String;
+        }
+        errors |= node.is_error() || node.is_missing();
+        current = node.parent();
+    }
+
+    if errors && open_quotes(line_before_cursor) {
+        QuoteContext::String
+    } else {
+        QuoteContext::Code
+    }
+}
+
+pub fn closer(open: char) -> Option<char> {
+    PAIRS
+        .into_iter()
+        .find(|&(o, _)| o == open)
+        .map(|(_, close)| close)
+}
+
+// Whether `byte` sits right before the closing quote of a string, or of a
+// char literal for `'`.
+pub fn closes_string(tree: &Tree, byte: usize, quote: char) -> bool {
+    let kind = if quote == '\'' {
+        "char_literal"
+    } else {
+        "string_literal"
+    };
+    let mut current = tree.root_node().descendant_for_byte_range(byte, byte + 1);
+
+    while let Some(node) = current {
+        if node.kind() == kind {
+            return node.end_byte() == byte + 1 && !node.has_error();
+        }
+        current = node.parent();
+    }
+
+    false
+}
+
+// A quote right after a letter or digit is an apostrophe, or the end of
+// something else, so it isn't closed. The prefix of a Rust raw, byte or C
+// string is the exception.
+pub fn after_word(line_before_cursor: &str, quote: char) -> bool {
+    let word: String = line_before_cursor
+        .chars()
+        .rev()
+        .take_while(|c| c.is_alphanumeric() || *c == '_')
+        .collect();
+    let prefix = quote == '"' && ["r", "b", "rb", "c", "rc"].contains(&word.as_str());
+    !word.is_empty() && !prefix
+}
+
+// Whether a `'` typed at `byte` of Rust starts a lifetime rather than a char
+// literal: right after `&` or `<`, or anywhere among generics and bounds.
+pub fn expects_lifetime(tree: &Tree, byte: usize, line_before_cursor: &str) -> bool {
+    if line_before_cursor.ends_with(['&', '<']) {
+        return true;
+    }
+
+    let mut current = tree
+        .root_node()
+        .descendant_for_byte_range(byte.saturating_sub(1), byte);
+    while let Some(node) = current {
+        if LIFETIME_PARENTS.contains(&node.kind()) {
+            return true;
+        }
+        current = node.parent();
+    }
+    false
+}
+
+// The cursor at the very end of a line comment is still inside it, while one
+// right after a closing quote or `*/` is not.
+fn contains(node: Node, byte: usize, inclusive_end: bool) -> bool {
+    node.start_byte() < byte && (byte < node.end_byte() || inclusive_end && byte == node.end_byte())
+}
+
+fn open_quotes(text: &str) -> bool {
+    let mut open = false;
+    let mut escaped = false;
+
+    for c in text.chars() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+
+        match c {
+            '\\' if open => escaped = true,
+            '"' => open = !open,
+            _ => {}
+        }
+    }
+
+    open
+}
@@ -0,0 +1,96 @@
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+pub const ELLIPSIS: &str = "…";
+
+// Abbreviates the middle components of a path so it fits in `max_width` columns,
+// e.g. `/home/u/…/deeply/nested/file.rs`. The file name is kept whenever possible.
+pub fn shorten(path: &str, max_width: usize) -> String {
+    if path.width() <= max_width {
+        return path.to_string();
+    }
+
+    let components: Vec<&str> = path.split('/').collect();
+    let file_name = components[components.len() - 1];
+    let mut tail = vec![file_name];
+    let mut head: Vec<&str> = Vec::new();
+    let fits = |head: &[&str], tail: &[&str]| {
+        let head_width: usize = head.iter().map(|c| c.width() + 1).sum();
+        let tail_width: usize = tail.iter().map(|c| c.width() + 1).sum();
+        head_width + ELLIPSIS.width() + tail_width <= max_width
+    };
+
+    if !fits(&head, &tail) {
+        return truncate_start(file_name, max_width);
+    }
+
+    let (mut front, mut back) = (0, components.len() - 1);
+
+    // Alternate between leading and trailing components so both ends stay recognizable.
+    while front < back {
+        let mut grew = false;
+
+        if front < back && fits(&[head.as_slice(), &[components[front]]].concat(), &tail) {
+            head.push(components[front]);
+            front += 1;
+            grew = true;
+        }
+
+        if front < back - 1 {
+            let mut candidate = vec![components[back - 1]];
+            candidate.extend_from_slice(&tail);
+
+            if fits(&head, &candidate) {
+                tail = candidate;
+                back -= 1;
+                grew = true;
+            }
+        }
+
+        if !grew {
+            break;
+        }
+    }
+
+    if front == back {
+        return path.to_string();
+    }
+
+    let mut shortened = String::new();
+    for component in head {
+        shortened.push_str(component);
+        shortened.push('/');
+    }
+    shortened.push_str(ELLIPSIS);
+    for component in tail {
+        shortened.push('/');
+        shortened.push_str(component);
+    }
+
+    shortened
+}
+
+// Keeps the end of `text`, cutting whole graphemes from the front.
+pub fn truncate_start(text: &str, max_width: usize) -> String {
+    if text.width() <= max_width {
+        return text.to_string();
+    }
+
+    if max_width == 0 {
+        return String::new();
+    }
+
+    let budget = max_width.saturating_sub(ELLIPSIS.width());
+    let mut width = 0;
+    let mut start = text.len();
+
+    for (i, grapheme) in text.grapheme_indices(true).rev() {
+        width += grapheme.width();
+        if width > budget {
+            break;
+        }
+        start = i;
+    }
+
+    format!("{ELLIPSIS}{}", &text[start..])
+}
@@ -0,0 +1,119 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process,
+};
+
+use crate::language::Language;
+
+// Files remembered at most; the ones left longest ago go first.
+const MAX_ENTRIES: usize = 300;
+
+// Where the cursor and view were when a file was last left.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+    pub scroll: usize,
+    // The language it was set to, when that wasn't the one detected.
+    pub language: Option<Language>,
+}
+
+fn path() -> Option<PathBuf> {
+    match env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir).join("shark/positions")),
+        _ => {
+            env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share/shark/positions"))
+        }
+    }
+}
+
+fn key(filename: &str) -> Option<String> {
+    let path = fs::canonicalize(filename).ok()?;
+    path.to_str()
+        .filter(|path| !path.contains('\n'))
+        .map(str::to_string)
+}
+
+// One `line column scroll language path` per line, left longest ago first,
+// with - for the language when it wasn't overridden. Lines from before the
+// language was kept have no such field, and the path in its place. A file that
+// can't be read, or lines that don't parse, are the same as nothing there.
+fn read(path: &Path) -> Vec<(String, Position)> {
+    let Ok(text) = fs::read_to_string(path) else {
+        return Vec::new();
+    };
+
+    text.lines()
+        .filter_map(|entry| {
+            let mut fields = entry.splitn(4, ' ');
+            let mut number = || fields.next()?.parse().ok();
+            let (line, column, scroll) = (number()?, number()?, number()?);
+            let rest = fields.next()?;
+            let (language, file) = match rest.split_once(' ') {
+                Some(("-", file)) => (None, file),
+                Some((key, file)) => match Language::from_key(key) {
+                    Some(language) => (Some(language), file),
+                    None => (None, rest),
+                },
+                None => (None, rest),
+            };
+            let position = Position {
+                line,
+                column,
+                scroll,
+                language,
+            };
+            Some((file, position))
+        })
+        .filter(|(file, _)| !file.is_empty())
+        .map(|(file, position)| (file.to_string(), position))
+        .collect()
+}
+
+pub fn find(filename: &str) -> Option<Position> {
+    let key = key(filename)?;
+    read(&path()?)
+        .into_iter()
+        .rev()
+        .find(|(file, _)| *file == key)
+        .map(|(_, position)| position)
+}
+
+// Merges `files` into what's on disk, so other instances' entries survive, and
+// swaps the result in with a rename so none of them reads half a file. Called
+// while the editor is going down, so every step is best-effort.
+pub fn remember(files: &[(String, Position)]) {
+    let Some(path) = path() else {
+        return;
+    };
+    let files: Vec<(String, Position)> = files
+        .iter()
+        .filter_map(|(filename, position)| Some((key(filename)?, *position)))
+        .collect();
+    if files.is_empty() {
+        return;
+    }
+
+    let mut entries = read(&path);
+    entries.retain(|(file, _)| files.iter().all(|(key, _)| key != file));
+    entries.extend(files);
+    let excess = entries.len().saturating_sub(MAX_ENTRIES);
+    entries.drain(..excess);
+
+    let text: String = entries
+        .iter()
+        .map(|(file, p)| {
+            let language = p.language.map_or("-", Language::key);
+            format!("{} {} {} {language} {file}\n", p.line, p.column, p.scroll)
+        })
+        .collect();
+    let Some(dir) = path.parent() else {
+        return;
+    };
+    let _ = fs::create_dir_all(dir);
+    let temporary = dir.join(format!("positions.{}.tmp", process::id()));
+    if fs::write(&temporary, text).is_err() || fs::rename(&temporary, &path).is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+}
@@ -0,0 +1,198 @@
+use std::{
+    env, fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+const ROOT_MARKERS: [&str; 2] = [".git", "Cargo.toml"];
+
+pub struct Project {
+    pub root: PathBuf,
+    pub restrict: bool,
+}
+
+impl Project {
+    // The root is the nearest ancestor of the working directory containing a
+    // root marker, or the working directory itself when there is none.
+    pub fn detect(restrict: bool) -> io::Result<Self> {
+        let cwd = fs::canonicalize(env::current_dir()?)?;
+
+        Ok(Self {
+            root: root_of(&cwd),
+            restrict,
+        })
+    }
+
+    // Path as it should be shown to people: relative to the root when inside it.
+    pub fn relative(&self, path: &Path) -> PathBuf {
+        let resolved = canonicalize(path);
+
+        match resolved.strip_prefix(&self.root) {
+            Ok(relative) => relative.to_path_buf(),
+            Err(_) => resolved,
+        }
+    }
+
+    // Errors when restriction is on and `path` resolves outside the root,
+    // following symlinks and `..` components.
+    pub fn check(&self, path: &Path) -> Result<(), String> {
+        if !self.restrict {
+            return Ok(());
+        }
+
+        let resolved = canonicalize(path);
+        if resolved.starts_with(&self.root) {
+            Ok(())
+        } else {
+            Err(format!(
+                "{} is outside the project root {}",
+                path.display(),
+                self.root.display()
+            ))
+        }
+    }
+}
+
+fn root_of(dir: &Path) -> PathBuf {
+    dir.ancestors()
+        .find(|dir| ROOT_MARKERS.iter().any(|m| dir.join(m).exists()))
+        .unwrap_or(dir)
+        .to_path_buf()
+}
+
+// Like fs::canonicalize, but also works for files that don't exist yet by
+// resolving the nearest existing ancestor and normalizing the rest.
+pub fn canonicalize(path: &Path) -> PathBuf {
+    let absolute = match env::current_dir() {
+        Ok(cwd) if path.is_relative() => cwd.join(path),
+        _ => path.to_path_buf(),
+    };
+
+    let mut resolved = PathBuf::new();
+
+    // Resolving one component at a time makes `..` apply to symlink targets,
+    // the way the OS would, while unresolvable components are kept as written.
+    for component in absolute.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            component => {
+                resolved.push(component);
+                if let Ok(canonical) = fs::canonicalize(&resolved) {
+                    resolved = canonical;
+                }
+            }
+        }
+    }
+
+    resolved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An empty directory of the test's own, resolved so it compares equal to
+    // what canonicalize() gives back.
+    fn scratch(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("shark-project-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::canonicalize(dir).unwrap()
+    }
+
+    fn restricted(root: &Path) -> Project {
+        Project {
+            root: root.to_path_buf(),
+            restrict: true,
+        }
+    }
+
+    #[test]
+    fn the_root_is_the_nearest_ancestor_with_a_marker() {
+        let dir = scratch("markers");
+        fs::create_dir_all(dir.join("outer/inner/src")).unwrap();
+        fs::write(dir.join("outer/Cargo.toml"), "").unwrap();
+        fs::create_dir(dir.join("outer/inner/.git")).unwrap();
+
+        assert_eq!(
+            root_of(&dir.join("outer/inner/src")),
+            dir.join("outer/inner")
+        );
+        assert_eq!(root_of(&dir.join("outer")), dir.join("outer"));
+    }
+
+    #[test]
+    fn without_a_marker_the_directory_is_its_own_root() {
+        let dir = scratch("unmarked");
+        fs::create_dir(dir.join("sub")).unwrap();
+
+        assert_eq!(root_of(&dir.join("sub")), dir.join("sub"));
+    }
+
+    #[test]
+    fn files_inside_the_root_are_allowed_even_if_new() {
+        let root = scratch("inside");
+        fs::create_dir(root.join("src")).unwrap();
+        let project = restricted(&root);
+
+        assert!(project.check(&root.join("src/main.rs")).is_ok());
+        assert!(project.check(&root.join("new/dir/file.rs")).is_ok());
+        assert!(project.check(&root.join("src/../Cargo.toml")).is_ok());
+    }
+
+    #[test]
+    fn parent_components_cant_climb_out_of_the_root() {
+        let dir = scratch("climb");
+        fs::create_dir(dir.join("root")).unwrap();
+        let project = restricted(&dir.join("root"));
+
+        let message = project.check(&dir.join("root/../outside.txt")).unwrap_err();
+        assert!(message.contains("is outside the project root"), "{message}");
+        assert!(project.check(&dir.join("root/missing/../../x")).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_out_of_the_root_are_followed() {
+        let dir = scratch("escape");
+        fs::create_dir_all(dir.join("root")).unwrap();
+        fs::create_dir_all(dir.join("elsewhere")).unwrap();
+        std::os::unix::fs::symlink(dir.join("elsewhere"), dir.join("root/link")).unwrap();
+        let project = restricted(&dir.join("root"));
+
+        assert!(project.check(&dir.join("root/link/file.txt")).is_err());
+        // `..` applies to where the link points, as the OS would apply it.
+        assert!(project
+            .check(&dir.join("root/link/../root/file.txt"))
+            .is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn a_symlinked_project_directory_is_the_same_root() {
+        let dir = scratch("linked");
+        fs::create_dir_all(dir.join("real/src")).unwrap();
+        fs::create_dir(dir.join("real/.git")).unwrap();
+        std::os::unix::fs::symlink(dir.join("real"), dir.join("alias")).unwrap();
+        let project = restricted(&root_of(&fs::canonicalize(dir.join("alias/src")).unwrap()));
+
+        assert!(project.check(&dir.join("alias/src/main.rs")).is_ok());
+        assert_eq!(
+            project.relative(&dir.join("alias/src/main.rs")),
+            Path::new("src/main.rs")
+        );
+    }
+
+    #[test]
+    fn without_restriction_anything_goes() {
+        let root = scratch("open");
+        let project = Project {
+            root: root.clone(),
+            restrict: false,
+        };
+
+        assert!(project.check(&root.join("../../etc/passwd")).is_ok());
+    }
+}
@@ -0,0 +1,286 @@
+use std::io::{self, Write};
+
+use crossterm::{
+    cursor,
+    event::{KeyCode, KeyEvent, KeyModifiers},
+    queue,
+    style::{self, Print},
+    terminal::{self, ClearType},
+};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::columns;
+
+pub enum PromptEvent {
+    Continue,
+    Submit,
+    Cancel,
+}
+
+// A single-line text field with its own cursor, used for every question the
+// editor asks on the bottom row.
+pub struct Prompt {
+    label: String,
+    text: String,
+    // Byte offset into `text`, always on a grapheme boundary.
+    cursor: usize,
+    // Pasted line breaks are kept, and shown as ↵.
+    multiline: bool,
+}
+
+// `text` on one row, each line break shown as ↵.
+pub fn one_line(text: &str) -> String {
+    text.replace("\r\n", "↵").replace(['\r', '\n'], "↵")
+}
+
+impl Prompt {
+    pub fn new(label: &str) -> Self {
+        Self {
+            label: label.to_string(),
+            text: String::new(),
+            cursor: 0,
+            multiline: false,
+        }
+    }
+
+    // For text that may span lines, like a search for a selection.
+    pub fn multiline(label: &str) -> Self {
+        Self {
+            multiline: true,
+            ..Self::new(label)
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    // Pasted text is inserted as one line; anything after a newline is dropped
+    // unless the prompt is multiline.
+    pub fn insert(&mut self, text: &str) {
+        let text = if self.multiline {
+            text
+        } else {
+            text.lines().next().unwrap_or("")
+        };
+        self.text.insert_str(self.cursor, text);
+        self.cursor += text.len();
+    }
+
+    pub fn handle_key(&mut self, event: &KeyEvent) -> PromptEvent {
+        let word = event
+            .modifiers
+            .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT);
+
+        match event.code {
+            KeyCode::Esc => return PromptEvent::Cancel,
+            KeyCode::Enter => return PromptEvent::Submit,
+            KeyCode::Left if word => self.cursor = self.previous_word(),
+            KeyCode::Right if word => self.cursor = self.next_word(),
+            KeyCode::Left => self.cursor = self.previous_grapheme(),
+            KeyCode::Right => self.cursor = self.next_grapheme(),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.text.len(),
+            KeyCode::Backspace if word => self.delete_before(self.previous_word()),
+            KeyCode::Backspace => self.delete_before(self.previous_grapheme()),
+            KeyCode::Delete => {
+                let end = self.next_grapheme();
+                self.text.replace_range(self.cursor..end, "");
+            }
+            KeyCode::Char('w') if event.modifiers == KeyModifiers::CONTROL => {
+                self.delete_before(self.previous_word())
+            }
+            KeyCode::Char('a') if event.modifiers == KeyModifiers::CONTROL => self.cursor = 0,
+            KeyCode::Char('e') if event.modifiers == KeyModifiers::CONTROL => {
+                self.cursor = self.text.len()
+            }
+            KeyCode::Char(c) if !word => {
+                let mut buffer = [0; 4];
+                self.insert(c.encode_utf8(&mut buffer));
+            }
+            _ => {}
+        }
+
+        PromptEvent::Continue
+    }
+
+    fn delete_before(&mut self, start: usize) {
+        self.text.replace_range(start..self.cursor, "");
+        self.cursor = start;
+    }
+
+    fn previous_grapheme(&self) -> usize {
+        self.text[..self.cursor]
+            .grapheme_indices(true)
+            .next_back()
+            .map_or(0, |(i, _)| i)
+    }
+
+    fn next_grapheme(&self) -> usize {
+        self.text[self.cursor..]
+            .graphemes(true)
+            .next()
+            .map_or(self.cursor, |g| self.cursor + g.len())
+    }
+
+    fn previous_word(&self) -> usize {
+        self.text[..self.cursor]
+            .split_word_bound_indices()
+            .rfind(|(_, w)| !w.trim().is_empty())
+            .map_or(0, |(i, _)| i)
+    }
+
+    fn next_word(&self) -> usize {
+        self.text[self.cursor..]
+            .split_word_bound_indices()
+            .find(|(_, w)| !w.trim().is_empty())
+            .map_or(self.text.len(), |(i, w)| self.cursor + i + w.len())
+    }
+
+    // Draws the label and text on `row`, scrolled horizontally so the cursor
+    // stays in view, and leaves the terminal cursor at the prompt's cursor.
+    pub fn render(&self, out: &mut impl Write, row: u16, width: u16) -> io::Result<()> {
+        let width = width as usize;
+        let before = one_line(&format!("{}{}", self.label, &self.text[..self.cursor]));
+        let offset = (columns::width(&before) + 1).saturating_sub(width);
+
+        let mut skipped = 0;
+        let mut visible = String::new();
+        let mut used = 0;
+        for grapheme in one_line(&format!("{}{}", self.label, self.text)).graphemes(true) {
+            let w = columns::width(grapheme);
+            if skipped < offset {
+                skipped += w;
+                continue;
+            }
+            if used + w > width {
+                break;
+            }
+            used += w;
+            visible.push_str(grapheme);
+        }
+
+        let column = columns::width(&before).saturating_sub(skipped);
+
+        queue!(
+            out,
+            cursor::MoveTo(0, row),
+            terminal::Clear(ClearType::CurrentLine),
+            style::ResetColor,
+            Print(visible),
+            cursor::MoveTo(column as u16, row),
+            cursor::Show,
+        )?;
+        out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terminal::Memory;
+
+    fn press(prompt: &mut Prompt, code: KeyCode, modifiers: KeyModifiers) {
+        prompt.handle_key(&KeyEvent::new(code, modifiers));
+    }
+
+    fn typed(text: &str) -> Prompt {
+        let mut prompt = Prompt::new("> ");
+        for c in text.chars() {
+            press(&mut prompt, KeyCode::Char(c), KeyModifiers::NONE);
+        }
+        prompt
+    }
+
+    #[test]
+    fn typing_inserts_at_the_cursor() {
+        let mut prompt = typed("hllo");
+        press(&mut prompt, KeyCode::Home, KeyModifiers::NONE);
+        press(&mut prompt, KeyCode::Right, KeyModifiers::NONE);
+        press(&mut prompt, KeyCode::Char('e'), KeyModifiers::NONE);
+
+        assert_eq!(prompt.text(), "hello");
+        assert_eq!(prompt.cursor, 2);
+    }
+
+    #[test]
+    fn left_and_right_step_over_whole_graphemes() {
+        // An accent combined onto the e, and a thumbs up with a skin tone.
+        let mut prompt = typed("ae\u{301}👍🏽b");
+        let mut stops = vec![prompt.cursor];
+        for _ in 0..4 {
+            press(&mut prompt, KeyCode::Left, KeyModifiers::NONE);
+            stops.push(prompt.cursor);
+        }
+        assert_eq!(stops, [13, 12, 4, 1, 0]);
+
+        press(&mut prompt, KeyCode::Right, KeyModifiers::NONE);
+        press(&mut prompt, KeyCode::Right, KeyModifiers::NONE);
+        assert_eq!(prompt.cursor, 4);
+    }
+
+    #[test]
+    fn home_end_and_their_ctrl_keys_go_to_either_end() {
+        let mut prompt = typed("some text");
+        for (code, modifiers, expected) in [
+            (KeyCode::Home, KeyModifiers::NONE, 0),
+            (KeyCode::End, KeyModifiers::NONE, 9),
+            (KeyCode::Char('a'), KeyModifiers::CONTROL, 0),
+            (KeyCode::Char('e'), KeyModifiers::CONTROL, 9),
+        ] {
+            press(&mut prompt, code, modifiers);
+            assert_eq!(prompt.cursor, expected, "{code:?}");
+        }
+        assert_eq!(prompt.text(), "some text");
+    }
+
+    #[test]
+    fn ctrl_w_and_ctrl_backspace_delete_the_word_before() {
+        let mut prompt = typed("one two three");
+        press(&mut prompt, KeyCode::Char('w'), KeyModifiers::CONTROL);
+        assert_eq!(prompt.text(), "one two ");
+        press(&mut prompt, KeyCode::Backspace, KeyModifiers::CONTROL);
+        assert_eq!(prompt.text(), "one ");
+        assert_eq!(prompt.cursor, 4);
+    }
+
+    #[test]
+    fn delete_at_the_end_does_nothing() {
+        let mut prompt = typed("ab");
+        press(&mut prompt, KeyCode::Delete, KeyModifiers::NONE);
+        assert_eq!(prompt.text(), "ab");
+        assert_eq!(prompt.cursor, 2);
+
+        press(&mut prompt, KeyCode::Home, KeyModifiers::NONE);
+        press(&mut prompt, KeyCode::Delete, KeyModifiers::NONE);
+        assert_eq!(prompt.text(), "b");
+    }
+
+    #[test]
+    fn a_multiline_paste_keeps_only_its_first_line() {
+        let mut prompt = typed("[]");
+        press(&mut prompt, KeyCode::Left, KeyModifiers::NONE);
+        prompt.insert("first\nsecond");
+        assert_eq!(prompt.text(), "[first]");
+        assert_eq!(prompt.cursor, 6);
+
+        let mut prompt = Prompt::multiline("> ");
+        prompt.insert("first\nsecond");
+        assert_eq!(prompt.text(), "first\nsecond");
+    }
+
+    #[test]
+    fn render_scrolls_to_keep_the_cursor_in_view() {
+        let mut prompt = typed("abcdefghijklmnopqrst");
+        let mut terminal = Memory::new(10, 1);
+
+        prompt.render(&mut terminal, 0, 10).unwrap();
+        assert_eq!(terminal.row(0), "lmnopqrst ");
+        assert_eq!(terminal.cursor(), (9, 0));
+
+        press(&mut prompt, KeyCode::Home, KeyModifiers::NONE);
+        prompt.render(&mut terminal, 0, 10).unwrap();
+        assert_eq!(terminal.row(0), "> abcdefgh");
+        assert_eq!(terminal.cursor(), (2, 0));
+    }
+}
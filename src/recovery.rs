@@ -0,0 +1,97 @@
+use std::{
+    env, fs,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use ropey::Rope;
+
+use crate::buffer;
+
+fn state_dir() -> Option<PathBuf> {
+    match env::var_os("XDG_STATE_HOME") {
+        Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir).join("shark")),
+        _ => env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state/shark")),
+    }
+}
+
+pub fn recovery_path(filename: &str) -> Option<PathBuf> {
+    let path = Path::new(filename);
+    let path = fs::canonicalize(path)
+        .or_else(|_| env::current_dir().map(|cwd| cwd.join(path)))
+        .ok()?;
+
+    let hash = fnv1a(path.as_os_str().as_encoded_bytes());
+
+    Some(state_dir()?.join(format!("{hash:016x}.recover")))
+}
+
+// 64-bit FNV-1a. Recovery files have to be found again by whichever build
+// runs next, so the name can't come from std's hashers, whose output may
+// change between Rust releases.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+// Called while the editor is going down, so every step is best-effort and
+// nothing here is allowed to panic.
+pub fn write(filename: &str, rope: &Rope) {
+    let Some(path) = recovery_path(filename) else {
+        return;
+    };
+
+    if let Some(dir) = path.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+
+    let Ok(file) = fs::File::create(&path) else {
+        return;
+    };
+    let mut file = BufWriter::new(file);
+
+    if buffer::write_rope(rope, &mut file).is_ok() {
+        let _ = file.flush();
+    }
+}
+
+pub fn remove(filename: &str) {
+    if let Some(path) = recovery_path(filename) {
+        let _ = fs::remove_file(path);
+    }
+}
+
+pub fn find(filename: &str) -> Option<(PathBuf, SystemTime)> {
+    let path = recovery_path(filename)?;
+    let modified = fs::metadata(&path).ok()?.modified().ok()?;
+
+    Some((path, modified))
+}
+
+pub fn format_age(time: SystemTime) -> String {
+    let age = SystemTime::now()
+        .duration_since(time)
+        .unwrap_or(Duration::ZERO)
+        .as_secs();
+
+    match age {
+        0..=59 => format!("{age} seconds ago"),
+        60..=3599 => format!("{} minutes ago", age / 60),
+        3600..=86399 => format!("{} hours ago", age / 3600),
+        _ => format!("{} days ago", age / 86400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_hash_is_fnv1a() {
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(fnv1a(b"foobar"), 0x8594_4171_f739_67e8);
+    }
+}
@@ -0,0 +1,41 @@
+use tree_sitter::{Node, Tree};
+
+// The identifier under `byte` and the start bytes of every identifier node with
+// the same name, in order. Strings and comments contain no identifier nodes, so
+// mentions there aren't counted.
+pub fn find(tree: &Tree, source: &str, byte: usize) -> Option<(String, Vec<usize>)> {
+    let node = identifier_at(tree, byte).or_else(|| identifier_at(tree, byte.checked_sub(1)?))?;
+    let name = node.utf8_text(source.as_bytes()).ok()?.to_string();
+
+    let mut starts = Vec::new();
+    let mut cursor = tree.walk();
+
+    'walk: loop {
+        let node = cursor.node();
+        if is_identifier(node) && node.utf8_text(source.as_bytes()) == Ok(name.as_str()) {
+            starts.push(node.start_byte());
+        }
+
+        if cursor.goto_first_child() {
+            continue;
+        }
+        while !cursor.goto_next_sibling() {
+            if !cursor.goto_parent() {
+                break 'walk;
+            }
+        }
+    }
+
+    Some((name, starts))
+}
+
+fn identifier_at(tree: &Tree, byte: usize) -> Option<Node<'_>> {
+    let node = tree.root_node().descendant_for_byte_range(byte, byte + 1)?;
+    is_identifier(node).then_some(node)
+}
+
+// Plain, field and type identifiers all count, so a struct field is found both
+// where it's declared and where it's used.
+fn is_identifier(node: Node) -> bool {
+    node.child_count() == 0 && node.kind().ends_with("identifier")
+}
@@ -0,0 +1,268 @@
+use std::ops::Range;
+
+use unicode_width::UnicodeWidthStr;
+
+// Indentation plus comment leader, and whether the text after the leader is
+// indented like a code sample, which must not be rewrapped. The leader is the
+// language's line comment token, and doc-comment variants of it like `///`,
+// `//!` or `##`.
+fn split<'a>(line: &'a str, comment: Option<&str>) -> (&'a str, &'a str, bool) {
+    let indent = line.len() - line.trim_start().len();
+    let rest = &line[indent..];
+
+    match comment.and_then(|token| leader(rest, token)) {
+        Some(leader) => {
+            let after = &rest[leader..];
+            let spaces = after.len() - after.trim_start_matches(' ').len();
+            (&line[..indent + leader], after.trim(), spaces >= 4)
+        }
+        None => (&line[..indent], rest.trim(), false),
+    }
+}
+
+// Length of the leader `text` starts with: the token, more of its last
+// character, and maybe a `!`.
+fn leader(text: &str, token: &str) -> Option<usize> {
+    let last = token.chars().last()?;
+    let after = text.strip_prefix(token)?.trim_start_matches(last);
+    let after = after.strip_prefix('!').unwrap_or(after);
+    Some(text.len() - after.len())
+}
+
+// Whether `line` can be in the same paragraph as `other`: it has the same
+// leader and indentation, and words after them that aren't a code sample.
+pub fn continues(other: &str, line: &str, comment: Option<&str>) -> bool {
+    let (key, _, _) = split(other, comment);
+    let (k, b, c) = split(line, comment);
+    k == key && !b.is_empty() && !c
+}
+
+pub struct Reflowed {
+    pub lines: Range<usize>,
+    pub text: Vec<String>,
+    pub cursor: (usize, usize),
+}
+
+// Rewraps the paragraph around the (line, column) cursor to `width` columns,
+// keeping comment leaders and indentation. Lines are given without terminators,
+// and `comment` is the line comment token of their language, if it has one.
+pub fn reflow(
+    lines: &[String],
+    cursor: (usize, usize),
+    width: usize,
+    comment: Option<&str>,
+) -> Option<Reflowed> {
+    let (key, body, code) = split(lines.get(cursor.0)?, comment);
+    if body.is_empty() || code {
+        return None;
+    }
+
+    let belongs = |line: &str| continues(&lines[cursor.0], line, comment);
+
+    let mut start = cursor.0;
+    while start > 0 && belongs(&lines[start - 1]) {
+        start -= 1;
+    }
+    let mut end = cursor.0 + 1;
+    while end < lines.len() && belongs(&lines[end]) {
+        end += 1;
+    }
+
+    let mut words = Vec::new();
+    let mut cursor_word = 0;
+
+    for (i, line) in lines[start..end].iter().enumerate() {
+        let body = &line[key.len()..];
+        let mut first = true;
+        let mut after_space = true;
+
+        for (column, (j, c)) in (key.chars().count()..).zip(body.char_indices()) {
+            if !c.is_whitespace() && after_space {
+                let row = start + i;
+                if row < cursor.0 || (row == cursor.0 && (first || column <= cursor.1)) {
+                    cursor_word = words.len();
+                }
+
+                words.push(body[j..].split_whitespace().next().unwrap());
+                first = false;
+            }
+
+            after_space = c.is_whitespace();
+        }
+    }
+
+    let prefix = if key.trim().is_empty() {
+        key.to_string()
+    } else {
+        format!("{key} ")
+    };
+
+    let mut text = vec![prefix.clone()];
+    let mut new_cursor = (start, prefix.chars().count());
+
+    for (i, word) in words.iter().enumerate() {
+        let line = text.last().unwrap();
+
+        if line.len() > prefix.len() {
+            if line.width() + 1 + word.width() > width {
+                text.push(prefix.clone());
+            } else {
+                text.last_mut().unwrap().push(' ');
+            }
+        }
+
+        let row = start + text.len() - 1;
+        let line = text.last_mut().unwrap();
+        if i == cursor_word {
+            new_cursor = (row, line.chars().count());
+        }
+        line.push_str(word);
+    }
+
+    Some(Reflowed {
+        lines: start..end,
+        text,
+        cursor: new_cursor,
+    })
+}
+
+// Rewraps every paragraph among `lines`, and keeps the lines between them,
+// like blank ones and code samples, as they are. The cursor stays on its word
+// when it's in a paragraph, and otherwise on its line. None when there's no
+// paragraph at all.
+pub fn reflow_all(
+    lines: &[String],
+    cursor: (usize, usize),
+    width: usize,
+    comment: Option<&str>,
+) -> Option<Reflowed> {
+    let mut text = Vec::new();
+    let mut new_cursor = None;
+    let mut found = false;
+    let mut line = 0;
+
+    while line < lines.len() {
+        let rest = &lines[line..];
+        let Some(paragraph) = reflow(rest, (0, 0), width, comment) else {
+            if line == cursor.0 {
+                new_cursor = Some((text.len(), cursor.1));
+            }
+            text.push(lines[line].clone());
+            line += 1;
+            continue;
+        };
+
+        let end = line + paragraph.lines.end;
+        let paragraph = match cursor.0.checked_sub(line) {
+            Some(row) if cursor.0 < end => {
+                let paragraph = reflow(rest, (row, cursor.1), width, comment)?;
+                new_cursor = Some((text.len() + paragraph.cursor.0, paragraph.cursor.1));
+                paragraph
+            }
+            _ => paragraph,
+        };
+        text.extend(paragraph.text);
+        found = true;
+        line = end;
+    }
+
+    if !found {
+        return None;
+    }
+
+    // Below the lines, moved by as many lines as they grew or shrank by.
+    let cursor = new_cursor.unwrap_or((
+        (cursor.0 + text.len()).saturating_sub(lines.len()),
+        cursor.1,
+    ));
+    Some(Reflowed {
+        lines: 0..lines.len(),
+        text,
+        cursor,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn rewraps_a_paragraph_to_the_width() {
+        let text = lines("one two three\nfour five six\n\nafter");
+        let reflowed = reflow(&text, (0, 0), 9, None).unwrap();
+
+        assert_eq!(reflowed.lines, 0..2);
+        assert_eq!(reflowed.text, ["one two", "three", "four five", "six"]);
+    }
+
+    #[test]
+    fn keeps_the_leader_of_rust_comments() {
+        let text = lines("    /// one two three four\n    /// five\n    // other");
+        let reflowed = reflow(&text, (1, 8), 20, Some("//")).unwrap();
+
+        assert_eq!(reflowed.lines, 0..2);
+        assert_eq!(
+            reflowed.text,
+            ["    /// one two", "    /// three four", "    /// five"]
+        );
+        assert_eq!(reflowed.cursor, (2, 8));
+    }
+
+    #[test]
+    fn takes_the_leader_from_the_language() {
+        let text = lines("# one two three four\n# five\nkey = 1");
+        let reflowed = reflow(&text, (0, 2), 14, Some("#")).unwrap();
+
+        assert_eq!(reflowed.lines, 0..2);
+        assert_eq!(reflowed.text, ["# one two", "# three four", "# five"]);
+    }
+
+    #[test]
+    fn another_languages_leader_is_plain_text() {
+        let text = lines("# one two\n# three");
+        let reflowed = reflow(&text, (0, 0), 80, Some("//")).unwrap();
+
+        assert_eq!(reflowed.text, ["# one two # three"]);
+    }
+
+    #[test]
+    fn leaves_code_samples_in_comments_alone() {
+        let text = lines("//     let x = 1;");
+
+        assert!(reflow(&text, (0, 0), 80, Some("//")).is_none());
+    }
+
+    #[test]
+    fn reflows_each_paragraph_and_keeps_what_is_between() {
+        let text = lines("one two three\nfour\n\n//     code();\nfive six seven");
+        let reflowed = reflow_all(&text, (4, 10), 9, Some("//")).unwrap();
+
+        assert_eq!(reflowed.lines, 0..5);
+        assert_eq!(
+            reflowed.text,
+            [
+                "one two",
+                "three",
+                "four",
+                "",
+                "//     code();",
+                "five six",
+                "seven"
+            ]
+        );
+        assert_eq!(reflowed.cursor, (6, 0));
+    }
+
+    #[test]
+    fn a_cursor_below_the_lines_moves_with_them() {
+        let text = lines("one two three");
+        let reflowed = reflow_all(&text, (1, 0), 9, None).unwrap();
+
+        assert_eq!(reflowed.text, ["one two", "three"]);
+        assert_eq!(reflowed.cursor, (2, 0));
+    }
+}
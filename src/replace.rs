@@ -0,0 +1,147 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::task::Token;
+
+// Files bigger than this are assumed not to be source and aren't searched.
+const MAX_FILE_BYTES: u64 = 8 * 1024 * 1024;
+
+const SKIPPED_DIRS: [&str; 1] = ["target"];
+
+pub struct Hunk {
+    pub line: usize,
+    pub before: String,
+    pub after: String,
+    pub enabled: bool,
+}
+
+pub struct FileChange {
+    pub path: PathBuf,
+    // Contents the preview was computed from. Applying refuses to touch a file
+    // whose contents no longer match.
+    pub original: String,
+    pub hunks: Vec<Hunk>,
+    pub enabled: bool,
+}
+
+impl FileChange {
+    pub fn occurrences(&self, pattern: &str) -> usize {
+        self.hunks
+            .iter()
+            .filter(|h| self.enabled && h.enabled)
+            .map(|h| h.before.matches(pattern).count())
+            .sum()
+    }
+
+    // The new contents with every enabled hunk applied.
+    pub fn apply(&self) -> String {
+        let mut hunks = self.hunks.iter().filter(|h| h.enabled).peekable();
+        let mut text = String::with_capacity(self.original.len());
+
+        for (i, line) in self.original.split_inclusive('\n').enumerate() {
+            match hunks.peek() {
+                Some(hunk) if hunk.line == i => {
+                    let content = line.trim_end_matches(['\r', '\n']);
+                    text.push_str(&hunk.after);
+                    text.push_str(&line[content.len()..]);
+                    hunks.next();
+                }
+                _ => text.push_str(line),
+            }
+        }
+
+        text
+    }
+}
+
+// Literal, case-sensitive search over every text file under `root`. Files in
+// `open` are searched using the given contents instead of what's on disk.
+// Returns `None` when cancelled through `token`, which advances once per file.
+pub fn search(
+    root: &Path,
+    pattern: &str,
+    replacement: &str,
+    open: &[(PathBuf, String)],
+    token: &Token,
+) -> Option<Vec<FileChange>> {
+    let mut paths = Vec::new();
+    collect_files(root, &mut paths, token);
+    // Cancelled while walking, so what was found is only part of it.
+    if token.is_cancelled() {
+        return None;
+    }
+    paths.sort();
+
+    let mut changes = Vec::new();
+    for path in paths {
+        if token.is_cancelled() {
+            return None;
+        }
+        token.advance();
+        changes.extend(change_for(path, pattern, replacement, open));
+    }
+
+    Some(changes)
+}
+
+fn change_for(
+    path: PathBuf,
+    pattern: &str,
+    replacement: &str,
+    open: &[(PathBuf, String)],
+) -> Option<FileChange> {
+    let original = match open.iter().find(|(p, _)| *p == path) {
+        Some((_, text)) => text.clone(),
+        None => fs::read_to_string(&path).ok()?,
+    };
+
+    let hunks: Vec<Hunk> = original
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| line.contains(pattern))
+        .map(|(line, before)| {
+            let before = before.trim_end_matches('\r');
+            Hunk {
+                line,
+                before: before.to_string(),
+                after: before.replace(pattern, replacement),
+                enabled: true,
+            }
+        })
+        .collect();
+
+    (!hunks.is_empty()).then_some(FileChange {
+        path,
+        original,
+        hunks,
+        enabled: true,
+    })
+}
+
+// Hidden directories and build output are skipped.
+fn collect_files(dir: &Path, paths: &mut Vec<PathBuf>, token: &Token) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    if token.is_cancelled() {
+        return;
+    }
+
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+
+        if file_type.is_dir() {
+            if !name.starts_with('.') && !SKIPPED_DIRS.contains(&name.as_ref()) {
+                collect_files(&entry.path(), paths, token);
+            }
+        } else if file_type.is_file() && entry.metadata().is_ok_and(|m| m.len() <= MAX_FILE_BYTES) {
+            paths.push(entry.path());
+        }
+    }
+}
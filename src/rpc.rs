@@ -0,0 +1,151 @@
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    os::unix::{
+        fs::FileTypeExt,
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+};
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+// A line-delimited JSON protocol on a unix socket, for scripts driving a
+// running editor. Each connection is read on its own thread, but requests are
+// only carried out by the editor's tick on the main thread, so nothing ever
+// touches a buffer concurrently. Lines and columns are counted from 1, like
+// the status bar shows them.
+
+#[derive(Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Command {
+    GetBuffer,
+    GetCursor,
+    Goto {
+        line: usize,
+        #[serde(default)]
+        column: Option<usize>,
+    },
+    Insert {
+        at: Position,
+        text: String,
+    },
+    Save,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+pub struct Request {
+    pub command: Command,
+    reply: Sender<Value>,
+}
+
+impl Request {
+    // `result` is what the command returns, or why it failed.
+    pub fn reply(self, result: Result<Value, String>) {
+        let reply = match result {
+            Ok(result) => json!({ "ok": true, "result": result }),
+            Err(error) => failure(&error),
+        };
+        // The client may have hung up while waiting for the tick.
+        let _ = self.reply.send(reply);
+    }
+}
+
+pub struct Server {
+    path: PathBuf,
+    requests: Receiver<Request>,
+}
+
+impl Server {
+    pub fn bind(path: &Path) -> io::Result<Self> {
+        remove_stale(path)?;
+
+        // The socket is created with user-only permissions instead of changing
+        // them afterwards, which would leave a moment where anyone could
+        // connect.
+        let previous = unsafe { libc::umask(0o177) };
+        let listener = UnixListener::bind(path);
+        unsafe { libc::umask(previous) };
+        let listener = listener?;
+
+        let (sender, requests) = mpsc::channel();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let sender = sender.clone();
+                thread::spawn(move || serve(stream, &sender));
+            }
+        });
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            requests,
+        })
+    }
+
+    pub fn try_recv(&self) -> Option<Request> {
+        self.requests.try_recv().ok()
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+// A socket nothing answers on was left behind by an editor that was killed.
+// Anything else at the path is left alone, and binding reports it.
+fn remove_stale(path: &Path) -> io::Result<()> {
+    let socket = fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_socket());
+    if socket && UnixStream::connect(path).is_err() {
+        fs::remove_file(path)?;
+    }
+
+    Ok(())
+}
+
+// Answers the connection's requests one at a time, in order. Malformed ones
+// are answered here without bothering the editor.
+fn serve(stream: UnixStream, requests: &Sender<Request>) {
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+
+    for line in BufReader::new(stream).lines().map_while(Result::ok) {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let reply = match serde_json::from_str::<Command>(&line) {
+            Ok(command) => {
+                let (reply, receiver) = mpsc::channel();
+                if requests.send(Request { command, reply }).is_err() {
+                    return;
+                }
+                // Only an editor that's exiting drops a request unanswered.
+                match receiver.recv() {
+                    Ok(reply) => reply,
+                    Err(_) => return,
+                }
+            }
+            Err(error) => failure(&format!("malformed request: {error}")),
+        };
+
+        if writeln!(writer, "{reply}").is_err() {
+            return;
+        }
+    }
+}
+
+fn failure(error: &str) -> Value {
+    json!({ "ok": false, "error": error })
+}
@@ -0,0 +1,348 @@
+use std::{
+    io::{self, Write},
+    time::Duration,
+};
+
+use crossterm::event::Event;
+
+use crate::terminal::Terminal;
+
+// Where the parser is within the escape sequences crossterm writes, which can
+// arrive split over several writes.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Text,
+    Escape,
+    Csi,
+    Osc,
+    OscEscape,
+}
+
+// The terminal, remembering which bytes drew each row of the last frame. Between
+// begin_frame() and present() everything drawn is kept per row instead of
+// written, and only the rows that came out different are sent to the
+// terminal. Drawing outside a frame goes straight through and marks the rows
+// it touches as unknown, so the next frame repaints them.
+pub struct Screen {
+    out: Box<dyn Terminal>,
+    // What each row shows; None when something drew there outside a frame.
+    rows: Vec<Option<Vec<u8>>>,
+    frame: Option<Vec<Vec<u8>>>,
+    // Rows of the last frame that only changed color and were held back by
+    // present(), with what they should show.
+    deferred: Vec<(usize, Vec<u8>)>,
+    // Bytes sent to the terminal so far, for the debug overlay.
+    written: usize,
+    row: usize,
+    saved_row: usize,
+    // Attributes and colors set since the last reset, so a row can start
+    // drawing in the style the one before it left off with.
+    style: Vec<u8>,
+    state: State,
+    sequence: Vec<u8>,
+}
+
+impl Screen {
+    pub fn new(out: Box<dyn Terminal>) -> Self {
+        Self {
+            out,
+            rows: Vec::new(),
+            frame: None,
+            deferred: Vec::new(),
+            written: 0,
+            row: 0,
+            saved_row: 0,
+            style: Vec::new(),
+            state: State::Text,
+            sequence: Vec::new(),
+        }
+    }
+
+    pub fn begin_frame(&mut self) {
+        self.frame = Some(Vec::new());
+    }
+
+    // Rows the frame left out are empty, so the frame has to reach the last
+    // row for everything below what it drew to be cleared. With `defer_restyle`
+    // set, rows whose text is the same and only colors differ are held back
+    // until flush_deferred().
+    pub fn present(&mut self, defer_restyle: bool) -> io::Result<()> {
+        let Some(frame) = self.frame.take() else {
+            return Ok(());
+        };
+        if self.rows.len() < frame.len() {
+            self.rows.resize(frame.len(), None);
+        }
+        self.deferred.clear();
+
+        for (row, bytes) in frame.into_iter().enumerate() {
+            match &self.rows[row] {
+                Some(shown) if *shown == bytes => continue,
+                Some(shown) if defer_restyle && text(shown) == text(&bytes) => {
+                    self.deferred.push((row, bytes));
+                }
+                _ => self.write_row(row, bytes)?,
+            }
+        }
+
+        self.style.clear();
+        Ok(())
+    }
+
+    // Writes the rows present() held back, leaving the cursor where it was.
+    pub fn flush_deferred(&mut self) -> io::Result<()> {
+        if self.deferred.is_empty() {
+            return Ok(());
+        }
+
+        self.send(b"\x1b[?25l\x1b7")?;
+        for (row, bytes) in std::mem::take(&mut self.deferred) {
+            self.write_row(row, bytes)?;
+        }
+        self.send(b"\x1b8\x1b[?25h")?;
+        self.out.flush()
+    }
+
+    // Moves what `rows` show by `lines` (up when positive) with the terminal's
+    // own scrolling, so a frame drawn after it only has to fill in the rows
+    // that came into view.
+    pub fn scroll(&mut self, rows: std::ops::Range<usize>, lines: isize) -> io::Result<()> {
+        let count = lines.unsigned_abs();
+        if count == 0 || count >= rows.len() {
+            return Ok(());
+        }
+        if self.rows.len() < rows.end {
+            self.rows.resize(rows.end, None);
+        }
+
+        let region = &mut self.rows[rows.clone()];
+        let (code, exposed) = if lines > 0 {
+            region.rotate_left(count);
+            ('S', region.len() - count..region.len())
+        } else {
+            region.rotate_right(count);
+            ('T', 0..count)
+        };
+        region[exposed].fill(None);
+        self.deferred.clear();
+
+        let sequence = format!(
+            "\x1b[0m\x1b[{};{}r\x1b[{count}{code}\x1b[r",
+            rows.start + 1,
+            rows.end
+        );
+        self.send(sequence.as_bytes())
+    }
+
+    // Rings the terminal's bell, which takes no room on any row.
+    pub fn bell(&mut self) -> io::Result<()> {
+        self.send(b"\x07")?;
+        self.out.flush()
+    }
+
+    pub fn written(&self) -> usize {
+        self.written
+    }
+
+    fn write_row(&mut self, row: usize, bytes: Vec<u8>) -> io::Result<()> {
+        self.send(format!("\x1b[{};1H\x1b[0m\x1b[2K", row + 1).as_bytes())?;
+        self.send(&bytes)?;
+        self.send(b"\x1b[0m")?;
+        self.rows[row] = Some(bytes);
+        Ok(())
+    }
+
+    fn send(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.written += bytes.len();
+        self.out.write_all(bytes)
+    }
+
+    fn text(&mut self, bytes: &[u8]) -> io::Result<()> {
+        if bytes.is_empty() {
+            return Ok(());
+        }
+
+        match &mut self.frame {
+            Some(frame) => Self::frame_row(frame, self.row).extend_from_slice(bytes),
+            None => {
+                self.forget_row();
+                self.send(bytes)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn sequence(&mut self) -> io::Result<()> {
+        let sequence = std::mem::take(&mut self.sequence);
+        let csi = sequence.starts_with(b"\x1b[");
+        let params = || {
+            std::str::from_utf8(&sequence[2..sequence.len() - 1])
+                .unwrap_or("")
+                .split(';')
+                .map(|p| p.parse::<usize>().unwrap_or(0))
+                .collect::<Vec<_>>()
+        };
+        let last = sequence.last().copied();
+
+        match (csi, last) {
+            (true, Some(b'H')) => {
+                let params = params();
+                self.row = params.first().copied().unwrap_or(1).saturating_sub(1);
+                // Kept as just the column, so a row's bytes don't depend on
+                // which row it is and still match after scroll().
+                if let Some(frame) = &mut self.frame {
+                    let column = params.get(1).copied().unwrap_or(1).max(1);
+                    let row = Self::frame_row(frame, self.row);
+                    row.extend_from_slice(format!("\x1b[{column}G").as_bytes());
+                    row.extend_from_slice(&self.style);
+                    return Ok(());
+                }
+            }
+            (true, Some(b'm')) => {
+                if sequence == b"\x1b[0m" {
+                    self.style.clear();
+                } else {
+                    self.style.extend_from_slice(&sequence);
+                }
+            }
+            (true, Some(b'J')) => {
+                self.rows.clear();
+                self.deferred.clear();
+                return self.send(&sequence);
+            }
+            (true, Some(b'K')) if self.frame.is_none() => self.forget_row(),
+            (false, Some(b'7')) => {
+                self.saved_row = self.row;
+                if self.frame.is_some() {
+                    return Ok(());
+                }
+            }
+            (false, Some(b'8')) => {
+                self.row = self.saved_row;
+                if self.frame.is_some() {
+                    return Ok(());
+                }
+            }
+            // Hiding the cursor, titles and the like aren't part of any row.
+            _ => return self.send(&sequence),
+        }
+
+        match &mut self.frame {
+            Some(frame) => Self::frame_row(frame, self.row).extend_from_slice(&sequence),
+            None => self.send(&sequence)?,
+        }
+        Ok(())
+    }
+
+    fn frame_row(frame: &mut Vec<Vec<u8>>, row: usize) -> &mut Vec<u8> {
+        if frame.len() <= row {
+            frame.resize(row + 1, Vec::new());
+        }
+        &mut frame[row]
+    }
+
+    fn forget_row(&mut self) {
+        if let Some(row) = self.rows.get_mut(self.row) {
+            *row = None;
+        }
+        self.deferred.retain(|(row, _)| *row != self.row);
+    }
+}
+
+// A row's bytes without its colors and attributes, i.e. the text it shows and
+// where.
+fn text(bytes: &[u8]) -> Vec<u8> {
+    let mut text = Vec::with_capacity(bytes.len());
+    let mut rest = bytes;
+
+    while let Some(start) = rest.windows(2).position(|w| w == b"\x1b[") {
+        text.extend_from_slice(&rest[..start]);
+        let sequence = &rest[start..];
+        let end = sequence[2..]
+            .iter()
+            .position(|b| (0x40..=0x7e).contains(b))
+            .map_or(sequence.len(), |i| i + 3);
+
+        if sequence[end - 1] != b'm' {
+            text.extend_from_slice(&sequence[..end]);
+        }
+        rest = &sequence[end..];
+    }
+
+    text.extend_from_slice(rest);
+    text
+}
+
+// What's drawn on the screen is unknown after entering or clearing it, so the
+// next frame repaints every row.
+impl Terminal for Screen {
+    fn size(&self) -> io::Result<(u16, u16)> {
+        self.out.size()
+    }
+
+    fn enter(&mut self, mouse: bool) -> io::Result<()> {
+        self.rows.clear();
+        self.deferred.clear();
+        self.out.enter(mouse)
+    }
+
+    fn leave(&mut self) -> io::Result<()> {
+        self.out.leave()
+    }
+
+    fn poll(&mut self, timeout: Duration) -> io::Result<bool> {
+        self.out.poll(timeout)
+    }
+
+    fn read(&mut self) -> io::Result<Event> {
+        self.out.read()
+    }
+
+    // For when the terminal's contents can't be trusted, e.g. after a resize.
+    fn clear(&mut self) -> io::Result<()> {
+        self.rows.clear();
+        self.deferred.clear();
+        self.send(b"\x1b[2J")
+    }
+}
+
+impl Write for Screen {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut text = 0;
+
+        for (i, &byte) in buf.iter().enumerate() {
+            self.state = match self.state {
+                State::Text if byte == 0x1b => {
+                    self.text(&buf[text..i])?;
+                    State::Escape
+                }
+                State::Text => continue,
+                State::Escape if byte == b'[' => State::Csi,
+                State::Escape if byte == b']' => State::Osc,
+                State::Escape => State::Text,
+                State::Csi if (0x40..=0x7e).contains(&byte) => State::Text,
+                State::Csi => State::Csi,
+                State::Osc if byte == 0x07 => State::Text,
+                State::Osc if byte == 0x1b => State::OscEscape,
+                State::Osc => State::Osc,
+                State::OscEscape => State::Text,
+            };
+
+            self.sequence.push(byte);
+            if self.state == State::Text {
+                self.sequence()?;
+            }
+            text = i + 1;
+        }
+
+        if self.state == State::Text {
+            self.text(&buf[text..])?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
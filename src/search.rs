@@ -0,0 +1,83 @@
+use std::ops::Range;
+
+use ropey::Rope;
+
+// Plain-text search over the rope. Candidates are compared char by char in
+// place, so the buffer is never flattened into a String.
+
+fn matches_at(rope: &Rope, start: usize, query: &[char]) -> bool {
+    start + query.len() <= rope.len_chars() && rope.chars_at(start).zip(query).all(|(a, &b)| a == b)
+}
+
+fn find_forward(rope: &Rope, query: &[char], range: Range<usize>) -> Option<usize> {
+    let first = *query.first()?;
+
+    rope.chars_at(range.start)
+        .zip(range)
+        .find(|&(c, i)| c == first && matches_at(rope, i, query))
+        .map(|(_, i)| i)
+}
+
+fn find_backward(rope: &Rope, query: &[char], range: Range<usize>) -> Option<usize> {
+    let first = *query.first()?;
+    let mut chars = rope.chars_at(range.end);
+
+    range
+        .rev()
+        .find(|&i| chars.prev() == Some(first) && matches_at(rope, i, query))
+}
+
+// The first match starting at or after char `from`, wrapping around to the
+// start of the buffer.
+pub fn next(rope: &Rope, query: &str, from: usize) -> Option<usize> {
+    let query: Vec<char> = query.chars().collect();
+    let from = from.min(rope.len_chars());
+
+    find_forward(rope, &query, from..rope.len_chars())
+        .or_else(|| find_forward(rope, &query, 0..from))
+}
+
+// The last match starting before char `from`, wrapping around to the end.
+pub fn previous(rope: &Rope, query: &str, from: usize) -> Option<usize> {
+    let query: Vec<char> = query.chars().collect();
+    let from = from.min(rope.len_chars());
+
+    find_backward(rope, &query, 0..from)
+        .or_else(|| find_backward(rope, &query, from..rope.len_chars()))
+}
+
+// Starts of the matches that begin within `range`, e.g. the visible lines.
+pub fn all(rope: &Rope, query: &str, range: Range<usize>) -> Vec<usize> {
+    let query: Vec<char> = query.chars().collect();
+    let mut starts = Vec::new();
+    let mut start = range.start;
+
+    while let Some(found) = find_forward(rope, &query, start..range.end) {
+        starts.push(found);
+        start = found + query.len();
+    }
+
+    starts
+}
+
+// Replaces the matches that lie within `range`, left to right, and returns how
+// many there were. Text put in is never searched again, so a replacement
+// containing the query still finishes.
+pub fn replace_all(rope: &mut Rope, query: &str, replacement: &str, range: Range<usize>) -> usize {
+    let query: Vec<char> = query.chars().collect();
+    let inserted = replacement.chars().count();
+    let (mut start, mut end, mut count) = (range.start, range.end, 0);
+
+    while let Some(found) = find_forward(rope, &query, start..end) {
+        if found + query.len() > end {
+            break;
+        }
+        rope.remove(found..found + query.len());
+        rope.insert(found, replacement);
+        start = found + inserted;
+        end = end - query.len() + inserted;
+        count += 1;
+    }
+
+    count
+}
@@ -0,0 +1,66 @@
+use std::{collections::VecDeque, time::Duration};
+
+// Number of frames the debug overlay averages over.
+const WINDOW: usize = 30;
+
+#[derive(Clone, Copy, Default)]
+pub struct Frame {
+    pub parse: Duration,
+    pub draw: Duration,
+    pub write: Duration,
+    pub lines: usize,
+    // Sent to the terminal for the frame.
+    pub bytes: usize,
+}
+
+#[derive(Default)]
+pub struct FrameStats {
+    frames: VecDeque<Frame>,
+}
+
+impl FrameStats {
+    pub fn push(&mut self, frame: Frame) {
+        if self.frames.len() == WINDOW {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(frame);
+    }
+
+    pub fn average(&self) -> Frame {
+        let n = self.frames.len().max(1) as u32;
+        let mut total = Frame::default();
+
+        for frame in &self.frames {
+            total.parse += frame.parse;
+            total.draw += frame.draw;
+            total.write += frame.write;
+            total.lines += frame.lines;
+            total.bytes += frame.bytes;
+        }
+
+        Frame {
+            parse: total.parse / n,
+            draw: total.draw / n,
+            write: total.write / n,
+            lines: total.lines / n as usize,
+            bytes: total.bytes / n as usize,
+        }
+    }
+
+    pub fn summary(&self, rope_chars: usize, undo: (usize, usize)) -> Vec<String> {
+        let average = self.average();
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+
+        vec![
+            format!("frames  {:>9}", self.frames.len()),
+            format!("parse   {:>7.2}ms", ms(average.parse)),
+            format!("draw    {:>7.2}ms", ms(average.draw)),
+            format!("write   {:>7.2}ms", ms(average.write)),
+            format!("lines   {:>9}", average.lines),
+            format!("bytes   {:>9}", average.bytes),
+            format!("chars   {:>9}", rope_chars),
+            format!("undo    {:>9}", undo.0),
+            format!("undo    {:>7}KB", undo.1.div_ceil(1024)),
+        ]
+    }
+}
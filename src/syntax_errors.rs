@@ -0,0 +1,38 @@
+use std::ops::Range;
+
+use ropey::Rope;
+use tree_sitter::Tree;
+
+// Char ranges of what the parser couldn't make sense of, in order: ERROR
+// nodes, and tokens it had to assume were there. Those take no room in the
+// text, so the char where one was expected stands in for it. Only subtrees
+// that contain an error are walked, so a clean tree costs one check.
+pub fn find(rope: &Rope, tree: &Tree) -> Vec<Range<usize>> {
+    let mut errors = Vec::new();
+    if !tree.root_node().has_error() {
+        return errors;
+    }
+
+    let mut cursor = tree.walk();
+    loop {
+        let node = cursor.node();
+        if node.is_error() || node.is_missing() {
+            let start = rope.byte_to_char(node.start_byte().min(rope.len_bytes()));
+            let end = rope.byte_to_char(node.end_byte().min(rope.len_bytes()));
+            let end = if end > start {
+                end
+            } else {
+                (start + 1).min(rope.len_chars())
+            };
+            errors.push(start..end);
+        } else if node.has_error() && cursor.goto_first_child() {
+            continue;
+        }
+
+        while !cursor.goto_next_sibling() {
+            if !cursor.goto_parent() {
+                return errors;
+            }
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+};
+
+// Shared between a long operation and whoever is waiting on it. The operation
+// checks `is_cancelled` between steps of work and gives up cleanly, without
+// having changed anything, when it's set.
+#[derive(Clone, Default)]
+pub struct Token {
+    cancelled: Arc<AtomicBool>,
+    progress: Arc<AtomicUsize>,
+}
+
+impl Token {
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    // Counts units of work done, e.g. files searched, for the progress display.
+    pub fn advance(&self) {
+        self.advance_by(1);
+    }
+
+    pub fn advance_by(&self, units: usize) {
+        self.progress.fetch_add(units, Ordering::Relaxed);
+    }
+
+    pub fn progress(&self) -> usize {
+        self.progress.load(Ordering::Relaxed)
+    }
+}
+
+// An operation running on a worker thread. It returns `None` when cancelled.
+pub struct Task<T> {
+    handle: JoinHandle<Option<T>>,
+    token: Token,
+}
+
+impl<T: Send + 'static> Task<T> {
+    pub fn spawn(work: impl FnOnce(&Token) -> Option<T> + Send + 'static) -> Self {
+        let token = Token::default();
+        let worker = token.clone();
+
+        Self {
+            handle: thread::spawn(move || work(&worker)),
+            token,
+        }
+    }
+
+    pub fn token(&self) -> &Token {
+        &self.token
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    // Blocks until the worker returns. A panicking worker counts as cancelled.
+    pub fn join(self) -> Option<T> {
+        self.handle.join().ok().flatten()
+    }
+}
@@ -0,0 +1,132 @@
+pub mod memory;
+
+use std::{
+    io::{self, Stdout, Write},
+    panic,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Once,
+    },
+    time::Duration,
+};
+
+use crossterm::{
+    cursor, event, execute,
+    terminal::{self, ClearType},
+    QueueableCommand,
+};
+
+pub use memory::Memory;
+
+// What the editor runs on: escape sequences are written to it, and its size
+// and input come from it. Everything the editor does with the terminal goes
+// through one of these, so another backend can stand in for the real one.
+pub trait Terminal: Write {
+    fn size(&self) -> io::Result<(u16, u16)>;
+
+    // Raw mode on the alternate screen, with bracketed paste, and with mouse
+    // capture when `mouse` is set.
+    fn enter(&mut self, mouse: bool) -> io::Result<()>;
+    // Puts back what enter() changed. Leaving when not entered does nothing.
+    fn leave(&mut self) -> io::Result<()>;
+
+    // Whether an event can be read without waiting longer than `timeout`.
+    fn poll(&mut self, timeout: Duration) -> io::Result<bool>;
+    fn read(&mut self) -> io::Result<event::Event>;
+
+    // These go out as escape sequences like everything else written, and
+    // aren't flushed until flush() is.
+    fn move_cursor(&mut self, column: u16, row: u16) -> io::Result<()> {
+        self.queue(cursor::MoveTo(column, row)).map(|_| ())
+    }
+
+    fn print(&mut self, text: &str) -> io::Result<()> {
+        self.write_all(text.as_bytes())
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        self.queue(terminal::Clear(ClearType::All)).map(|_| ())
+    }
+}
+
+// Set while the real terminal is in raw mode on the alternate screen, so that
+// however the editor goes away, the terminal is put back exactly once.
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static PANIC_HOOK: Once = Once::new();
+
+// The terminal the editor was started in, through crossterm.
+pub struct Crossterm {
+    out: Stdout,
+}
+
+impl Crossterm {
+    pub fn new(out: Stdout) -> Self {
+        Self { out }
+    }
+}
+
+// A panic puts the terminal back before its message is printed, so the message
+// is readable and the shell usable afterwards.
+fn restore() -> io::Result<()> {
+    if !ACTIVE.swap(false, Ordering::SeqCst) {
+        return Ok(());
+    }
+
+    execute!(
+        io::stdout(),
+        event::DisableMouseCapture,
+        event::DisableBracketedPaste,
+        terminal::LeaveAlternateScreen
+    )?;
+    terminal::disable_raw_mode()
+}
+
+impl Terminal for Crossterm {
+    fn size(&self) -> io::Result<(u16, u16)> {
+        terminal::size()
+    }
+
+    fn enter(&mut self, mouse: bool) -> io::Result<()> {
+        PANIC_HOOK.call_once(|| {
+            let report = panic::take_hook();
+            panic::set_hook(Box::new(move |info| {
+                let _ = restore();
+                report(info);
+            }));
+        });
+
+        ACTIVE.store(true, Ordering::SeqCst);
+        execute!(
+            self.out,
+            terminal::EnterAlternateScreen,
+            event::EnableBracketedPaste
+        )?;
+        if mouse {
+            execute!(self.out, event::EnableMouseCapture)?;
+        }
+        terminal::enable_raw_mode()
+    }
+
+    fn leave(&mut self) -> io::Result<()> {
+        self.out.flush()?;
+        restore()
+    }
+
+    fn poll(&mut self, timeout: Duration) -> io::Result<bool> {
+        event::poll(timeout)
+    }
+
+    fn read(&mut self) -> io::Result<event::Event> {
+        event::read()
+    }
+}
+
+impl Write for Crossterm {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.out.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
@@ -0,0 +1,566 @@
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    io::{self, ErrorKind, Write},
+    rc::Rc,
+    time::Duration,
+};
+
+use crossterm::{event::Event, style::Color};
+use unicode_width::UnicodeWidthChar;
+
+use super::Terminal;
+
+// The attributes and colors a cell was drawn with.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Style {
+    pub foreground: Option<Color>,
+    pub background: Option<Color>,
+    pub bold: bool,
+    pub dim: bool,
+    pub italic: bool,
+    pub underlined: bool,
+    pub reverse: bool,
+}
+
+// What one column of a row shows. A wide character takes its cell and the one
+// after it, which is left with an empty symbol.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Cell {
+    pub symbol: String,
+    pub style: Style,
+}
+
+impl Cell {
+    fn blank(style: Style) -> Self {
+        Self {
+            symbol: " ".to_string(),
+            style: Style {
+                background: style.background,
+                ..Style::default()
+            },
+        }
+    }
+}
+
+// A terminal kept in memory, for running the editor without one. What's
+// written to it is interpreted into a grid of cells, and events are read from
+// a queue filled in beforehand; with the queue empty, poll() says there is
+// nothing to read rather than waiting. Clones share the same terminal, so one
+// can be handed to the editor while another is kept to look at.
+//
+// Only what the editor writes is understood: cursor movement, erasing,
+// scrolling regions, colors and attributes, and titles and clipboard copies.
+// Text running past the right edge is dropped instead of wrapping.
+#[derive(Clone)]
+pub struct Memory {
+    state: Rc<RefCell<State>>,
+}
+
+struct State {
+    size: (u16, u16),
+    cells: Vec<Vec<Cell>>,
+    // Column and row. The column is the width once the last column is written.
+    cursor: (u16, u16),
+    saved: (u16, u16),
+    cursor_visible: bool,
+    style: Style,
+    // First and last row scrolling happens in.
+    region: (u16, u16),
+    events: VecDeque<Event>,
+    entered: bool,
+    mouse: bool,
+    title: String,
+    clipboard: Option<String>,
+    bells: usize,
+    output: Vec<u8>,
+    // An escape sequence or character split between writes.
+    pending: Vec<u8>,
+}
+
+impl Memory {
+    pub fn new(width: u16, height: u16) -> Self {
+        let state = State {
+            size: (width, height),
+            cells: vec![vec![Cell::blank(Style::default()); width as usize]; height as usize],
+            cursor: (0, 0),
+            saved: (0, 0),
+            cursor_visible: true,
+            style: Style::default(),
+            region: (0, height.saturating_sub(1)),
+            events: VecDeque::new(),
+            entered: false,
+            mouse: false,
+            title: String::new(),
+            clipboard: None,
+            bells: 0,
+            output: Vec::new(),
+            pending: Vec::new(),
+        };
+
+        Self {
+            state: Rc::new(RefCell::new(state)),
+        }
+    }
+
+    // Queues an event for the editor to read.
+    pub fn push(&self, event: Event) {
+        self.state.borrow_mut().events.push_back(event);
+    }
+
+    // Events queued but not read yet.
+    pub fn pending(&self) -> usize {
+        self.state.borrow().events.len()
+    }
+
+    // Changes the size and queues the resize event a terminal would send.
+    // What's on screen is kept where it still fits.
+    pub fn resize(&self, width: u16, height: u16) {
+        let mut state = self.state.borrow_mut();
+        state.size = (width, height);
+        state.region = (0, height.saturating_sub(1));
+        state.cells.resize(
+            height as usize,
+            vec![Cell::blank(Style::default()); width as usize],
+        );
+        for row in &mut state.cells {
+            row.resize(width as usize, Cell::blank(Style::default()));
+        }
+        state.cursor.0 = state.cursor.0.min(width);
+        state.cursor.1 = state.cursor.1.min(height.saturating_sub(1));
+        state.events.push_back(Event::Resize(width, height));
+    }
+
+    pub fn cell(&self, column: u16, row: u16) -> Cell {
+        self.state.borrow().cells[row as usize][column as usize].clone()
+    }
+
+    // The text a row shows, trailing blanks included.
+    pub fn row(&self, row: u16) -> String {
+        self.state.borrow().cells[row as usize]
+            .iter()
+            .map(|cell| cell.symbol.as_str())
+            .collect()
+    }
+
+    pub fn rows(&self) -> Vec<String> {
+        (0..self.state.borrow().size.1)
+            .map(|row| self.row(row))
+            .collect()
+    }
+
+    pub fn cursor(&self) -> (u16, u16) {
+        self.state.borrow().cursor
+    }
+
+    pub fn cursor_visible(&self) -> bool {
+        self.state.borrow().cursor_visible
+    }
+
+    // Whether the editor is between enter() and leave().
+    pub fn entered(&self) -> bool {
+        self.state.borrow().entered
+    }
+
+    pub fn mouse(&self) -> bool {
+        self.state.borrow().mouse
+    }
+
+    pub fn title(&self) -> String {
+        self.state.borrow().title.clone()
+    }
+
+    // The text of the last OSC 52 copy.
+    pub fn clipboard(&self) -> Option<String> {
+        self.state.borrow().clipboard.clone()
+    }
+
+    pub fn bells(&self) -> usize {
+        self.state.borrow().bells
+    }
+
+    // Every byte written so far, escape sequences and all, and forgets them.
+    pub fn take_output(&self) -> Vec<u8> {
+        std::mem::take(&mut self.state.borrow_mut().output)
+    }
+}
+
+impl Terminal for Memory {
+    fn size(&self) -> io::Result<(u16, u16)> {
+        Ok(self.state.borrow().size)
+    }
+
+    fn enter(&mut self, mouse: bool) -> io::Result<()> {
+        let mut state = self.state.borrow_mut();
+        state.entered = true;
+        state.mouse = mouse;
+        Ok(())
+    }
+
+    fn leave(&mut self) -> io::Result<()> {
+        let mut state = self.state.borrow_mut();
+        state.entered = false;
+        state.mouse = false;
+        Ok(())
+    }
+
+    fn poll(&mut self, _: Duration) -> io::Result<bool> {
+        Ok(!self.state.borrow().events.is_empty())
+    }
+
+    fn read(&mut self) -> io::Result<Event> {
+        self.state
+            .borrow_mut()
+            .events
+            .pop_front()
+            .ok_or_else(|| io::Error::new(ErrorKind::UnexpectedEof, "no events left to read"))
+    }
+}
+
+impl Write for Memory {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut state = self.state.borrow_mut();
+        state.output.extend_from_slice(buf);
+        state.pending.extend_from_slice(buf);
+        state.interpret();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl State {
+    // Takes everything complete off the front of `pending`.
+    fn interpret(&mut self) {
+        let pending = std::mem::take(&mut self.pending);
+        let mut at = 0;
+
+        while at < pending.len() {
+            let rest = &pending[at..];
+            let Some(used) = self.next(rest) else {
+                break;
+            };
+            at += used;
+        }
+
+        self.pending = pending[at..].to_vec();
+    }
+
+    // Handles the character, control or sequence `bytes` starts with, and
+    // returns how many bytes it took, or None when it's cut off.
+    fn next(&mut self, bytes: &[u8]) -> Option<usize> {
+        match bytes[0] {
+            0x1b => match *bytes.get(1)? {
+                b'[' => {
+                    let end = 2 + bytes[2..].iter().position(|b| (0x40..=0x7e).contains(b))?;
+                    let params = String::from_utf8_lossy(&bytes[2..end]).into_owned();
+                    self.csi(&params, bytes[end]);
+                    Some(end + 1)
+                }
+                b']' => {
+                    let body = &bytes[2..];
+                    let (end, terminator) =
+                        body.iter().enumerate().find_map(|(i, &b)| match b {
+                            0x07 => Some((i, 1)),
+                            0x1b if body.get(i + 1) == Some(&b'\\') => Some((i, 2)),
+                            _ => None,
+                        })?;
+                    self.osc(&String::from_utf8_lossy(&body[..end]));
+                    Some(2 + end + terminator)
+                }
+                b'7' => {
+                    self.saved = self.cursor;
+                    Some(2)
+                }
+                b'8' => {
+                    self.cursor = self.saved;
+                    Some(2)
+                }
+                _ => Some(2),
+            },
+            b'\r' => {
+                self.cursor.0 = 0;
+                Some(1)
+            }
+            b'\n' => {
+                self.line_feed();
+                Some(1)
+            }
+            0x08 => {
+                self.cursor.0 = self.cursor.0.saturating_sub(1);
+                Some(1)
+            }
+            b'\t' => {
+                self.cursor.0 = (self.cursor.0 / 8 + 1).saturating_mul(8).min(self.size.0);
+                Some(1)
+            }
+            0x07 => {
+                self.bells += 1;
+                Some(1)
+            }
+            byte if byte < 0x20 || byte == 0x7f => Some(1),
+            byte => {
+                let len = match byte {
+                    0xf0.. => 4,
+                    0xe0.. => 3,
+                    0xc0.. => 2,
+                    _ => 1,
+                };
+                let encoded = bytes.get(..len)?;
+                let c = std::str::from_utf8(encoded)
+                    .ok()
+                    .and_then(|s| s.chars().next())
+                    .unwrap_or(char::REPLACEMENT_CHARACTER);
+                self.put(c);
+                Some(len)
+            }
+        }
+    }
+
+    fn put(&mut self, c: char) {
+        let (width, height) = (self.size.0 as usize, self.size.1 as usize);
+        let (column, row) = (self.cursor.0 as usize, self.cursor.1 as usize);
+        if row >= height {
+            return;
+        }
+
+        let cells = &mut self.cells[row];
+        let wide = c.width().unwrap_or(0);
+        // Joins the character before it, skipping the empty half of a wide
+        // one; so does anything after a zero width joiner, as in emoji
+        // sequences.
+        let joined = cells[..column.min(width)]
+            .iter_mut()
+            .rev()
+            .find(|cell| !cell.symbol.is_empty());
+        match joined {
+            Some(cell) if wide == 0 || cell.symbol.ends_with('\u{200d}') => {
+                cell.symbol.push(c);
+                return;
+            }
+            None if wide == 0 => return,
+            _ => {}
+        }
+        if column + wide > width {
+            self.cursor.0 = self.size.0;
+            return;
+        }
+
+        // Overwriting half of a wide character blanks the other half.
+        if cells[column].symbol.is_empty() && column > 0 {
+            cells[column - 1] = Cell::blank(self.style);
+        }
+        let end = column + wide;
+        if end < width && cells[end].symbol.is_empty() {
+            cells[end] = Cell::blank(self.style);
+        }
+
+        cells[column] = Cell {
+            symbol: c.to_string(),
+            style: self.style,
+        };
+        if wide == 2 {
+            cells[column + 1] = Cell {
+                symbol: String::new(),
+                style: self.style,
+            };
+        }
+        self.cursor.0 = end as u16;
+    }
+
+    fn line_feed(&mut self) {
+        if self.cursor.1 == self.region.1 {
+            self.scroll(1);
+        } else if self.cursor.1 + 1 < self.size.1 {
+            self.cursor.1 += 1;
+        }
+    }
+
+    // Moves the rows of the scrolling region up by `lines`, or down when
+    // negative, blanking the ones that come into view.
+    fn scroll(&mut self, lines: isize) {
+        let (top, bottom) = (self.region.0 as usize, self.region.1 as usize);
+        if bottom >= self.cells.len() || top > bottom {
+            return;
+        }
+
+        let region = &mut self.cells[top..=bottom];
+        let count = lines.unsigned_abs().min(region.len());
+        let blank = vec![Cell::blank(self.style); self.size.0 as usize];
+        if lines > 0 {
+            region.rotate_left(count);
+            let len = region.len();
+            region[len - count..].fill(blank);
+        } else {
+            region.rotate_right(count);
+            region[..count].fill(blank);
+        }
+    }
+
+    fn erase(&mut self, row: usize, columns: std::ops::Range<usize>) {
+        let blank = Cell::blank(self.style);
+        if let Some(cells) = self.cells.get_mut(row) {
+            let end = columns.end.min(cells.len());
+            cells[columns.start.min(end)..end].fill(blank);
+        }
+    }
+
+    fn csi(&mut self, params: &str, function: u8) {
+        let private = params.starts_with('?');
+        let numbers: Vec<usize> = params
+            .trim_start_matches('?')
+            .split([';', ':'])
+            .map(|p| p.trim().parse().unwrap_or(0))
+            .collect();
+        let first = numbers.first().copied().unwrap_or(0);
+        let count = first.max(1) as u16;
+        let (width, height) = self.size;
+
+        match function {
+            b'H' | b'f' => {
+                let row = first.max(1) - 1;
+                let column = numbers.get(1).copied().unwrap_or(1).max(1) - 1;
+                self.cursor = (
+                    (column as u16).min(width.saturating_sub(1)),
+                    (row as u16).min(height.saturating_sub(1)),
+                );
+            }
+            b'G' => self.cursor.0 = (count - 1).min(width.saturating_sub(1)),
+            b'd' => self.cursor.1 = (count - 1).min(height.saturating_sub(1)),
+            b'A' => self.cursor.1 = self.cursor.1.saturating_sub(count),
+            b'B' => self.cursor.1 = (self.cursor.1 + count).min(height.saturating_sub(1)),
+            b'C' => self.cursor.0 = (self.cursor.0 + count).min(width.saturating_sub(1)),
+            b'D' => self.cursor.0 = self.cursor.0.saturating_sub(count),
+            b'K' => {
+                let (column, row) = (self.cursor.0 as usize, self.cursor.1 as usize);
+                let columns = match first {
+                    0 => column..width as usize,
+                    1 => 0..column + 1,
+                    _ => 0..width as usize,
+                };
+                self.erase(row, columns);
+            }
+            b'J' => {
+                let (column, row) = (self.cursor.0 as usize, self.cursor.1 as usize);
+                let rows = match first {
+                    0 => {
+                        self.erase(row, column..width as usize);
+                        row + 1..height as usize
+                    }
+                    1 => {
+                        self.erase(row, 0..column + 1);
+                        0..row
+                    }
+                    _ => 0..height as usize,
+                };
+                for row in rows {
+                    self.erase(row, 0..width as usize);
+                }
+            }
+            b'r' => {
+                let top = first.max(1) - 1;
+                let bottom = numbers
+                    .get(1)
+                    .copied()
+                    .filter(|&b| b > 0)
+                    .unwrap_or(height as usize)
+                    - 1;
+                self.region = (top as u16, (bottom as u16).min(height.saturating_sub(1)));
+                self.cursor = (0, 0);
+            }
+            b'S' => self.scroll(count as isize),
+            b'T' => self.scroll(-(count as isize)),
+            b'm' => self.sgr(&numbers),
+            b'h' | b'l' if private && first == 25 => self.cursor_visible = function == b'h',
+            _ => {}
+        }
+    }
+
+    fn sgr(&mut self, numbers: &[usize]) {
+        let mut numbers = numbers.iter().copied();
+
+        while let Some(n) = numbers.next() {
+            let style = &mut self.style;
+            match n {
+                0 => *style = Style::default(),
+                1 => style.bold = true,
+                2 => style.dim = true,
+                3 => style.italic = true,
+                4 => style.underlined = true,
+                7 => style.reverse = true,
+                22 => (style.bold, style.dim) = (false, false),
+                23 => style.italic = false,
+                24 => style.underlined = false,
+                27 => style.reverse = false,
+                30..=37 => style.foreground = Some(ansi(n - 30)),
+                90..=97 => style.foreground = Some(ansi(n - 90 + 8)),
+                40..=47 => style.background = Some(ansi(n - 40)),
+                100..=107 => style.background = Some(ansi(n - 100 + 8)),
+                38 | 48 | 58 => {
+                    let color = match numbers.next() {
+                        Some(5) => numbers.next().map(|n| format!("5;{n}")),
+                        Some(2) => {
+                            let rgb: Vec<String> =
+                                numbers.by_ref().take(3).map(|n| n.to_string()).collect();
+                            Some(format!("2;{}", rgb.join(";")))
+                        }
+                        _ => None,
+                    }
+                    .and_then(|code| Color::parse_ansi(&code));
+                    match n {
+                        38 => style.foreground = color,
+                        48 => style.background = color,
+                        _ => {}
+                    }
+                }
+                39 => style.foreground = None,
+                49 => style.background = None,
+                _ => {}
+            }
+        }
+    }
+
+    // OSC 0 and 2 set the title, and OSC 52 copies to the clipboard.
+    fn osc(&mut self, body: &str) {
+        let (code, rest) = body.split_once(';').unwrap_or((body, ""));
+        match code {
+            "0" | "2" => self.title = rest.to_string(),
+            "52" => {
+                let data = rest.split_once(';').map_or(rest, |(_, data)| data);
+                self.clipboard = decode_base64(data).and_then(|b| String::from_utf8(b).ok());
+            }
+            _ => {}
+        }
+    }
+}
+
+fn ansi(n: usize) -> Color {
+    Color::parse_ansi(&format!("5;{n}")).unwrap_or(Color::Reset)
+}
+
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut bits = 0u32;
+    let mut count = 0;
+
+    for c in text.bytes().take_while(|&c| c != b'=') {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        bits = (bits << 6) | value as u32;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            bytes.push((bits >> count) as u8);
+        }
+    }
+
+    Some(bytes)
+}
@@ -0,0 +1,228 @@
+use std::env;
+
+use crossterm::style::Color::{self, *};
+
+use crate::highlight::Group;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum ColorMode {
+    Never,
+    Auto,
+    Always,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum ThemeChoice {
+    Auto,
+    Dark,
+    Light,
+}
+
+// Indexed by highlight::Group, in the order of its variants.
+const DARK: [Color; 8] = [
+    Magenta, Green, DarkGrey, Blue, Yellow, Cyan, DarkYellow, DarkCyan,
+];
+
+// Yellow and the bright colors wash out on white, so light backgrounds get the
+// darker variants only.
+const LIGHT: [Color; 8] = [
+    DarkMagenta,
+    DarkGreen,
+    DarkGrey,
+    DarkBlue,
+    DarkRed,
+    DarkCyan,
+    DarkYellow,
+    DarkCyan,
+];
+
+// How long to wait for the terminal to report its background.
+#[cfg(unix)]
+const QUERY_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(100);
+
+// Every color the editor draws with is looked up here. Without colors the UI
+// relies on reverse video alone, so nothing should need a color to be usable.
+#[derive(Clone, Copy)]
+pub struct Theme {
+    colors: bool,
+    choice: ThemeChoice,
+    light: bool,
+    // Syntax colors from the config file, used on either background.
+    syntax: [Option<Color>; 8],
+}
+
+impl Theme {
+    // `auto` follows the NO_COLOR convention: any non-empty value turns colors off.
+    pub fn new(mode: ColorMode, choice: ThemeChoice) -> Self {
+        let colors = match mode {
+            ColorMode::Never => false,
+            ColorMode::Always => true,
+            ColorMode::Auto => env::var_os("NO_COLOR").is_none_or(|v| v.is_empty()),
+        };
+
+        Self {
+            colors,
+            choice,
+            light: choice == ThemeChoice::Light,
+            syntax: [None; 8],
+        }
+    }
+
+    // Asks the terminal for its background when the choice is left to us. Needs
+    // raw mode, and has to run before anything else reads terminal input.
+    pub fn detect_background(&mut self) {
+        if self.choice != ThemeChoice::Auto || !self.colors {
+            return;
+        }
+
+        self.light = query_background()
+            .or_else(background_from_env)
+            .unwrap_or(false);
+    }
+
+    // Background for text an operation just changed. Without colors it's shown
+    // in reverse video instead.
+    pub fn flash(&self) -> Option<Color> {
+        let color = if self.light { Grey } else { DarkGrey };
+        self.colors.then_some(color)
+    }
+
+    // Background for search matches, reverse video without colors.
+    pub fn search_match(&self) -> Option<Color> {
+        let color = if self.light { Yellow } else { DarkYellow };
+        self.colors.then_some(color)
+    }
+
+    // Foreground for the line numbers in the gutter, dimmed without colors.
+    pub fn line_number(&self) -> Option<Color> {
+        let color = if self.light { Grey } else { DarkGrey };
+        self.colors.then_some(color)
+    }
+
+    // Background for selected text, reverse video without colors.
+    pub fn selection(&self) -> Option<Color> {
+        let color = if self.light { Cyan } else { DarkBlue };
+        self.colors.then_some(color)
+    }
+
+    // For the status message when something failed, e.g. a save. Without
+    // colors the message is shown in bold instead.
+    pub fn error(&self) -> Option<Color> {
+        let color = if self.light { Red } else { DarkRed };
+        self.colors.then_some(color)
+    }
+
+    // Foregrounds for the lines a diff adds and removes.
+    pub fn diff_added(&self) -> Option<Color> {
+        let color = if self.light { DarkGreen } else { Green };
+        self.colors.then_some(color)
+    }
+
+    pub fn diff_removed(&self) -> Option<Color> {
+        let color = if self.light { DarkRed } else { Red };
+        self.colors.then_some(color)
+    }
+
+    pub fn syntax(&self, group: Group) -> Option<Color> {
+        let palette = if self.light { &LIGHT } else { &DARK };
+        let color = self.syntax[group as usize].unwrap_or(palette[group as usize]);
+        self.colors.then_some(color)
+    }
+
+    pub fn set_syntax(&mut self, group: Group, color: Color) {
+        self.syntax[group as usize] = Some(color);
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::new(ColorMode::Auto, ThemeChoice::Dark)
+    }
+}
+
+// COLORFGBG is "fg;bg" (sometimes "fg;default;bg") with ANSI color numbers.
+fn background_from_env() -> Option<bool> {
+    let value = env::var("COLORFGBG").ok()?;
+    let background: u8 = value.rsplit(';').next()?.parse().ok()?;
+    Some(matches!(background, 7 | 9..=15))
+}
+
+// Parses an OSC 11 reply like `\x1b]11;rgb:ffff/ffff/dddd\x1b\\`.
+fn parse_background(reply: &str) -> Option<bool> {
+    let rgb = &reply[reply.find("rgb:")? + 4..];
+    let channels: Vec<f64> = rgb
+        .split(['/', '\x1b', '\x07'])
+        .take(3)
+        .map(|c| {
+            if c.len() > 4 {
+                return None;
+            }
+            let value = u32::from_str_radix(c, 16).ok()?;
+            Some(value as f64 / ((1u64 << (4 * c.len())) - 1) as f64)
+        })
+        .collect::<Option<_>>()?;
+
+    let [r, g, b] = channels[..] else {
+        return None;
+    };
+    Some(0.2126 * r + 0.7152 * g + 0.0722 * b > 0.5)
+}
+
+// Sends OSC 11 followed by a device attributes request, which every terminal
+// answers. Reading stops at that answer, so terminals that ignore OSC 11 don't
+// cost the whole timeout and no keys typed afterwards are consumed.
+#[cfg(unix)]
+fn query_background() -> Option<bool> {
+    use std::{
+        fs::OpenOptions,
+        io::{Read, Write},
+        os::fd::AsRawFd,
+        time::Instant,
+    };
+
+    let mut tty = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .ok()?;
+    tty.write_all(b"\x1b]11;?\x1b\\\x1b[c").ok()?;
+    tty.flush().ok()?;
+
+    let deadline = Instant::now() + QUERY_TIMEOUT;
+    let mut reply = Vec::new();
+
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+
+        let mut poll = libc::pollfd {
+            fd: tty.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: polls a single descriptor owned by `tty` for the duration of the call.
+        if unsafe { libc::poll(&mut poll, 1, remaining.as_millis() as libc::c_int) } <= 0 {
+            break;
+        }
+
+        let mut chunk = [0; 64];
+        let read = tty.read(&mut chunk).ok()?;
+        reply.extend_from_slice(&chunk[..read]);
+
+        let text = String::from_utf8_lossy(&reply);
+        if let Some(start) = text.find("\x1b[?") {
+            if text[start..].contains('c') {
+                break;
+            }
+        }
+    }
+
+    parse_background(&String::from_utf8_lossy(&reply))
+}
+
+#[cfg(not(unix))]
+fn query_background() -> Option<bool> {
+    None
+}
@@ -0,0 +1,273 @@
+use ropey::Rope;
+
+#[derive(Clone, Copy)]
+pub struct UndoLimits {
+    pub max_entries: usize,
+    // Approximate, counting each snapshot's full text. A single snapshot bigger
+    // than this is not kept at all.
+    pub max_bytes: usize,
+}
+
+impl Default for UndoLimits {
+    fn default() -> Self {
+        Self {
+            max_entries: 100,
+            max_bytes: 64 * 1024 * 1024,
+        }
+    }
+}
+
+// Goes back into the buffer along with the text.
+#[derive(Clone, Copy)]
+pub struct Restore {
+    pub modified: bool,
+    pub scroll: usize,
+    // (line, char column)
+    pub cursor: (usize, usize),
+}
+
+pub enum Change {
+    // The whole text, for operations that replace it wholesale.
+    Contents(Rope),
+    // An edit at a char offset, kept as what it removed and inserted there.
+    Edit {
+        at: usize,
+        removed: String,
+        inserted: String,
+    },
+}
+
+impl Change {
+    fn bytes(&self) -> usize {
+        match self {
+            Change::Contents(rope) => rope.len_bytes(),
+            Change::Edit {
+                removed, inserted, ..
+            } => removed.len() + inserted.len(),
+        }
+    }
+
+    // Reverts the change in `rope` and returns the change that redoes it.
+    fn revert(self, rope: &mut Rope) -> Change {
+        match self {
+            Change::Contents(contents) => Change::Contents(std::mem::replace(rope, contents)),
+            Change::Edit {
+                at,
+                removed,
+                inserted,
+            } => {
+                rope.remove(at..at + inserted.chars().count());
+                rope.insert(at, &removed);
+                Change::Edit {
+                    at,
+                    removed: inserted,
+                    inserted: removed,
+                }
+            }
+        }
+    }
+
+    // Typing a character right after the one before, on the same line, extends
+    // that edit instead of starting a new one.
+    fn extend(&mut self, next: &Change) -> bool {
+        let (
+            Change::Edit {
+                at,
+                removed,
+                inserted,
+            },
+            Change::Edit {
+                at: next_at,
+                removed: next_removed,
+                inserted: next_inserted,
+            },
+        ) = (self, next)
+        else {
+            return false;
+        };
+
+        let typed = |text: &str| text.chars().count() == 1 && !text.contains(['\r', '\n']);
+        if !removed.is_empty()
+            || !next_removed.is_empty()
+            || !typed(next_inserted)
+            || inserted.contains(['\r', '\n'])
+            || *at + inserted.chars().count() != *next_at
+        {
+            return false;
+        }
+
+        inserted.push_str(next_inserted);
+        true
+    }
+}
+
+struct Entry {
+    change: Change,
+    restore: Restore,
+    // Edit generation the buffer has to be at for the entry to apply.
+    generation: u64,
+}
+
+pub enum UndoError {
+    Empty,
+    Diverged,
+}
+
+#[derive(Default)]
+pub struct UndoStack {
+    entries: Vec<Entry>,
+    // Undone entries, newest last, until the next edit.
+    redo: Vec<Entry>,
+    bytes: usize,
+    limits: UndoLimits,
+}
+
+impl UndoStack {
+    pub fn set_limits(&mut self, limits: UndoLimits) {
+        self.limits = limits;
+        self.evict();
+    }
+
+    pub fn fits(&self, rope: &Rope) -> bool {
+        rope.len_bytes() <= self.limits.max_bytes && self.limits.max_entries > 0
+    }
+
+    // Records an operation that took the buffer from generation `from` to `to`.
+    // Anything undone before it can't be redone anymore.
+    pub fn push(&mut self, change: Change, restore: Restore, from: u64, to: u64) {
+        self.discard_redo();
+
+        if let Some(last) = self.entries.last_mut() {
+            if last.generation == from {
+                let before = last.change.bytes();
+                if last.change.extend(&change) {
+                    self.bytes = self.bytes - before + last.change.bytes();
+                    last.generation = to;
+                    return;
+                }
+            }
+        }
+
+        self.bytes += change.bytes();
+        self.entries.push(Entry {
+            change,
+            restore,
+            generation: to,
+        });
+        self.evict();
+    }
+
+    pub fn discard_redo(&mut self) {
+        for entry in self.redo.drain(..) {
+            self.bytes -= entry.change.bytes();
+        }
+    }
+
+    // Only the oldest entries go, so everything left is still a contiguous
+    // history ending at the current state.
+    fn evict(&mut self) {
+        let excess = self.entries.len().saturating_sub(self.limits.max_entries);
+        self.remove_oldest(excess);
+
+        while self.bytes > self.limits.max_bytes && !self.entries.is_empty() {
+            self.remove_oldest(1);
+        }
+    }
+
+    fn remove_oldest(&mut self, count: usize) {
+        for entry in self.entries.drain(..count) {
+            self.bytes -= entry.change.bytes();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.redo.clear();
+        self.bytes = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len() + self.redo.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty() && self.redo.is_empty()
+    }
+
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    // Entries can only be applied while the buffer is exactly as the operation
+    // left it; otherwise the edits made since would be lost. `current` is what
+    // to restore when the undo is redone, and `next` the generation the buffer
+    // has afterwards.
+    pub fn undo(
+        &mut self,
+        rope: &mut Rope,
+        current: Restore,
+        generation: u64,
+        next: u64,
+    ) -> Result<(Restore, &Change), UndoError> {
+        self.travel(false, rope, current, generation, next)
+    }
+
+    pub fn redo(
+        &mut self,
+        rope: &mut Rope,
+        current: Restore,
+        generation: u64,
+        next: u64,
+    ) -> Result<(Restore, &Change), UndoError> {
+        self.travel(true, rope, current, generation, next)
+    }
+
+    // Undo and redo are the same move in opposite directions: the entry is
+    // reverted and its reverse goes on the other stack. That reverse is
+    // returned too, since it describes the change just made to `rope`.
+    fn travel(
+        &mut self,
+        redo: bool,
+        rope: &mut Rope,
+        current: Restore,
+        generation: u64,
+        next: u64,
+    ) -> Result<(Restore, &Change), UndoError> {
+        let last = if redo {
+            self.redo.last()
+        } else {
+            self.entries.last()
+        };
+
+        match last {
+            None => return Err(UndoError::Empty),
+            Some(entry) if entry.generation != generation => {
+                self.clear();
+                return Err(UndoError::Diverged);
+            }
+            Some(_) => {}
+        }
+
+        let (from, to) = if redo {
+            (&mut self.redo, &mut self.entries)
+        } else {
+            (&mut self.entries, &mut self.redo)
+        };
+        let entry = from.pop().unwrap();
+        let before = entry.change.bytes();
+        let change = entry.change.revert(rope);
+        self.bytes = self.bytes - before + change.bytes();
+
+        // The entries below are back to matching the buffer.
+        if let Some(below) = from.last_mut() {
+            below.generation = next;
+        }
+        to.push(Entry {
+            change,
+            restore: current,
+            generation: next,
+        });
+
+        Ok((entry.restore, &to.last().unwrap().change))
+    }
+}
@@ -0,0 +1,427 @@
+use std::ops::Range;
+
+// Rows and columns taken away from the text area by other UI.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Chrome {
+    pub top: u16,
+    pub bottom: u16,
+    pub left: u16,
+    pub right: u16,
+}
+
+// A screen row of buffer text: the line it shows and which of the line's
+// display columns. `continued` rows carry on a line wrapped from the row above.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Row {
+    pub line: usize,
+    pub columns: Range<usize>,
+    pub continued: bool,
+}
+
+// The part of the terminal showing buffer text. All conversions between buffer
+// lines and screen rows go through here, so a new piece of chrome only has to
+// be accounted for once.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Viewport {
+    pub top: u16,
+    pub left: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl Viewport {
+    // The text area is never smaller than one cell, however much chrome there is.
+    pub fn new(terminal: (u16, u16), chrome: Chrome) -> Self {
+        let (columns, rows) = terminal;
+        let top = chrome.top.min(rows.saturating_sub(1));
+        let left = chrome.left.min(columns.saturating_sub(1));
+
+        Self {
+            top,
+            left,
+            width: columns
+                .saturating_sub(left)
+                .saturating_sub(chrome.right)
+                .max(1),
+            height: rows
+                .saturating_sub(top)
+                .saturating_sub(chrome.bottom)
+                .max(1),
+        }
+    }
+
+    pub fn bottom_row(&self) -> u16 {
+        self.top + self.height - 1
+    }
+
+    pub fn buffer_line_to_screen_row(&self, line: usize, scroll: usize) -> Option<u16> {
+        self.is_line_visible(line, scroll)
+            .then(|| self.top + (line - scroll) as u16)
+    }
+
+    pub fn is_line_visible(&self, line: usize, scroll: usize) -> bool {
+        line >= scroll && line < scroll.saturating_add(self.height as usize)
+    }
+
+    // Counted without walking the lines, and empty for a scroll past the end.
+    pub fn visible_lines(&self, scroll: usize, line_count: usize) -> Range<usize> {
+        let start = scroll.min(line_count);
+        start..scroll.saturating_add(self.height as usize).min(line_count)
+    }
+
+    // One row per visible line, scrolled `h_scroll` columns to the right.
+    pub fn rows(&self, scroll: usize, h_scroll: usize, line_count: usize) -> Vec<Row> {
+        self.visible_lines(scroll, line_count)
+            .map(|line| Row {
+                line,
+                columns: self.visible_columns(h_scroll),
+                continued: false,
+            })
+            .collect()
+    }
+
+    // The rows of wrapped lines from row `skip` of line `scroll` on, where
+    // `starts` gives the display columns each row of a line starts at.
+    pub fn wrapped_rows(
+        &self,
+        scroll: usize,
+        skip: usize,
+        line_count: usize,
+        mut starts: impl FnMut(usize) -> Vec<usize>,
+    ) -> Vec<Row> {
+        let height = self.height as usize;
+        let mut rows = Vec::with_capacity(height);
+
+        for line in scroll..line_count {
+            let starts = starts(line);
+            let skipped = if line == scroll { skip } else { 0 };
+            for (i, &start) in starts.iter().enumerate().skip(skipped) {
+                if rows.len() == height {
+                    return rows;
+                }
+                let end = starts
+                    .get(i + 1)
+                    .copied()
+                    .unwrap_or(start + self.width as usize);
+                rows.push(Row {
+                    line,
+                    columns: start..end,
+                    continued: i > 0,
+                });
+            }
+        }
+
+        rows
+    }
+
+    // Lines one Page Up or Page Down moves, keeping `overlap` lines of the
+    // previous screen in view. The overlap is capped so a page always moves.
+    pub fn page_lines(&self, overlap: usize, half: bool) -> usize {
+        let height = self.height as usize;
+        let page = if half {
+            height / 2
+        } else {
+            height - overlap.min(height - 1)
+        };
+        page.max(1)
+    }
+
+    // Scrolling further would only show empty rows below the last line.
+    pub fn max_scroll(&self, line_count: usize) -> usize {
+        line_count.saturating_sub(self.height as usize)
+    }
+
+    // Display columns of a line that are on screen when scrolled `h_scroll`
+    // columns to the right.
+    pub fn visible_columns(&self, h_scroll: usize) -> Range<usize> {
+        h_scroll..h_scroll + self.width as usize
+    }
+
+    pub fn column_to_screen(&self, column: usize, h_scroll: usize) -> Option<u16> {
+        self.visible_columns(h_scroll)
+            .contains(&column)
+            .then(|| self.left + (column - h_scroll) as u16)
+    }
+
+    // The buffer line and display column under a screen cell, None outside the
+    // text area. The line may be past the end of the buffer.
+    pub fn screen_to_buffer(
+        &self,
+        (column, row): (u16, u16),
+        scroll: usize,
+        h_scroll: usize,
+    ) -> Option<(usize, usize)> {
+        let rows = self.top..=self.bottom_row();
+        if !rows.contains(&row) || column < self.left || column >= self.left + self.width {
+            return None;
+        }
+
+        Some((
+            scroll + (row - self.top) as usize,
+            h_scroll + (column - self.left) as usize,
+        ))
+    }
+
+    // The smallest change to `h_scroll` that brings `column` into view.
+    pub fn h_scroll_to_show(&self, column: usize, h_scroll: usize) -> usize {
+        if column < h_scroll {
+            column
+        } else if column >= h_scroll + self.width as usize {
+            column + 1 - self.width as usize
+        } else {
+            h_scroll
+        }
+    }
+
+    // The smallest change to `scroll` that brings `line` into view with
+    // `margin` lines around it, as far as the start and end of the buffer
+    // allow.
+    pub fn scroll_to_show(
+        &self,
+        line: usize,
+        scroll: usize,
+        margin: usize,
+        line_count: usize,
+    ) -> usize {
+        let height = self.height as usize;
+        let margin = self.fit_margin(margin);
+        if line < scroll + margin {
+            line.saturating_sub(margin)
+        } else if line + margin >= scroll + height {
+            (line + margin + 1 - height)
+                .min(self.max_scroll(line_count))
+                .max(scroll)
+        } else {
+            scroll
+        }
+    }
+
+    // A scroll margin shrunk to leave at least one line between the margins,
+    // for views too short for it.
+    pub fn fit_margin(&self, margin: usize) -> usize {
+        margin.min((self.height as usize).saturating_sub(1) / 2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The chrome the editor puts up, alone and stacked: the status bar, a
+    // line-number gutter, the location panel, and more than a small
+    // terminal has room for.
+    const CHROME: [Chrome; 6] = [
+        Chrome {
+            top: 0,
+            bottom: 0,
+            left: 0,
+            right: 0,
+        },
+        Chrome {
+            top: 0,
+            bottom: 1,
+            left: 0,
+            right: 0,
+        },
+        Chrome {
+            top: 0,
+            bottom: 1,
+            left: 4,
+            right: 0,
+        },
+        Chrome {
+            top: 0,
+            bottom: 8,
+            left: 3,
+            right: 0,
+        },
+        Chrome {
+            top: 1,
+            bottom: 1,
+            left: 2,
+            right: 1,
+        },
+        Chrome {
+            top: 30,
+            bottom: 30,
+            left: 100,
+            right: 100,
+        },
+    ];
+
+    const TERMINALS: [(u16, u16); 4] = [(80, 24), (20, 5), (1, 1), (200, 60)];
+
+    fn matrix() -> impl Iterator<Item = (Chrome, (u16, u16), Viewport)> {
+        CHROME.into_iter().flat_map(|chrome| {
+            TERMINALS
+                .into_iter()
+                .map(move |terminal| (chrome, terminal, Viewport::new(terminal, chrome)))
+        })
+    }
+
+    #[test]
+    fn the_text_area_fits_the_terminal() {
+        for (chrome, (columns, rows), view) in matrix() {
+            assert!(view.width >= 1 && view.height >= 1, "{chrome:?}");
+            assert!(view.left + view.width <= columns.max(1), "{chrome:?}");
+            assert!(view.bottom_row() < rows.max(1), "{chrome:?}");
+        }
+    }
+
+    #[test]
+    fn visible_lines_map_to_text_rows_and_back() {
+        for (chrome, _, view) in matrix() {
+            let scroll = 3;
+            for line in view.visible_lines(scroll, 100) {
+                let row = view.buffer_line_to_screen_row(line, scroll).unwrap();
+                assert!((view.top..=view.bottom_row()).contains(&row), "{chrome:?}");
+                let cell = (view.left, row);
+                assert_eq!(view.screen_to_buffer(cell, scroll, 0), Some((line, 0)));
+            }
+
+            let below = scroll + view.height as usize;
+            assert_eq!(view.buffer_line_to_screen_row(scroll - 1, scroll), None);
+            assert_eq!(view.buffer_line_to_screen_row(below, scroll), None);
+        }
+    }
+
+    #[test]
+    fn chrome_cells_are_not_text() {
+        for (chrome, _, view) in matrix() {
+            if view.top > 0 {
+                assert_eq!(view.screen_to_buffer((view.left, view.top - 1), 0, 0), None);
+            }
+            if view.left > 0 {
+                assert_eq!(view.screen_to_buffer((view.left - 1, view.top), 0, 0), None);
+            }
+            let below = view.bottom_row() + 1;
+            assert_eq!(view.screen_to_buffer((view.left, below), 0, 0), None);
+            let right = view.left + view.width;
+            assert_eq!(
+                view.screen_to_buffer((right, view.top), 0, 0),
+                None,
+                "{chrome:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn columns_map_to_cells_and_back() {
+        for (_, _, view) in matrix() {
+            let h_scroll = 5;
+            for column in view.visible_columns(h_scroll) {
+                let cell = view.column_to_screen(column, h_scroll).unwrap();
+                let back = view.screen_to_buffer((cell, view.top), 0, h_scroll);
+                assert_eq!(back, Some((0, column)));
+            }
+            assert_eq!(view.column_to_screen(h_scroll - 1, h_scroll), None);
+            let past = h_scroll + view.width as usize;
+            assert_eq!(view.column_to_screen(past, h_scroll), None);
+        }
+    }
+
+    #[test]
+    fn scrolling_stops_at_the_last_line() {
+        for (_, _, view) in matrix() {
+            let height = view.height as usize;
+            assert_eq!(view.max_scroll(height / 2), 0);
+            assert_eq!(view.max_scroll(height + 7), 7);
+
+            let last = view.visible_lines(view.max_scroll(height + 7), height + 7);
+            assert_eq!(last.end, height + 7);
+            assert_eq!(last.len(), height);
+        }
+    }
+
+    #[test]
+    fn visible_lines_stop_at_the_end_of_the_buffer() {
+        for (chrome, _, view) in matrix() {
+            let height = view.height as usize;
+
+            // An empty buffer still has its one empty line.
+            assert_eq!(view.visible_lines(0, 0), 0..0, "{chrome:?}");
+            assert_eq!(view.visible_lines(0, 1), 0..1, "{chrome:?}");
+
+            let short = height.div_ceil(2);
+            assert_eq!(view.visible_lines(0, short), 0..short, "{chrome:?}");
+            assert_eq!(view.visible_lines(0, height), 0..height, "{chrome:?}");
+            assert_eq!(view.visible_lines(1, height), 1..height, "{chrome:?}");
+            assert_eq!(view.visible_lines(0, height + 1), 0..height, "{chrome:?}");
+        }
+    }
+
+    #[test]
+    fn visible_lines_at_the_extremes_of_scrolling() {
+        for (chrome, _, view) in matrix() {
+            let height = view.height as usize;
+            let count = height * 3;
+
+            assert_eq!(view.visible_lines(0, count).len(), height, "{chrome:?}");
+            let last = view.visible_lines(count - 1, count);
+            assert_eq!(last, count - 1..count, "{chrome:?}");
+            assert!(view.visible_lines(count, count).is_empty(), "{chrome:?}");
+            assert!(
+                view.visible_lines(count + 5, count).is_empty(),
+                "{chrome:?}"
+            );
+
+            assert!(view.visible_lines(usize::MAX, count).is_empty());
+            assert!(!view.is_line_visible(0, usize::MAX));
+            assert!(view.is_line_visible(usize::MAX - 1, usize::MAX - 1));
+        }
+    }
+
+    #[test]
+    fn scroll_to_show_keeps_the_margin_in_view() {
+        for (chrome, _, view) in matrix() {
+            let margin = view.fit_margin(3);
+            for line in [0, 10, 50, 99] {
+                for scroll in [0, 40, 95] {
+                    let scroll = view.scroll_to_show(line, scroll, 3, 100);
+                    assert!(view.is_line_visible(line, scroll), "{chrome:?} {line}");
+                    let shown = view.visible_lines(scroll, 100);
+                    assert!(line >= shown.start + margin || shown.start == 0);
+                    assert!(line + margin < shown.end || shown.end == 100);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn a_page_always_moves() {
+        for (_, _, view) in matrix() {
+            let height = view.height as usize;
+            for overlap in [0, 2, 1000] {
+                let page = view.page_lines(overlap, false);
+                assert!(page >= 1 && page <= height);
+            }
+            assert_eq!(view.page_lines(0, false), height);
+            assert_eq!(view.page_lines(0, true), (height / 2).max(1));
+        }
+    }
+
+    #[test]
+    fn wrapped_rows_fill_the_height() {
+        let view = Viewport::new((10, 6), CHROME[1]);
+        let rows = view.wrapped_rows(1, 1, 4, |_| vec![0, 10, 20]);
+
+        assert_eq!(rows.len(), 5);
+        assert_eq!(
+            rows[0],
+            Row {
+                line: 1,
+                columns: 10..20,
+                continued: true
+            }
+        );
+        assert_eq!(
+            rows[2],
+            Row {
+                line: 2,
+                columns: 0..10,
+                continued: false
+            }
+        );
+        assert_eq!(rows[4].line, 2);
+    }
+}
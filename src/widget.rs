@@ -0,0 +1,281 @@
+// The editor as a pane of another program's screen. The host owns the
+// terminal: it enters and leaves raw mode, reads the events and hands them
+// over, and says where the pane goes. The editor draws into a grid of its own,
+// which render() paints into the pane's rectangle, so nothing outside it is
+// ever touched.
+//
+// Prompts the editor opens (search, save as, confirming a quit) wait for
+// their answer, and read it straight from the terminal while handle() hasn't
+// returned yet.
+
+use std::{
+    cell::Cell,
+    io::{self, Write},
+    path::Path,
+    rc::Rc,
+    time::Duration,
+};
+
+use crossterm::{
+    cursor::MoveTo,
+    event::{self, MouseEvent},
+    style::{Attribute, Print, ResetColor, SetAttribute, SetBackgroundColor, SetForegroundColor},
+    QueueableCommand,
+};
+use ropey::Rope;
+
+use crate::{
+    project::Project,
+    terminal::{memory::Style, Memory, Terminal},
+};
+
+// What a host needs to drive the pane. Event is crossterm's, so a host built
+// against another crossterm version still gets the one shark reads.
+pub use crate::{buffer::Buffer, editor::Editor};
+pub use crossterm::event::Event;
+
+// Where on the host's screen the pane goes, in columns and rows from the top
+// left corner.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rect {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl Rect {
+    pub fn new(x: u16, y: u16, width: u16, height: u16) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    fn contains(&self, column: u16, row: u16) -> bool {
+        (self.x..self.x + self.width).contains(&column)
+            && (self.y..self.y + self.height).contains(&row)
+    }
+}
+
+pub struct EditorView {
+    editor: Editor,
+    grid: Memory,
+    // Shared with the pane, which places the mouse events of prompts by it.
+    area: Rc<Cell<Rect>>,
+}
+
+impl EditorView {
+    // An editor on `buffer` as given, without reading anything from disk.
+    pub fn new(buffer: Buffer, area: Rect) -> io::Result<Self> {
+        Self::start(buffer, area, false)
+    }
+
+    // An editor on the file at `path`, which saving writes back to. Nothing
+    // there yet means a new, empty file.
+    pub fn open(path: &Path, area: Rect) -> io::Result<Self> {
+        let buffer = Buffer::new(Rope::new(), Some(path.to_string_lossy().into_owned()));
+        Self::start(buffer, area, true)
+    }
+
+    fn start(buffer: Buffer, area: Rect, load: bool) -> io::Result<Self> {
+        let grid = Memory::new(area.width.max(1), area.height.max(1));
+        let area = Rc::new(Cell::new(area));
+        let pane = Pane {
+            grid: grid.clone(),
+            area: area.clone(),
+        };
+        let mut editor = Editor::new(Box::new(pane), vec![buffer], Project::detect(false)?);
+        editor.init()?;
+        if load {
+            editor.load_buffers(&[0])?;
+        }
+
+        Ok(Self { editor, grid, area })
+    }
+
+    // For setting it up, e.g. with line numbers or a theme, and for reading
+    // the text back out.
+    pub fn editor(&self) -> &Editor {
+        &self.editor
+    }
+
+    pub fn editor_mut(&mut self) -> &mut Editor {
+        &mut self.editor
+    }
+
+    pub fn area(&self) -> Rect {
+        self.area.get()
+    }
+
+    // Moves or resizes the pane. Render afterwards, and have the host clear
+    // whatever the pane covered before.
+    pub fn set_area(&mut self, area: Rect) -> io::Result<()> {
+        self.area.set(area);
+        self.grid.resize(area.width.max(1), area.height.max(1));
+        self.run()?;
+        Ok(())
+    }
+
+    // Gives the editor one event read by the host. Mouse events are taken
+    // relative to the pane, and ones outside it are ignored, as are resizes of
+    // the whole terminal, which set_area() is for. Returns false once the
+    // editor quits, after which it shouldn't be given more.
+    pub fn handle(&mut self, event: Event) -> io::Result<bool> {
+        if let Some(event) = relative(event, self.area()) {
+            self.grid.push(event);
+        }
+        self.run()
+    }
+
+    // The work the editor does while idle, like highlighting what was just
+    // opened or noticing the file change on disk. Hosts call this between
+    // events; true means the pane changed and wants rendering.
+    pub fn tick(&mut self) -> io::Result<bool> {
+        self.grid.take_output();
+        self.editor.tick()?;
+        Ok(!self.grid.take_output().is_empty())
+    }
+
+    fn run(&mut self) -> io::Result<bool> {
+        while self.editor.poll(Duration::ZERO)? {
+            if !self.editor.step()? {
+                return Ok(false);
+            }
+        }
+        self.editor.tick()?;
+        Ok(true)
+    }
+
+    // Paints the whole pane into `out`, which is queued to, not flushed. The
+    // style is reset afterwards, and the cursor left wherever painting ended.
+    pub fn render(&self, out: &mut impl Write) -> io::Result<()> {
+        let area = self.area();
+        for (y, row) in (area.y..).zip(0..area.height) {
+            out.queue(MoveTo(area.x, y))?;
+            let mut current = None;
+
+            for column in 0..area.width {
+                let cell = self.grid.cell(column, row);
+                // The second column of a wide character.
+                if cell.symbol.is_empty() {
+                    continue;
+                }
+                if current != Some(cell.style) {
+                    apply(out, cell.style)?;
+                    current = Some(cell.style);
+                }
+                out.queue(Print(&cell.symbol))?;
+            }
+
+            out.queue(SetAttribute(Attribute::Reset))?;
+            out.queue(ResetColor)?;
+        }
+
+        Ok(())
+    }
+
+    // Where the host should show the cursor, on its screen; None while the
+    // editor has it hidden.
+    pub fn cursor(&self) -> Option<(u16, u16)> {
+        let (column, row) = self.grid.cursor();
+        let area = self.area();
+        let column = column.min(area.width.saturating_sub(1));
+        self.grid
+            .cursor_visible()
+            .then_some((area.x + column, area.y + row))
+    }
+}
+
+fn apply(out: &mut impl Write, style: Style) -> io::Result<()> {
+    out.queue(SetAttribute(Attribute::Reset))?;
+    out.queue(ResetColor)?;
+
+    let attributes = [
+        (style.bold, Attribute::Bold),
+        (style.dim, Attribute::Dim),
+        (style.italic, Attribute::Italic),
+        (style.underlined, Attribute::Underlined),
+        (style.reverse, Attribute::Reverse),
+    ];
+    for (_, attribute) in attributes.into_iter().filter(|(on, _)| *on) {
+        out.queue(SetAttribute(attribute))?;
+    }
+    if let Some(color) = style.foreground {
+        out.queue(SetForegroundColor(color))?;
+    }
+    if let Some(color) = style.background {
+        out.queue(SetBackgroundColor(color))?;
+    }
+
+    Ok(())
+}
+
+// The event as the pane sees it, None for the ones it shouldn't.
+fn relative(event: Event, area: Rect) -> Option<Event> {
+    match event {
+        Event::Mouse(mouse) if area.contains(mouse.column, mouse.row) => {
+            Some(Event::Mouse(MouseEvent {
+                column: mouse.column - area.x,
+                row: mouse.row - area.y,
+                ..mouse
+            }))
+        }
+        Event::Mouse(_) | Event::Resize(..) => None,
+        event => Some(event),
+    }
+}
+
+// What the editor runs on inside the pane: it draws into the grid, and
+// reads the events handle() queued, then the terminal's own for prompts.
+// The host set the terminal up, so entering and leaving change nothing.
+struct Pane {
+    grid: Memory,
+    area: Rc<Cell<Rect>>,
+}
+
+impl Write for Pane {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.grid.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.grid.flush()
+    }
+}
+
+impl Terminal for Pane {
+    fn size(&self) -> io::Result<(u16, u16)> {
+        self.grid.size()
+    }
+
+    fn enter(&mut self, _: bool) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn leave(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    // Only the queue, so that the editor catching up never takes the events
+    // the host hasn't read yet.
+    fn poll(&mut self, timeout: Duration) -> io::Result<bool> {
+        self.grid.poll(timeout)
+    }
+
+    fn read(&mut self) -> io::Result<Event> {
+        if self.grid.pending() > 0 {
+            return self.grid.read();
+        }
+
+        let area = self.area.get();
+        loop {
+            if let Some(event) = relative(event::read()?, area) {
+                return Ok(event);
+            }
+        }
+    }
+}
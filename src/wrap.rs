@@ -0,0 +1,43 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::columns;
+
+// Soft wrapping: a line too wide for the text area is shown over several
+// screen rows, broken after whitespace where there is any and mid-word
+// otherwise.
+
+// Display columns where each row of `line` starts, the first always 0. One
+// cell past the end of the text is laid out too, so the cursor after a row
+// that's exactly full has somewhere to go.
+pub fn row_starts(line: &str, width: usize) -> Vec<usize> {
+    let width = width.max(1);
+    let mut starts = vec![0];
+    let mut start = 0;
+    let mut column = 0;
+    // Just past the last whitespace on the current row.
+    let mut after_space = None;
+
+    for grapheme in line.graphemes(true).chain([" "]) {
+        let grapheme_width = columns::width(grapheme);
+        // A grapheme wider than a whole row still gets one to itself.
+        while column + grapheme_width > start + width && column > start {
+            start = after_space.filter(|&c| c > start).unwrap_or(column);
+            after_space = None;
+            starts.push(start);
+        }
+
+        column += grapheme_width;
+        if grapheme.chars().all(char::is_whitespace) {
+            after_space = Some(column);
+        }
+    }
+
+    starts
+}
+
+// Which of the rows starting at `starts` shows `column`.
+pub fn row_of(starts: &[usize], column: usize) -> usize {
+    starts
+        .partition_point(|&start| start <= column)
+        .saturating_sub(1)
+}
@@ -0,0 +1,221 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+// Numbers temporary files, which also carry the process id, so no two saves
+// ever go through the same one.
+static TEMPORARIES: AtomicUsize = AtomicUsize::new(0);
+// Names already taken that are skipped before giving up.
+const ATTEMPTS: usize = 100;
+
+// Writes next to the target and renames over it, so a failure part way
+// through never leaves a truncated file behind. The rename replaces the
+// inode, so the original's mode, owner and extended attributes are copied
+// over first; when any of them can't be, or the directory doesn't let us
+// create the temporary file, the file is written in place instead and the
+// returned warning says why.
+pub fn write_atomic(path: &Path, text: &str) -> io::Result<Option<String>> {
+    write_atomic_with(path, |out| out.write_all(text.as_bytes()))
+}
+
+// Like write_atomic, with `contents` writing the text, which may be called
+// a second time when falling back to writing in place.
+pub fn write_atomic_with(
+    path: &Path,
+    contents: impl Fn(&mut dyn Write) -> io::Result<()>,
+) -> io::Result<Option<String>> {
+    // Through a symlink it's the file it points to that's replaced, so the
+    // temporary file goes beside that one and the link stays a link.
+    let target = match fs::canonicalize(path) {
+        Ok(real) => real,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => path.to_path_buf(),
+        Err(error) => return Err(error),
+    };
+
+    // Either the temporary file, written, or why the file is written in
+    // place instead.
+    let temporary = match create_temporary(&target) {
+        Ok((temporary, file)) => {
+            match write_synced(file, &contents).and_then(|()| copy_metadata(&target, &temporary)) {
+                Ok(lost) if lost.is_empty() => Ok(temporary),
+                Ok(lost) => {
+                    let _ = fs::remove_file(&temporary);
+                    Err(format!("to keep its {}", lost.join(", ")))
+                }
+                Err(error) => {
+                    let _ = fs::remove_file(&temporary);
+                    return Err(error);
+                }
+            }
+        }
+        Err(error) if error.kind() == io::ErrorKind::PermissionDenied => {
+            Err("as its directory isn't writable".to_string())
+        }
+        Err(error) => return Err(error),
+    };
+
+    let temporary = match temporary {
+        Ok(temporary) => temporary,
+        Err(reason) => {
+            write_synced(File::create(&target)?, &contents)?;
+            return Ok(Some(format!(
+                "{} written in place {reason}",
+                path.display()
+            )));
+        }
+    };
+
+    fs::rename(&temporary, &target).inspect_err(|_| {
+        let _ = fs::remove_file(&temporary);
+    })?;
+    Ok(None)
+}
+
+// Creates a file beside `path` to write the text into. It's only ever a new
+// one: whatever is already there, another save's file or a symlink left to
+// send the text elsewhere, is left alone and the next name tried.
+fn create_temporary(path: &Path) -> io::Result<(PathBuf, File)> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file"))?
+        .to_string_lossy();
+    let mut attempts = 1;
+    loop {
+        let count = TEMPORARIES.fetch_add(1, Ordering::Relaxed);
+        let temporary = path.with_file_name(format!(
+            ".{file_name}.{}.{count}.shark-tmp",
+            std::process::id()
+        ));
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temporary)
+        {
+            Err(error) if error.kind() == io::ErrorKind::AlreadyExists && attempts < ATTEMPTS => {
+                attempts += 1;
+            }
+            result => return result.map(|file| (temporary, file)),
+        }
+    }
+}
+
+// Copies what's at `path` to `<name>~` before it's written over. Nothing there
+// yet means there's nothing to keep.
+pub fn backup(path: &Path) -> io::Result<()> {
+    let mut name = path.as_os_str().to_owned();
+    name.push("~");
+    match fs::copy(path, name) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+        _ => Ok(()),
+    }
+}
+
+// Whether the current user may write over the file at `path`. Nothing there
+// yet counts as writable, since saving creates it.
+#[cfg(unix)]
+pub fn writable(path: &Path) -> bool {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    let Ok(path) = CString::new(path.as_os_str().as_bytes()) else {
+        return true;
+    };
+    let allowed = unsafe { libc::access(path.as_ptr(), libc::W_OK) } == 0;
+    allowed || io::Error::last_os_error().kind() == io::ErrorKind::NotFound
+}
+
+#[cfg(not(unix))]
+pub fn writable(path: &Path) -> bool {
+    fs::metadata(path).map_or(true, |metadata| !metadata.permissions().readonly())
+}
+
+// Only returns once the text is on the disk, so a crash right after a rename
+// can't leave an empty file in the original's place.
+fn write_synced(file: File, contents: impl Fn(&mut dyn Write) -> io::Result<()>) -> io::Result<()> {
+    let mut out = BufWriter::new(file);
+    contents(&mut out)?;
+    out.into_inner().map_err(|e| e.into_error())?.sync_all()
+}
+
+// Returns the names of whatever couldn't be copied. A missing original has
+// nothing to preserve.
+#[cfg(unix)]
+fn copy_metadata(original: &Path, copy: &Path) -> io::Result<Vec<String>> {
+    use std::os::unix::fs::{chown, MetadataExt};
+
+    let Ok(metadata) = fs::metadata(original) else {
+        return Ok(Vec::new());
+    };
+    let mut lost = Vec::new();
+
+    // Before the mode, since changing the owner clears setuid and setgid.
+    let current = fs::metadata(copy)?;
+    if (current.uid(), current.gid()) != (metadata.uid(), metadata.gid())
+        && chown(copy, Some(metadata.uid()), Some(metadata.gid())).is_err()
+    {
+        lost.push("owner".to_string());
+    }
+
+    if fs::set_permissions(copy, metadata.permissions()).is_err() {
+        lost.push("permissions".to_string());
+    }
+
+    if let Ok(names) = xattr::list(original) {
+        for name in names {
+            let copied = match xattr::get(original, &name) {
+                Ok(Some(value)) => xattr::set(copy, &name, &value).is_ok(),
+                Ok(None) => true,
+                Err(_) => false,
+            };
+
+            if !copied {
+                lost.push(format!("attribute {}", name.to_string_lossy()));
+            }
+        }
+    }
+
+    Ok(lost)
+}
+
+#[cfg(not(unix))]
+fn copy_metadata(original: &Path, copy: &Path) -> io::Result<Vec<String>> {
+    match fs::metadata(original) {
+        Ok(metadata) if fs::set_permissions(copy, metadata.permissions()).is_err() => {
+            Ok(vec!["permissions".to_string()])
+        }
+        _ => Ok(Vec::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The next few names are taken, one of them by a symlink to a file that
+    // mustn't be written.
+    #[cfg(unix)]
+    #[test]
+    fn names_already_taken_are_skipped() {
+        let directory = std::env::temp_dir().join(format!("shark-write-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("taken.txt");
+        let victim = directory.join("victim.txt");
+        fs::write(&victim, "untouched\n").unwrap();
+
+        let next = TEMPORARIES.load(Ordering::Relaxed);
+        for count in next..next + 3 {
+            let taken = directory.join(format!(
+                ".taken.txt.{}.{count}.shark-tmp",
+                std::process::id()
+            ));
+            std::os::unix::fs::symlink(&victim, taken).unwrap();
+        }
+
+        assert_eq!(write_atomic(&path, "saved\n").unwrap(), None);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "saved\n");
+        assert_eq!(fs::read_to_string(&victim).unwrap(), "untouched\n");
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,84 @@
+// Characters typed with AltGr look like Alt chords, or Ctrl+Alt ones on
+// Windows. Unbound ones that can't be meant as Alt chords are typed, and
+// --altgr-inserts types the rest too; bound chords stay bindings either way.
+mod common;
+
+use common::Harness;
+use crossterm::event::{KeyCode, KeyModifiers};
+
+const ALTGR: [KeyModifiers; 2] = [
+    KeyModifiers::ALT,
+    KeyModifiers::CONTROL.union(KeyModifiers::ALT),
+];
+
+fn harness(altgr_inserts: bool) -> Harness {
+    Harness::setup(Vec::new(), 40, 10, |editor| {
+        editor.set_altgr_inserts(altgr_inserts)
+    })
+}
+
+fn altgr(h: &mut Harness, modifiers: KeyModifiers, c: char) {
+    h.chord(KeyCode::Char(c), modifiers);
+}
+
+#[test]
+fn symbols_and_non_ascii_letters_are_typed_either_way() {
+    for altgr_inserts in [false, true] {
+        for modifiers in ALTGR {
+            let mut h = harness(altgr_inserts);
+            for c in ['@', '{', '[', '\\', '€', 'ł'] {
+                altgr(&mut h, modifiers, c);
+            }
+            assert_eq!(h.text(), "@{[\\€ł\n", "{modifiers:?}, {altgr_inserts}");
+            assert_eq!(h.cursor(), (0, 6));
+        }
+    }
+}
+
+#[test]
+fn unbound_ascii_letters_are_typed_only_when_asked() {
+    let mut h = harness(false);
+    altgr(&mut h, KeyModifiers::ALT, 'x');
+    altgr(&mut h, KeyModifiers::CONTROL | KeyModifiers::ALT, 'x');
+    assert_eq!(h.text(), "\n");
+
+    let mut h = harness(true);
+    altgr(&mut h, KeyModifiers::ALT, 'x');
+    altgr(&mut h, KeyModifiers::CONTROL | KeyModifiers::ALT, 'x');
+    assert_eq!(h.text(), "xx\n");
+}
+
+#[test]
+fn bound_alt_chords_stay_bindings() {
+    for altgr_inserts in [false, true] {
+        let mut h = harness(altgr_inserts);
+        h.type_text("one\ntwo");
+        // Alt+D would duplicate the line commented out, but plain text has no
+        // comments. Alt+. goes to the last change.
+        h.alt('d');
+        assert_eq!(h.text(), "one\ntwo\n", "{altgr_inserts}");
+        assert!(h.status().contains("no line comments"), "{altgr_inserts}");
+        h.key(KeyCode::Up).alt('.');
+        assert_eq!(h.text(), "one\ntwo\n", "{altgr_inserts}");
+        assert!(h.status().contains("Change 1 of"), "{altgr_inserts}");
+    }
+}
+
+// Composed on the way in, a dead key's character arrives as plain input.
+#[test]
+fn dead_key_compositions_are_plain_characters() {
+    let mut h = harness(false);
+    h.type_text("é");
+    h.chord(KeyCode::Char('Ê'), KeyModifiers::SHIFT);
+    assert_eq!(h.text(), "éÊ\n");
+}
+
+#[test]
+fn alt_digits_stay_the_count_prefix() {
+    for altgr_inserts in [false, true] {
+        let mut h = harness(altgr_inserts);
+        h.type_text("abcd");
+        h.alt('2').key(KeyCode::Backspace);
+        assert_eq!(h.text(), "ab\n", "{altgr_inserts}");
+    }
+}
@@ -0,0 +1,98 @@
+// Lines mixing left-to-right and right-to-left text keep the cursor's column
+// math in logical order: it gets to each end of the line and back to where it
+// started. BiDi controls are drawn as placeholders and zero-width characters
+// take no column.
+mod common;
+
+use common::Harness;
+use crossterm::event::{KeyCode, KeyModifiers};
+use ropey::Rope;
+use shark::buffer::Buffer;
+
+// "let name = " then the Hebrew word shalom, then "; // done".
+const MIXED: &str = "let name = \u{5e9}\u{5dc}\u{5d5}\u{5dd}; // done\n";
+const MIXED_CHARS: usize = 24;
+
+fn mixed(lines: &str) -> Harness {
+    Harness::with_size(lines, 60, 6)
+}
+
+#[test]
+fn the_cursor_gets_to_both_ends_of_a_mixed_line_and_back() {
+    let mut h = mixed(MIXED);
+    h.key(KeyCode::End);
+    assert_eq!(h.cursor(), (0, MIXED_CHARS));
+    assert_eq!(h.terminal.cursor(), (MIXED_CHARS as u16, 0));
+
+    h.key(KeyCode::Home);
+    assert_eq!(h.cursor(), (0, 0));
+    assert_eq!(h.terminal.cursor(), (0, 0));
+
+    h.repeat(KeyCode::Right, KeyModifiers::NONE, MIXED_CHARS + 3);
+    assert_eq!(h.cursor(), (0, MIXED_CHARS));
+    h.repeat(KeyCode::Left, KeyModifiers::NONE, MIXED_CHARS + 3);
+    assert_eq!(h.cursor(), (0, 0));
+    assert_eq!(h.terminal.cursor(), (0, 0));
+}
+
+#[test]
+fn a_line_starting_or_ending_right_to_left_round_trips() {
+    let mut h = mixed("\u{5d0}\u{5d1} ab\nab \u{627}\u{628}\n");
+    h.key(KeyCode::End);
+    assert_eq!(h.cursor(), (0, 5));
+    h.key(KeyCode::Home);
+    assert_eq!(h.cursor(), (0, 0));
+
+    h.key(KeyCode::Down).key(KeyCode::End);
+    assert_eq!(h.cursor(), (1, 5));
+    assert_eq!(h.terminal.cursor(), (5, 1));
+    h.key(KeyCode::Up);
+    assert_eq!(h.cursor(), (0, 5));
+    h.key(KeyCode::Down).key(KeyCode::Home);
+    assert_eq!(h.terminal.cursor(), (0, 1));
+}
+
+#[test]
+fn typing_after_right_to_left_text_lands_at_the_cursor() {
+    let mut h = mixed(MIXED);
+    h.key(KeyCode::End).type_text("!");
+    assert_eq!(h.text(), MIXED.replace('\n', "!\n"));
+    h.key(KeyCode::Home).type_text("x");
+    assert!(h.text().starts_with("xlet name"));
+}
+
+#[test]
+fn bidi_controls_are_drawn_and_counted_as_a_column() {
+    // A right-to-left override, the classic way to disguise source code.
+    let mut h = mixed("a\u{202e}b\n");
+    assert_eq!(h.row(0), "a\u{fffd}b");
+
+    h.key(KeyCode::End);
+    assert_eq!(h.cursor(), (0, 3));
+    assert_eq!(h.terminal.cursor(), (3, 0));
+}
+
+#[test]
+fn zero_width_characters_take_no_column() {
+    // Zero-width space, joiner and non-joiner between the letters.
+    let mut h = mixed("a\u{200b}b\u{200d}c\u{200c}d\n");
+    h.key(KeyCode::End);
+    assert_eq!(h.terminal.cursor(), (4, 0));
+}
+
+#[test]
+fn right_to_left_text_in_view_is_flagged() {
+    let h = mixed(MIXED);
+    assert!(h.status().contains("RTL"));
+
+    let h = mixed("plain\n");
+    assert!(!h.status().contains("RTL"));
+
+    let h = Harness::setup(
+        vec![Buffer::new(Rope::from_str(MIXED), None)],
+        60,
+        6,
+        |editor| editor.set_rtl_warning(false),
+    );
+    assert!(!h.status().contains("RTL"));
+}
@@ -0,0 +1,113 @@
+// Leaving a buffer and coming back finds it exactly as it was left.
+mod common;
+
+use common::{file, Harness};
+use crossterm::event::{KeyCode, KeyModifiers};
+
+fn two_files(name: &str) -> Harness {
+    let long: String = (0..100)
+        .map(|n| format!("fn f{n}() {{ let value = {n}; /* a long line */ }}\n"))
+        .collect();
+    let a = file(&format!("{name}-a.rs"), &long);
+    let b = file(&format!("{name}-b.rs"), "fn other() {}\nfn more() {}\n");
+    Harness::open(&[&a, &b], 30, 10)
+}
+
+fn switch(h: &mut Harness) {
+    h.chord(KeyCode::PageDown, KeyModifiers::CONTROL);
+}
+
+#[test]
+fn an_edited_buffer_is_intact_after_switching_away_and_back() {
+    let mut h = two_files("intact");
+    h.repeat(KeyCode::Down, KeyModifiers::NONE, 50)
+        .key(KeyCode::End)
+        .type_text(" // edited")
+        .repeat(KeyCode::Left, KeyModifiers::SHIFT, 6);
+
+    let buffer = h.editor.buffer();
+    let before = (
+        h.text(),
+        h.cursor(),
+        buffer.scroll,
+        buffer.h_scroll,
+        buffer.modified,
+    );
+    assert!(before.2 > 0 && before.3 > 0, "{before:?}");
+    let frame = h.rows();
+
+    switch(&mut h);
+    assert_eq!(h.row(0), "fn other() {}");
+    h.key(KeyCode::Down).shift(KeyCode::Right);
+    switch(&mut h);
+
+    let buffer = h.editor.buffer();
+    let after = (
+        h.text(),
+        h.cursor(),
+        buffer.scroll,
+        buffer.h_scroll,
+        buffer.modified,
+    );
+    assert_eq!(after, before);
+    assert_eq!(h.rows(), frame);
+
+    // Still selected: typing replaces the six chars.
+    h.type_text("x");
+    assert!(h.text().contains("/* a long line */ } // x\n"));
+}
+
+#[test]
+fn the_other_buffers_selection_stays_with_it() {
+    let mut h = two_files("selection");
+    h.shift(KeyCode::Right).shift(KeyCode::Right);
+    switch(&mut h);
+
+    // Nothing selected here, so typing just inserts.
+    h.type_text("x");
+    assert_eq!(h.row(0), "xfn other() {}");
+    switch(&mut h);
+    h.type_text("y");
+    assert_eq!(h.row(0), "y f0() { let value = 0; /* a l");
+}
+
+#[test]
+fn an_unedited_buffer_keeps_its_tree() {
+    let mut h = two_files("tree");
+    let root = |h: &Harness| {
+        let buffer = h.editor.buffer();
+        let tree = buffer.tree.as_ref().expect("parsed");
+        (tree.root_node().id(), buffer.tree_stale)
+    };
+    h.run();
+    let before = root(&h);
+
+    switch(&mut h);
+    switch(&mut h);
+    assert_eq!(root(&h), before);
+}
+
+#[test]
+fn a_position_past_the_end_of_a_reloaded_file_is_clamped() {
+    let a = file("clamped-a.rs", &"fn f() {}\n".repeat(100));
+    let b = file("clamped-b.rs", "fn other() {}\n");
+    let mut h = Harness::open(&[&a, &b], 30, 10);
+    h.repeat(KeyCode::Down, KeyModifiers::NONE, 80)
+        .repeat(KeyCode::Right, KeyModifiers::SHIFT, 4);
+    switch(&mut h);
+
+    // Shrunk behind the editor's back while it's in the background.
+    std::fs::write(&a, "fn f() {}\nfn g() {}\n").unwrap();
+    switch(&mut h);
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    h.run();
+
+    assert_eq!(h.text(), "fn f() {}\nfn g() {}\n");
+    assert!(h.cursor().0 <= 2);
+    assert_eq!(h.editor.buffer().scroll, 0);
+    assert_eq!(h.row(0), "fn f() {}");
+    // And whatever of the selection is left is still within the text.
+    h.type_text("x");
+    assert!(h.text().contains('x'));
+    assert!(h.text().lines().count() <= 3);
+}
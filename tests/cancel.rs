@@ -0,0 +1,202 @@
+// Cancelling a project-wide replace part way through, while it searches and
+// while it applies, leaves every file and buffer as it was.
+mod common;
+
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use common::home;
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use ropey::Rope;
+use shark::{
+    buffer::Buffer,
+    editor::Editor,
+    project::Project,
+    terminal::{Memory, Terminal},
+};
+
+// A terminal that presses a key as soon as the text it waits for has been
+// drawn, the way a user would on seeing the progress or the preview.
+struct Presses {
+    terminal: Memory,
+    drawn: String,
+    keys: Vec<(&'static str, KeyCode)>,
+}
+
+impl Write for Presses {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.drawn.push_str(&String::from_utf8_lossy(buf));
+        while let Some(&(marker, code)) = self.keys.first() {
+            if !self.drawn.contains(marker) {
+                break;
+            }
+            self.keys.remove(0);
+            let key = KeyEvent::new(code, KeyModifiers::NONE);
+            self.terminal.push(Event::Key(key));
+        }
+        self.terminal.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.terminal.flush()
+    }
+}
+
+impl Terminal for Presses {
+    fn size(&self) -> io::Result<(u16, u16)> {
+        self.terminal.size()
+    }
+
+    fn enter(&mut self, mouse: bool) -> io::Result<()> {
+        self.terminal.enter(mouse)
+    }
+
+    fn leave(&mut self) -> io::Result<()> {
+        self.terminal.leave()
+    }
+
+    fn poll(&mut self, timeout: Duration) -> io::Result<bool> {
+        self.terminal.poll(timeout)
+    }
+
+    fn read(&mut self) -> io::Result<Event> {
+        self.terminal.read()
+    }
+}
+
+// `files` files containing the word to replace, the first of them open.
+fn project(name: &str, files: usize) -> (PathBuf, Vec<(PathBuf, String)>) {
+    let root = home().join(name);
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+
+    let originals = (0..files)
+        .map(|n| {
+            let path = root.join(format!("file{n:04}.txt"));
+            let text = format!("a needle in file {n}\nand another needle\n");
+            fs::write(&path, &text).unwrap();
+            (path, text)
+        })
+        .collect();
+    (root, originals)
+}
+
+fn replace_all(root: &Path, open: &Path, keys: Vec<(&'static str, KeyCode)>) -> (Editor, Memory) {
+    let terminal = Memory::new(80, 12);
+    let presses = Presses {
+        terminal: terminal.clone(),
+        drawn: String::new(),
+        keys,
+    };
+    let project = Project {
+        root: root.to_path_buf(),
+        restrict: false,
+    };
+    let filename = open.to_str().unwrap().to_string();
+    let buffers = vec![Buffer::new(Rope::new(), Some(filename))];
+    let mut editor = Editor::new(Box::new(presses), buffers, project);
+    editor.set_line_numbers(false);
+    editor.init().unwrap();
+    assert!(editor.load_buffers(&[0]).unwrap());
+
+    let key = |code, modifiers| Event::Key(KeyEvent::new(code, modifiers));
+    terminal.push(key(
+        KeyCode::Char('R'),
+        KeyModifiers::ALT | KeyModifiers::SHIFT,
+    ));
+    for c in "needle".chars() {
+        terminal.push(key(KeyCode::Char(c), KeyModifiers::NONE));
+    }
+    terminal.push(key(KeyCode::Enter, KeyModifiers::NONE));
+    for c in "pin".chars() {
+        terminal.push(key(KeyCode::Char(c), KeyModifiers::NONE));
+    }
+    terminal.push(key(KeyCode::Enter, KeyModifiers::NONE));
+
+    while editor.poll(Duration::ZERO).unwrap() {
+        assert!(editor.step().unwrap());
+    }
+    editor.tick().unwrap();
+    (editor, terminal)
+}
+
+fn status(terminal: &Memory) -> String {
+    terminal.rows().last().unwrap().trim().to_string()
+}
+
+fn assert_untouched(editor: &Editor, originals: &[(PathBuf, String)]) {
+    for (path, text) in originals {
+        assert_eq!(&fs::read_to_string(path).unwrap(), text, "{path:?}");
+    }
+    assert_eq!(editor.buffer().rope.to_string(), originals[0].1);
+    assert!(!editor.buffer().modified);
+}
+
+#[test]
+fn cancelling_the_search_changes_nothing() {
+    let (root, originals) = project("cancel-search", 3000);
+    let (editor, terminal) = replace_all(
+        &root,
+        &originals[0].0,
+        vec![("Searching files", KeyCode::Esc)],
+    );
+
+    let status = status(&terminal);
+    assert!(status.starts_with("Search cancelled"), "{status}");
+    assert_untouched(&editor, &originals);
+}
+
+#[test]
+fn cancelling_part_way_through_applying_rolls_back_what_was_done() {
+    let (root, originals) = project("cancel-apply", 20);
+    let keys = vec![
+        ("Enter applies", KeyCode::Enter),
+        ("Replacing… 5/20", KeyCode::Esc),
+    ];
+    let (mut editor, terminal) = replace_all(&root, &originals[0].0, keys);
+
+    let status = status(&terminal);
+    assert!(
+        status.starts_with("Replace cancelled, nothing was changed"),
+        "{status}"
+    );
+    assert_untouched(&editor, &originals);
+
+    // Nor does the replacement come back with redo.
+    terminal.push(Event::Key(KeyEvent::new(
+        KeyCode::Char('y'),
+        KeyModifiers::CONTROL,
+    )));
+    editor.step().unwrap();
+    assert_eq!(editor.buffer().rope.to_string(), originals[0].1);
+}
+
+#[test]
+fn letting_it_finish_replaces_everywhere() {
+    let (root, originals) = project("replace-all", 20);
+    let (editor, terminal) = replace_all(
+        &root,
+        &originals[0].0,
+        vec![("Enter applies", KeyCode::Enter)],
+    );
+
+    let status = status(&terminal);
+    assert!(
+        status.starts_with("Replaced 40 occurrences in 20 files"),
+        "{status}"
+    );
+    for (path, text) in &originals[1..] {
+        assert_eq!(
+            fs::read_to_string(path).unwrap(),
+            text.replace("needle", "pin")
+        );
+    }
+    assert_eq!(
+        editor.buffer().rope.to_string(),
+        originals[0].1.replace("needle", "pin")
+    );
+}
@@ -0,0 +1,107 @@
+// Two-key sequences like ctrl+k ctrl+d: the first key waits in the status bar
+// for the second, which runs the action. Esc, a key that continues nothing, or
+// waiting too long each drop the first key without typing anything.
+mod common;
+
+use std::{thread, time::Duration};
+
+use common::Harness;
+use crossterm::event::KeyCode;
+use ropey::Rope;
+use shark::{
+    buffer::Buffer,
+    keymap::{parse_sequence, Action, Keymap},
+};
+
+const TEXT: &str = "one\ntwo\nthree\nfour\n";
+
+// Longer than the editor waits for the rest of a sequence.
+const PAST_TIMEOUT: Duration = Duration::from_millis(1200);
+
+fn with_sequence() -> Harness {
+    let buffers = vec![Buffer::new(Rope::from_str(TEXT), None)];
+    let mut h = Harness::setup(buffers, 60, 6, |editor| {
+        let mut keymap = Keymap::default();
+        keymap.bind(
+            &parse_sequence("ctrl+k ctrl+d").unwrap(),
+            Action::DuplicateLine,
+        );
+        keymap.bind(&parse_sequence("ctrl+k j").unwrap(), Action::MoveDown);
+        editor.set_keymap(keymap);
+    });
+    h.run();
+    h
+}
+
+fn waiting(h: &Harness) -> bool {
+    h.status().contains("ctrl+k")
+}
+
+#[test]
+fn the_second_key_runs_the_action() {
+    let mut h = with_sequence();
+    h.ctrl('k');
+    assert!(waiting(&h));
+    assert_eq!(h.text(), TEXT);
+
+    h.ctrl('d');
+    assert_eq!(h.text(), format!("one\n{TEXT}"));
+    assert!(!waiting(&h));
+}
+
+#[test]
+fn a_count_waits_along_with_the_sequence() {
+    let mut h = with_sequence();
+    h.alt('3').ctrl('k');
+    assert!(waiting(&h));
+    h.key(KeyCode::Char('j'));
+    assert_eq!(h.cursor(), (3, 0));
+}
+
+#[test]
+fn esc_cancels_the_waiting_key() {
+    let mut h = with_sequence();
+    h.ctrl('k').key(KeyCode::Esc);
+    assert!(!waiting(&h));
+
+    // Nothing is left waiting to swallow the next key.
+    h.type_text("x");
+    assert_eq!(h.text(), format!("x{TEXT}"));
+}
+
+#[test]
+fn a_key_continuing_nothing_is_reported_and_not_typed() {
+    let mut h = with_sequence();
+    h.ctrl('k').key(KeyCode::Char('x'));
+    assert_eq!(h.text(), TEXT);
+    assert!(h.status().contains("undefined: ctrl+k x"));
+    assert_eq!(h.terminal.bells(), 1);
+
+    // Only the one key was dropped.
+    h.type_text("y");
+    assert_eq!(h.text(), format!("y{TEXT}"));
+}
+
+#[test]
+fn the_waiting_key_times_out() {
+    let mut h = with_sequence();
+    h.ctrl('k');
+    thread::sleep(PAST_TIMEOUT);
+    h.run();
+    assert!(!waiting(&h));
+
+    // The second key arrives on its own, and types as it would have anyway.
+    h.type_text("j");
+    assert_eq!(h.text(), format!("j{TEXT}"));
+    assert_eq!(h.cursor(), (0, 1));
+}
+
+#[test]
+fn overlays_take_the_first_key_before_it_can_wait() {
+    let mut h = with_sequence();
+    h.key(KeyCode::F(1)).ctrl('k');
+    assert!(!waiting(&h));
+
+    h.key(KeyCode::Esc).ctrl('d');
+    assert_eq!(h.text(), TEXT);
+}
@@ -0,0 +1,102 @@
+// With colors off nothing is drawn in color, and reverse video still marks
+// the selection, the matches and the status bar.
+mod common;
+
+use common::{file, Harness};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use shark::theme::{ColorMode, Theme, ThemeChoice};
+
+const SOURCE: &str = "fn main() {\n    // greet\n    let s = \"hi\";\n    println!(\"{s}\");\n}\n";
+
+fn with_colors(name: &str, mode: ColorMode) -> Harness {
+    let path = file(name, SOURCE);
+    let mut h = Harness::open_with(&[&path], 60, 10, |editor| {
+        editor.set_theme(Theme::new(mode, ThemeChoice::Dark));
+        editor.set_line_numbers(true);
+    });
+
+    // A selection, then searching for a letter with several matches, which
+    // extends it to the first.
+    h.repeat(KeyCode::Right, KeyModifiers::SHIFT, 2);
+    for event in [
+        KeyEvent::new(KeyCode::Char('f'), KeyModifiers::CONTROL),
+        KeyEvent::new(KeyCode::Char('s'), KeyModifiers::NONE),
+        KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE),
+    ] {
+        h.terminal.push(Event::Key(event));
+    }
+    h.run();
+    h
+}
+
+// SGR parameters that set a foreground or background color, default ones
+// included.
+fn color_sequences(output: &[u8]) -> Vec<String> {
+    let output = String::from_utf8_lossy(output);
+    output
+        .split("\x1b[")
+        .skip(1)
+        .filter_map(|rest| {
+            let end = rest.find(|c: char| !c.is_ascii_digit() && c != ';')?;
+            (rest[end..].starts_with('m')).then(|| rest[..end].to_string())
+        })
+        .filter(|params| {
+            params.split(';').next().is_some_and(|first| {
+                matches!(first.parse::<u8>(), Ok(30..=49 | 90..=97 | 100..=107))
+            })
+        })
+        .collect()
+}
+
+#[test]
+fn never_draws_no_color_sequences() {
+    let h = with_colors("never.rs", ColorMode::Never);
+
+    assert_eq!(
+        color_sequences(&h.terminal.take_output()),
+        Vec::<String>::new()
+    );
+    for row in 0..10 {
+        for column in 0..60 {
+            let style = h.terminal.cell(column, row).style;
+            assert_eq!((style.foreground, style.background), (None, None));
+        }
+    }
+}
+
+#[test]
+fn never_marks_the_selection_matches_and_status_bar_in_reverse_video() {
+    let h = with_colors("reverse.rs", ColorMode::Never);
+
+    // The selection reaches from the start to the match jumped to, on the
+    // third line; the `s` on the fourth is another match.
+    let reverse = |column, row| h.terminal.cell(column, row).style.reverse;
+    assert_eq!(h.row(2), "3     let s = \"hi\";");
+    assert!(reverse(2, 1) && reverse(9, 2));
+    assert!(!reverse(12, 2));
+    assert_eq!(h.terminal.cell(17, 3).symbol, "s");
+    assert!(reverse(17, 3) && !reverse(16, 3));
+    assert!((0..60).all(|column| reverse(column, 9)));
+}
+
+#[test]
+fn always_draws_in_color() {
+    let h = with_colors("always.rs", ColorMode::Always);
+
+    assert!(!color_sequences(&h.terminal.take_output()).is_empty());
+}
+
+#[test]
+fn auto_follows_no_color() {
+    std::env::set_var("NO_COLOR", "1");
+    let off = Theme::new(ColorMode::Auto, ThemeChoice::Dark);
+    std::env::set_var("NO_COLOR", "");
+    let on = Theme::new(ColorMode::Auto, ThemeChoice::Dark);
+    std::env::remove_var("NO_COLOR");
+
+    assert_eq!(off.selection(), None);
+    assert!(on.selection().is_some());
+    assert!(Theme::new(ColorMode::Always, ThemeChoice::Dark)
+        .selection()
+        .is_some());
+}
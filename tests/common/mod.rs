@@ -0,0 +1,199 @@
+// Shared by the integration tests: an editor on an in-memory terminal, fed
+// events and read back through the rope and the rendered grid.
+#![allow(dead_code)]
+
+use std::{env, fs, path::PathBuf, sync::Once, time::Duration};
+
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use ropey::Rope;
+use shark::{buffer::Buffer, editor::Editor, project::Project, terminal::Memory};
+
+// Config, recovery files and remembered positions go to a directory of this
+// test process's own, never the real ones.
+pub fn home() -> PathBuf {
+    static ISOLATE: Once = Once::new();
+    let home = env::temp_dir().join(format!("shark-tests-{}", std::process::id()));
+    ISOLATE.call_once(|| {
+        fs::create_dir_all(&home).expect("create the test home");
+        env::set_var("HOME", &home);
+        for name in ["XDG_CONFIG_HOME", "XDG_DATA_HOME", "XDG_STATE_HOME"] {
+            env::remove_var(name);
+        }
+    });
+    home
+}
+
+// A file of `text` under home(), named after the calling test.
+pub fn file(name: &str, text: &str) -> String {
+    let path = home().join(name);
+    fs::write(&path, text).expect("write the test file");
+    path.to_str().expect("utf-8 path").to_string()
+}
+
+pub struct Harness {
+    pub editor: Editor,
+    pub terminal: Memory,
+}
+
+impl Harness {
+    pub fn new(text: &str) -> Self {
+        Self::with_size(text, 40, 10)
+    }
+
+    pub fn with_size(text: &str, width: u16, height: u16) -> Self {
+        Self::with_buffers(vec![Buffer::new(Rope::from_str(text), None)], width, height)
+    }
+
+    pub fn with_buffers(buffers: Vec<Buffer>, width: u16, height: u16) -> Self {
+        Self::setup(buffers, width, height, |_| {})
+    }
+
+    // The files are read the way the binary reads the ones it's given.
+    pub fn open(filenames: &[&str], width: u16, height: u16) -> Self {
+        Self::open_with(filenames, width, height, |_| {})
+    }
+
+    pub fn open_with(
+        filenames: &[&str],
+        width: u16,
+        height: u16,
+        configure: impl FnOnce(&mut Editor),
+    ) -> Self {
+        let buffers = filenames
+            .iter()
+            .map(|filename| Buffer::new(Rope::new(), Some(filename.to_string())))
+            .collect();
+        let mut harness = Self::setup(buffers, width, height, configure);
+        let indices: Vec<usize> = (0..filenames.len()).collect();
+        assert!(harness.editor.load_buffers(&indices).expect("load"));
+        harness
+    }
+
+    // `configure` runs before the first frame is drawn. Line numbers are off
+    // unless it turns them on, so rows start with the text itself.
+    pub fn setup(
+        buffers: Vec<Buffer>,
+        width: u16,
+        height: u16,
+        configure: impl FnOnce(&mut Editor),
+    ) -> Self {
+        let project = Project {
+            root: home(),
+            restrict: false,
+        };
+        Self::in_project(project, buffers, width, height, configure)
+    }
+
+    pub fn in_project(
+        project: Project,
+        buffers: Vec<Buffer>,
+        width: u16,
+        height: u16,
+        configure: impl FnOnce(&mut Editor),
+    ) -> Self {
+        let terminal = Memory::new(width, height);
+        let mut editor = Editor::new(Box::new(terminal.clone()), buffers, project);
+        editor.set_line_numbers(false);
+        configure(&mut editor);
+        editor.init().expect("init");
+
+        Self { editor, terminal }
+    }
+
+    pub fn key(&mut self, code: KeyCode) -> &mut Self {
+        self.chord(code, KeyModifiers::NONE)
+    }
+
+    pub fn chord(&mut self, code: KeyCode, modifiers: KeyModifiers) -> &mut Self {
+        self.terminal
+            .push(Event::Key(KeyEvent::new(code, modifiers)));
+        self.run()
+    }
+
+    pub fn shift(&mut self, code: KeyCode) -> &mut Self {
+        self.chord(code, KeyModifiers::SHIFT)
+    }
+
+    // `code` pressed `times` times in a row, handled together like fast typing.
+    pub fn repeat(&mut self, code: KeyCode, modifiers: KeyModifiers, times: usize) -> &mut Self {
+        for _ in 0..times {
+            self.terminal
+                .push(Event::Key(KeyEvent::new(code, modifiers)));
+        }
+        self.run()
+    }
+
+    pub fn ctrl(&mut self, c: char) -> &mut Self {
+        self.chord(KeyCode::Char(c), KeyModifiers::CONTROL)
+    }
+
+    pub fn alt(&mut self, c: char) -> &mut Self {
+        let modifiers = if c.is_uppercase() {
+            KeyModifiers::ALT | KeyModifiers::SHIFT
+        } else {
+            KeyModifiers::ALT
+        };
+        self.chord(KeyCode::Char(c), modifiers)
+    }
+
+    // A newline is sent as Enter, the way a terminal would.
+    pub fn type_text(&mut self, text: &str) -> &mut Self {
+        for c in text.chars() {
+            let code = match c {
+                '\n' => KeyCode::Enter,
+                '\t' => KeyCode::Tab,
+                c => KeyCode::Char(c),
+            };
+            self.terminal
+                .push(Event::Key(KeyEvent::new(code, KeyModifiers::NONE)));
+        }
+        self.run()
+    }
+
+    // Each char as a keystroke of its own, handled before the next arrives,
+    // where type_text delivers them all at once like composed input.
+    pub fn type_slowly(&mut self, text: &str) -> &mut Self {
+        for c in text.chars() {
+            self.type_text(&c.to_string());
+        }
+        self
+    }
+
+    // Handles every queued event, then lets the editor catch up as if idle.
+    pub fn run(&mut self) -> &mut Self {
+        while self.editor.poll(Duration::ZERO).expect("poll") {
+            if !self.editor.step().expect("step") {
+                break;
+            }
+        }
+        self.editor.tick().expect("tick");
+        self
+    }
+
+    pub fn text(&self) -> String {
+        self.editor.buffer().rope.to_string()
+    }
+
+    pub fn cursor(&self) -> (usize, usize) {
+        self.editor.cursor()
+    }
+
+    // Row `row` of the terminal as drawn, without trailing blanks.
+    pub fn row(&self, row: u16) -> String {
+        self.terminal.row(row).trim_end().to_string()
+    }
+
+    // The bottom row, where messages show.
+    pub fn status(&self) -> String {
+        let rows = self.rows();
+        rows.last().cloned().unwrap_or_default()
+    }
+
+    pub fn rows(&self) -> Vec<String> {
+        self.terminal
+            .rows()
+            .iter()
+            .map(|row| row.trim_end().to_string())
+            .collect()
+    }
+}
@@ -0,0 +1,61 @@
+// The popup with the cursor line's diagnostics, which Esc closes again, and
+// which a terminal too small to hold it doesn't open.
+mod common;
+
+use common::Harness;
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use ropey::Rope;
+use shark::buffer::Buffer;
+
+const TEXT: &str = "fn main( {}\n";
+
+fn broken(width: u16, height: u16) -> Harness {
+    let buffer = Buffer::new(Rope::from_str(TEXT), Some("broken.rs".to_string()));
+    Harness::with_buffers(vec![buffer], width, height)
+}
+
+// Opens the popup and closes it with Esc, and returns what was drawn.
+fn show(h: &mut Harness) -> String {
+    h.terminal.take_output();
+    for event in [
+        KeyEvent::new(KeyCode::Char('e'), KeyModifiers::ALT),
+        KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE),
+    ] {
+        h.terminal.push(Event::Key(event));
+    }
+    h.run();
+    String::from_utf8_lossy(&h.terminal.take_output()).into_owned()
+}
+
+#[test]
+fn the_popup_shows_the_lines_syntax_error() {
+    let mut h = broken(60, 10);
+    let drawn = show(&mut h);
+
+    assert!(
+        drawn.contains(" 1:9: syntax error: missing `)` "),
+        "{drawn}"
+    );
+    // Gone again, with the text under it back.
+    assert_eq!(h.row(0), TEXT.trim_end());
+    assert_eq!(h.row(1), "");
+}
+
+#[test]
+fn a_terminal_too_small_for_it_gets_a_message() {
+    for (width, height) in [(1, 6), (2, 6), (3, 6), (4, 6), (60, 1)] {
+        let mut h = broken(width, height);
+        let drawn = show(&mut h);
+
+        assert!(!drawn.contains("missing"), "{width}x{height}");
+        assert_eq!(h.text(), TEXT);
+    }
+
+    // The smallest that fits it, a column of text wide, with words broken
+    // across its lines.
+    let mut h = broken(5, 6);
+    let drawn = show(&mut h);
+    for row in [" 1 ", " : ", " 9 "] {
+        assert!(drawn.contains(row), "{drawn}");
+    }
+}
@@ -0,0 +1,81 @@
+// Duplicate-and-comment on Alt+D: the original lines are commented out with
+// the buffer's own leader, the cursor stays on the copy to edit, and one undo
+// takes both back.
+mod common;
+
+use common::Harness;
+use crossterm::event::{KeyCode, KeyModifiers};
+use ropey::Rope;
+use shark::buffer::Buffer;
+
+fn named(filename: &str, text: &str) -> Harness {
+    let buffer = Buffer::new(Rope::from_str(text), Some(filename.to_string()));
+    Harness::with_buffers(vec![buffer], 60, 10)
+}
+
+#[test]
+fn the_line_is_copied_below_its_commented_original() {
+    let mut h = named("dup.rs", "fn a() {\n    let x = 1;\n}\n");
+    h.key(KeyCode::Down)
+        .repeat(KeyCode::Right, KeyModifiers::NONE, 8)
+        .alt('d');
+
+    assert_eq!(h.text(), "fn a() {\n    // let x = 1;\n    let x = 1;\n}\n");
+    assert_eq!(h.cursor(), (2, 8));
+    h.type_text("2");
+    assert_eq!(
+        h.text(),
+        "fn a() {\n    // let x = 1;\n    let 2x = 1;\n}\n"
+    );
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn the_leader_comes_from_the_language() {
+    let mut h = named("dup.toml", "name = \"shark\"\n");
+    h.alt('d');
+    assert_eq!(h.text(), "# name = \"shark\"\nname = \"shark\"\n");
+}
+
+#[test]
+fn a_language_without_line_comments_is_refused() {
+    let mut h = named("dup.txt", "plain\n");
+    h.alt('d');
+    assert_eq!(h.text(), "plain\n");
+    assert!(
+        h.status().contains("has no line comments"),
+        "{}",
+        h.status()
+    );
+}
+
+#[test]
+fn a_selection_is_duplicated_as_one_block() {
+    let text = "if a {\n    b();\n\n        c();\n}\n";
+    let mut h = named("block.rs", text);
+    h.key(KeyCode::Down)
+        .shift(KeyCode::Down)
+        .shift(KeyCode::Down)
+        .shift(KeyCode::Right)
+        .alt('d');
+
+    // Commented at the block's shallowest indent, the blank line left alone.
+    assert_eq!(
+        h.text(),
+        "if a {\n    // b();\n\n    //     c();\n    b();\n\n        c();\n}\n"
+    );
+    // The selection moved down onto the copy with the cursor.
+    assert_eq!(h.cursor(), (6, 1));
+    assert_eq!(h.editor.selection(), Some((4, 0)));
+}
+
+#[test]
+fn one_undo_takes_back_the_copy_and_the_comments() {
+    let text = "a();\nb();\nc();\n";
+    let mut h = named("undo.rs", text);
+    h.shift(KeyCode::Down).shift(KeyCode::Right).alt('d');
+    assert_eq!(h.text(), "// a();\n// b();\na();\nb();\nc();\n");
+
+    h.ctrl('z');
+    assert_eq!(h.text(), text);
+}
@@ -0,0 +1,107 @@
+mod common;
+
+use common::Harness;
+use crossterm::event::KeyCode;
+
+#[test]
+fn typing_inserts_at_the_cursor() {
+    let mut h = Harness::new("world\n");
+    h.type_text("hello ");
+
+    assert_eq!(h.text(), "hello world\n");
+    assert_eq!(h.cursor(), (0, 6));
+    assert_eq!(h.row(0), "hello world");
+    assert_eq!(h.terminal.cursor(), (6, 0));
+}
+
+#[test]
+fn enter_splits_the_line() {
+    let mut h = Harness::new("onetwo\n");
+    h.key(KeyCode::Right)
+        .key(KeyCode::Right)
+        .key(KeyCode::Right);
+    h.key(KeyCode::Enter);
+
+    assert_eq!(h.text(), "one\ntwo\n");
+    assert_eq!(h.cursor(), (1, 0));
+    assert_eq!(h.row(0), "one");
+    assert_eq!(h.row(1), "two");
+}
+
+#[test]
+fn backspace_at_a_line_start_joins_it_to_the_previous_line() {
+    let mut h = Harness::new("one\ntwo\n");
+    h.key(KeyCode::Down).key(KeyCode::Backspace);
+
+    assert_eq!(h.text(), "onetwo\n");
+    assert_eq!(h.cursor(), (0, 3));
+    assert_eq!(h.row(0), "onetwo");
+    assert_eq!(h.row(1), "");
+}
+
+#[test]
+fn backspace_at_the_start_of_the_buffer_does_nothing() {
+    let mut h = Harness::new("one\n");
+    h.key(KeyCode::Backspace);
+
+    assert_eq!(h.text(), "one\n");
+    assert_eq!(h.cursor(), (0, 0));
+}
+
+#[test]
+fn moving_past_the_bottom_scrolls_the_view() {
+    let text: String = (0..50).map(|n| format!("line {n}\n")).collect();
+    let mut h = Harness::with_size(&text, 40, 10);
+    let height = h.rows().len() as u16;
+    for _ in 0..20 {
+        h.key(KeyCode::Down);
+    }
+
+    assert_eq!(h.cursor(), (20, 0));
+    let scroll = h.editor.buffer().scroll;
+    assert!(scroll > 0);
+    assert_eq!(h.row(0), format!("line {scroll}"));
+    let drawn = (0..height).find(|&row| h.row(row) == "line 20");
+    assert_eq!(drawn, Some(h.terminal.cursor().1));
+}
+
+#[test]
+fn typing_after_scrolling_edits_the_line_under_the_cursor() {
+    let text: String = (0..50).map(|n| format!("line {n}\n")).collect();
+    let mut h = Harness::with_size(&text, 40, 10);
+    for _ in 0..30 {
+        h.key(KeyCode::Down);
+    }
+    h.type_text("new\n");
+
+    let text = h.text();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines[30], "new");
+    assert_eq!(lines[31], "line 30");
+    let (_, row) = h.terminal.cursor();
+    assert_eq!(h.row(row), "line 30");
+    assert_eq!(h.row(row - 1), "new");
+}
+
+#[test]
+fn backspace_inside_a_line_removes_the_char_before_the_cursor() {
+    let mut h = Harness::new("héllo\n");
+    h.key(KeyCode::Right).key(KeyCode::Right);
+    h.key(KeyCode::Backspace);
+
+    assert_eq!(h.text(), "hllo\n");
+    assert_eq!(h.cursor(), (0, 1));
+    assert_eq!(h.terminal.cursor(), (1, 0));
+}
+
+#[test]
+fn wide_characters_move_the_drawn_cursor_by_their_width() {
+    let mut h = Harness::new("\n");
+    h.type_text("漢字a");
+
+    assert_eq!(h.cursor(), (0, 3));
+    assert_eq!(h.terminal.cursor(), (5, 0));
+    h.key(KeyCode::Backspace).key(KeyCode::Backspace);
+    assert_eq!(h.text(), "漢\n");
+    assert_eq!(h.terminal.cursor(), (2, 0));
+}
@@ -0,0 +1,77 @@
+// Evaluating arithmetic in the buffer: the result replaces the expression or
+// is appended after it, in one undo step, and an expression that can't be
+// evaluated leaves the text as it was.
+mod common;
+
+use common::Harness;
+use crossterm::event::{KeyCode, KeyModifiers};
+use ropey::Rope;
+use shark::{
+    buffer::Buffer,
+    editor::Editor,
+    keymap::{parse_sequence, Action, Keymap},
+    undo::UndoLimits,
+};
+
+fn harness(text: &str, configure: impl FnOnce(&mut Editor)) -> Harness {
+    let buffers = vec![Buffer::new(Rope::from_str(text), None)];
+    let mut h = Harness::setup(buffers, 80, 6, |editor| {
+        let mut keymap = Keymap::default();
+        keymap.bind(&parse_sequence("ctrl+k =").unwrap(), Action::EvaluateAppend);
+        editor.set_keymap(keymap);
+        configure(editor);
+    });
+    h.run();
+    h
+}
+
+#[test]
+fn append_mode_keeps_the_expression() {
+    let mut h = harness("1920*1080/8\n", |_| {});
+    h.ctrl('k').key(KeyCode::Char('='));
+
+    assert_eq!(h.text(), "1920*1080/8 = 259200\n");
+    assert_eq!(h.cursor(), (0, 20));
+    h.ctrl('z');
+    assert_eq!(h.text(), "1920*1080/8\n");
+}
+
+#[test]
+fn errors_leave_the_text_alone() {
+    for (expression, error) in [
+        ("1/0", "division by zero"),
+        ("2*(3", "missing ')'"),
+        ("1..2", "invalid number '1..2'"),
+    ] {
+        let text = format!("{expression}\n");
+        let mut h = harness(&text, |_| {});
+        h.alt('=');
+
+        assert_eq!(h.text(), text);
+        assert!(!h.editor.buffer().modified);
+        assert!(
+            h.status()
+                .contains(&format!("Can't evaluate {expression}: {error}")),
+            "{}",
+            h.status()
+        );
+    }
+}
+
+// Past the undo byte cap a whole-buffer snapshot couldn't be kept, but the
+// few chars replaced can.
+#[test]
+fn a_substitution_in_a_large_buffer_is_undone_in_one_step() {
+    let text = format!("{}6*7\n", "filler line\n".repeat(1000));
+    let mut h = harness(&text, |editor| {
+        editor.set_undo_limits(UndoLimits {
+            max_entries: 100,
+            max_bytes: 1024,
+        })
+    });
+    h.chord(KeyCode::End, KeyModifiers::CONTROL).alt('=');
+
+    assert!(h.text().ends_with("filler line\n42\n"));
+    h.ctrl('z');
+    assert_eq!(h.text(), text);
+}
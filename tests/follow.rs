@@ -0,0 +1,121 @@
+// Opening and following a file while another thread is still appending to
+// it, the way a build writes its log.
+mod common;
+
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    path::Path,
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use common::{file, Harness};
+use crossterm::event::KeyCode;
+use shark::buffer;
+
+// Appends lines `from..to`, each in two writes so a reader can catch one
+// half-written.
+fn append(path: &str, from: usize, to: usize) -> JoinHandle<()> {
+    let path = path.to_string();
+    thread::spawn(move || {
+        let mut log = OpenOptions::new().append(true).open(path).unwrap();
+        for n in from..to {
+            write!(log, "line {n}").unwrap();
+            log.flush().unwrap();
+            thread::sleep(Duration::from_micros(200));
+            writeln!(log).unwrap();
+        }
+    })
+}
+
+fn log(lines: usize) -> String {
+    (0..lines).map(|n| format!("line {n}\n")).collect()
+}
+
+// Lets the editor catch up with the file until `done` holds.
+fn wait_for(h: &mut Harness, done: impl Fn(&Harness) -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !done(h) {
+        assert!(Instant::now() < deadline, "timed out at {:?}", h.text());
+        thread::sleep(Duration::from_millis(5));
+        h.run();
+    }
+}
+
+#[test]
+fn a_read_during_appends_is_consistent() {
+    let path = file("growing.log", &log(1));
+    let writer = append(&path, 1, 2000);
+
+    while !writer.is_finished() {
+        let (rope, len) = buffer::read_file(Path::new(&path)).unwrap();
+        let text = rope.to_string();
+        assert_eq!(text.len() as u64, len);
+        assert!(log(2000).starts_with(&text), "{text:?}");
+    }
+}
+
+#[test]
+fn opening_a_file_being_written_shows_what_was_there() {
+    let path = file("opened.log", &log(10));
+    let writer = append(&path, 10, 500);
+    let h = Harness::open(&[&path], 40, 8);
+    writer.join().unwrap();
+
+    let text = h.text();
+    assert!(text.len() >= log(10).len());
+    assert!(log(500).starts_with(&text));
+    assert!(!h.editor.buffer().modified);
+}
+
+#[test]
+fn following_keeps_the_cursor_on_the_last_line() {
+    let path = file("followed.log", &log(3));
+    let mut h = Harness::open(&[&path], 40, 8);
+    h.alt('f');
+    assert_eq!(h.cursor(), (2, 0));
+
+    append(&path, 3, 300).join().unwrap();
+    wait_for(&mut h, |h| h.text() == log(300));
+
+    assert_eq!(h.cursor(), (299, 0));
+    assert!(h.status().contains("following"));
+    assert_eq!(h.row(6), "line 299");
+}
+
+#[test]
+fn following_picks_up_a_line_finished_later() {
+    let path = file("half.log", "line 0\nline");
+    let mut h = Harness::open(&[&path], 40, 8);
+    h.alt('f');
+
+    let mut log_file = OpenOptions::new().append(true).open(&path).unwrap();
+    writeln!(log_file, " 1").unwrap();
+    wait_for(&mut h, |h| h.text() == log(2));
+    assert_eq!(h.cursor(), (1, 0));
+}
+
+#[test]
+fn moving_up_pauses_following_and_the_key_resumes_it() {
+    let path = file("paused.log", &log(5));
+    let mut h = Harness::open(&[&path], 40, 8);
+    h.alt('f').key(KeyCode::Up).key(KeyCode::Up);
+    assert_eq!(h.cursor(), (2, 0));
+
+    append(&path, 5, 100).join().unwrap();
+    wait_for(&mut h, |h| h.text() == log(100));
+    assert_eq!(h.cursor(), (2, 0));
+    assert_eq!(h.row(0), "line 0");
+
+    h.alt('f');
+    assert_eq!(h.cursor(), (99, 0));
+    append(&path, 100, 120).join().unwrap();
+    wait_for(&mut h, |h| h.text() == log(120));
+    assert_eq!(h.cursor(), (119, 0));
+
+    // At the end, the key stops following.
+    h.alt('f');
+    assert!(h.status().contains("Stopped following"));
+    assert_eq!(h.editor.buffer().follow, None);
+}
@@ -0,0 +1,210 @@
+// Frames rendered from known files at fixed sizes, compared with the ones
+// checked in under tests/golden. A snapshot is the grid of characters, where
+// the cursor is, and each run of cells drawn in a style other than the default.
+// Run with SHARK_BLESS=1 to write the frames as they're drawn now, after
+// checking the difference is the one intended.
+mod common;
+
+use std::{env, fmt::Write, fs, io, path::PathBuf};
+
+use common::{file, Harness};
+use crossterm::{
+    event::{KeyCode, KeyModifiers},
+    style::Color,
+};
+use shark::{
+    editor::Editor,
+    terminal::{memory::Style, Memory, Terminal},
+    theme::{ColorMode, Theme, ThemeChoice},
+};
+
+const RUST: &str = "// Greets whoever is named.
+fn main() {
+    let name = \"world\";
+    if name.len() > 3 {
+        println!(\"hello, {name}\");
+    }
+}
+";
+
+fn open(name: &str, text: &str, width: u16, height: u16) -> Harness {
+    open_with(name, text, width, height, |_| {})
+}
+
+// Colors are on and always the dark theme, whatever the machine running the
+// tests is set up with.
+fn open_with(
+    name: &str,
+    text: &str,
+    width: u16,
+    height: u16,
+    configure: impl FnOnce(&mut Editor),
+) -> Harness {
+    let path = file(name, text);
+    let mut h = Harness::open_with(&[&path], width, height, |editor| {
+        editor.set_theme(Theme::new(ColorMode::Always, ThemeChoice::Dark));
+        configure(editor);
+    });
+    h.run();
+    h
+}
+
+fn color(color: Color) -> String {
+    match color {
+        Color::Rgb { r, g, b } => format!("#{r:02x}{g:02x}{b:02x}"),
+        Color::AnsiValue(value) => format!("ansi{value}"),
+        color => format!("{color:?}").to_lowercase(),
+    }
+}
+
+fn tags(style: &Style) -> String {
+    let mut tags = Vec::new();
+    if let Some(foreground) = style.foreground {
+        tags.push(format!("fg={}", color(foreground)));
+    }
+    if let Some(background) = style.background {
+        tags.push(format!("bg={}", color(background)));
+    }
+    for (on, tag) in [
+        (style.bold, "bold"),
+        (style.dim, "dim"),
+        (style.italic, "italic"),
+        (style.underlined, "underlined"),
+        (style.reverse, "reverse"),
+    ] {
+        if on {
+            tags.push(tag.to_string());
+        }
+    }
+    tags.join(" ")
+}
+
+fn frame(terminal: &Memory) -> String {
+    let (width, height) = terminal.size().unwrap();
+    let border = format!("+{}+\n", "-".repeat(width as usize));
+
+    let mut out = border.clone();
+    for row in terminal.rows() {
+        writeln!(out, "|{row}|").unwrap();
+    }
+    out.push_str(&border);
+
+    let (column, row) = terminal.cursor();
+    let shown = if terminal.cursor_visible() {
+        ""
+    } else {
+        " hidden"
+    };
+    writeln!(out, "cursor {column},{row}{shown}").unwrap();
+
+    for row in 0..height {
+        let mut start = 0;
+        while start < width {
+            let style = terminal.cell(start, row).style;
+            let mut end = start + 1;
+            while end < width && terminal.cell(end, row).style == style {
+                end += 1;
+            }
+            if style != Style::default() {
+                writeln!(out, "{row}:{start}-{} {}", end - 1, tags(&style)).unwrap();
+            }
+            start = end;
+        }
+    }
+    out
+}
+
+fn check(name: &str, terminal: &Memory) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{name}.txt"));
+    let drawn = frame(terminal);
+
+    if env::var_os("SHARK_BLESS").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, &drawn).unwrap();
+        return;
+    }
+    let Ok(golden) = fs::read_to_string(&path) else {
+        panic!(
+            "no snapshot at {}; run with SHARK_BLESS=1 to write it:\n{drawn}",
+            path.display()
+        );
+    };
+    assert!(
+        golden == drawn,
+        "{name} is drawn differently from {}; run with SHARK_BLESS=1 if that's \
+         intended.\n--- expected\n{golden}\n--- drawn\n{drawn}",
+        path.display()
+    );
+}
+
+#[test]
+fn plain_file() {
+    let h = open(
+        "plain.txt",
+        "Plain text, nothing to color.\n\n  indented\nlast line\n",
+        40,
+        8,
+    );
+    check("plain_file", &h.terminal);
+}
+
+#[test]
+fn highlighted_rust() {
+    let mut h = open_with("greet.rs", RUST, 44, 10, |editor| {
+        editor.set_line_numbers(true);
+    });
+    h.key(KeyCode::Down).key(KeyCode::Down);
+    check("highlighted_rust", &h.terminal);
+}
+
+#[test]
+fn scrolled_view() {
+    let text: String = (1..=40).map(|n| format!("line {n}\n")).collect();
+    let mut h = open_with("scrolled.txt", &text, 30, 8, |editor| {
+        editor.set_line_numbers(true);
+    });
+    h.repeat(KeyCode::Down, KeyModifiers::NONE, 22);
+    check("scrolled_view", &h.terminal);
+}
+
+#[test]
+fn selection_active() {
+    let mut h = open("selected.rs", RUST, 44, 10);
+    h.key(KeyCode::Down).key(KeyCode::Down);
+    h.repeat(KeyCode::Right, KeyModifiers::NONE, 8);
+    h.repeat(KeyCode::Right, KeyModifiers::SHIFT, 4);
+    h.shift(KeyCode::Down);
+    check("selection_active", &h.terminal);
+}
+
+#[test]
+fn narrow_terminal() {
+    let mut h = open_with("a_rather_long_file_name.rs", RUST, 16, 6, |editor| {
+        editor.set_line_numbers(true);
+    });
+    h.key(KeyCode::Down).key(KeyCode::End);
+    check("narrow_terminal", &h.terminal);
+}
+
+// What one edit writes, replayed onto a grid of dots: the cells it repaints
+// show, and the ones it leaves alone stay dots. Typing next to the -> repaints
+// its whole row, so a font drawing -> as a ligature gets both characters at
+// once, never one of them alone. The != on the row below isn't touched.
+#[test]
+fn ligature_repaint() {
+    let mut h = open("arrow.rs", "fn f(a: u8)->u8 {\n    a != 0\n}\n", 30, 5);
+    h.key(KeyCode::Down).key(KeyCode::Up).key(KeyCode::End);
+    h.repeat(KeyCode::Left, KeyModifiers::NONE, 6);
+    h.terminal.take_output();
+    h.type_text(" ");
+
+    let replay = Memory::new(30, 5);
+    let dots: String = (1..=5)
+        .map(|row| format!("\x1b[{row};1H{}", ".".repeat(30)))
+        .collect();
+    io::Write::write_all(&mut replay.clone(), dots.as_bytes()).unwrap();
+    io::Write::write_all(&mut replay.clone(), &h.terminal.take_output()).unwrap();
+    check("ligature_repaint", &replay);
+}
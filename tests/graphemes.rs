@@ -0,0 +1,69 @@
+// Text typed a char event at a time, the way an IME or a terminal hands over
+// a grapheme made of several code points.
+mod common;
+
+use common::Harness;
+use crossterm::event::KeyCode;
+
+const FAMILY: &str = "👨\u{200d}👩\u{200d}👧";
+
+#[test]
+fn a_combining_accent_joins_the_letter_before_it() {
+    let mut h = Harness::new("");
+    h.type_text("e\u{301}x");
+
+    assert_eq!(h.text(), "e\u{301}x");
+    assert_eq!(h.cursor(), (0, 3));
+    assert_eq!(h.terminal.cursor(), (2, 0));
+    assert_eq!(h.terminal.cell(0, 0).symbol, "e\u{301}");
+}
+
+#[test]
+fn a_zwj_sequence_is_one_wide_character() {
+    let mut h = Harness::new("\n");
+    h.type_text(FAMILY).type_text("|");
+
+    assert_eq!(h.text(), format!("{FAMILY}|\n"));
+    assert_eq!(h.cursor(), (0, 6));
+    assert_eq!(h.terminal.cursor(), (3, 0));
+    assert_eq!(h.terminal.cell(2, 0).symbol, "|");
+}
+
+#[test]
+fn movement_steps_over_whole_graphemes() {
+    let text = format!("a{FAMILY}🇫🇷e\u{301}字b\n");
+    let mut h = Harness::new(&text);
+    let mut columns = Vec::new();
+    for _ in 0..6 {
+        h.key(KeyCode::Right);
+        columns.push((h.cursor().1, h.terminal.cursor().0));
+    }
+
+    // (char column, screen column) after each step.
+    assert_eq!(columns, [(1, 1), (6, 3), (8, 5), (10, 6), (11, 8), (12, 9)]);
+
+    h.key(KeyCode::Left).key(KeyCode::Left);
+    assert_eq!(h.cursor(), (0, 10));
+}
+
+#[test]
+fn backspace_removes_a_whole_grapheme() {
+    let mut h = Harness::new("");
+    h.type_text(&format!("a{FAMILY}")).key(KeyCode::Backspace);
+
+    assert_eq!(h.text(), "a");
+    assert_eq!(h.terminal.cursor(), (1, 0));
+
+    h.type_text("e\u{301}").key(KeyCode::Backspace);
+    assert_eq!(h.text(), "a");
+}
+
+#[test]
+fn a_grapheme_typed_in_pieces_undoes_in_one_step() {
+    let mut h = Harness::new("x\n");
+    h.type_text("🇫🇷");
+
+    assert_eq!(h.text(), "🇫🇷x\n");
+    h.ctrl('z');
+    assert_eq!(h.text(), "x\n");
+}
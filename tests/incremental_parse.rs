@@ -0,0 +1,99 @@
+// Edits are told to the syntax tree as they happen, so the next parse starts
+// from the old tree: the parts the edit didn't touch come through as the very
+// same nodes, and the result matches parsing from scratch.
+mod common;
+
+use common::{file, Harness};
+use crossterm::event::{KeyCode, KeyModifiers};
+use tree_sitter::{Node, Parser, Tree};
+
+fn source(functions: usize) -> String {
+    (0..functions)
+        .map(|n| format!("fn f{n}(x: u32) -> u32 {{\n    let y = x + {n};\n    y * 2\n}}\n\n"))
+        .collect()
+}
+
+fn fresh(text: &str) -> Tree {
+    let mut parser = Parser::new();
+    parser
+        .set_language(&tree_sitter_rust::LANGUAGE.into())
+        .unwrap();
+    parser.parse(text, None).unwrap()
+}
+
+fn functions(tree: &Tree) -> Vec<Node<'_>> {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    root.named_children(&mut cursor).collect()
+}
+
+fn tree(h: &Harness) -> Tree {
+    h.editor.buffer().tree.clone().expect("a tree")
+}
+
+#[test]
+fn typing_reuses_the_untouched_parts_of_the_tree() {
+    let path = file("incremental.rs", &source(2000));
+    let mut h = Harness::open(&[&path], 80, 10);
+    h.run();
+    let before = tree(&h);
+
+    h.repeat(KeyCode::Down, KeyModifiers::NONE, 1)
+        .key(KeyCode::End)
+        .type_slowly(" + 1");
+    let after = tree(&h);
+
+    assert!(after.root_node().id() != before.root_node().id());
+    let (before, after) = (functions(&before), functions(&after));
+    assert_eq!(before.len(), after.len());
+    let kept = before
+        .iter()
+        .zip(&after)
+        .filter(|(old, new)| old.id() == new.id())
+        .count();
+    assert!(before[0].id() != after[0].id());
+    // A few neighbours of the edit get reparsed with it, and the rest of the
+    // file is kept as it was.
+    assert!(kept > 1900, "{kept} of {} kept", before.len());
+    assert_eq!(before.last().unwrap().id(), after.last().unwrap().id());
+}
+
+#[test]
+fn the_reused_tree_matches_a_fresh_parse() {
+    let path = file("matches.rs", &source(50));
+    let mut h = Harness::open(&[&path], 80, 10);
+
+    h.repeat(KeyCode::Down, KeyModifiers::NONE, 11)
+        .type_slowly("    // note\n")
+        .key(KeyCode::Backspace)
+        .key(KeyCode::Backspace);
+    h.chord(KeyCode::End, KeyModifiers::CONTROL)
+        .type_text("fn tail() {}\n");
+    h.ctrl('z');
+    h.key(KeyCode::Up).key(KeyCode::Delete);
+
+    let text = h.text();
+    assert_eq!(
+        tree(&h).root_node().to_sexp(),
+        fresh(&text).root_node().to_sexp()
+    );
+}
+
+#[test]
+fn undo_and_redo_keep_the_tree_in_step() {
+    let path = file("travel.rs", &source(20));
+    let mut h = Harness::open(&[&path], 80, 10);
+
+    h.key(KeyCode::Down).type_slowly("    let z = {\n");
+    h.ctrl('z');
+    assert_eq!(
+        tree(&h).root_node().to_sexp(),
+        fresh(&h.text()).root_node().to_sexp()
+    );
+
+    h.ctrl('y');
+    assert_eq!(
+        tree(&h).root_node().to_sexp(),
+        fresh(&h.text()).root_node().to_sexp()
+    );
+}
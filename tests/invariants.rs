@@ -0,0 +1,183 @@
+// Random sequences of actions, typing, pastes and resizes, after each of which
+// the cursor and the selection are on the text, and the view shows the cursor.
+mod common;
+
+use common::Harness;
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use proptest::prelude::*;
+use ropey::Rope;
+use shark::{
+    buffer::Buffer,
+    keymap::{parse_sequence, Action, Keymap},
+    terminal::Terminal,
+};
+
+// The actions that do their work at once, without asking anything.
+const ACTIONS: [Action; 43] = [
+    Action::Cancel,
+    Action::MoveUp,
+    Action::MoveDown,
+    Action::MoveLeft,
+    Action::MoveRight,
+    Action::Newline,
+    Action::InsertTab,
+    Action::Backspace,
+    Action::Delete,
+    Action::DuplicateAndComment,
+    Action::ToggleComment,
+    Action::FixWhitespace,
+    Action::Undo,
+    Action::Redo,
+    Action::OpenLineBelow,
+    Action::OpenLineAbove,
+    Action::CopyLine,
+    Action::CutLine,
+    Action::DeleteLine,
+    Action::DuplicateLine,
+    Action::MoveLineUp,
+    Action::MoveLineDown,
+    Action::Paste,
+    Action::JumpToMatch,
+    Action::CenterCursor,
+    Action::JumpBack,
+    Action::JumpForward,
+    Action::LineStart,
+    Action::LineEnd,
+    Action::PageUp,
+    Action::PageDown,
+    Action::FileStart,
+    Action::FileEnd,
+    Action::WordLeft,
+    Action::WordRight,
+    Action::BackspaceWord,
+    Action::DeleteWord,
+    Action::SelectUp,
+    Action::SelectDown,
+    Action::SelectLeft,
+    Action::SelectRight,
+    Action::Copy,
+    Action::ToggleWrap,
+];
+
+#[derive(Clone, Debug)]
+enum Step {
+    // With a count typed first when there is one.
+    Act(Action, Option<u8>),
+    Type(String),
+    Paste(String),
+    Resize(u16, u16),
+}
+
+fn text() -> impl Strategy<Value = String> {
+    "[a-z (){}\t\n é字]{0,12}"
+}
+
+fn step() -> impl Strategy<Value = Step> {
+    prop_oneof![
+        6 => (prop::sample::select(ACTIONS.to_vec()), prop::option::of(1..12u8))
+            .prop_map(|(action, count)| Step::Act(action, count)),
+        2 => text().prop_map(Step::Type),
+        1 => text().prop_map(Step::Paste),
+        1 => (4..50u16, 3..12u16).prop_map(|(width, height)| Step::Resize(width, height)),
+    ]
+}
+
+fn press(h: &mut Harness, events: impl IntoIterator<Item = KeyEvent>) {
+    for event in events {
+        h.terminal.push(Event::Key(event));
+    }
+    h.run();
+}
+
+fn take(h: &mut Harness, keymap: &Keymap, step: &Step) {
+    match step {
+        Step::Act(action, count) => {
+            if let Some(count) = count {
+                let digits = count.to_string();
+                press(
+                    h,
+                    digits
+                        .chars()
+                        .map(|c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::ALT)),
+                );
+            }
+            let sequence = &keymap.chords_for(*action)[0];
+            let chords = parse_sequence(sequence).unwrap();
+            press(
+                h,
+                chords
+                    .into_iter()
+                    .map(|(code, modifiers)| KeyEvent::new(code, modifiers)),
+            );
+        }
+        Step::Type(text) => {
+            h.type_text(text);
+        }
+        Step::Paste(text) => {
+            h.terminal.push(Event::Paste(text.clone()));
+            h.run();
+        }
+        Step::Resize(width, height) => {
+            h.terminal.resize(*width, *height);
+            h.run();
+        }
+    }
+}
+
+// Chars on `line` before its line break.
+fn line_length(rope: &Rope, line: usize) -> usize {
+    let line = rope.line(line).to_string();
+    line.trim_end_matches(['\n', '\r']).chars().count()
+}
+
+fn check(h: &Harness) -> Result<(), TestCaseError> {
+    let buffer = h.editor.buffer();
+    let lines = buffer.rope.len_lines();
+
+    let (line, column) = h.cursor();
+    prop_assert!(line < lines, "cursor line {line} of {lines}");
+    prop_assert!(
+        column <= line_length(&buffer.rope, line),
+        "cursor {line}:{column}"
+    );
+
+    if let Some((line, column)) = h.editor.selection() {
+        prop_assert!(line < lines, "selection line {line} of {lines}");
+        prop_assert!(
+            column <= line_length(&buffer.rope, line),
+            "selection {line}:{column}"
+        );
+    }
+
+    // In view, above the status bar.
+    prop_assert!(
+        buffer.scroll <= line,
+        "scrolled to {} past {line}",
+        buffer.scroll
+    );
+    let (width, height) = h.terminal.size().unwrap();
+    let (x, y) = h.terminal.cursor();
+    prop_assert!(x < width && y < height - 1, "cursor drawn at {x},{y}");
+    Ok(())
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn every_step_leaves_the_cursor_and_selection_on_the_text(
+        start in text(),
+        steps in prop::collection::vec(step(), 1..40),
+    ) {
+        let keymap = Keymap::default();
+        let buffers = vec![Buffer::new(Rope::from_str(&start), None)];
+        let mut h = Harness::setup(buffers, 30, 8, |_| {});
+        h.run();
+        check(&h)?;
+
+        for step in &steps {
+            take(&mut h, &keymap, step);
+            check(&h)?;
+        }
+    }
+}
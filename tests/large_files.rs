@@ -0,0 +1,60 @@
+// Frames in a buffer of many megabytes cost about what they do in a small one,
+// since only the lines in view are sliced, highlighted and drawn. Timed
+// against the small buffer rather than a clock, so a slow or loaded machine
+// slows both alike.
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::Harness;
+use crossterm::event::{KeyCode, KeyModifiers};
+use ropey::Rope;
+use shark::buffer::Buffer;
+
+fn log(lines: usize) -> String {
+    (0..lines)
+        .map(|n| format!("2026-10-14T12:00:00Z INFO request {n} served in 3ms\n"))
+        .collect()
+}
+
+fn rust(functions: usize) -> String {
+    (0..functions)
+        .map(|n| format!("fn f{n}(x: u32) -> u32 {{\n    x + {n}\n}}\n"))
+        .collect()
+}
+
+// The average frame over paging through the buffer, from the top down to the
+// end and a way back up.
+fn per_frame(text: &str, name: Option<&str>) -> Duration {
+    let buffers = vec![Buffer::new(Rope::from_str(text), name.map(String::from))];
+    let mut h = Harness::with_buffers(buffers, 120, 50);
+    h.run();
+
+    let start = Instant::now();
+    let mut frames = 0;
+    for key in [KeyCode::PageDown, KeyCode::Down, KeyCode::PageUp] {
+        for _ in 0..40 {
+            h.key(key);
+            frames += 1;
+        }
+        h.chord(KeyCode::End, KeyModifiers::CONTROL);
+        frames += 1;
+    }
+    start.elapsed() / frames
+}
+
+// Hundreds of times the text; a frame costing in proportion to it would be
+// that much slower, not a few times.
+#[test]
+fn frames_in_a_large_plain_file_cost_what_small_ones_do() {
+    let small = per_frame(&log(1_000), None);
+    let large = per_frame(&log(300_000), None);
+    assert!(large < small * 5, "{large:?} a frame, against {small:?}");
+}
+
+#[test]
+fn highlighting_a_large_file_only_walks_the_lines_in_view() {
+    let small = per_frame(&rust(300), Some("small.rs"));
+    let large = per_frame(&rust(60_000), Some("large.rs"));
+    assert!(large < small * 5, "{large:?} a frame, against {small:?}");
+}
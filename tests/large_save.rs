@@ -0,0 +1,76 @@
+// Saving a buffer of several megabytes writes exactly the bytes of the text,
+// the same as writing them one at a time would, and much faster.
+mod common;
+
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    time::Instant,
+};
+
+use common::{file, Harness};
+use ropey::Rope;
+use shark::buffer;
+
+// Every few lines end in \r\n, and wide characters land across the rope's
+// chunk boundaries somewhere along the way.
+fn text(bytes: usize) -> String {
+    let mut text = String::with_capacity(bytes + 100);
+    let mut n = 0;
+    while text.len() < bytes {
+        let ending = if n % 7 == 0 { "\r\n" } else { "\n" };
+        text.push_str(&format!("let value_{n} = \"héllo 漢字 🦀\";{ending}"));
+        n += 1;
+    }
+    text
+}
+
+fn byte_at_a_time(rope: &Rope, out: &mut impl Write) -> io::Result<()> {
+    for byte in rope.bytes() {
+        out.write_all(&[byte])?;
+    }
+    Ok(())
+}
+
+#[test]
+fn a_large_buffer_saves_byte_for_byte() {
+    let text = text(8 << 20);
+    let path = file("large.rs", &text);
+    let mut h = Harness::open(&[&path], 80, 10);
+    fs::remove_file(&path).unwrap();
+    h.ctrl('s');
+
+    let saved = fs::read(&path).unwrap();
+    assert_eq!(saved.len(), text.len());
+    assert!(saved == text.as_bytes());
+
+    let mut old = Vec::new();
+    byte_at_a_time(&h.editor.buffer().rope, &mut old).unwrap();
+    assert!(saved == old);
+}
+
+#[test]
+fn writing_chunks_beats_writing_bytes() {
+    let rope = Rope::from_str(&text(4 << 20));
+    let path = file("chunks.rs", "");
+
+    let start = Instant::now();
+    let mut out = BufWriter::new(File::create(&path).unwrap());
+    buffer::write_rope(&rope, &mut out).unwrap();
+    out.flush().unwrap();
+    let chunks = start.elapsed();
+    drop(out);
+    let by_chunks = fs::read(&path).unwrap();
+
+    let start = Instant::now();
+    let mut out = BufWriter::new(File::create(&path).unwrap());
+    byte_at_a_time(&rope, &mut out).unwrap();
+    out.flush().unwrap();
+    let bytes = start.elapsed();
+    drop(out);
+
+    assert!(by_chunks == fs::read(&path).unwrap());
+    // The difference is a couple of orders of magnitude, so this holds on
+    // even a loaded machine.
+    assert!(chunks < bytes, "{chunks:?} for chunks, {bytes:?} for bytes");
+}
@@ -0,0 +1,45 @@
+// Going to the last change is a jump like any other, which JumpBack returns
+// from.
+mod common;
+
+use common::Harness;
+use crossterm::event::{KeyCode, KeyModifiers};
+
+fn lines(count: usize) -> String {
+    (0..count).map(|n| format!("line {n}\n")).collect()
+}
+
+#[test]
+fn jump_back_returns_from_the_last_change() {
+    let mut h = Harness::with_size(&lines(200), 40, 10);
+    h.repeat(KeyCode::Down, KeyModifiers::NONE, 5)
+        .type_text("x");
+    h.chord(KeyCode::End, KeyModifiers::CONTROL);
+    assert_eq!(h.cursor(), (199, 8));
+
+    h.alt('.');
+    assert_eq!(h.cursor(), (5, 1));
+    assert!(h.rows().iter().any(|row| row.starts_with("xline 5")));
+
+    h.ctrl('o');
+    assert_eq!(h.cursor(), (199, 8));
+}
+
+#[test]
+fn cycling_through_changes_jumps_back_to_where_it_started() {
+    let mut h = Harness::with_size(&lines(200), 40, 10);
+    h.repeat(KeyCode::Down, KeyModifiers::NONE, 5)
+        .type_text("a");
+    h.repeat(KeyCode::Down, KeyModifiers::NONE, 50)
+        .type_text("b");
+    h.chord(KeyCode::End, KeyModifiers::CONTROL);
+
+    h.alt('.');
+    assert_eq!(h.cursor(), (55, 2));
+    h.alt('.');
+    assert_eq!(h.cursor(), (5, 1));
+    assert!(h.status().contains("Change 2 of 2"));
+
+    h.ctrl('o');
+    assert_eq!(h.cursor(), (199, 8));
+}
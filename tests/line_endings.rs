@@ -0,0 +1,79 @@
+// LF and CRLF files saved back byte for byte: untouched lines keep their
+// endings, Enter inserts the file's own, and Backspace at a line start takes
+// a whole CRLF at once.
+mod common;
+
+use std::fs;
+
+use common::{file, Harness};
+use crossterm::event::KeyCode;
+
+fn open(name: &str, bytes: &str) -> (Harness, String) {
+    let path = file(name, bytes);
+    let mut h = Harness::open(&[&path], 60, 8);
+    h.run();
+    (h, path)
+}
+
+// Opened, changed and changed back, then saved.
+fn resaved(name: &str, bytes: &str) -> Vec<u8> {
+    let (mut h, path) = open(name, bytes);
+    h.type_text("x").key(KeyCode::Backspace);
+    assert!(h.editor.buffer().modified);
+    h.ctrl('s');
+    assert!(!h.editor.buffer().modified);
+    fs::read(path).unwrap()
+}
+
+#[test]
+fn files_save_back_unchanged() {
+    for (name, bytes) in [
+        ("lf.txt", "one\ntwo\n"),
+        ("crlf.txt", "one\r\ntwo\r\n"),
+        ("lf-unterminated.txt", "one\ntwo"),
+        ("crlf-unterminated.txt", "one\r\ntwo"),
+        ("blank-lines.txt", "\r\n\r\none\r\n\r\n"),
+    ] {
+        assert_eq!(resaved(name, bytes), bytes.as_bytes(), "{name}");
+    }
+}
+
+#[test]
+fn the_ending_is_shown_and_kept_out_of_the_text() {
+    let (h, _) = open("shown.txt", "one\r\ntwo\r\n");
+    assert!(h.status().contains("CRLF"));
+    assert_eq!(h.row(0), "one");
+    assert_eq!(h.editor.buffer().line_count(), 2);
+
+    let (h, _) = open("shown-lf.txt", "one\ntwo\n");
+    assert!(h.status().contains(" LF "));
+}
+
+#[test]
+fn edits_to_a_crlf_file_save_with_crlf() {
+    let (mut h, path) = open("edited-crlf.txt", "one\r\ntwo\r\n");
+    h.key(KeyCode::End).key(KeyCode::Enter).type_text("new");
+    h.key(KeyCode::Down)
+        .key(KeyCode::Home)
+        .key(KeyCode::Backspace);
+    assert_eq!(h.cursor(), (1, 3));
+    h.ctrl('s');
+    assert_eq!(fs::read(&path).unwrap(), b"one\r\nnewtwo\r\n");
+}
+
+#[test]
+fn edits_to_an_lf_file_save_with_lf() {
+    let (mut h, path) = open("edited-lf.txt", "one\ntwo\n");
+    h.key(KeyCode::End).key(KeyCode::Enter).type_text("new");
+    h.key(KeyCode::Down)
+        .key(KeyCode::Home)
+        .key(KeyCode::Backspace);
+    h.ctrl('s');
+    assert_eq!(fs::read(&path).unwrap(), b"one\nnewtwo\n");
+}
+
+#[test]
+fn a_lone_cr_inside_a_line_is_kept() {
+    let bytes = "one\rstill one\r\ntwo\r\n";
+    assert_eq!(resaved("lone-cr.txt", bytes), bytes.as_bytes());
+}
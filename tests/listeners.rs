@@ -0,0 +1,145 @@
+// A scripted session against listeners that record what they hear: each
+// buffer event reaches every listener, in the order the events happened and
+// the listeners were added, once the step causing it is over.
+mod common;
+
+use std::cell::RefCell;
+
+use common::{file, Harness};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use shark::{buffer::BufferEvent, editor::Editor};
+
+thread_local! {
+    static HEARD: RefCell<Vec<(&'static str, usize, BufferEvent)>> = const { RefCell::new(Vec::new()) };
+}
+
+fn first(_: &mut Editor, index: usize, event: &BufferEvent) {
+    HEARD.with(|heard| heard.borrow_mut().push(("first", index, event.clone())));
+}
+
+fn second(_: &mut Editor, index: usize, event: &BufferEvent) {
+    HEARD.with(|heard| heard.borrow_mut().push(("second", index, event.clone())));
+}
+
+// What was heard since the last call, each event once both listeners have
+// heard it, first then second.
+fn heard() -> Vec<(usize, BufferEvent)> {
+    let heard = HEARD.with(|heard| std::mem::take(&mut *heard.borrow_mut()));
+    assert_eq!(heard.len() % 2, 0, "{heard:?}");
+    heard
+        .chunks(2)
+        .map(|pair| {
+            let [(by_first, index, event), (by_second, again, same)] = pair else {
+                unreachable!()
+            };
+            assert_eq!((*by_first, *by_second), ("first", "second"));
+            assert_eq!((index, event), (again, same));
+            (*index, event.clone())
+        })
+        .collect()
+}
+
+fn changed(at: usize, removed: usize, inserted: usize) -> BufferEvent {
+    BufferEvent::Changed {
+        at,
+        removed,
+        inserted,
+    }
+}
+
+fn session() -> Harness {
+    let (one, two) = (file("one.txt", "one\n"), file("two.txt", "two\n"));
+    Harness::open_with(&[&one, &two], 60, 10, |editor| {
+        editor.listen(first);
+        editor.listen(second);
+    })
+}
+
+#[test]
+fn every_listener_hears_every_event_in_order() {
+    let mut h = session();
+    h.run();
+    // Opened with no text yet, and then loaded.
+    assert_eq!(
+        heard(),
+        [
+            (0, BufferEvent::Opened),
+            (1, BufferEvent::Opened),
+            (0, BufferEvent::Replaced),
+            (1, BufferEvent::Replaced)
+        ]
+    );
+
+    h.type_slowly("ab").key(KeyCode::Backspace);
+    assert_eq!(
+        heard(),
+        [
+            (0, changed(0, 0, 1)),
+            (0, changed(1, 0, 1)),
+            (0, changed(1, 1, 0))
+        ]
+    );
+
+    h.ctrl('s');
+    assert_eq!(heard(), [(0, BufferEvent::Saved)]);
+
+    // The backspace, then the typing as the one step it undoes in.
+    h.ctrl('z');
+    assert_eq!(heard(), [(0, changed(1, 0, 1))]);
+    h.ctrl('z');
+    assert_eq!(heard(), [(0, changed(0, 2, 0))]);
+}
+
+thread_local! {
+    static SEEN: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+fn snapshot(editor: &mut Editor, _: usize, event: &BufferEvent) {
+    if let BufferEvent::Changed { .. } = event {
+        let text = editor.buffer().rope.to_string();
+        SEEN.with(|seen| seen.borrow_mut().push(text));
+    }
+}
+
+// A listener hears about an edit once it's done, so it sees the text with
+// the edit in it, never half-way.
+#[test]
+fn listeners_see_the_text_after_the_edit() {
+    let path = file("after.txt", "one\n");
+    let mut h = Harness::open_with(&[&path], 60, 10, |editor| editor.listen(snapshot));
+    h.terminal.push(Event::Paste("a\nb".to_string()));
+    h.run();
+    h.ctrl('z');
+
+    let seen = SEEN.with(|seen| std::mem::take(&mut *seen.borrow_mut()));
+    assert_eq!(seen, ["a\nbone\n", "one\n"]);
+}
+
+#[test]
+fn closing_reports_under_the_index_the_buffer_had() {
+    let mut h = session();
+    h.run();
+    heard();
+
+    // The buffer list, down to the second buffer, and close it.
+    for key in [
+        KeyEvent::new(KeyCode::Char('b'), KeyModifiers::CONTROL),
+        KeyEvent::new(KeyCode::Down, KeyModifiers::NONE),
+        KeyEvent::new(KeyCode::Char('d'), KeyModifiers::NONE),
+        KeyEvent::new(KeyCode::Char('d'), KeyModifiers::NONE),
+        KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE),
+    ] {
+        h.terminal.push(Event::Key(key));
+    }
+    h.run();
+
+    // Closing the last one leaves a scratch buffer in its place.
+    assert_eq!(
+        heard(),
+        [
+            (1, BufferEvent::Closed),
+            (0, BufferEvent::Closed),
+            (0, BufferEvent::Opened)
+        ]
+    );
+}
@@ -0,0 +1,51 @@
+mod common;
+
+use std::fs;
+
+use common::Harness;
+
+fn in_subdirectory(name: &str, text: &str) -> Harness {
+    fs::create_dir_all(common::home().join("src")).unwrap();
+    let filename = common::file(&format!("src/{name}"), text);
+    Harness::open(&[&filename], 80, 10)
+}
+
+#[test]
+fn the_location_is_relative_to_the_project_root() {
+    let mut h = in_subdirectory("location.rs", "one\ntwo\nthree\n");
+    h.key(crossterm::event::KeyCode::Down).alt('l');
+
+    assert_eq!(h.terminal.clipboard().as_deref(), Some("src/location.rs:2"));
+    assert!(h.status().contains("Copied src/location.rs:2"));
+}
+
+#[test]
+fn the_column_counts_characters_from_one() {
+    let mut h = in_subdirectory("column.rs", "héllo\n");
+    for _ in 0..3 {
+        h.key(crossterm::event::KeyCode::Right);
+    }
+    h.alt('L');
+
+    assert_eq!(h.terminal.clipboard().as_deref(), Some("src/column.rs:1:4"));
+}
+
+#[test]
+fn a_buffer_without_a_file_has_no_location() {
+    let mut h = Harness::new("text\n");
+    h.alt('l');
+
+    assert_eq!(h.terminal.clipboard(), None);
+    assert!(h.status().contains("Buffer has no file name"));
+}
+
+#[test]
+fn the_line_is_copied_in_a_fence_tagged_with_the_language() {
+    let mut h = in_subdirectory("fence.rs", "fn main() {}\n");
+    h.alt('C');
+
+    assert_eq!(
+        h.terminal.clipboard().as_deref(),
+        Some("```rust\nfn main() {}\n```\n")
+    );
+}
@@ -0,0 +1,113 @@
+// A 500-character line in a 40-column terminal: the view scrolls sideways to
+// keep the cursor on screen, every row shows the same window of its line, and
+// highlighting is cut to that window along with the text.
+mod common;
+
+use common::{file, Harness};
+use crossterm::event::{KeyCode, KeyModifiers};
+use shark::theme::{ColorMode, Theme, ThemeChoice};
+
+const WIDTH: u16 = 40;
+
+// A string literal of numbered blocks, so every window of it is different.
+fn long_line() -> String {
+    let mut body: String = (0..122).map(|n| format!("{n:03}|")).collect();
+    body.push('.');
+    let line = format!("let s = \"{body}\";");
+    assert_eq!(line.chars().count(), 500);
+    line
+}
+
+fn open(name: &str) -> (Harness, String) {
+    let line = long_line();
+    let path = file(name, &format!("{line}\nshort\n"));
+    let mut h = Harness::open_with(&[&path], WIDTH, 6, |editor| {
+        editor.set_theme(Theme::new(ColorMode::Always, ThemeChoice::Dark));
+    });
+    h.run();
+    (h, line)
+}
+
+// The first display column on screen, worked out from where the cursor is in
+// the line and where the terminal shows it.
+fn h_scroll(h: &Harness) -> usize {
+    let (column, _) = h.terminal.cursor();
+    h.cursor().1 - column as usize
+}
+
+fn window(line: &str, from: usize) -> String {
+    let end = (from + WIDTH as usize).min(line.len());
+    line.get(from..end).unwrap_or_default().to_string()
+}
+
+#[test]
+fn the_view_follows_the_cursor_to_the_end_and_back() {
+    let (mut h, line) = open("end.rs");
+    assert_eq!(h.row(0), window(&line, 0));
+
+    h.key(KeyCode::End);
+    assert_eq!(h.cursor(), (0, 500));
+    let scroll = h_scroll(&h);
+    assert!(scroll > 0 && 500 - scroll < WIDTH as usize);
+    assert_eq!(h.row(0), window(&line, scroll));
+    // The short line is scrolled just as far, out of view.
+    assert_eq!(h.row(1), "");
+
+    h.key(KeyCode::Home);
+    assert_eq!(h_scroll(&h), 0);
+    assert_eq!(h.row(0), window(&line, 0));
+    assert_eq!(h.row(1), "short");
+}
+
+#[test]
+fn moving_past_the_right_edge_shifts_the_view() {
+    let (mut h, line) = open("right.rs");
+    // Thirteen columns at a time, which lands on every offset from the edges
+    // of the window somewhere along the line.
+    for column in (13..=500).step_by(13) {
+        h.repeat(KeyCode::Right, KeyModifiers::NONE, 13);
+        assert_eq!(h.cursor(), (0, column));
+        let (on_screen, _) = h.terminal.cursor();
+        assert!(on_screen < WIDTH, "column {column}");
+        assert_eq!(h.row(0), window(&line, h_scroll(&h)), "column {column}");
+    }
+
+    // Back past the left edge a few times.
+    for column in (390..=481).rev().step_by(13) {
+        h.repeat(KeyCode::Left, KeyModifiers::NONE, 13);
+        assert_eq!(h.cursor(), (0, column));
+        assert_eq!(h.row(0), window(&line, h_scroll(&h)), "column {column}");
+    }
+    assert!(h_scroll(&h) <= 390);
+}
+
+#[test]
+fn highlighting_is_cut_to_the_window() {
+    let (mut h, _) = open("colors.rs");
+    // The opening quote is where the string's color starts.
+    let string = h.terminal.cell(8, 0).style.foreground;
+    assert!(string.is_some());
+    assert!(h.terminal.cell(0, 0).style.foreground != string);
+
+    h.repeat(KeyCode::Right, KeyModifiers::NONE, 250);
+    assert!(h_scroll(&h) > 200);
+    for column in 0..WIDTH {
+        let cell = h.terminal.cell(column, 0);
+        assert_eq!(cell.style.foreground, string, "{column}: {}", cell.symbol);
+    }
+
+    h.key(KeyCode::End);
+    let end = 500 - h_scroll(&h) as u16;
+    assert_eq!(h.terminal.cell(end - 2, 0).style.foreground, string);
+    assert!(h.terminal.cell(end - 1, 0).style.foreground != string);
+}
+
+#[test]
+fn typing_at_the_far_end_edits_the_long_line() {
+    let (mut h, line) = open("typing.rs");
+    h.key(KeyCode::End).type_text(" // end");
+
+    assert_eq!(h.text(), format!("{line} // end\nshort\n"));
+    assert!(h.row(0).ends_with("\"; // end"));
+    assert_eq!(h.terminal.cursor().0 as usize, 507 - h_scroll(&h));
+}
@@ -0,0 +1,187 @@
+// Moving the cursor, checked against where the editor keeps it and where the
+// terminal's cursor is drawn for it.
+mod common;
+
+use common::Harness;
+use crossterm::event::{KeyCode, KeyModifiers};
+
+#[test]
+fn left_and_right_stay_on_the_line() {
+    let mut h = Harness::new("ab\ncd\n");
+    h.repeat(KeyCode::Right, KeyModifiers::NONE, 3);
+    assert_eq!(h.cursor(), (0, 2));
+    assert_eq!(h.terminal.cursor(), (2, 0));
+
+    h.key(KeyCode::Down).key(KeyCode::Left);
+    assert_eq!(h.cursor(), (1, 1));
+    assert_eq!(h.terminal.cursor(), (1, 1));
+    h.repeat(KeyCode::Left, KeyModifiers::NONE, 3);
+    assert_eq!(h.cursor(), (1, 0));
+}
+
+#[test]
+fn the_ends_of_the_buffer_stop_the_cursor() {
+    let mut h = Harness::new("ab\n");
+    h.key(KeyCode::Left).key(KeyCode::Up);
+    assert_eq!(h.cursor(), (0, 0));
+
+    h.repeat(KeyCode::Down, KeyModifiers::NONE, 5)
+        .repeat(KeyCode::Right, KeyModifiers::NONE, 5);
+    let end = h.cursor();
+    h.key(KeyCode::Right).key(KeyCode::Down);
+    assert_eq!(h.cursor(), end);
+}
+
+// A last line without a terminator is as far as Down goes, and a file that
+// exactly fills the view doesn't scroll.
+#[test]
+fn down_stops_at_the_last_line_of_a_short_file() {
+    let mut h = Harness::new("one\ntwo");
+    h.repeat(KeyCode::Down, KeyModifiers::NONE, 3);
+    assert_eq!(h.cursor(), (1, 0));
+    assert_eq!(h.row(0), "one");
+
+    let text: String = (0..9).map(|n| format!("line {n}\n")).collect();
+    let mut h = Harness::new(text.trim_end());
+    h.repeat(KeyCode::Down, KeyModifiers::NONE, 12);
+    assert_eq!(h.cursor(), (8, 0));
+    assert_eq!(h.row(0), "line 0");
+    assert_eq!(h.row(8), "line 8");
+}
+
+#[test]
+fn up_and_down_keep_the_column_across_shorter_lines() {
+    let mut h = Harness::new("long line\nab\nanother long\n");
+    h.key(KeyCode::End);
+    assert_eq!(h.cursor(), (0, 9));
+
+    h.key(KeyCode::Down);
+    assert_eq!(h.cursor(), (1, 2));
+    assert_eq!(h.terminal.cursor(), (2, 1));
+    h.key(KeyCode::Down);
+    assert_eq!(h.cursor(), (2, 9));
+
+    // Moving sideways forgets it.
+    h.key(KeyCode::Up).key(KeyCode::Left).key(KeyCode::Down);
+    assert_eq!(h.cursor(), (2, 1));
+}
+
+#[test]
+fn home_and_end_go_to_the_ends_of_the_line() {
+    let mut h = Harness::new("one\n    two three\n");
+    h.key(KeyCode::Down).key(KeyCode::End);
+    assert_eq!(h.cursor(), (1, 13));
+
+    h.key(KeyCode::Home);
+    assert_eq!(h.cursor().0, 1);
+    assert!(h.cursor().1 <= 4);
+    assert_eq!(h.terminal.cursor(), (h.cursor().1 as u16, 1));
+}
+
+#[test]
+fn moving_by_columns_steps_over_wide_characters() {
+    let mut h = Harness::new("a漢b\nxyzw\n");
+    h.key(KeyCode::Right).key(KeyCode::Right);
+    assert_eq!(h.cursor(), (0, 2));
+    assert_eq!(h.terminal.cursor(), (3, 0));
+
+    // Three columns in, which is where b starts below the wide char.
+    h.key(KeyCode::Down);
+    assert_eq!(h.cursor(), (1, 3));
+    h.key(KeyCode::Up);
+    assert_eq!(h.cursor(), (0, 2));
+}
+
+#[test]
+fn edits_leave_the_cursor_where_the_text_says() {
+    let mut h = Harness::new("one\ntwo\n");
+    h.key(KeyCode::Down).key(KeyCode::End).type_text("!\nthree");
+    assert_eq!(h.text(), "one\ntwo!\nthree\n");
+    assert_eq!(h.cursor(), (2, 5));
+    assert_eq!(h.terminal.cursor(), (5, 2));
+
+    h.repeat(KeyCode::Backspace, KeyModifiers::NONE, 6);
+    assert_eq!(h.text(), "one\ntwo!\n");
+    assert_eq!(h.cursor(), (1, 4));
+    assert_eq!(h.terminal.cursor(), (4, 1));
+}
+
+#[test]
+fn word_moves_cross_lines_and_ctrl_backspace_undoes_in_one_step() {
+    let mut h = Harness::new("let x_1 = y;\n\n  next\n");
+    h.chord(KeyCode::Right, KeyModifiers::CONTROL);
+    assert_eq!(h.cursor(), (0, 4));
+    h.repeat(KeyCode::Right, KeyModifiers::CONTROL, 2);
+    assert_eq!(h.cursor(), (2, 2));
+    h.chord(KeyCode::Left, KeyModifiers::CONTROL);
+    assert_eq!(h.cursor(), (0, 10));
+
+    h.key(KeyCode::End)
+        .chord(KeyCode::Left, KeyModifiers::CONTROL);
+    h.chord(KeyCode::Backspace, KeyModifiers::CONTROL);
+    assert_eq!(h.text(), "let y;\n\n  next\n");
+    h.ctrl('h');
+    assert_eq!(h.text(), "y;\n\n  next\n");
+
+    h.ctrl('z');
+    assert_eq!(h.text(), "let y;\n\n  next\n");
+    assert_eq!(h.cursor(), (0, 4));
+}
+
+// Long lines with empty ones between them, ten of each.
+fn alternating() -> String {
+    (0..10)
+        .map(|n| format!("line {n} is long enough\n\n"))
+        .collect()
+}
+
+#[test]
+fn the_column_snaps_back_after_each_empty_line() {
+    let mut h = Harness::with_size(&alternating(), 40, 30);
+    h.repeat(KeyCode::Right, KeyModifiers::NONE, 12);
+
+    for line in 1..20 {
+        h.key(KeyCode::Down);
+        let column = if line % 2 == 0 { 12 } else { 0 };
+        assert_eq!(h.cursor(), (line, column));
+        assert_eq!(h.terminal.cursor(), (column as u16, line as u16));
+    }
+    for line in (0..19).rev() {
+        h.key(KeyCode::Up);
+        let column = if line % 2 == 0 { 12 } else { 0 };
+        assert_eq!(h.cursor(), (line, column));
+    }
+}
+
+#[test]
+fn counts_and_pages_come_back_to_the_column_too() {
+    let mut h = Harness::with_size(&alternating(), 40, 5);
+    h.repeat(KeyCode::Right, KeyModifiers::NONE, 12);
+
+    // Through an empty line and onto the next long one at once.
+    h.alt('3').key(KeyCode::Down);
+    assert_eq!(h.cursor(), (3, 0));
+    h.key(KeyCode::Down);
+    assert_eq!(h.cursor(), (4, 12));
+
+    // Four rows of text, so a page goes from a long line to another.
+    h.key(KeyCode::PageDown);
+    assert_eq!(h.cursor(), (8, 12));
+    h.key(KeyCode::Down).key(KeyCode::PageUp);
+    assert_eq!(h.cursor(), (5, 0));
+    h.key(KeyCode::Up);
+    assert_eq!(h.cursor(), (4, 12));
+}
+
+#[test]
+fn typing_on_an_empty_line_sets_the_column_anew() {
+    let mut h = Harness::with_size(&alternating(), 40, 30);
+    h.repeat(KeyCode::Right, KeyModifiers::NONE, 12);
+    h.key(KeyCode::Down).type_text("ab");
+    assert_eq!(h.cursor(), (1, 2));
+
+    h.key(KeyCode::Down);
+    assert_eq!(h.cursor(), (2, 2));
+    h.key(KeyCode::Down).key(KeyCode::Down);
+    assert_eq!(h.cursor(), (4, 2));
+}
@@ -0,0 +1,120 @@
+// A fixture of accented, CJK and emoji lines: columns count chars, the drawn
+// cursor moves by display width, and inserting or deleting next to any of them
+// changes exactly the char intended.
+mod common;
+
+use common::{file, Harness};
+use crossterm::event::{KeyCode, KeyModifiers};
+
+const FIXTURE: &str = include_str!("fixtures/multibyte.txt");
+
+fn open(name: &str) -> Harness {
+    let path = file(name, FIXTURE);
+    let mut h = Harness::open(&[&path], 40, 10);
+    h.run();
+    h
+}
+
+fn lines() -> Vec<&'static str> {
+    FIXTURE.lines().collect()
+}
+
+// The fixture with line `line` replaced.
+fn with_line(line: usize, text: &str) -> String {
+    let mut lines = lines();
+    lines[line] = text;
+    lines.iter().map(|line| format!("{line}\n")).collect()
+}
+
+fn go_to(h: &mut Harness, line: usize) {
+    h.chord(KeyCode::Home, KeyModifiers::CONTROL);
+    h.repeat(KeyCode::Down, KeyModifiers::NONE, line);
+}
+
+#[test]
+fn every_line_is_drawn_whole() {
+    let h = open("drawn.txt");
+    for (row, line) in lines().iter().enumerate() {
+        assert_eq!(h.row(row as u16), *line);
+    }
+    // Each CJK char takes a cell and the empty one after it.
+    assert_eq!(h.terminal.cell(0, 2).symbol, "中");
+    assert_eq!(h.terminal.cell(1, 2).symbol, "");
+    assert_eq!(h.terminal.cell(2, 2).symbol, "文");
+}
+
+#[test]
+fn the_end_of_each_line_is_its_char_count_and_display_width() {
+    let mut h = open("ends.txt");
+    let ends = [(12, 12), (12, 12), (4, 8), (8, 16), (14, 16), (10, 12)];
+    for (line, (chars, width)) in ends.into_iter().enumerate() {
+        go_to(&mut h, line);
+        h.key(KeyCode::End);
+        assert_eq!(h.cursor(), (line, chars), "line {line}");
+        assert_eq!(h.terminal.cursor(), (width, line as u16), "line {line}");
+    }
+}
+
+#[test]
+fn backspace_at_the_end_of_each_line_removes_its_last_char() {
+    let mut h = open("backspace.txt");
+    for (line, text) in lines().iter().enumerate() {
+        go_to(&mut h, line);
+        h.key(KeyCode::End).key(KeyCode::Backspace);
+        let mut shorter = text.chars();
+        shorter.next_back();
+        assert_eq!(h.text(), with_line(line, shorter.as_str()), "line {line}");
+        h.ctrl('z');
+        assert_eq!(h.text(), FIXTURE);
+    }
+}
+
+#[test]
+fn delete_at_the_start_of_each_line_removes_its_first_char() {
+    let mut h = open("delete.txt");
+    for (line, text) in lines().iter().enumerate() {
+        go_to(&mut h, line);
+        h.key(KeyCode::Delete);
+        let mut shorter = text.chars();
+        shorter.next();
+        assert_eq!(h.text(), with_line(line, shorter.as_str()), "line {line}");
+        h.ctrl('z');
+    }
+}
+
+#[test]
+fn typing_between_wide_chars_inserts_there() {
+    let mut h = open("typing.txt");
+    go_to(&mut h, 2);
+    h.repeat(KeyCode::Right, KeyModifiers::NONE, 2);
+    assert_eq!(h.terminal.cursor(), (4, 2));
+    h.type_text("x");
+    assert_eq!(h.text(), with_line(2, "中文x字符"));
+    assert_eq!(h.terminal.cursor(), (5, 2));
+
+    // After the emoji on the last line, then backspaced over it.
+    go_to(&mut h, 5);
+    h.repeat(KeyCode::Right, KeyModifiers::NONE, 9);
+    h.type_text("!");
+    assert_eq!(h.text().lines().nth(5), Some("mixed é中😀!x"));
+    h.key(KeyCode::Backspace).key(KeyCode::Backspace);
+    assert_eq!(h.text().lines().nth(5), Some("mixed é中x"));
+    assert_eq!(h.cursor(), (5, 8));
+    assert_eq!(h.terminal.cursor(), (9, 5));
+}
+
+#[test]
+fn moving_between_lines_keeps_the_display_column() {
+    let mut h = open("columns.txt");
+    // Four columns into the CJK line is two chars in.
+    go_to(&mut h, 0);
+    h.repeat(KeyCode::Right, KeyModifiers::NONE, 4);
+    h.key(KeyCode::Down).key(KeyCode::Down);
+    assert_eq!(h.cursor(), (2, 2));
+    assert_eq!(h.terminal.cursor(), (4, 2));
+
+    h.key(KeyCode::Down);
+    assert_eq!(h.cursor(), (3, 2));
+    h.key(KeyCode::Up).key(KeyCode::Up).key(KeyCode::Up);
+    assert_eq!(h.cursor(), (0, 4));
+}
@@ -0,0 +1,128 @@
+// Text goes into a buffer with its line breaks, whichever style they're in,
+// converted to the buffer's own, so the line count and the saved file come
+// out as if it had been typed there.
+mod common;
+
+use std::{fs, path::Path};
+
+use common::{file, Harness};
+use crossterm::event::{Event, KeyCode, KeyModifiers};
+use proptest::prelude::*;
+use ropey::Rope;
+use shark::buffer::{self, Buffer, LineEnding};
+
+const ENDINGS: [LineEnding; 2] = [LineEnding::Lf, LineEnding::CrLf];
+
+// Pieces of text joined by breaks of every style, lone \r included.
+fn inserted() -> impl Strategy<Value = String> {
+    let piece = ("[a-z é字]{0,3}", prop_oneof!["\n", "\r\n", "\r", ""]);
+    prop::collection::vec(piece, 0..6).prop_map(|pieces| {
+        pieces
+            .iter()
+            .map(|(text, end)| format!("{text}{end}"))
+            .collect()
+    })
+}
+
+// Line contents of a file, and whether the last one is terminated.
+fn lines() -> impl Strategy<Value = (Vec<String>, bool)> {
+    (prop::collection::vec("[a-z é]{0,4}", 1..5), any::<bool>())
+}
+
+fn render(lines: &[String], terminated: bool, ending: LineEnding) -> String {
+    let mut text = lines.join(ending.as_str());
+    if terminated {
+        text.push_str(ending.as_str());
+    }
+    text
+}
+
+// What inserting `text` should give, worked out on plain strings.
+fn expected(before: &str, at: usize, text: &str, ending: LineEnding) -> String {
+    let unified = text.replace("\r\n", "\n").replace('\r', "\n");
+    let text = unified.replace('\n', ending.as_str());
+    let at = before
+        .char_indices()
+        .nth(at)
+        .map_or(before.len(), |(i, _)| i);
+    format!("{}{text}{}", &before[..at], &before[at..])
+}
+
+// Counted the way the editor counts them: a final terminator ends the last
+// line rather than starting another.
+fn line_count(text: &str, ending: LineEnding) -> usize {
+    let breaks = text.matches(ending.as_str()).count();
+    if text.is_empty() || text.ends_with(ending.as_str()) {
+        breaks
+    } else {
+        breaks + 1
+    }
+}
+
+fn saved(rope: &Rope) -> Vec<u8> {
+    let mut out = Vec::new();
+    buffer::write_rope(rope, &mut out).unwrap();
+    out
+}
+
+proptest! {
+    #[test]
+    fn inserted_breaks_take_the_buffer_convention(
+        (lines, terminated) in lines(),
+        line in 0..5usize,
+        column in 0..5usize,
+        text in inserted(),
+        ending in prop::sample::select(ENDINGS.to_vec()),
+    ) {
+        let before = render(&lines, terminated, ending);
+        let mut buffer = Buffer::new(Rope::from_str(&before), None);
+        // A single unterminated line has no convention, and gets \n.
+        let ending = buffer.line_ending();
+
+        // Somewhere on a line, never inside a terminator.
+        let line = line.min(lines.len() - 1);
+        let start = buffer.rope.line_to_char(line);
+        let at = start + column.min(lines[line].chars().count());
+        let after = buffer.insert_text(at, &text, (line, 0));
+        let want = expected(&before, at, &text, ending);
+
+        prop_assert_eq!(buffer.rope.to_string(), want.clone());
+        prop_assert_eq!(after, at + (want.chars().count() - before.chars().count()));
+        prop_assert_eq!(buffer.line_count(), line_count(&want, ending));
+        let rest = buffer.rope.to_string().replace(ending.as_str(), "");
+        prop_assert!(!rest.contains(['\r', '\n']));
+        prop_assert_eq!(saved(&buffer.rope), want.as_bytes());
+
+        // One edit, undone to the exact text it replaced.
+        prop_assert!(buffer.modified);
+        prop_assert!(buffer.undo((line, 0)).is_ok());
+        prop_assert_eq!(buffer.rope.to_string(), before);
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(24))]
+
+    // Pasted into the editor and saved, then read back from the file.
+    #[test]
+    fn pastes_save_and_load_back_the_same(
+        text in inserted(),
+        ending in prop::sample::select(ENDINGS.to_vec()),
+    ) {
+        let before = format!("one{0}two{0}", ending.as_str());
+        let path = file("pasted.txt", &before);
+        let mut h = Harness::open(&[&path], 60, 10);
+        // The same file each time, so back from wherever the last case left it.
+        h.chord(KeyCode::Home, KeyModifiers::CONTROL).key(KeyCode::Down);
+        h.terminal.push(Event::Paste(text.clone()));
+        h.run().ctrl('s');
+
+        let want = expected(&before, 3 + ending.as_str().len(), &text, ending);
+        let on_disk = fs::read(&path).unwrap();
+        prop_assert_eq!(&on_disk, want.as_bytes());
+        prop_assert_eq!(h.editor.buffer().line_count(), line_count(&want, ending));
+
+        let (rope, _) = buffer::read_file(Path::new(&path)).unwrap();
+        prop_assert_eq!(rope.to_string(), want);
+    }
+}
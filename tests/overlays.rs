@@ -0,0 +1,84 @@
+// Help opened over search: keys go to the topmost overlay, Esc takes off one
+// layer at a time, and each dismissal puts back the frame from underneath.
+mod common;
+
+use common::Harness;
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+
+const TEXT: &str = "one two\nthree two\nfour\nfive two\n";
+
+fn searching() -> Harness {
+    let mut h = Harness::with_size(TEXT, 80, 12);
+    for key in [
+        KeyEvent::new(KeyCode::Char('f'), KeyModifiers::CONTROL),
+        KeyEvent::new(KeyCode::Char('t'), KeyModifiers::NONE),
+        KeyEvent::new(KeyCode::Char('w'), KeyModifiers::NONE),
+        KeyEvent::new(KeyCode::Char('o'), KeyModifiers::NONE),
+        KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE),
+    ] {
+        h.terminal.push(Event::Key(key));
+    }
+    h.run();
+    assert_eq!(h.cursor(), (0, 4));
+    assert!(h.status().contains("Search: two"));
+    h
+}
+
+fn showing_help(h: &Harness) -> bool {
+    h.rows()
+        .iter()
+        .any(|row| row.contains("Quit, asking first"))
+}
+
+#[test]
+fn help_over_search_takes_the_keys_until_it_is_dismissed() {
+    let mut h = searching();
+    let search_frame = h.rows();
+
+    h.key(KeyCode::F(1));
+    assert!(showing_help(&h));
+
+    // n and Down belong to help now: the cursor stays on the first match.
+    h.key(KeyCode::Char('n')).key(KeyCode::Down);
+    assert_eq!(h.cursor(), (0, 4));
+    assert_eq!(h.text(), TEXT);
+
+    // Esc closes help alone, back to the search frame as it was.
+    h.key(KeyCode::Esc);
+    assert!(!showing_help(&h));
+    assert_eq!(h.rows(), search_frame);
+
+    // Search has the keys again.
+    h.key(KeyCode::Char('n'));
+    assert_eq!(h.cursor(), (1, 6));
+    assert!(h.status().contains("Search: two"));
+}
+
+#[test]
+fn each_esc_takes_off_one_layer() {
+    let mut h = searching();
+
+    h.key(KeyCode::F(1)).key(KeyCode::Esc).key(KeyCode::Esc);
+    assert!(!showing_help(&h));
+    assert!(!h.status().contains("Search:"));
+
+    // Nothing is left to take the keys but the buffer.
+    h.key(KeyCode::Char('x'));
+    assert_eq!(h.text(), format!("one x{}", &TEXT[4..]));
+    h.ctrl('z');
+    assert_eq!(h.row(0), "one two");
+}
+
+#[test]
+fn help_opened_by_itself_closes_back_to_the_buffer() {
+    let mut h = Harness::with_size(TEXT, 80, 12);
+    let plain = h.run().rows();
+
+    h.key(KeyCode::F(1));
+    assert!(showing_help(&h));
+    h.type_text("abc");
+    assert_eq!(h.text(), TEXT);
+
+    h.key(KeyCode::Esc);
+    assert_eq!(h.rows(), plain);
+}
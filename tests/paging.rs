@@ -0,0 +1,115 @@
+// Paging with lines of overlap: each page keeps that many lines from the one
+// before in view, and the last page stops with the last line at the bottom
+// rather than scrolling into empty rows. The mouse wheel scrolls the lines
+// it's set to.
+mod common;
+
+use common::Harness;
+use crossterm::event::{Event, KeyCode, KeyModifiers, MouseEvent, MouseEventKind};
+use ropey::Rope;
+use shark::buffer::Buffer;
+
+// Ten rows of text, then the status bar.
+const HEIGHT: u16 = 11;
+const LINES: usize = 35;
+
+// No scroll margin, which would pull the view back to keep the cursor off its
+// top rows and leave the overlap looking bigger than it is.
+fn paging(overlap: usize, half_page: bool) -> Harness {
+    let text: String = (0..LINES).map(|n| format!("line {n}\n")).collect();
+    let buffers = vec![Buffer::new(Rope::from_str(&text), None)];
+    let mut h = Harness::setup(buffers, 40, HEIGHT, |editor| {
+        editor.set_paging(overlap, half_page);
+        editor.set_scroll_margin(0);
+    });
+    h.run();
+    h
+}
+
+// The line at the top of the view.
+fn scroll(h: &Harness) -> usize {
+    let top = h.row(0);
+    top.strip_prefix("line ").unwrap().parse().unwrap()
+}
+
+// Where each press leaves the view and the cursor.
+fn pages(h: &mut Harness, key: KeyCode, presses: usize) -> Vec<(usize, usize)> {
+    (0..presses)
+        .map(|_| {
+            h.key(key);
+            (scroll(h), h.cursor().0)
+        })
+        .collect()
+}
+
+#[test]
+fn each_page_keeps_the_overlap_in_view() {
+    let mut h = paging(2, false);
+    assert_eq!(
+        pages(&mut h, KeyCode::PageDown, 3),
+        [(8, 8), (16, 16), (24, 24)]
+    );
+    // The two lines that were at the bottom are at the top now.
+    h.key(KeyCode::PageUp);
+    assert_eq!((scroll(&h), h.cursor().0), (16, 16));
+    assert_eq!(h.row(0), "line 16");
+    assert_eq!(h.row(1), "line 17");
+}
+
+#[test]
+fn the_last_page_stops_at_the_end_of_the_file() {
+    let mut h = paging(2, false);
+    h.key(KeyCode::PageDown)
+        .key(KeyCode::PageDown)
+        .key(KeyCode::PageDown);
+
+    // Only a line left to scroll: the view stops there, the cursor goes on to
+    // the last line, and further presses change neither.
+    assert_eq!(
+        pages(&mut h, KeyCode::PageDown, 3),
+        [(25, 32), (25, 34), (25, 34)]
+    );
+    assert_eq!(h.row(9), format!("line {}", LINES - 1));
+
+    assert_eq!(
+        pages(&mut h, KeyCode::PageUp, 5),
+        [(17, 26), (9, 18), (1, 10), (0, 2), (0, 0)]
+    );
+}
+
+#[test]
+fn an_overlap_as_tall_as_the_view_still_moves_a_line() {
+    let mut h = paging(50, false);
+    assert_eq!(pages(&mut h, KeyCode::PageDown, 2), [(1, 1), (2, 2)]);
+}
+
+#[test]
+fn half_pages_ignore_the_overlap() {
+    let mut h = paging(2, true);
+    assert_eq!(
+        pages(&mut h, KeyCode::PageDown, 6),
+        [(5, 5), (10, 10), (15, 15), (20, 20), (25, 25), (25, 30)]
+    );
+}
+
+fn wheel(h: &mut Harness, kind: MouseEventKind) -> usize {
+    h.terminal.push(Event::Mouse(MouseEvent {
+        kind,
+        column: 10,
+        row: 5,
+        modifiers: KeyModifiers::NONE,
+    }));
+    h.run();
+    scroll(h)
+}
+
+#[test]
+fn the_wheel_scrolls_the_lines_it_is_set_to() {
+    let mut h = paging(0, false);
+    assert_eq!(wheel(&mut h, MouseEventKind::ScrollDown), 3);
+
+    h.editor.set_wheel_lines(7);
+    assert_eq!(wheel(&mut h, MouseEventKind::ScrollDown), 10);
+    assert_eq!(wheel(&mut h, MouseEventKind::ScrollUp), 3);
+    assert_eq!(wheel(&mut h, MouseEventKind::ScrollUp), 0);
+}
@@ -0,0 +1,67 @@
+// Pastes over the guard's limits ask before they go in: n throws the paste
+// away and y inserts it as a single undo step. Pastes within them never ask.
+mod common;
+
+use common::Harness;
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+
+const QUESTION: &str = "Paste 30 KB (6000 lines)? [y/N]";
+
+// More lines than the default limit of 5000.
+fn huge() -> String {
+    "line\n".repeat(6000)
+}
+
+// Pastes `text` with `answer` queued behind it, and returns what was drawn.
+fn paste(h: &mut Harness, text: &str, answer: char) -> String {
+    h.terminal.take_output();
+    h.terminal.push(Event::Paste(text.to_string()));
+    h.terminal.push(Event::Key(KeyEvent::new(
+        KeyCode::Char(answer),
+        KeyModifiers::NONE,
+    )));
+    h.run();
+    String::from_utf8_lossy(&h.terminal.take_output()).into_owned()
+}
+
+#[test]
+fn declining_a_huge_paste_discards_it() {
+    let mut h = Harness::new("kept\n");
+    let drawn = paste(&mut h, &huge(), 'n');
+
+    assert!(drawn.contains(QUESTION));
+    assert_eq!(h.text(), "kept\n");
+    assert!(!h.editor.buffer().modified);
+}
+
+#[test]
+fn accepting_a_huge_paste_inserts_it_in_one_undo_step() {
+    let mut h = Harness::new("kept\n");
+    let drawn = paste(&mut h, &huge(), 'y');
+
+    assert!(drawn.contains(QUESTION));
+    assert_eq!(h.text(), format!("{}kept\n", huge()));
+    assert_eq!(h.cursor(), (6000, 0));
+
+    h.ctrl('z');
+    assert_eq!(h.text(), "kept\n");
+}
+
+#[test]
+fn a_normal_paste_goes_straight_in() {
+    let mut h = Harness::new("kept\n");
+    let drawn = paste(&mut h, "a few\nlines\n", 'n');
+
+    // The n wasn't taken as an answer, so it's typed after the paste.
+    assert!(!drawn.contains("Paste "));
+    assert_eq!(h.text(), "a few\nlines\nnkept\n");
+}
+
+#[test]
+fn without_the_guard_nothing_asks() {
+    let mut h = Harness::setup(Vec::new(), 40, 10, |editor| editor.set_paste_guard(None));
+    let drawn = paste(&mut h, &huge(), 'n');
+
+    assert!(!drawn.contains("Paste "));
+    assert_eq!(h.text(), format!("{}n\n", huge()));
+}
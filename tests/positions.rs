@@ -0,0 +1,71 @@
+// Where files were left, and the language they were set to, come back the
+// next time they're opened.
+mod common;
+
+use std::{fs, path::PathBuf, sync::Mutex};
+
+use common::{file, home, Harness};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+
+// The tests share one positions file, and each rewrites it whole.
+static POSITIONS: Mutex<()> = Mutex::new(());
+
+fn positions() -> PathBuf {
+    home().join(".local/share/shark/positions")
+}
+
+fn push(h: &mut Harness, code: KeyCode, modifiers: KeyModifiers) {
+    h.terminal.push(Event::Key(KeyEvent::new(code, modifiers)));
+}
+
+#[test]
+fn a_language_set_for_a_file_is_kept() {
+    let _lock = POSITIONS.lock().unwrap();
+    let path = file("kept.txt", "fn main() {}\nfn other() {}\n");
+    let mut h = Harness::open(&[&path], 60, 12);
+    h.key(KeyCode::Down);
+    // The menu starts at plain text, the last, and Rust is first.
+    push(&mut h, KeyCode::Char('g'), KeyModifiers::ALT);
+    for _ in 0..10 {
+        push(&mut h, KeyCode::Up, KeyModifiers::NONE);
+    }
+    push(&mut h, KeyCode::Enter, KeyModifiers::NONE);
+    h.run();
+    assert!(h.status().contains("Language set to Rust"));
+    h.editor.remember_positions();
+
+    let text = fs::read_to_string(positions()).unwrap();
+    let canonical = fs::canonicalize(&path).unwrap();
+    assert!(text.contains(&format!("1 0 0 rust {}\n", canonical.display())));
+
+    let mut h = Harness::open(&[&path], 60, 12);
+    h.run();
+    assert_eq!(h.cursor(), (1, 0));
+    assert!(h.status().contains("rust"));
+}
+
+#[test]
+fn a_detected_language_isnt_stored() {
+    let _lock = POSITIONS.lock().unwrap();
+    let path = file("detected.rs", "fn main() {}\n");
+    let mut h = Harness::open(&[&path], 60, 12);
+    h.key(KeyCode::End);
+    h.editor.remember_positions();
+
+    let text = fs::read_to_string(positions()).unwrap();
+    let canonical = fs::canonicalize(&path).unwrap();
+    assert!(text.contains(&format!("0 12 0 - {}\n", canonical.display())));
+}
+
+#[test]
+fn entries_from_before_languages_were_kept_still_apply() {
+    let _lock = POSITIONS.lock().unwrap();
+    let path = file("older.txt", "one\ntwo\nthree\n");
+    let canonical = fs::canonicalize(&path).unwrap();
+    fs::create_dir_all(positions().parent().unwrap()).unwrap();
+    fs::write(positions(), format!("2 3 0 {}\n", canonical.display())).unwrap();
+
+    let h = Harness::open(&[&path], 60, 12);
+    assert_eq!(h.cursor(), (2, 3));
+    assert!(!h.status().contains("plain text"));
+}
@@ -0,0 +1,60 @@
+// Auto-closing quotes only where a string can start, by the syntax tree.
+mod common;
+
+use common::{file, Harness};
+use crossterm::event::{KeyCode, KeyModifiers};
+
+fn rust(name: &str, text: &str) -> Harness {
+    let path = file(name, text);
+    Harness::open_with(&[&path], 40, 8, |editor| editor.set_auto_pairs(true))
+}
+
+#[test]
+fn a_string_typed_from_scratch_is_closed_once() {
+    let mut h = rust("scratch.rs", "");
+    h.type_slowly("let s = \"hi");
+
+    assert_eq!(h.text(), "let s = \"hi\"");
+    assert_eq!(h.cursor(), (0, 11));
+
+    // The closing quote is stepped over, not doubled.
+    h.type_slowly("\";");
+    assert_eq!(h.text(), "let s = \"hi\";");
+    assert_eq!(h.cursor(), (0, 13));
+}
+
+#[test]
+fn closing_an_unterminated_string_adds_one_quote() {
+    let mut h = rust("unterminated.rs", "let s = \"abc;\n");
+    h.repeat(KeyCode::Right, KeyModifiers::NONE, 12);
+    h.type_slowly("\"");
+
+    assert_eq!(h.text(), "let s = \"abc\";\n");
+    assert_eq!(h.cursor(), (0, 13));
+}
+
+#[test]
+fn quotes_in_comments_are_not_closed() {
+    let mut h = rust("comment.rs", "// say \nfn f() {}\n");
+    h.key(KeyCode::End).type_slowly("\"it's");
+
+    assert_eq!(h.text(), "// say \"it's\nfn f() {}\n");
+}
+
+#[test]
+fn quotes_inside_a_string_are_not_closed() {
+    let mut h = rust("inside.rs", "let s = \"a b\";\n");
+    h.repeat(KeyCode::Right, KeyModifiers::NONE, 10);
+    h.type_slowly("'");
+
+    assert_eq!(h.text(), "let s = \"a' b\";\n");
+}
+
+#[test]
+fn quotes_are_typed_as_is_with_auto_pairs_off() {
+    let path = file("off.rs", "");
+    let mut h = Harness::open(&[&path], 40, 8);
+    h.type_slowly("let s = \"");
+
+    assert_eq!(h.text(), "let s = \"");
+}
@@ -0,0 +1,45 @@
+mod common;
+
+use std::{
+    fs,
+    panic::{self, AssertUnwindSafe},
+};
+
+use common::Harness;
+use shark::{buffer::BufferEvent, editor::Editor, recovery};
+
+fn crash_on_edit(_: &mut Editor, _: usize, event: &BufferEvent) {
+    if matches!(event, BufferEvent::Changed { .. }) {
+        panic!("induced");
+    }
+}
+
+#[test]
+fn a_panic_mid_edit_leaves_the_unsaved_text_recoverable() {
+    let filename = common::file("crash.txt", "saved\n");
+    let mut h = Harness::open(&[&filename], 40, 10);
+    h.editor.listen(crash_on_edit);
+
+    let crashed = panic::catch_unwind(AssertUnwindSafe(|| {
+        h.type_text("unsaved ");
+    }));
+    assert!(crashed.is_err());
+    h.editor.dump_recovery();
+
+    let (path, _) = recovery::find(&filename).expect("a recovery file");
+    assert_eq!(fs::read_to_string(&path).unwrap(), h.text());
+    assert!(h.text().starts_with('u'));
+    assert_eq!(fs::read_to_string(&filename).unwrap(), "saved\n");
+
+    h.editor.remove_recovery();
+    assert!(recovery::find(&filename).is_none());
+}
+
+#[test]
+fn unmodified_buffers_leave_nothing_to_recover() {
+    let filename = common::file("clean.txt", "saved\n");
+    let h = Harness::open(&[&filename], 40, 10);
+    h.editor.dump_recovery();
+
+    assert!(recovery::find(&filename).is_none());
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use std::fs;
+
+use common::Harness;
+use shark::undo::UndoLimits;
+
+#[test]
+fn undoing_a_reload_brings_the_unsaved_edits_back() {
+    let filename = common::file("reload.txt", "disk\n");
+    let mut h = Harness::open(&[&filename], 80, 10);
+    h.type_text("unsaved ");
+    fs::write(&filename, "changed elsewhere\n").unwrap();
+
+    h.alt('r');
+    assert_eq!(h.text(), "changed elsewhere\n");
+    assert!(h.status().contains("Reloaded"));
+
+    h.ctrl('z');
+    assert_eq!(h.text(), "unsaved disk\n");
+    assert_eq!(h.cursor(), (0, 8));
+    assert!(h.editor.buffer().modified);
+    assert_eq!(h.row(0), "unsaved disk");
+
+    h.ctrl('y');
+    assert_eq!(h.text(), "changed elsewhere\n");
+}
+
+#[test]
+fn a_buffer_too_big_to_snapshot_reloads_without_undo() {
+    let filename = common::file("reload-big.txt", "disk\n");
+    let mut h = Harness::open_with(&[&filename], 120, 10, |editor| {
+        editor.set_undo_limits(UndoLimits {
+            max_entries: 100,
+            max_bytes: 8,
+        })
+    });
+    h.type_text("unsaved ");
+    fs::write(&filename, "changed elsewhere\n").unwrap();
+
+    h.alt('r');
+    assert_eq!(h.text(), "changed elsewhere\n");
+    assert!(h.status().contains("can't be undone"), "{}", h.status());
+
+    h.ctrl('z');
+    assert_eq!(h.text(), "changed elsewhere\n");
+}
@@ -0,0 +1,45 @@
+mod common;
+
+use std::{fs, path::Path};
+
+use common::Harness;
+use ropey::Rope;
+use shark::{buffer::Buffer, project::Project};
+
+fn restricted(root: &Path, filename: &Path) -> Harness {
+    fs::create_dir_all(root).unwrap();
+    let project = Project {
+        root: fs::canonicalize(root).unwrap(),
+        restrict: true,
+    };
+    let filename = filename.to_str().unwrap().to_string();
+    let buffer = Buffer::new(Rope::from_str("text\n"), Some(filename));
+    Harness::in_project(project, vec![buffer], 160, 10, |_| {})
+}
+
+#[test]
+fn saving_outside_the_root_is_refused() {
+    let root = common::home().join("restricted");
+    let outside = root.join("../escaped.txt");
+    let mut h = restricted(&root, &outside);
+    h.type_text("more ").ctrl('s');
+
+    assert!(h.status().contains("Refusing to save"), "{}", h.status());
+    assert!(h.status().contains("outside the project root"));
+    assert!(!common::home().join("escaped.txt").exists());
+    assert!(h.editor.buffer().modified);
+}
+
+#[test]
+fn saving_inside_the_root_is_allowed() {
+    let root = common::home().join("restricted-inside");
+    let inside = root.join("sub/../kept.txt");
+    let mut h = restricted(&root, &inside);
+    h.type_text("more ").ctrl('s');
+
+    assert_eq!(
+        fs::read_to_string(root.join("kept.txt")).unwrap(),
+        "more text\n"
+    );
+    assert!(!h.editor.buffer().modified);
+}
@@ -0,0 +1,172 @@
+// A headless editor driven over its --listen socket: requests are answered in
+// order once the editor ticks, the edits they make show on screen like typed
+// ones, and malformed requests get an error back with the editor unharmed.
+#![cfg(unix)]
+
+mod common;
+
+use std::{
+    fs,
+    io::{BufRead, BufReader, Write},
+    os::unix::{fs::PermissionsExt, net::UnixStream},
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+    thread,
+    time::{Duration, Instant},
+};
+
+use common::{file, home, Harness};
+use serde_json::{json, Value};
+use shark::rpc::Server;
+
+struct Client {
+    stream: UnixStream,
+    replies: Receiver<Value>,
+}
+
+impl Client {
+    // Replies are read on a thread of their own, since they only come once
+    // the editor's tick on this one has carried out the request.
+    fn connect(path: &Path) -> Self {
+        let stream = UnixStream::connect(path).expect("connect");
+        let reader = BufReader::new(stream.try_clone().unwrap());
+        let (sender, replies) = mpsc::channel();
+        thread::spawn(move || {
+            for line in reader.lines().map_while(Result::ok) {
+                let reply = serde_json::from_str(&line).expect("a JSON reply");
+                if sender.send(reply).is_err() {
+                    return;
+                }
+            }
+        });
+
+        Self { stream, replies }
+    }
+
+    // Sends `line` and ticks the editor until the reply comes.
+    fn send(&mut self, h: &mut Harness, line: &str) -> Value {
+        writeln!(self.stream, "{line}").unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            h.run();
+            if let Ok(reply) = self.replies.recv_timeout(Duration::from_millis(5)) {
+                return reply;
+            }
+            assert!(Instant::now() < deadline, "no reply to {line}");
+        }
+    }
+
+    fn ok(&mut self, h: &mut Harness, request: Value) -> Value {
+        let reply = self.send(h, &request.to_string());
+        assert_eq!(reply["ok"], true, "{reply}");
+        reply["result"].clone()
+    }
+
+    fn error(&mut self, h: &mut Harness, line: &str) -> String {
+        let reply = self.send(h, line);
+        assert_eq!(reply["ok"], false, "{reply}");
+        reply["error"].as_str().unwrap().to_string()
+    }
+}
+
+// The socket's path, with nothing left there by an earlier run.
+fn socket(name: &str) -> PathBuf {
+    let path = home().join(name);
+    let _ = fs::remove_file(&path);
+    path
+}
+
+fn listening(name: &str, text: &str) -> (Harness, PathBuf, String) {
+    let path = socket(&format!("{name}.sock"));
+    let filename = file(&format!("{name}.txt"), text);
+    let server = Server::bind(&path).expect("bind");
+    let mut h = Harness::open_with(&[&filename], 60, 6, |editor| editor.set_rpc(server));
+    h.run();
+    (h, path, filename)
+}
+
+#[test]
+fn a_script_reads_edits_and_saves_the_buffer() {
+    let (mut h, path, filename) = listening("session", "one\ntwo\n");
+    let mut client = Client::connect(&path);
+
+    assert_eq!(
+        client.ok(&mut h, json!({ "command": "get_buffer" })),
+        json!({ "filename": filename, "text": "one\ntwo\n" })
+    );
+    assert_eq!(
+        client.ok(&mut h, json!({ "command": "get_cursor" })),
+        json!({ "line": 1, "column": 1 })
+    );
+
+    assert_eq!(
+        client.ok(&mut h, json!({ "command": "goto", "line": 2, "column": 3 })),
+        json!({ "line": 2, "column": 3 })
+    );
+    assert_eq!(h.cursor(), (1, 2));
+
+    // Inserted before the cursor, which stays on the text it was at.
+    let at = json!({ "line": 2, "column": 1 });
+    assert_eq!(
+        client.ok(
+            &mut h,
+            json!({ "command": "insert", "at": at, "text": "a\nb" })
+        ),
+        json!({ "end": { "line": 3, "column": 2 } })
+    );
+    assert_eq!(h.text(), "one\na\nbtwo\n");
+    assert_eq!(h.cursor(), (2, 3));
+    assert_eq!(h.row(1), "a");
+    assert_eq!(h.row(2), "btwo");
+
+    assert_eq!(
+        client.ok(&mut h, json!({ "command": "save" })),
+        json!({ "filename": filename })
+    );
+    assert_eq!(fs::read_to_string(&filename).unwrap(), "one\na\nbtwo\n");
+    assert!(!h.editor.buffer().modified);
+}
+
+#[test]
+fn bad_requests_get_error_replies() {
+    let (mut h, path, _) = listening("errors", "one\n");
+    let mut client = Client::connect(&path);
+
+    for line in [
+        "not json",
+        r#"{"command": "no_such_command"}"#,
+        r#"{"command": "goto"}"#,
+        r#"{"command": "goto", "line": "two"}"#,
+    ] {
+        let error = client.error(&mut h, line);
+        assert!(error.starts_with("malformed request: "), "{line}: {error}");
+    }
+
+    // Well formed, but out of range for the buffer.
+    assert_eq!(
+        client.error(&mut h, r#"{"command": "goto", "line": 9}"#),
+        "line 9 is outside 1..=1"
+    );
+    let insert = json!({ "command": "insert", "at": { "line": 1, "column": 7 }, "text": "x" });
+    assert_eq!(
+        client.error(&mut h, &insert.to_string()),
+        "column 7 is outside 1..=4 on line 1"
+    );
+
+    // The connection and the editor both carry on.
+    assert_eq!(h.text(), "one\n");
+    assert_eq!(
+        client.ok(&mut h, json!({ "command": "get_cursor" })),
+        json!({ "line": 1, "column": 1 })
+    );
+}
+
+#[test]
+fn the_socket_is_private_and_removed_with_the_editor() {
+    let (h, path, _) = listening("lifetime", "one\n");
+    let mode = fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+
+    drop(h);
+    assert!(!path.exists());
+}
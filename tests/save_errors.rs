@@ -0,0 +1,125 @@
+// Saving where the file can't be written: the error shows on the status bar
+// in red, the buffer keeps its text and stays modified, and saving under
+// another name goes through.
+mod common;
+
+use std::{fs, path::PathBuf};
+
+use common::{file, home, Harness};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use ropey::Rope;
+use shark::{
+    buffer::Buffer,
+    theme::{ColorMode, Theme, ThemeChoice},
+};
+
+const TEXT: &str = "kept\n";
+
+// A buffer named for a file in a directory that doesn't exist. Missing
+// directories are created on save, so this one is under a plain file, where
+// it can't be.
+fn nowhere(name: &str) -> (Harness, String) {
+    let path = PathBuf::from(file("not-a-directory", ""))
+        .join("sub")
+        .join(name);
+    let path = path.to_str().unwrap().to_string();
+    let buffers = vec![Buffer::new(Rope::from_str(TEXT), Some(path.clone()))];
+    let mut h = Harness::setup(buffers, 120, 6, |editor| {
+        editor.set_theme(Theme::new(ColorMode::Always, ThemeChoice::Dark));
+    });
+    h.type_text("x");
+    (h, path)
+}
+
+fn press(h: &mut Harness, keys: &[KeyEvent]) {
+    for key in keys {
+        h.terminal.push(Event::Key(*key));
+    }
+    h.run();
+}
+
+fn key(code: KeyCode) -> KeyEvent {
+    KeyEvent::new(code, KeyModifiers::NONE)
+}
+
+const SAVE: KeyEvent = KeyEvent::new(KeyCode::Char('s'), KeyModifiers::CONTROL);
+
+#[test]
+fn saving_into_a_missing_directory_reports_the_error() {
+    let (mut h, path) = nowhere("missing.txt");
+
+    // Asked what to do about it, and Esc gives up.
+    press(&mut h, &[SAVE, key(KeyCode::Esc)]);
+    let status = h.status();
+    let status = status.trim_start();
+    assert!(
+        status.starts_with(&format!("Couldn't save {path}: ")),
+        "{status}"
+    );
+    // The message in red, the rest of the bar as usual.
+    let message = h.terminal.cell(1, 5).style.foreground;
+    assert!(message.is_some());
+    assert_ne!(message, h.terminal.cell(119, 5).style.foreground);
+
+    assert_eq!(h.text(), format!("x{TEXT}"));
+    assert!(h.editor.buffer().modified);
+    assert!(!PathBuf::from(&path).exists());
+
+    // Still editing, and the message goes with the next key.
+    h.type_text("y");
+    assert_eq!(h.text(), format!("xy{TEXT}"));
+    assert!(!h.status().contains("Couldn't save"));
+}
+
+#[test]
+fn retrying_asks_again() {
+    let (mut h, path) = nowhere("retried.txt");
+    press(&mut h, &[SAVE, key(KeyCode::Char('r')), key(KeyCode::Esc)]);
+    assert!(h.status().contains(&format!("Couldn't save {path}: ")));
+    assert!(h.editor.buffer().modified);
+}
+
+#[test]
+fn saving_as_another_file_after_the_error() {
+    let (mut h, path) = nowhere("elsewhere.txt");
+    let elsewhere = home().join("saved-elsewhere.txt");
+    let _ = fs::remove_file(&elsewhere);
+
+    // The name offered is the old one, erased before typing the new.
+    let mut keys = vec![SAVE, key(KeyCode::Char('a'))];
+    keys.extend((0..path.chars().count()).map(|_| key(KeyCode::Backspace)));
+    keys.extend(
+        elsewhere
+            .to_str()
+            .unwrap()
+            .chars()
+            .map(|c| key(KeyCode::Char(c))),
+    );
+    keys.push(key(KeyCode::Enter));
+    press(&mut h, &keys);
+
+    assert_eq!(fs::read_to_string(&elsewhere).unwrap(), format!("x{TEXT}"));
+    assert!(!h.editor.buffer().modified);
+    assert_eq!(h.editor.buffer().filename.as_deref(), elsewhere.to_str());
+}
+
+// The temporary file a save goes through can't be created, as the file's
+// name is as long as a name can be and leaves no room for the suffix. The
+// message is longer than the bar, which shows its end.
+#[cfg(unix)]
+#[test]
+fn a_failed_save_leaves_the_file_and_the_buffer_alone() {
+    let path = file(&format!("{}.txt", "b".repeat(251)), TEXT);
+    let mut h = Harness::open(&[&path], 120, 6);
+    h.type_text("x");
+
+    press(&mut h, &[SAVE, key(KeyCode::Esc)]);
+    assert!(
+        h.status().contains(".txt: File name too long"),
+        "{}",
+        h.status()
+    );
+    assert_eq!(fs::read_to_string(&path).unwrap(), TEXT);
+    assert_eq!(h.text(), format!("x{TEXT}"));
+    assert!(h.editor.buffer().modified);
+}
@@ -0,0 +1,112 @@
+// Tab in search mode selects the match the cursor is on, exactly as far as
+// it goes, even across a line break, and typing over it undoes in one step.
+mod common;
+
+use common::Harness;
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+
+fn press(h: &mut Harness, code: KeyCode, modifiers: KeyModifiers) {
+    h.terminal.push(Event::Key(KeyEvent::new(code, modifiers)));
+}
+
+// Searches for `query`, typed into the prompt, and selects the match it lands
+// on.
+fn select(h: &mut Harness, query: &str) {
+    press(h, KeyCode::Char('f'), KeyModifiers::CONTROL);
+    for c in query.chars() {
+        press(h, KeyCode::Char(c), KeyModifiers::NONE);
+    }
+    press(h, KeyCode::Enter, KeyModifiers::NONE);
+    press(h, KeyCode::Tab, KeyModifiers::NONE);
+    h.run();
+}
+
+#[test]
+fn the_match_becomes_the_selection() {
+    let mut h = Harness::new("one two three two\n");
+    select(&mut h, "two");
+    assert_eq!(h.cursor(), (0, 7));
+    assert!(!h.status().contains("Search:"));
+
+    h.ctrl('c');
+    assert_eq!(h.terminal.clipboard().as_deref(), Some("two"));
+}
+
+#[test]
+fn the_next_match_can_be_selected_instead() {
+    let mut h = Harness::new("one two three two\n");
+    press(&mut h, KeyCode::Char('f'), KeyModifiers::CONTROL);
+    for c in "two".chars() {
+        press(&mut h, KeyCode::Char(c), KeyModifiers::NONE);
+    }
+    press(&mut h, KeyCode::Enter, KeyModifiers::NONE);
+    press(&mut h, KeyCode::Char('n'), KeyModifiers::NONE);
+    press(&mut h, KeyCode::Tab, KeyModifiers::NONE);
+    h.run();
+
+    h.type_text("2");
+    assert_eq!(h.text(), "one two three 2\n");
+}
+
+#[test]
+fn typing_over_the_match_undoes_in_one_step() {
+    let mut h = Harness::new("let old = 1;\nold + old\n");
+    select(&mut h, "old");
+    h.type_text("n");
+    assert_eq!(h.text(), "let n = 1;\nold + old\n");
+    assert_eq!(h.cursor(), (0, 5));
+
+    h.ctrl('z');
+    assert_eq!(h.text(), "let old = 1;\nold + old\n");
+    h.ctrl('z');
+    assert_eq!(h.text(), "let old = 1;\nold + old\n");
+}
+
+#[test]
+fn a_match_across_lines_is_selected_whole() {
+    let mut h = Harness::new("alpha\nbeta\nalpha\nbeta\n");
+    press(&mut h, KeyCode::Char('f'), KeyModifiers::CONTROL);
+    h.terminal.push(Event::Paste("a\nbe".to_string()));
+    press(&mut h, KeyCode::Enter, KeyModifiers::NONE);
+    press(&mut h, KeyCode::Char('n'), KeyModifiers::NONE);
+    press(&mut h, KeyCode::Tab, KeyModifiers::NONE);
+    h.run();
+
+    assert_eq!(h.cursor(), (3, 2));
+    h.ctrl('c');
+    assert_eq!(h.terminal.clipboard().as_deref(), Some("a\nbe"));
+
+    h.type_text("-");
+    assert_eq!(h.text(), "alpha\nbeta\nalph-ta\n");
+    h.ctrl('z');
+    assert_eq!(h.text(), "alpha\nbeta\nalpha\nbeta\n");
+}
+
+#[test]
+fn a_pasted_query_keeps_its_line_break() {
+    let mut h = Harness::with_size("ab\ncd\nab\ncd\n", 60, 10);
+    press(&mut h, KeyCode::Char('f'), KeyModifiers::CONTROL);
+    h.terminal.push(Event::Paste("b\nc".to_string()));
+    press(&mut h, KeyCode::Enter, KeyModifiers::NONE);
+    h.run();
+    assert!(h.status().contains("Search: b↵c"));
+
+    press(&mut h, KeyCode::Tab, KeyModifiers::NONE);
+    h.run();
+    h.key(KeyCode::Backspace);
+    assert_eq!(h.text(), "ad\nab\ncd\n");
+}
+
+#[test]
+fn the_query_stays_on_a_narrow_status_bar() {
+    let mut h = Harness::new("one two\n");
+    press(&mut h, KeyCode::Char('f'), KeyModifiers::CONTROL);
+    for c in "two".chars() {
+        press(&mut h, KeyCode::Char(c), KeyModifiers::NONE);
+    }
+    press(&mut h, KeyCode::Enter, KeyModifiers::NONE);
+    h.run();
+
+    assert!(h.status().starts_with(" Search: two"));
+    assert!(!h.status().contains("Tab to select"));
+}
@@ -0,0 +1,142 @@
+mod common;
+
+use common::Harness;
+use crossterm::event::{KeyCode, KeyModifiers};
+
+fn numbered(lines: usize) -> String {
+    (0..lines).map(|n| format!("line {n}\n")).collect()
+}
+
+// From the cursor down `lines` lines and `columns` columns, with Shift held.
+fn select(h: &mut Harness, lines: usize, columns: usize) {
+    h.repeat(KeyCode::Down, KeyModifiers::SHIFT, lines);
+    h.repeat(KeyCode::Right, KeyModifiers::SHIFT, columns);
+}
+
+#[test]
+fn backspace_removes_a_selection_taller_than_the_view() {
+    let mut h = Harness::with_size(&numbered(40), 40, 10);
+    h.key(KeyCode::Down).key(KeyCode::Right);
+    select(&mut h, 25, 2);
+    h.key(KeyCode::Backspace);
+
+    let text = h.text();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines[..3], ["line 0", "le 26", "line 27"]);
+    assert_eq!(lines.len(), 15);
+    assert_eq!(h.cursor(), (1, 1));
+    assert_eq!(h.row(1), "le 26");
+
+    h.ctrl('z');
+    assert_eq!(h.text(), numbered(40));
+}
+
+#[test]
+fn enter_replaces_a_selection_with_one_indented_newline() {
+    let text = "fn main() {\n    let a = 1;\n    let b = 2;\n    let c = 3;\n}\n";
+    let mut h = Harness::new(text);
+    h.key(KeyCode::Down).key(KeyCode::End);
+    select(&mut h, 2, 0);
+    h.key(KeyCode::Enter);
+
+    assert_eq!(h.text(), "fn main() {\n    let a = 1;\n    \n}\n");
+    assert_eq!(h.cursor(), (2, 4));
+
+    h.ctrl('z');
+    assert_eq!(h.text(), text);
+}
+
+#[test]
+fn delete_removes_a_selection_and_collapses_to_its_start() {
+    let mut h = Harness::with_size(&numbered(40), 40, 10);
+    h.repeat(KeyCode::Down, KeyModifiers::NONE, 30);
+    h.repeat(KeyCode::Up, KeyModifiers::SHIFT, 28);
+    h.key(KeyCode::Delete);
+
+    let text = h.text();
+    assert_eq!(
+        text.lines().take(3).collect::<Vec<_>>(),
+        ["line 0", "line 1", "line 30"]
+    );
+    assert_eq!(h.cursor(), (2, 0));
+}
+
+// The final newline ends the last line rather than starting another, so
+// the whole buffer is everything up to it.
+#[test]
+fn backspace_over_the_whole_buffer_leaves_it_empty() {
+    let mut h = Harness::with_size(&numbered(40), 40, 10);
+    select(&mut h, 39, 7);
+    h.key(KeyCode::Backspace);
+
+    assert_eq!(h.text(), "\n");
+    assert_eq!(h.cursor(), (0, 0));
+    assert!(h.rows()[..8].iter().all(String::is_empty));
+
+    h.ctrl('z');
+    assert_eq!(h.text(), numbered(40));
+}
+
+#[test]
+fn selecting_to_the_end_of_an_unterminated_crlf_file_leaves_no_cr() {
+    let mut h = Harness::new("one\r\ntwo\r\nthree");
+    h.key(KeyCode::Right);
+    select(&mut h, 2, 5);
+    h.key(KeyCode::Backspace);
+
+    assert_eq!(h.text(), "o");
+
+    let mut h = Harness::new("one\r\ntwo\r\nthree");
+    select(&mut h, 2, 5);
+    h.key(KeyCode::Enter);
+
+    assert_eq!(h.text(), "\r\n");
+    assert_eq!(h.cursor(), (1, 0));
+}
+
+#[test]
+fn typing_over_a_selection_of_every_line_replaces_it() {
+    let mut h = Harness::new(&numbered(5));
+    select(&mut h, 4, 6);
+    h.type_text("x");
+
+    assert_eq!(h.text(), "x\n");
+}
+
+#[test]
+fn enter_at_the_end_of_an_unterminated_file_moves_onto_the_new_line() {
+    let mut h = Harness::new("last");
+    h.key(KeyCode::End).type_text("\nnext");
+
+    assert_eq!(h.text(), "last\nnext");
+    assert_eq!(h.cursor(), (1, 4));
+    assert_eq!(h.row(1), "next");
+
+    let mut h = Harness::new("");
+    h.type_text("\n\nthird");
+    assert_eq!(h.text(), "\n\nthird");
+    assert_eq!(h.terminal.cursor(), (5, 2));
+}
+
+#[test]
+fn typing_over_a_selection_undoes_in_one_step() {
+    let mut h = Harness::new("one\ntwo\n");
+    select(&mut h, 1, 2);
+    h.type_text("new");
+
+    assert_eq!(h.text(), "newo\n");
+    h.ctrl('z');
+    assert_eq!(h.text(), "one\ntwo\n");
+    assert_eq!(h.cursor(), (1, 2));
+}
+
+#[test]
+fn the_new_last_line_shows_while_wrapping_too() {
+    let mut h = Harness::setup(Vec::new(), 20, 6, |editor| editor.set_wrap(true));
+    h.type_text("a line long enough to wrap\n");
+
+    assert_eq!(h.cursor(), (1, 0));
+    assert_eq!(h.terminal.cursor(), (0, 2));
+    h.type_text("b");
+    assert_eq!(h.row(2), "b");
+}
@@ -0,0 +1,62 @@
+// Drawing with a syntax tree from other text than the buffer's, as a parse
+// lagging behind an edit would leave it.
+mod common;
+
+use common::Harness;
+use ropey::Rope;
+use shark::buffer::Buffer;
+use tree_sitter::Parser;
+
+fn with_tree_of(text: &str, parsed: &str) -> Harness {
+    let mut parser = Parser::new();
+    parser
+        .set_language(&tree_sitter_rust::LANGUAGE.into())
+        .unwrap();
+    let mut buffer = Buffer::new(Rope::from_str(text), Some("stale.rs".to_string()));
+    buffer.tree = parser.parse(parsed, None);
+    buffer.tree_stale = false;
+
+    Harness::with_buffers(vec![buffer], 40, 8)
+}
+
+#[test]
+fn a_tree_longer_than_the_buffer_is_clamped() {
+    let h = with_tree_of(
+        "fn a() {}\n",
+        "fn a() {}\nfn longer_function_name() { let x = \"string\"; }\n// comment\n",
+    );
+
+    assert_eq!(h.row(0), "fn a() {}");
+    assert_eq!(h.row(1), "");
+}
+
+#[test]
+fn a_tree_shorter_than_the_buffer_leaves_the_rest_plain() {
+    let h = with_tree_of("fn a() {}\nlet b = 1;\nlet c = 2;\n", "fn a() {}\n");
+
+    assert_eq!(h.row(0), "fn a() {}");
+    assert_eq!(h.row(1), "let b = 1;");
+    assert_eq!(h.row(2), "let c = 2;");
+    // The keyword past the end of the tree gets no color.
+    let plain = h.terminal.cell(0, 1).style;
+    assert_eq!(plain.foreground, None);
+}
+
+#[test]
+fn spans_ending_inside_a_character_dont_split_it() {
+    // The string literal's offsets land in the middle of the é's and 字.
+    let h = with_tree_of("let s = \"éé字\";\n", "let s = \"abcdef\"; let t = 0;\n");
+
+    assert_eq!(h.row(0), "let s = \"éé字\";");
+}
+
+#[test]
+fn editing_with_a_stale_tree_reparses() {
+    let mut h = with_tree_of("fn a() {}\n", "struct Something;\n// comment\n");
+    h.type_text("// ");
+
+    assert_eq!(h.text(), "// fn a() {}\n");
+    assert_eq!(h.row(0), "// fn a() {}");
+    assert!(!h.editor.buffer().tree_stale);
+    assert!(h.terminal.cell(3, 0).style.foreground.is_some());
+}
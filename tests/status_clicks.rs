@@ -0,0 +1,163 @@
+// Clicking the segments of the status bar: the position goes to a line, the
+// language picks another, the line ending converts the buffer and [+] saves.
+mod common;
+
+use std::fs;
+
+use common::{file, Harness};
+use crossterm::event::{
+    Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+};
+use ropey::Rope;
+use shark::buffer::Buffer;
+
+// Queues a click on the status bar where `segment` is drawn, to be handled
+// together with `then`, the keys for whatever prompt or menu it opens.
+fn click(h: &mut Harness, segment: &str, then: &[KeyCode]) {
+    let status = h.status();
+    let column = status
+        .find(segment)
+        .unwrap_or_else(|| panic!("{segment:?} isn't on {status:?}"));
+    // By columns, which the ellipsis of a truncated bar is one of.
+    click_at(h, status[..column].chars().count() as u16, then);
+}
+
+fn click_at(h: &mut Harness, column: u16, then: &[KeyCode]) {
+    let height = h.rows().len() as u16;
+    h.terminal.push(Event::Mouse(MouseEvent {
+        kind: MouseEventKind::Down(MouseButton::Left),
+        column,
+        row: height - 1,
+        modifiers: KeyModifiers::NONE,
+    }));
+    for &code in then {
+        h.terminal
+            .push(Event::Key(KeyEvent::new(code, KeyModifiers::NONE)));
+    }
+    h.run();
+}
+
+#[test]
+fn the_position_goes_to_a_line() {
+    let mut h = Harness::with_size("one\ntwo\nthree\nfour\n", 60, 8);
+    click(&mut h, "1:1", &[KeyCode::Char('3'), KeyCode::Enter]);
+
+    assert_eq!(h.cursor(), (2, 0));
+    assert!(h.status().contains("3:1"));
+}
+
+#[test]
+fn the_language_opens_the_menu_to_override_it() {
+    let path = file("clicked.rs", "fn main() {}\n");
+    let mut h = Harness::open(&[&path], 60, 12);
+    h.run();
+    // Plain text is last.
+    let mut keys = vec![KeyCode::Down; 10];
+    keys.push(KeyCode::Enter);
+    click(&mut h, "rust", &keys);
+
+    assert!(h.status().contains("Language set to Plain text"));
+    h.key(KeyCode::Right);
+    assert!(h.status().contains("plain text"));
+    assert!(!h.status().contains("rust"));
+}
+
+#[test]
+fn the_line_ending_converts_the_buffer_and_back() {
+    let mut h = Harness::with_size("one\r\ntwo\r\n", 60, 8);
+    h.key(KeyCode::Down).key(KeyCode::End);
+
+    click(&mut h, "CRLF", &[]);
+    assert_eq!(h.text(), "one\ntwo\n");
+    assert!(h.status().contains("LF") && !h.status().contains("CRLF"));
+    assert_eq!(h.cursor(), (1, 3));
+    // Lines added from now on end the same way.
+    h.key(KeyCode::Enter);
+    assert_eq!(h.text(), "one\ntwo\n\n");
+
+    click(&mut h, "LF", &[]);
+    assert_eq!(h.text(), "one\r\ntwo\r\n\r\n");
+    assert!(h.status().contains("CRLF"));
+
+    // Each conversion is undone in one step, the line ending with it.
+    h.ctrl('z');
+    assert_eq!(h.text(), "one\ntwo\n\n");
+    assert!(!h.status().contains("CRLF"));
+}
+
+#[test]
+fn a_read_only_buffer_keeps_its_line_endings() {
+    let mut buffer = Buffer::new(Rope::from_str("one\r\n"), None);
+    buffer.read_only = true;
+    let mut h = Harness::with_buffers(vec![buffer], 60, 8);
+    assert!(h.status().contains("[RO]"));
+    click(&mut h, "CRLF", &[]);
+
+    assert_eq!(h.text(), "one\r\n");
+}
+
+#[test]
+fn the_modified_flag_saves() {
+    let path = file("clicked.txt", "text\n");
+    let mut h = Harness::open(&[&path], 60, 8);
+    h.type_text("more ");
+    assert!(h.status().contains("[+]"));
+
+    click(&mut h, "[+]", &[]);
+    assert_eq!(fs::read_to_string(&path).unwrap(), "more text\n");
+    assert!(!h.editor.buffer().modified);
+    assert!(!h.status().contains("[+]"));
+}
+
+#[test]
+fn the_rest_of_the_bar_does_nothing() {
+    let mut h = Harness::with_size("one\ntwo\n", 60, 8);
+    h.key(KeyCode::Down);
+    let before = h.rows();
+
+    click(&mut h, "2 lines", &[]);
+    click_at(&mut h, 0, &[]);
+    assert_eq!(h.rows(), before);
+    assert_eq!(h.text(), "one\ntwo\n");
+    assert_eq!(h.cursor(), (1, 0));
+}
+
+// Segments truncation cut off can't be clicked, and the ones still shown
+// are where they were drawn.
+#[test]
+fn clicks_go_by_the_bar_as_drawn() {
+    let mut h = Harness::with_size("one\ntwo\n", 18, 8);
+    let status = h.status();
+    assert!(
+        status.starts_with('…') && !status.contains("LF"),
+        "{status:?}"
+    );
+
+    click_at(&mut h, 0, &[]);
+    assert_eq!(h.text(), "one\ntwo\n");
+    click(&mut h, "1:1", &[KeyCode::Char('2'), KeyCode::Enter]);
+    assert_eq!(h.cursor(), (1, 0));
+}
+
+// A message takes the room of the language and line ending, so a short one
+// fits whole on a narrow bar, and they come back with the next key.
+#[test]
+fn a_message_pushes_out_the_language_and_line_ending() {
+    let buffer = Buffer::new(
+        Rope::from_str("fn a() {}\n"),
+        Some("message.rs".to_string()),
+    );
+    let mut h = Harness::with_buffers(vec![buffer], 40, 8);
+    assert!(h.status().contains("rust | LF"));
+
+    h.ctrl('6');
+    let status = h.status();
+    assert!(status.starts_with(" No other buffer "), "{status:?}");
+    assert!(
+        !status.contains("rust") && !status.contains("LF"),
+        "{status:?}"
+    );
+
+    h.key(KeyCode::Right);
+    assert!(h.status().contains("rust | LF"));
+}
@@ -0,0 +1,146 @@
+// Commands act on the selection when there is one, and otherwise on the
+// cursor's line or the whole buffer, whichever each falls back to.
+mod common;
+
+use common::Harness;
+use crossterm::event::{KeyCode, KeyModifiers};
+use ropey::Rope;
+use shark::buffer::Buffer;
+
+fn rust(text: &str) -> Harness {
+    let buffer = Buffer::new(Rope::from_str(text), Some("targets.rs".to_string()));
+    Harness::with_buffers(vec![buffer], 80, 10)
+}
+
+fn select_right(h: &mut Harness, count: usize) {
+    h.repeat(KeyCode::Right, KeyModifiers::SHIFT, count);
+}
+
+#[test]
+fn toggle_comment_takes_the_line_or_the_selected_lines() {
+    let mut h = rust("a();\nb();\nc();\n");
+    h.key(KeyCode::Down).ctrl('/');
+    assert_eq!(h.text(), "a();\n// b();\nc();\n");
+
+    let mut h = rust("a();\nb();\nc();\n");
+    h.shift(KeyCode::Down).shift(KeyCode::Right).ctrl('/');
+    assert_eq!(h.text(), "// a();\n// b();\nc();\n");
+}
+
+#[test]
+fn evaluate_takes_the_expression_under_the_cursor_or_the_selection() {
+    let mut h = rust("x = 2*3 + 1\n");
+    h.repeat(KeyCode::Right, KeyModifiers::NONE, 5).alt('=');
+    assert_eq!(h.text(), "x = 6 + 1\n");
+
+    // With spaces inside, which only a selection can take in.
+    let mut h = rust("x = 2*3 + 1\n");
+    h.repeat(KeyCode::Right, KeyModifiers::NONE, 3);
+    select_right(&mut h, 9);
+    h.alt('=');
+    assert_eq!(h.text(), "x = 7\n");
+}
+
+#[test]
+fn evaluate_wants_a_selection_on_one_line() {
+    let mut h = rust("1 +\n2\n");
+    h.shift(KeyCode::Down).shift(KeyCode::Right).alt('=');
+
+    assert_eq!(h.text(), "1 +\n2\n");
+    assert!(h.status().contains("Select an expression on one line"));
+}
+
+#[test]
+fn copy_takes_the_selection_or_the_line() {
+    let mut h = rust("one two\nthree\n");
+    select_right(&mut h, 3);
+    h.ctrl('c');
+    assert_eq!(h.terminal.clipboard().as_deref(), Some("one"));
+
+    let mut h = rust("one two\nthree\n");
+    h.key(KeyCode::Down).ctrl('c');
+    assert_eq!(h.terminal.clipboard().as_deref(), Some("three\n"));
+    assert_eq!(h.text(), "one two\nthree\n");
+}
+
+#[test]
+fn cut_takes_the_selection_or_the_line() {
+    let mut h = rust("one two\nthree\n");
+    select_right(&mut h, 4);
+    h.ctrl('x');
+    assert_eq!(h.text(), "two\nthree\n");
+    assert_eq!(h.cursor(), (0, 0));
+
+    let mut h = rust("one two\nthree\n");
+    h.ctrl('x');
+    assert_eq!(h.text(), "three\n");
+    assert_eq!(h.terminal.clipboard().as_deref(), Some("one two\n"));
+}
+
+#[test]
+fn copy_line_takes_every_selected_line() {
+    let mut h = rust("one\ntwo\nthree\n");
+    h.alt('y');
+    assert_eq!(h.terminal.clipboard().as_deref(), Some("one\n"));
+
+    h.key(KeyCode::Right).shift(KeyCode::Down);
+    h.alt('y');
+    assert_eq!(h.terminal.clipboard().as_deref(), Some("one\ntwo\n"));
+
+    h.alt('k');
+    assert_eq!(h.text(), "three\n");
+    assert_eq!(h.cursor(), (0, 0));
+}
+
+#[test]
+fn fix_whitespace_takes_the_buffer_or_the_selected_lines() {
+    let mut h = rust("a  \nb \nc\t\n\n\n");
+    h.alt('W');
+    assert_eq!(h.text(), "a\nb\nc\n");
+
+    let mut h = rust("a  \nb \nc\t\n\n\n");
+    h.key(KeyCode::Down).shift(KeyCode::Right);
+    h.alt('W');
+    assert_eq!(h.text(), "a  \nb\nc\t\n\n\n");
+    // One step to undo.
+    h.ctrl('z');
+    assert_eq!(h.text(), "a  \nb \nc\t\n\n\n");
+}
+
+#[test]
+fn reflow_takes_the_paragraph_or_the_selected_lines() {
+    let text = "// one two three\n// four\n\n// five six seven\n";
+    let narrow = || {
+        let buffer = Buffer::new(Rope::from_str(text), Some("reflow.rs".to_string()));
+        Harness::setup(vec![buffer], 80, 10, |editor| editor.set_text_width(14))
+    };
+
+    let mut h = narrow();
+    h.alt('q');
+    assert_eq!(h.text(), "// one two\n// three four\n\n// five six seven\n");
+
+    let mut h = narrow();
+    h.repeat(KeyCode::Down, KeyModifiers::SHIFT, 3);
+    select_right(&mut h, 9);
+    h.alt('q');
+    assert_eq!(
+        h.text(),
+        "// one two\n// three four\n\n// five six\n// seven\n"
+    );
+    // Still on "six".
+    assert_eq!(h.cursor(), (3, 8));
+    // One step to undo.
+    h.ctrl('z');
+    assert_eq!(h.text(), text);
+}
+
+// A selection that ends inside a grapheme, as one kept across an edit can,
+// takes the whole of it.
+#[test]
+fn selections_take_whole_graphemes() {
+    let mut h = rust("e\u{301}x\n");
+    select_right(&mut h, 1);
+    h.ctrl('c');
+
+    assert_eq!(h.terminal.clipboard().as_deref(), Some("e\u{301}"));
+}
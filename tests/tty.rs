@@ -0,0 +1,140 @@
+// Runs the binary itself, with and without a terminal to draw on.
+#![cfg(unix)]
+
+use std::{
+    env,
+    fs::File,
+    io::{Read, Write},
+    os::{fd::FromRawFd, unix::process::CommandExt},
+    process::{Child, Command, Stdio},
+    ptr,
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+fn shark() -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_shark"));
+    command
+        .env("HOME", env::temp_dir())
+        .env_remove("XDG_CONFIG_HOME")
+        .env_remove("XDG_DATA_HOME")
+        .env_remove("SHARK_LOG");
+    command
+}
+
+#[test]
+fn refuses_to_start_when_stdout_is_not_a_terminal() {
+    let output = shark()
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()
+        .expect("run shark");
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    let message = String::from_utf8_lossy(&output.stderr);
+    assert!(message.contains("stdout is not a tty"), "{message}");
+}
+
+#[test]
+fn starts_and_quits_on_a_pseudo_terminal() {
+    let (mut master, mut child, output) = start(&[], b" lines ");
+    assert!(contains(&output, b"\x1b[?1049h"));
+    quit(&mut master, &mut child);
+}
+
+#[test]
+fn an_overlap_as_tall_as_the_screen_is_reported() {
+    let message = b"--page-overlap 30 isn't smaller than the 23-line screen";
+    let (mut master, mut child, _) = start(&["--page-overlap", "30"], message);
+    quit(&mut master, &mut child);
+}
+
+// Runs shark with `args` on an 80x24 pseudo-terminal until it has written
+// `until`, and returns what it wrote up to then.
+fn start(args: &[&str], until: &[u8]) -> (File, Child, Vec<u8>) {
+    let (mut master, slave) = open_pty(80, 24);
+    let (stdin, stdout, stderr) = (slave.try_clone(), slave.try_clone(), slave);
+    let mut command = shark();
+    command
+        .args(args)
+        .stdin(stdin.expect("dup"))
+        .stdout(stdout.expect("dup"))
+        .stderr(stderr);
+    // The pty becomes the child's controlling terminal, which is where
+    // crossterm asks for the size.
+    unsafe {
+        command.pre_exec(|| {
+            if libc::setsid() < 0 || libc::ioctl(0, libc::TIOCSCTTY, 0) < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    let child = command.spawn().expect("spawn shark");
+    drop(command);
+
+    let (sender, received) = mpsc::channel();
+    let mut reader = master.try_clone().expect("dup");
+    thread::spawn(move || {
+        let mut buffer = [0; 4096];
+        while let Ok(read) = reader.read(&mut buffer) {
+            if read == 0 || sender.send(buffer[..read].to_vec()).is_err() {
+                break;
+            }
+        }
+    });
+
+    // The background query ends with a device attributes request, which a
+    // terminal answers straight away; the status bar comes after it.
+    let mut output = Vec::new();
+    let mut answered = false;
+    while !contains(&output, until) {
+        let chunk = received
+            .recv_timeout(Duration::from_secs(10))
+            .unwrap_or_else(|_| panic!("shark never wrote {:?}", String::from_utf8_lossy(until)));
+        output.extend(chunk);
+        if !answered && contains(&output, b"\x1b[c") {
+            master.write_all(b"\x1b[?62c").expect("write to the pty");
+            answered = true;
+        }
+    }
+    (master, child, output)
+}
+
+fn quit(master: &mut File, child: &mut Child) {
+    // Ctrl+Q.
+    master.write_all(b"\x11").expect("write to the pty");
+
+    for _ in 0..100 {
+        if let Some(status) = child.try_wait().expect("wait") {
+            assert!(status.success(), "{status}");
+            return;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    let _ = child.kill();
+    panic!("shark didn't quit on Ctrl+Q");
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack
+        .windows(needle.len())
+        .any(|window| window == needle)
+}
+
+fn open_pty(width: u16, height: u16) -> (File, File) {
+    let (mut master, mut slave) = (0, 0);
+    let size = libc::winsize {
+        ws_row: height,
+        ws_col: width,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    let opened =
+        unsafe { libc::openpty(&mut master, &mut slave, ptr::null_mut(), ptr::null(), &size) };
+    assert_eq!(opened, 0, "openpty failed");
+    unsafe { (File::from_raw_fd(master), File::from_raw_fd(slave)) }
+}
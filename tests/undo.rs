@@ -0,0 +1,100 @@
+// Undo puts back both the text and the cursor from before an edit, a run of
+// typed characters on one line being a single edit, and redo goes forward
+// again until the next edit.
+mod common;
+
+use common::Harness;
+use crossterm::event::{KeyCode, KeyModifiers};
+
+fn redo_with_shift(h: &mut Harness) {
+    h.chord(
+        KeyCode::Char('Z'),
+        KeyModifiers::CONTROL | KeyModifiers::SHIFT,
+    );
+}
+
+#[test]
+fn an_insert_undoes_and_redoes() {
+    let mut h = Harness::with_size("one\ntwo\n", 40, 10);
+    h.key(KeyCode::Down).key(KeyCode::End).type_text("!");
+    assert_eq!(h.text(), "one\ntwo!\n");
+
+    h.ctrl('z');
+    assert_eq!(h.text(), "one\ntwo\n");
+    assert_eq!(h.cursor(), (1, 3));
+
+    h.ctrl('y');
+    assert_eq!(h.text(), "one\ntwo!\n");
+    assert_eq!(h.cursor(), (1, 4));
+}
+
+#[test]
+fn a_backspace_joining_lines_undoes_to_both() {
+    let mut h = Harness::with_size("one\ntwo\n", 40, 10);
+    h.key(KeyCode::Down).key(KeyCode::Backspace);
+    assert_eq!(h.text(), "onetwo\n");
+    assert_eq!(h.cursor(), (0, 3));
+
+    h.ctrl('z');
+    assert_eq!(h.text(), "one\ntwo\n");
+    assert_eq!(h.cursor(), (1, 0));
+
+    redo_with_shift(&mut h);
+    assert_eq!(h.text(), "onetwo\n");
+    assert_eq!(h.cursor(), (0, 3));
+}
+
+#[test]
+fn typing_on_one_line_undoes_as_one_step() {
+    let mut h = Harness::new("\n");
+    h.type_slowly("hello");
+
+    h.ctrl('z');
+    assert_eq!(h.text(), "\n");
+    assert_eq!(h.cursor(), (0, 0));
+}
+
+#[test]
+fn a_new_line_or_a_move_starts_a_new_step() {
+    let mut h = Harness::new("\n");
+    h.type_slowly("ab\ncd");
+    h.key(KeyCode::Left).type_slowly("x");
+    assert_eq!(h.text(), "ab\ncxd\n");
+
+    h.ctrl('z');
+    assert_eq!(h.text(), "ab\ncd\n");
+    h.ctrl('z');
+    assert_eq!(h.text(), "ab\n\n");
+    h.ctrl('z');
+    assert_eq!(h.text(), "ab\n");
+    h.ctrl('z');
+    assert_eq!(h.text(), "\n");
+}
+
+#[test]
+fn a_deletion_is_a_step_of_its_own() {
+    let mut h = Harness::new("\n");
+    h.type_slowly("abc")
+        .key(KeyCode::Backspace)
+        .type_slowly("d");
+    assert_eq!(h.text(), "abd\n");
+
+    h.ctrl('z');
+    assert_eq!(h.text(), "ab\n");
+    h.ctrl('z');
+    assert_eq!(h.text(), "abc\n");
+}
+
+#[test]
+fn an_edit_after_undo_drops_the_redo_history() {
+    let mut h = Harness::new("\n");
+    h.type_slowly("one").key(KeyCode::Enter).type_slowly("two");
+    h.ctrl('z').ctrl('z');
+    assert_eq!(h.text(), "one\n");
+
+    h.type_slowly("!");
+    h.ctrl('y');
+    assert_eq!(h.text(), "one!\n");
+    redo_with_shift(&mut h);
+    assert_eq!(h.text(), "one!\n");
+}
@@ -0,0 +1,105 @@
+// Thousands of edits against a bounded undo log: the bound holds, and the
+// most recent edits still undo and redo.
+mod common;
+
+use common::Harness;
+use crossterm::event::KeyCode;
+use ropey::Rope;
+use shark::{buffer::Buffer, undo::UndoLimits};
+
+fn limited(text: &str, limits: UndoLimits) -> Harness {
+    let buffers = vec![Buffer::new(Rope::from_str(text), None)];
+    Harness::setup(buffers, 40, 10, |editor| editor.set_undo_limits(limits))
+}
+
+// One undo entry per edit: each goes on a line of its own, away from the one
+// before, so they aren't merged.
+fn edit(h: &mut Harness, text: &str) {
+    h.type_text(text).key(KeyCode::Down);
+}
+
+fn edited(lines: usize, text: &str) -> String {
+    format!(
+        "{}{}",
+        format!("{text}\n").repeat(lines),
+        "\n".repeat(2001 - lines)
+    )
+}
+
+#[test]
+fn the_entry_count_stays_bounded() {
+    let limits = UndoLimits {
+        max_entries: 50,
+        max_bytes: 1 << 20,
+    };
+    let mut h = limited(&"\n".repeat(2001), limits);
+    for i in 0..2000 {
+        edit(&mut h, "x");
+        if i % 100 == 0 {
+            assert!(h.editor.buffer().undo_usage().0 <= 50);
+        }
+    }
+
+    assert_eq!(h.editor.buffer().undo_usage(), (50, 50));
+    for _ in 0..50 {
+        h.ctrl('z');
+    }
+    assert_eq!(h.text(), edited(1950, "x"));
+
+    // The evicted edits are gone for good.
+    h.ctrl('z');
+    assert!(h.status().contains("Nothing to undo"));
+    assert_eq!(h.text(), edited(1950, "x"));
+
+    for _ in 0..50 {
+        h.ctrl('y');
+    }
+    assert_eq!(h.text(), edited(2000, "x"));
+}
+
+#[test]
+fn the_byte_size_stays_bounded() {
+    let limits = UndoLimits {
+        max_entries: 1000,
+        max_bytes: 100,
+    };
+    let mut h = limited(&"\n".repeat(2001), limits);
+    let run = "abcdefghijklmnopqrstuvwxyz0123";
+    for _ in 0..500 {
+        edit(&mut h, run);
+        assert!(h.editor.buffer().undo_usage().1 <= 100);
+    }
+
+    // Three entries of thirty bytes fit.
+    assert_eq!(h.editor.buffer().undo_usage(), (3, 90));
+    h.ctrl('z').ctrl('z').ctrl('z').ctrl('z');
+    assert_eq!(h.text(), edited(497, run));
+    h.ctrl('y');
+    assert_eq!(h.text(), edited(498, run));
+}
+
+#[test]
+fn tightening_the_limits_keeps_redo_working() {
+    let mut h = limited(&"\n".repeat(2001), UndoLimits::default());
+    for _ in 0..20 {
+        edit(&mut h, "x");
+    }
+    for _ in 0..5 {
+        h.ctrl('z');
+    }
+
+    h.editor.set_undo_limits(UndoLimits {
+        max_entries: 2,
+        max_bytes: 1 << 20,
+    });
+    for _ in 0..5 {
+        h.ctrl('y');
+    }
+    assert_eq!(h.text(), edited(20, "x"));
+
+    // A new edit drops the history down to the limit.
+    edit(&mut h, "y");
+    assert_eq!(h.editor.buffer().undo_usage().0, 2);
+    h.ctrl('z').ctrl('z');
+    assert_eq!(h.text(), edited(19, "x"));
+}
@@ -0,0 +1,128 @@
+// The editor as a pane of a host's screen, painted into what the host
+// writes to and driven by the events it hands over.
+mod common;
+
+use std::io::Write;
+
+use common::{file, home};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+use ropey::Rope;
+use shark::{
+    terminal::Memory,
+    widget::{Buffer, EditorView, Event, Rect},
+};
+
+// A host screen filled with #, so anything painted outside the pane shows.
+fn host() -> Memory {
+    let mut screen = Memory::new(40, 12);
+    for row in 1..=12 {
+        write!(screen, "\x1b[{row};1H{}", "#".repeat(40)).unwrap();
+    }
+    screen
+}
+
+fn view(text: &str) -> EditorView {
+    home();
+    let buffer = Buffer::new(Rope::from_str(text), None);
+    let mut view = EditorView::new(buffer, Rect::new(5, 2, 30, 6)).unwrap();
+    view.editor_mut().set_line_numbers(false);
+    view.handle(Event::FocusGained).unwrap();
+    view
+}
+
+fn key(code: KeyCode) -> Event {
+    Event::Key(KeyEvent::new(code, KeyModifiers::NONE))
+}
+
+fn paint(view: &EditorView, screen: &mut Memory) {
+    view.render(screen).unwrap();
+}
+
+#[test]
+fn the_pane_is_painted_into_its_rectangle_only() {
+    let view = view("first line\nsecond line\n");
+    let mut screen = host();
+    paint(&view, &mut screen);
+
+    assert_eq!(
+        screen.row(2),
+        format!("#####first line{}#####", " ".repeat(20))
+    );
+    assert_eq!(&screen.row(3)[5..16], "second line");
+    assert_eq!(screen.row(1), "#".repeat(40));
+    assert_eq!(screen.row(8), "#".repeat(40));
+    // The status bar is the pane's last row.
+    assert!(screen.row(7)[5..35].contains("1:1"));
+    assert!(screen.cell(5, 7).style.reverse && !screen.cell(4, 7).style.reverse);
+    assert_eq!(view.cursor(), Some((5, 2)));
+}
+
+#[test]
+fn events_edit_the_buffer() {
+    let mut view = view("world\n");
+    for c in "hello ".chars() {
+        assert!(view.handle(key(KeyCode::Char(c))).unwrap());
+    }
+
+    let mut screen = host();
+    paint(&view, &mut screen);
+    assert_eq!(view.editor().buffer().rope.to_string(), "hello world\n");
+    assert_eq!(&screen.row(2)[5..16], "hello world");
+    assert_eq!(view.cursor(), Some((11, 2)));
+}
+
+#[test]
+fn mouse_events_are_relative_to_the_pane() {
+    let mut view = view("one\ntwo\nthree\n");
+    let click = |column, row| {
+        Event::Mouse(MouseEvent {
+            kind: MouseEventKind::Down(MouseButton::Left),
+            column,
+            row,
+            modifiers: KeyModifiers::NONE,
+        })
+    };
+
+    view.handle(click(7, 4)).unwrap();
+    assert_eq!(view.editor().cursor(), (2, 2));
+
+    // Outside the pane, so the host's.
+    view.handle(click(1, 1)).unwrap();
+    assert_eq!(view.editor().cursor(), (2, 2));
+}
+
+#[test]
+fn moving_the_pane_repaints_it_elsewhere() {
+    let mut view = view("text\n");
+    view.set_area(Rect::new(0, 0, 20, 4)).unwrap();
+    // Resizing the host's terminal is the host's business.
+    view.handle(Event::Resize(80, 24)).unwrap();
+
+    let mut screen = host();
+    paint(&view, &mut screen);
+    assert_eq!(&screen.row(0)[..20], format!("text{}", " ".repeat(16)));
+    assert_eq!(&screen.row(0)[20..], "#".repeat(20));
+    assert!(screen.row(3)[..20].contains("1:1"));
+    assert_eq!(&screen.row(4), &"#".repeat(40));
+    assert_eq!(view.cursor(), Some((0, 0)));
+}
+
+#[test]
+fn quitting_ends_the_pane() {
+    let mut view = view("text\n");
+    let quit = Event::Key(KeyEvent::new(KeyCode::Char('q'), KeyModifiers::CONTROL));
+
+    assert!(!view.handle(quit).unwrap());
+}
+
+#[test]
+fn an_opened_file_saves_back_to_it() {
+    let path = file("widget.toml", "key = 1\n");
+    let mut view = EditorView::open(path.as_ref(), Rect::new(0, 0, 30, 6)).unwrap();
+    view.handle(key(KeyCode::End)).unwrap();
+    view.handle(key(KeyCode::Char('0'))).unwrap();
+    let save = Event::Key(KeyEvent::new(KeyCode::Char('s'), KeyModifiers::CONTROL));
+    view.handle(save).unwrap();
+
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "key = 10\n");
+}
@@ -0,0 +1,172 @@
+// What atomic saves keep of the file they replace, and when they fall back to
+// writing in place.
+mod common;
+
+use std::{fs, io, path::Path};
+
+use common::{file, home};
+use shark::write;
+
+#[test]
+fn a_new_file_is_created() {
+    let path = home().join("created.txt");
+    let _ = fs::remove_file(&path);
+
+    assert_eq!(write::write_atomic(&path, "new\n").unwrap(), None);
+    assert_eq!(fs::read_to_string(&path).unwrap(), "new\n");
+}
+
+// Hidden files in the home that start with `name`'s temporary prefix.
+fn temporaries(name: &str) -> Vec<String> {
+    fs::read_dir(home())
+        .unwrap()
+        .filter_map(|entry| entry.unwrap().file_name().into_string().ok())
+        .filter(|file| file.starts_with(&format!(".{name}.")))
+        .collect()
+}
+
+#[test]
+fn the_temporary_file_is_cleaned_up() {
+    let path = file("cleaned.txt", "before\n");
+    write::write_atomic(Path::new(&path), "after\n").unwrap();
+
+    assert_eq!(temporaries("cleaned.txt"), Vec::<String>::new());
+    assert_eq!(fs::read_to_string(&path).unwrap(), "after\n");
+}
+
+// What's written fails part of the way through, the way a full disk would.
+#[test]
+fn a_failed_write_leaves_the_original_untouched() {
+    let path = file("interrupted.txt", "original\n");
+    let result = write::write_atomic_with(Path::new(&path), |out| {
+        out.write_all(b"partial")?;
+        Err(io::Error::other("no space left"))
+    });
+
+    assert_eq!(result.unwrap_err().to_string(), "no space left");
+    assert_eq!(fs::read_to_string(&path).unwrap(), "original\n");
+    assert_eq!(temporaries("interrupted.txt"), Vec::<String>::new());
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::{
+        fs,
+        os::unix::fs::{chown, MetadataExt, PermissionsExt},
+        path::Path,
+    };
+
+    use super::common::{file, home};
+    use shark::write;
+
+    fn save(path: &str, text: &str) -> Option<String> {
+        write::write_atomic(Path::new(path), text).unwrap()
+    }
+
+    #[test]
+    fn saving_through_a_symlink_writes_the_file_it_points_to() {
+        let real = file("linked-real.txt", "before\n");
+        let link = home().join("linked.txt");
+        let _ = fs::remove_file(&link);
+        std::os::unix::fs::symlink(&real, &link).unwrap();
+
+        assert_eq!(save(link.to_str().unwrap(), "after\n"), None);
+        assert!(fs::symlink_metadata(&link).unwrap().is_symlink());
+        assert_eq!(fs::read_link(&link).unwrap(), Path::new(&real));
+        assert_eq!(fs::read_to_string(&real).unwrap(), "after\n");
+    }
+
+    // Where the temporary file used to go, always under the same name, a
+    // symlink to somewhere the save must not write.
+    #[test]
+    fn a_symlink_where_the_temporary_file_might_go_is_left_alone() {
+        let path = file("planted.txt", "before\n");
+        let victim = file("planted-victim.txt", "untouched\n");
+        let planted = home().join(".planted.txt.shark-tmp");
+        let _ = fs::remove_file(&planted);
+        std::os::unix::fs::symlink(&victim, &planted).unwrap();
+
+        assert_eq!(save(&path, "after\n"), None);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "after\n");
+        assert_eq!(fs::read_to_string(&victim).unwrap(), "untouched\n");
+        assert!(fs::symlink_metadata(&planted).unwrap().is_symlink());
+    }
+
+    #[test]
+    fn the_mode_survives_a_save_including_setgid() {
+        let path = file("setgid.sh", "#!/bin/sh\n");
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o2751)).unwrap();
+        let inode = fs::metadata(&path).unwrap().ino();
+
+        assert_eq!(save(&path, "#!/bin/sh\necho\n"), None);
+        let metadata = fs::metadata(&path).unwrap();
+        assert_ne!(metadata.ino(), inode, "not replaced by a rename");
+        assert_eq!(metadata.mode() & 0o7777, 0o2751);
+    }
+
+    // Only root can give a file away.
+    #[test]
+    fn the_owner_survives_a_save() {
+        let path = file("owned.txt", "text\n");
+        if chown(&path, Some(4242), Some(4343)).is_err() {
+            return;
+        }
+
+        assert_eq!(save(&path, "more text\n"), None);
+        let metadata = fs::metadata(&path).unwrap();
+        assert_eq!((metadata.uid(), metadata.gid()), (4242, 4343));
+    }
+
+    // Skipped where the filesystem has no user attributes.
+    #[test]
+    fn extended_attributes_survive_a_save() {
+        let path = file("tagged.txt", "text\n");
+        if xattr::set(&path, "user.shark.test", b"kept").is_err() {
+            return;
+        }
+
+        assert_eq!(save(&path, "more text\n"), None);
+        assert_eq!(
+            xattr::get(&path, "user.shark.test").unwrap(),
+            Some(b"kept".to_vec())
+        );
+    }
+
+    // Root can create files in any directory, so there's nothing to fall
+    // back from then.
+    #[test]
+    fn a_read_only_directory_is_written_in_place() {
+        let directory = home().join("read-only");
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("kept.txt");
+        fs::write(&path, "before\n").unwrap();
+        let inode = fs::metadata(&path).unwrap().ino();
+        fs::set_permissions(&directory, fs::Permissions::from_mode(0o555)).unwrap();
+
+        let probe = directory.join("probe");
+        if fs::write(&probe, "").is_ok() {
+            let _ = fs::remove_file(&probe);
+            fs::set_permissions(&directory, fs::Permissions::from_mode(0o755)).unwrap();
+            return;
+        }
+
+        let warning = save(path.to_str().unwrap(), "after\n");
+        fs::set_permissions(&directory, fs::Permissions::from_mode(0o755)).unwrap();
+        let warning = warning.expect("a warning");
+        assert!(warning.ends_with("written in place as its directory isn't writable"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "after\n");
+        assert_eq!(fs::metadata(&path).unwrap().ino(), inode);
+    }
+}
+
+#[cfg(not(unix))]
+#[test]
+fn a_read_only_flag_survives_a_save() {
+    let path = file("read-only.txt", "text\n");
+    let mut permissions = fs::metadata(&path).unwrap().permissions();
+    permissions.set_readonly(true);
+    fs::set_permissions(&path, permissions).unwrap();
+
+    write::write_atomic(Path::new(&path), "more text\n").unwrap();
+    assert!(fs::metadata(&path).unwrap().permissions().readonly());
+}